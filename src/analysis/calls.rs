@@ -0,0 +1,107 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::core::ast::*;
+
+/// What every subprogram calls in its body, by name
+#[derive(Debug, Default)]
+pub struct CallGraph {
+    calls: HashMap<String, HashSet<String>>,
+}
+
+impl CallGraph {
+    pub fn build(program: &ProgramNode) -> CallGraph {
+        let mut graph = CallGraph::default();
+        for f in program.functions.iter() {
+            calls(&f.block, graph.calls.entry(f.name.clone()).or_default());
+        }
+        for p in program.procedures.iter() {
+            calls(&p.block, graph.calls.entry(p.name.clone()).or_default());
+        }
+        graph
+    }
+
+    /// Tells if calling `from` can end up calling `to`, directly or
+    /// through other subprograms
+    pub fn reaches(&self, from: &str, to: &str) -> bool {
+        let mut seen = HashSet::new();
+        let mut pending = vec![from];
+        while let Some(name) = pending.pop() {
+            if name == to {
+                return true;
+            }
+            if seen.insert(name) {
+                if let Some(callees) = self.calls.get(name) {
+                    pending.extend(callees.iter().map(|c| c.as_str()));
+                }
+            }
+        }
+        false
+    }
+}
+
+/// Collects the targets of the calls nested in the node
+fn calls(node: &ASTNode, found: &mut HashSet<String>) {
+    match node {
+        ASTNode::Block(b) => b.statements.iter().for_each(|s| calls(s, found)),
+        ASTNode::FunctionCallStmt(f) => {
+            found.insert(f.target.clone());
+            f.args.iter().for_each(|(_, a)| calls(a, found));
+        }
+        ASTNode::ProcedureCallStmt(p) => {
+            found.insert(p.target.clone());
+            p.args.iter().for_each(|(_, a)| calls(a, found));
+        }
+        ASTNode::ForStmt(l) => {
+            calls(&l.start, found);
+            calls(&l.end, found);
+            calls(&l.block, found);
+        }
+        ASTNode::VarReassignment(v) => {
+            calls(&v.variable_to_reassign, found);
+            calls(&v.new_value, found);
+        }
+        ASTNode::ReadStmt(r) => calls(&r.variable_to_read_in, found),
+        ASTNode::ArrayRef(a) => a.indexes.iter().for_each(|i| calls(i, found)),
+        ASTNode::FieldRef(f) => calls(&f.record, found),
+        ASTNode::BinaryExpression(b) => {
+            calls(&b.left, found);
+            calls(&b.right, found);
+        }
+        ASTNode::UnaryExpression(u) => calls(&u.expression, found),
+        ASTNode::IfStmt(i) => {
+            calls(&i.guard, found);
+            calls(&i.then, found);
+            if let Some(e) = &i.else_stmt {
+                calls(e, found);
+            }
+        }
+        ASTNode::CaseStmt(c) => {
+            calls(&c.value, found);
+            c.branches.iter().for_each(|b| calls(&b.body, found));
+            if let Some(e) = &c.else_stmt {
+                calls(e, found);
+            }
+        }
+        ASTNode::ElseStmt(e) => calls(&e.block, found),
+        ASTNode::WhileStmt(w) => {
+            calls(&w.guard, found);
+            calls(&w.block, found);
+        }
+        ASTNode::RepeatStmt(r) => {
+            calls(&r.block, found);
+            calls(&r.guard, found);
+        }
+        ASTNode::PrintStmt(p) => p
+            .args
+            .iter()
+            .flat_map(|a| a.nodes())
+            .for_each(|n| calls(n, found)),
+        ASTNode::AssertStmt(a) => calls(&a.expr, found),
+        ASTNode::ReturnStmt(r) => {
+            if let Some(value) = &r.value {
+                calls(value, found);
+            }
+        }
+        _ => {}
+    }
+}
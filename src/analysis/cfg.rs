@@ -0,0 +1,577 @@
+use std::fmt::Write;
+
+use crate::{
+    core::{
+        ast::*,
+        objects::Object,
+        pretty,
+        token::{Kind, Token},
+        types::{SimpleType, Type},
+    },
+    scanner::position::Position,
+};
+
+/// How the control leaves a basic block
+#[derive(Clone, Debug)]
+pub enum Terminator {
+    /// Unconditional jump
+    Goto(usize),
+    /// Jump to `then` if the guard holds, to `otherwise` if not
+    Branch {
+        guard: ASTNode,
+        then: usize,
+        otherwise: usize,
+    },
+    /// Jump to the target whose labels (as source text) contain the
+    /// value, to `otherwise` if none does
+    Switch {
+        value: ASTNode,
+        targets: Vec<(String, usize)>,
+        otherwise: usize,
+    },
+    /// Return from the subprogram, the successor is the exit block
+    Return(ReturnStmtNode),
+    /// End of the subprogram, only for the exit block
+    Exit,
+}
+
+/// Straight sequence of statements with a single entry and a single
+/// exit, the kind names the role of the block in the statement that
+/// generated it, matching the labels emitted by the C backend
+#[derive(Clone, Debug)]
+pub struct BasicBlock {
+    pub id: usize,
+    pub kind: &'static str,
+    pub statements: Vec<ASTNode>,
+    pub terminator: Terminator,
+}
+
+/// Control flow graph of a subprogram or of the main block
+#[derive(Clone, Debug)]
+pub struct Cfg {
+    pub name: String,
+    pub blocks: Vec<BasicBlock>,
+    pub entry: usize,
+    pub exit: usize,
+}
+
+/// Binary expression over ints at the position of the loop
+fn int_binary(
+    l: &ForStmtNode,
+    (kind, lexeme, op_type): (Kind, &str, BinaryExprType),
+    right: ASTNode,
+    r_type: SimpleType,
+) -> ASTNode {
+    ASTNode::BinaryExpression(BinaryExprNode {
+        position: l.position,
+        left: l.variable.clone(),
+        op: Token {
+            kind,
+            lexeme: lexeme.to_string(),
+            position: l.position,
+        },
+        op_type,
+        right: Box::new(right),
+        r_type: Type::Simple(r_type),
+    })
+}
+
+/// The guard of a for loop and the step of its control variable
+fn for_parts(l: &ForStmtNode) -> (ASTNode, ASTNode) {
+    let (compare, step) = match l.downto {
+        false => (
+            (Kind::LessEqual, "<=", BinaryExprType::LogicLessThanEQ),
+            (Kind::Plus, "+", BinaryExprType::Addition),
+        ),
+        true => (
+            (Kind::GreaterEqual, ">=", BinaryExprType::LogicGreaterThanEQ),
+            (Kind::Minus, "-", BinaryExprType::Subtraction),
+        ),
+    };
+    let one = ASTNode::Literal(LiteralExprNode {
+        position: l.position,
+        value: Object::Int(1),
+        r_type: Type::Simple(SimpleType::Int),
+    });
+    let guard = int_binary(l, compare, l.end.as_ref().clone(), SimpleType::Bool);
+    let step = ASTNode::VarReassignment(VarReassignmentExprNode {
+        position: l.position,
+        variable_to_reassign: l.variable.clone(),
+        new_value: Box::new(int_binary(l, step, one, SimpleType::Int)),
+    });
+    (guard, step)
+}
+
+/// Builds the graph of a subprogram from its body
+struct Builder {
+    blocks: Vec<BasicBlock>,
+    exit: usize,
+    current: Option<usize>,
+    /// Loops being lowered, the innermost last: the blocks `continue`
+    /// and `break` jump to, and whether a reachable `continue` does
+    loops: Vec<(usize, usize, bool)>,
+}
+
+impl Builder {
+    fn new_block(&mut self, kind: &'static str) -> usize {
+        let id = self.blocks.len();
+        self.blocks.push(BasicBlock {
+            id,
+            kind,
+            statements: Vec::new(),
+            terminator: Terminator::Goto(self.exit),
+        });
+        id
+    }
+
+    /// Block statements are being added to, code following a return
+    /// goes in a fresh block that no other block jumps to
+    fn current(&mut self) -> usize {
+        match self.current {
+            Some(id) => id,
+            None => {
+                let id = self.new_block("dead");
+                self.current = Some(id);
+                id
+            }
+        }
+    }
+
+    /// Terminates the current block (if reachable from the previous
+    /// statement) and continues in `next`
+    fn seal(&mut self, terminator: Terminator, next: Option<usize>) {
+        if let Some(id) = self.current {
+            self.blocks[id].terminator = terminator;
+        }
+        self.current = next;
+    }
+
+    fn jump_to(&mut self, target: usize) {
+        self.seal(Terminator::Goto(target), Some(target));
+    }
+
+    /// Lowers the body of a loop, returns whether a reachable
+    /// `continue` in it jumps to `next`
+    fn lower_body(&mut self, body: &ASTNode, next: usize, exit: usize) -> bool {
+        self.loops.push((next, exit, false));
+        self.lower(body);
+        self.loops.pop().is_some_and(|(_, _, continued)| continued)
+    }
+
+    fn lower(&mut self, node: &ASTNode) {
+        match node {
+            ASTNode::Block(b) => b.statements.iter().for_each(|s| self.lower(s)),
+            ASTNode::IfStmt(i) => {
+                let head = self.current();
+                let then = self.new_block("then");
+                let endif = self.new_block("endif");
+                let otherwise = match &i.else_stmt {
+                    Some(_) => self.new_block("else"),
+                    None => endif,
+                };
+                self.blocks[head].terminator = Terminator::Branch {
+                    guard: i.guard.as_ref().clone(),
+                    then,
+                    otherwise,
+                };
+                self.current = Some(then);
+                self.lower(&i.then);
+                self.seal(Terminator::Goto(endif), None);
+                if let Some(else_stmt) = &i.else_stmt {
+                    self.current = Some(otherwise);
+                    self.lower(else_stmt);
+                    self.seal(Terminator::Goto(endif), None);
+                }
+                self.current = Some(endif);
+            }
+            ASTNode::CaseStmt(c) => {
+                let head = self.current();
+                let branches: Vec<usize> = c
+                    .branches
+                    .iter()
+                    .map(|_| self.new_block("branch"))
+                    .collect();
+                let endcase = self.new_block("endcase");
+                let otherwise = match &c.else_stmt {
+                    Some(_) => self.new_block("else"),
+                    None => endcase,
+                };
+                let targets = c
+                    .branches
+                    .iter()
+                    .zip(branches.iter())
+                    .map(|(b, id)| {
+                        let labels: Vec<String> = b
+                            .labels
+                            .iter()
+                            .map(|l| pretty::expression(&ASTNode::Literal(l.clone())))
+                            .collect();
+                        (labels.join(", "), *id)
+                    })
+                    .collect();
+                self.blocks[head].terminator = Terminator::Switch {
+                    value: c.value.as_ref().clone(),
+                    targets,
+                    otherwise,
+                };
+                for (b, id) in c.branches.iter().zip(branches) {
+                    self.current = Some(id);
+                    self.lower(&b.body);
+                    self.seal(Terminator::Goto(endcase), None);
+                }
+                if let Some(else_stmt) = &c.else_stmt {
+                    self.current = Some(otherwise);
+                    self.lower(else_stmt);
+                    self.seal(Terminator::Goto(endcase), None);
+                }
+                self.current = Some(endcase);
+            }
+            ASTNode::ElseStmt(e) => self.lower(&e.block),
+            ASTNode::WhileStmt(w) => {
+                self.current();
+                let guard = self.new_block("guard");
+                let body = self.new_block("body");
+                let endwhile = self.new_block("endwhile");
+                self.jump_to(guard);
+                self.seal(
+                    Terminator::Branch {
+                        guard: w.guard.as_ref().clone(),
+                        then: body,
+                        otherwise: endwhile,
+                    },
+                    Some(body),
+                );
+                self.lower_body(&w.block, guard, endwhile);
+                self.seal(Terminator::Goto(guard), Some(endwhile));
+            }
+            ASTNode::RepeatStmt(r) => {
+                self.current();
+                let body = self.new_block("repeat");
+                let until = self.new_block("until");
+                let endrepeat = self.new_block("endrepeat");
+                self.jump_to(body);
+                self.lower_body(&r.block, until, endrepeat);
+                self.jump_to(until);
+                self.seal(
+                    Terminator::Branch {
+                        guard: r.guard.as_ref().clone(),
+                        then: endrepeat,
+                        otherwise: body,
+                    },
+                    Some(endrepeat),
+                );
+            }
+            ASTNode::BreakStmt(_) => {
+                self.current();
+                if let Some(&(_, exit, _)) = self.loops.last() {
+                    self.seal(Terminator::Goto(exit), None);
+                }
+            }
+            ASTNode::ContinueStmt(_) => {
+                let reachable = self.current.is_some();
+                self.current();
+                if let Some((next, _, continued)) = self.loops.last_mut() {
+                    *continued |= reachable;
+                    let next = *next;
+                    self.seal(Terminator::Goto(next), None);
+                }
+            }
+            ASTNode::ForStmt(l) => {
+                let head = self.current();
+                self.blocks[head].statements.push(ASTNode::VarReassignment(
+                    VarReassignmentExprNode {
+                        position: l.position,
+                        variable_to_reassign: l.variable.clone(),
+                        new_value: l.start.clone(),
+                    },
+                ));
+                let (guard, step) = for_parts(l);
+                let guard_block = self.new_block("for");
+                let body = self.new_block("body");
+                let step_block = self.new_block("step");
+                let endfor = self.new_block("endfor");
+                self.jump_to(guard_block);
+                self.seal(
+                    Terminator::Branch {
+                        guard,
+                        then: body,
+                        otherwise: endfor,
+                    },
+                    Some(body),
+                );
+                let continued = self.lower_body(&l.block, step_block, endfor);
+                if self.current.is_some() {
+                    self.jump_to(step_block);
+                } else if continued {
+                    self.current = Some(step_block);
+                }
+                // the step is only there if something reaches it, a
+                // body ending in a return has none
+                if self.current.is_some() {
+                    self.blocks[step_block].statements.push(step);
+                }
+                self.blocks[step_block].terminator = Terminator::Goto(guard_block);
+                self.seal(Terminator::Goto(guard_block), Some(endfor));
+            }
+            ASTNode::ReturnStmt(r) => {
+                self.current();
+                self.seal(Terminator::Return(r.clone()), None);
+            }
+            other => {
+                let id = self.current();
+                self.blocks[id].statements.push(other.clone());
+            }
+        }
+    }
+}
+
+impl Cfg {
+    /// Builds the graph of the given body, named after the subprogram
+    pub fn build(name: &str, body: &ASTNode) -> Cfg {
+        let mut builder = Builder {
+            blocks: Vec::new(),
+            exit: 1,
+            current: None,
+            loops: Vec::new(),
+        };
+        let entry = builder.new_block("entry");
+        let exit = builder.new_block("exit");
+        builder.blocks[exit].terminator = Terminator::Exit;
+        builder.current = Some(entry);
+        builder.lower(body);
+        builder.seal(Terminator::Goto(exit), None);
+        Cfg {
+            name: name.to_string(),
+            blocks: builder.blocks,
+            entry,
+            exit,
+        }
+    }
+
+    /// Builds the graphs of all functions, procedures and of the main
+    /// block of a program, in this order
+    pub fn build_program(program: &ProgramNode) -> Vec<Cfg> {
+        let mut graphs = Vec::new();
+        for f in program.functions.iter() {
+            graphs.push(Cfg::build(&format!("function {}", f.name), &f.block));
+        }
+        for p in program.procedures.iter() {
+            graphs.push(Cfg::build(&format!("procedure {}", p.name), &p.block));
+        }
+        graphs.push(Cfg::build(
+            "main",
+            &ASTNode::Block(program.main_block.clone()),
+        ));
+        graphs
+    }
+
+    /// Blocks the control can go to after the block, a branch on a
+    /// constant guard (as in `while true`) only goes one way
+    pub fn successors(&self, id: usize) -> Vec<usize> {
+        match &self.blocks[id].terminator {
+            Terminator::Goto(target) => vec![*target],
+            Terminator::Branch {
+                guard,
+                then,
+                otherwise,
+            } => match guard {
+                ASTNode::Literal(LiteralExprNode {
+                    value: Object::Bool(true),
+                    ..
+                }) => vec![*then],
+                ASTNode::Literal(LiteralExprNode {
+                    value: Object::Bool(false),
+                    ..
+                }) => vec![*otherwise],
+                _ => vec![*then, *otherwise],
+            },
+            Terminator::Switch {
+                targets, otherwise, ..
+            } => targets
+                .iter()
+                .map(|(_, t)| *t)
+                .chain([*otherwise])
+                .collect(),
+            Terminator::Return(_) => vec![self.exit],
+            Terminator::Exit => vec![],
+        }
+    }
+
+    /// Blocks reachable from `from`, indexed by block id
+    pub fn reachable_from(&self, from: usize) -> Vec<bool> {
+        let mut seen = vec![false; self.blocks.len()];
+        let mut stack = vec![from];
+        while let Some(id) = stack.pop() {
+            if !seen[id] {
+                seen[id] = true;
+                stack.extend(self.successors(id));
+            }
+        }
+        seen
+    }
+
+    /// True if the exit can be reached without executing a return
+    pub fn falls_off_end(&self) -> bool {
+        let reachable = self.reachable_from(self.entry);
+        self.blocks.iter().any(|b| {
+            reachable[b.id] && matches!(b.terminator, Terminator::Goto(t) if t == self.exit)
+        })
+    }
+
+    /// Position of the first thing executed by the block, if it
+    /// executes anything at all
+    pub fn block_position(&self, id: usize) -> Option<Position> {
+        let block = &self.blocks[id];
+        match (block.statements.first(), &block.terminator) {
+            (Some(stmt), _) => Some(stmt.position()),
+            (None, Terminator::Branch { guard, .. }) => Some(guard.position()),
+            (None, Terminator::Switch { value, .. }) => Some(value.position()),
+            (None, Terminator::Return(r)) => Some(r.token.position),
+            _ => None,
+        }
+    }
+
+    /// Positions of the code that can never be executed, one for each
+    /// region of unreachable blocks
+    pub fn unreachable_code(&self) -> Vec<Position> {
+        let reachable = self.reachable_from(self.entry);
+        let mut covered = vec![false; self.blocks.len()];
+        let mut positions = Vec::new();
+        for block in self.blocks.iter() {
+            if reachable[block.id] || covered[block.id] {
+                continue;
+            }
+            if let Some(position) = self.block_position(block.id) {
+                positions.push(position);
+                for (id, r) in self.reachable_from(block.id).iter().enumerate() {
+                    covered[id] |= *r;
+                }
+            }
+        }
+        positions
+    }
+
+    fn block_text(&self, block: &BasicBlock) -> Vec<String> {
+        let mut lines: Vec<String> = block.statements.iter().map(pretty::statement).collect();
+        match &block.terminator {
+            Terminator::Branch { guard, .. } => {
+                lines.push(format!("if {}", pretty::expression(guard)))
+            }
+            Terminator::Switch { value, .. } => {
+                lines.push(format!("case {} of", pretty::expression(value)))
+            }
+            Terminator::Return(r) => lines.push(pretty::statement(&ASTNode::ReturnStmt(r.clone()))),
+            _ => {}
+        }
+        lines
+    }
+
+    /// Plain text listing of the blocks and their jumps
+    pub fn to_text(&self) -> String {
+        let mut out = format!("{}:\n", self.name);
+        for block in self.blocks.iter() {
+            let _ = writeln!(out, "  B{} ({}):", block.id, block.kind);
+            for line in self.block_text(block) {
+                let _ = writeln!(out, "    {}", line);
+            }
+            let _ = match &block.terminator {
+                Terminator::Goto(t) => writeln!(out, "    goto B{}", t),
+                Terminator::Branch {
+                    then, otherwise, ..
+                } => writeln!(out, "    then B{} else B{}", then, otherwise),
+                Terminator::Switch {
+                    targets, otherwise, ..
+                } => {
+                    for (labels, t) in targets.iter() {
+                        let _ = writeln!(out, "    {}: B{}", labels, t);
+                    }
+                    writeln!(out, "    else B{}", otherwise)
+                }
+                Terminator::Return(_) => writeln!(out, "    goto B{}", self.exit),
+                Terminator::Exit => Ok(()),
+            };
+        }
+        out
+    }
+
+    /// Graphviz cluster with a node per block, `index` makes the node
+    /// names unique when more graphs are in the same file
+    pub fn to_dot_cluster(&self, index: usize) -> String {
+        let escape = |s: &str| s.replace('\\', "\\\\").replace('"', "\\\"");
+        let node = |id: usize| format!("g{}_b{}", index, id);
+        let mut out = String::new();
+        let _ = writeln!(out, "  subgraph cluster_{} {{", index);
+        let _ = writeln!(out, "    label=\"{}\";", escape(&self.name));
+        for block in self.blocks.iter() {
+            let mut label = format!("B{} ({})\\l", block.id, block.kind);
+            for line in self.block_text(block) {
+                label += &escape(&line);
+                label += "\\l";
+            }
+            // record shapes would read `|`, `{` and `<` in the label
+            let style = if block.id == self.entry || block.id == self.exit {
+                ", style=rounded"
+            } else {
+                ""
+            };
+            let _ = writeln!(
+                out,
+                "    {} [shape=box{}, label=\"{}\"];",
+                node(block.id),
+                style,
+                label
+            );
+        }
+        for block in self.blocks.iter() {
+            let _ = match &block.terminator {
+                Terminator::Branch {
+                    then, otherwise, ..
+                } => writeln!(
+                    out,
+                    "    {} -> {} [label=\"true\"];\n    {} -> {} [label=\"false\"];",
+                    node(block.id),
+                    node(*then),
+                    node(block.id),
+                    node(*otherwise)
+                ),
+                Terminator::Switch {
+                    targets, otherwise, ..
+                } => {
+                    for (labels, t) in targets.iter() {
+                        let _ = writeln!(
+                            out,
+                            "    {} -> {} [label=\"{}\"];",
+                            node(block.id),
+                            node(*t),
+                            escape(labels)
+                        );
+                    }
+                    writeln!(
+                        out,
+                        "    {} -> {} [label=\"else\"];",
+                        node(block.id),
+                        node(*otherwise)
+                    )
+                }
+                Terminator::Return(_) => writeln!(
+                    out,
+                    "    {} -> {} [style=dashed];",
+                    node(block.id),
+                    node(self.exit)
+                ),
+                Terminator::Goto(t) => writeln!(out, "    {} -> {};", node(block.id), node(*t)),
+                Terminator::Exit => Ok(()),
+            };
+        }
+        out + "  }\n"
+    }
+}
+
+/// Graphviz digraph with a cluster for each of the given graphs
+pub fn to_dot(graphs: &[Cfg]) -> String {
+    let mut out = "digraph cfg {\n  node [fontname=\"monospace\"];\n".to_string();
+    for (index, graph) in graphs.iter().enumerate() {
+        out += &graph.to_dot_cluster(index);
+    }
+    out + "}\n"
+}
@@ -0,0 +1,531 @@
+use std::collections::{HashMap, HashSet};
+
+use super::{cfg::Cfg, lints};
+use crate::{
+    core::{
+        ast::*,
+        errors::{CompilationError, Warning},
+        lints::{Lint, LintConfig, LintLevel},
+        symbol_table::{SymbolTable, SymbolType},
+        types::Type,
+    },
+    scanner::position::Position,
+};
+
+/// Variables definitely assigned at a point of the program, `None`
+/// if the point can not be reached (e.g. right after a `return`)
+type Assigned = Option<HashSet<usize>>;
+
+/// Merges the states of two paths that join, a variable is definitely
+/// assigned only if it is on both paths
+fn join(a: Assigned, b: Assigned) -> Assigned {
+    match (a, b) {
+        (None, other) | (other, None) => other,
+        (Some(a), Some(b)) => Some(a.intersection(&b).copied().collect()),
+    }
+}
+
+/// Collects all the return statements nested in the node
+fn returns<'a>(node: &'a ASTNode, found: &mut Vec<&'a ReturnStmtNode>) {
+    match node {
+        ASTNode::ReturnStmt(r) => found.push(r),
+        ASTNode::Block(b) => b.statements.iter().for_each(|s| returns(s, found)),
+        ASTNode::IfStmt(i) => {
+            returns(&i.then, found);
+            if let Some(e) = &i.else_stmt {
+                returns(e, found);
+            }
+        }
+        ASTNode::CaseStmt(c) => {
+            c.branches.iter().for_each(|b| returns(&b.body, found));
+            if let Some(e) = &c.else_stmt {
+                returns(e, found);
+            }
+        }
+        ASTNode::ElseStmt(e) => returns(&e.block, found),
+        ASTNode::WhileStmt(w) => returns(&w.block, found),
+        ASTNode::ForStmt(l) => returns(&l.block, found),
+        ASTNode::RepeatStmt(r) => returns(&r.block, found),
+        _ => {}
+    }
+}
+
+/// Checks that every function returns a value of its return type on
+/// every path, and that procedures never return a value
+pub fn check_returns(program: &ProgramNode) -> Vec<CompilationError> {
+    let mut errors = Vec::new();
+    for f in program.functions.iter() {
+        let mut found = Vec::new();
+        returns(&f.block, &mut found);
+        for ret in found {
+            match &ret.value {
+                Some(value) if value.r_type() != f.r_type => errors.push(CompilationError {
+                    position: ret.token.position,
+                    description: format!(
+                        "function `{}` returns {}, found a return of type {}",
+                        f.name,
+                        f.r_type,
+                        value.r_type()
+                    ),
+                }),
+                Some(_) => {}
+                None => errors.push(CompilationError {
+                    position: ret.token.position,
+                    description: format!(
+                        "function `{}` must return a value of type {}",
+                        f.name, f.r_type
+                    ),
+                }),
+            }
+        }
+        if Cfg::build(&f.name, &f.block).falls_off_end() {
+            errors.push(CompilationError {
+                position: f.position,
+                description: format!(
+                    "function `{}` can reach its end without returning a value",
+                    f.name
+                ),
+            });
+        }
+    }
+    for p in program.procedures.iter() {
+        let mut found = Vec::new();
+        returns(&p.block, &mut found);
+        for ret in found.iter().filter(|r| r.value.is_some()) {
+            errors.push(CompilationError {
+                position: ret.token.position,
+                description: format!("procedure `{}` can not return a value", p.name),
+            });
+        }
+    }
+    errors
+}
+
+/// Walks the program keeping the control variables of the enclosing
+/// for loops, the statements changing one of them are errors
+struct ForLoops {
+    /// Parameters of every subprogram, by lowercase name
+    params: HashMap<String, SymbolTable>,
+    controls: Vec<String>,
+    errors: Vec<CompilationError>,
+}
+
+/// Checks that the body of every for loop leaves its control variable
+/// alone: it can not be assigned, read into, passed to a `var`
+/// parameter or used as control variable of a nested loop
+pub fn check_for_loops(program: &ProgramNode) -> Vec<CompilationError> {
+    let mut check = ForLoops {
+        params: lints::subprogram_params(program),
+        controls: Vec::new(),
+        errors: Vec::new(),
+    };
+    for f in program.functions.iter() {
+        check.node(&f.block);
+    }
+    for p in program.procedures.iter() {
+        check.node(&p.block);
+    }
+    check.node(&ASTNode::Block(program.main_block.clone()));
+    check.errors
+}
+
+impl ForLoops {
+    /// Reports the target if it is the control variable of an
+    /// enclosing loop
+    fn changed(&mut self, target: &ASTNode, how: &str) {
+        if let ASTNode::VarName(v) = target {
+            if self.controls.contains(&v.id.lexeme.to_lowercase()) {
+                self.errors.push(CompilationError {
+                    position: v.position,
+                    description: format!(
+                        "`{}` is the control variable of a for loop, it can not be {}",
+                        v.id.lexeme, how
+                    ),
+                });
+            }
+        }
+    }
+
+    /// Checks the `var` arguments of a call, and the variables a
+    /// nested subprogram changes without them being arguments
+    fn call(&mut self, target: &str, position: Position, args: &[(String, ASTNode)]) {
+        let params = self
+            .params
+            .get(&target.to_lowercase())
+            .cloned()
+            .unwrap_or_default();
+        for (param, (_, arg)) in params.iter().zip(args.iter()) {
+            if param.s_type == SymbolType::VarParam {
+                self.changed(arg, "passed as a var argument in its body");
+            }
+        }
+        for param in params.iter().skip(args.len()) {
+            let name = param.name.to_lowercase();
+            if param.s_type == SymbolType::VarParam && self.controls.contains(&name) {
+                self.errors.push(CompilationError {
+                    position,
+                    description: format!(
+                        "`{}` is the control variable of a for loop, it can not be changed by `{}` in its body",
+                        param.name, target
+                    ),
+                });
+            }
+        }
+        args.iter().for_each(|(_, a)| self.node(a));
+    }
+
+    fn node(&mut self, node: &ASTNode) {
+        match node {
+            ASTNode::Block(b) => b.statements.iter().for_each(|s| self.node(s)),
+            ASTNode::ForStmt(l) => {
+                self.changed(&l.variable, "the control variable of a nested loop");
+                self.node(&l.start);
+                self.node(&l.end);
+                if let ASTNode::VarName(v) = l.variable.as_ref() {
+                    self.controls.push(v.id.lexeme.to_lowercase());
+                    self.node(&l.block);
+                    self.controls.pop();
+                }
+            }
+            ASTNode::VarReassignment(v) => {
+                self.changed(&v.variable_to_reassign, "assigned in its body");
+                self.node(&v.variable_to_reassign);
+                self.node(&v.new_value);
+            }
+            ASTNode::ReadStmt(r) => {
+                self.changed(&r.variable_to_read_in, "read in its body");
+                self.node(&r.variable_to_read_in);
+            }
+            ASTNode::ProcedureCallStmt(p) => self.call(&p.target, p.position, &p.args),
+            ASTNode::FunctionCallStmt(f) => self.call(&f.target, f.position, &f.args),
+            ASTNode::ArrayRef(a) => a.indexes.iter().for_each(|i| self.node(i)),
+            ASTNode::FieldRef(f) => self.node(&f.record),
+            ASTNode::BinaryExpression(b) => {
+                self.node(&b.left);
+                self.node(&b.right);
+            }
+            ASTNode::UnaryExpression(u) => self.node(&u.expression),
+            ASTNode::IfStmt(i) => {
+                self.node(&i.guard);
+                self.node(&i.then);
+                if let Some(e) = &i.else_stmt {
+                    self.node(e);
+                }
+            }
+            ASTNode::CaseStmt(c) => {
+                self.node(&c.value);
+                c.branches.iter().for_each(|b| self.node(&b.body));
+                if let Some(e) = &c.else_stmt {
+                    self.node(e);
+                }
+            }
+            ASTNode::ElseStmt(e) => self.node(&e.block),
+            ASTNode::WhileStmt(w) => {
+                self.node(&w.guard);
+                self.node(&w.block);
+            }
+            ASTNode::RepeatStmt(r) => {
+                self.node(&r.block);
+                self.node(&r.guard);
+            }
+            ASTNode::PrintStmt(p) => p
+                .args
+                .iter()
+                .flat_map(|a| a.nodes())
+                .for_each(|n| self.node(n)),
+            ASTNode::AssertStmt(a) => self.node(&a.expr),
+            ASTNode::ReturnStmt(r) => {
+                if let Some(value) = &r.value {
+                    self.node(value);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Definite assignment analysis, follows every path through the
+/// subprograms and the main block and reports reads of variables
+/// that are not assigned on all the paths leading to them
+struct DefiniteAssignment<'a> {
+    config: &'a LintConfig,
+    /// Parameters of every subprogram, by lowercase name
+    params: HashMap<String, SymbolTable>,
+    scopes: Vec<Vec<(String, usize, bool)>>,
+    /// States at the `break` and at the `continue` statements of the
+    /// loops being analysed, joined
+    loops: Vec<(Assigned, Assigned)>,
+    next_id: usize,
+    reported: HashSet<usize>,
+    warnings: Vec<Warning>,
+}
+
+/// Reports, through the `uninitialized-read` lint, the variables that
+/// are used before being definitely assigned
+pub fn uninitialized_reads(program: &ProgramNode, config: &LintConfig) -> Vec<Warning> {
+    let mut analysis = DefiniteAssignment {
+        config,
+        params: lints::subprogram_params(program),
+        scopes: Vec::new(),
+        loops: Vec::new(),
+        next_id: 0,
+        reported: HashSet::new(),
+        warnings: Vec::new(),
+    };
+    for f in program.functions.iter() {
+        analysis.subprogram(&f.args, &f.block);
+    }
+    for p in program.procedures.iter() {
+        analysis.subprogram(&p.args, &p.block);
+    }
+    analysis.stmt(
+        &ASTNode::Block(program.main_block.clone()),
+        Some(HashSet::new()),
+    );
+    analysis.warnings
+}
+
+impl<'a> DefiniteAssignment<'a> {
+    fn declare(&mut self, name: &str, r_type: Type) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        if let Some(scope) = self.scopes.last_mut() {
+            // records start with the initial value of every field
+            let tracked = matches!(r_type, Type::Simple(s) if !s.is_record());
+            scope.push((name.to_lowercase(), id, tracked));
+        }
+        id
+    }
+
+    fn lookup(&self, name: &str) -> Option<(usize, bool)> {
+        let name = name.to_lowercase();
+        self.scopes.iter().rev().find_map(|scope| {
+            scope
+                .iter()
+                .rev()
+                .find(|(n, _, _)| *n == name)
+                .map(|(_, id, simple)| (*id, *simple))
+        })
+    }
+
+    fn subprogram(&mut self, args: &SymbolTable, block: &ASTNode) {
+        self.scopes.push(Vec::new());
+        let mut assigned = HashSet::new();
+        for arg in args.iter() {
+            assigned.insert(self.declare(&arg.name, arg.r_type.clone()));
+        }
+        self.stmt(block, Some(assigned));
+        self.scopes.pop();
+    }
+
+    fn read(&mut self, name: &str, position: Position, state: &Assigned) {
+        let level = self.config.level(Lint::UninitializedRead);
+        if let (Some(assigned), Some((id, true))) = (state, self.lookup(name)) {
+            if !assigned.contains(&id) && level != LintLevel::Allow && self.reported.insert(id) {
+                self.warnings.push(Warning {
+                    position,
+                    lint: Lint::UninitializedRead,
+                    level,
+                    description: format!("variable `{}` may be read before being assigned", name),
+                });
+            }
+        }
+    }
+
+    fn assign(&self, target: &ASTNode, state: &mut Assigned) {
+        if let (ASTNode::VarName(v), Some(assigned)) = (target, state) {
+            if let Some((id, _)) = self.lookup(&v.id.lexeme) {
+                assigned.insert(id);
+            }
+        }
+    }
+
+    /// Marks as assigned what the calls in the node can change, their
+    /// `var` arguments and the variables captured by nested subprograms
+    fn calls(&self, node: &ASTNode, state: &mut Assigned) {
+        let mut names = Vec::new();
+        lints::assigned_variables(node, &self.params, &mut names);
+        if let Some(assigned) = state {
+            for name in names {
+                if let Some((id, _)) = self.lookup(&name) {
+                    assigned.insert(id);
+                }
+            }
+        }
+    }
+
+    /// Reads the indexes in the target of an assignment
+    fn target(&mut self, target: &ASTNode, state: &Assigned) {
+        match target {
+            ASTNode::ArrayRef(a) => a.indexes.iter().for_each(|i| self.expr(i, state)),
+            ASTNode::FieldRef(f) => self.target(&f.record, state),
+            _ => {}
+        }
+    }
+
+    fn expr(&mut self, node: &ASTNode, state: &Assigned) {
+        match node {
+            ASTNode::VarName(v) => match v.s_type {
+                SymbolType::Function | SymbolType::Procedure => {}
+                _ => self.read(&v.id.lexeme, v.position, state),
+            },
+            ASTNode::ArrayRef(a) => a.indexes.iter().for_each(|i| self.expr(i, state)),
+            ASTNode::FieldRef(f) => self.expr(&f.record, state),
+            ASTNode::BinaryExpression(b) => {
+                self.expr(&b.left, state);
+                self.expr(&b.right, state);
+            }
+            ASTNode::UnaryExpression(u) => self.expr(&u.expression, state),
+            ASTNode::FunctionCallStmt(f) => self.args(&f.target, &f.args, state),
+            _ => {}
+        }
+    }
+
+    /// Reads the arguments of a call, those given to `var` parameters
+    /// are written by the call and can be unassigned before it
+    fn args(&mut self, target: &str, args: &[(String, ASTNode)], state: &Assigned) {
+        let params = self.params.get(&target.to_lowercase()).cloned();
+        for (i, (_, arg)) in args.iter().enumerate() {
+            match params.as_ref().and_then(|p| p.iter().nth(i)) {
+                Some(param) if param.s_type == SymbolType::VarParam => self.target(arg, state),
+                _ => self.expr(arg, state),
+            }
+        }
+    }
+
+    /// Analyses the body of a loop, returns the states at its end, at
+    /// its `break` and at its `continue` statements
+    fn body(&mut self, block: &ASTNode, state: Assigned) -> (Assigned, Assigned, Assigned) {
+        self.loops.push((None, None));
+        let end = self.stmt(block, state);
+        let (breaks, continues) = self.loops.pop().unwrap_or_default();
+        (end, breaks, continues)
+    }
+
+    /// Analyses a statement reached with the given state, returns the
+    /// state after it
+    fn stmt(&mut self, node: &ASTNode, mut state: Assigned) -> Assigned {
+        match node {
+            ASTNode::Block(b) => {
+                self.scopes.push(Vec::new());
+                for s in b.statements.iter() {
+                    state = self.stmt(s, state);
+                }
+                self.scopes.pop();
+                state
+            }
+            ASTNode::VariableDecl(decl) => {
+                let id = self.declare(&decl.id.lexeme, decl.var_type.clone());
+                if let Some(assigned) = state.as_mut() {
+                    assigned.remove(&id);
+                }
+                state
+            }
+            ASTNode::VarReassignment(v) => {
+                self.expr(&v.new_value, &state);
+                self.calls(&v.new_value, &mut state);
+                self.target(&v.variable_to_reassign, &state);
+                self.assign(&v.variable_to_reassign, &mut state);
+                state
+            }
+            ASTNode::ReadStmt(r) => {
+                self.target(&r.variable_to_read_in, &state);
+                self.assign(&r.variable_to_read_in, &mut state);
+                state
+            }
+            ASTNode::IfStmt(i) => {
+                self.expr(&i.guard, &state);
+                self.calls(&i.guard, &mut state);
+                let then_state = self.stmt(&i.then, state.clone());
+                let else_state = match &i.else_stmt {
+                    Some(e) => self.stmt(e, state),
+                    None => state,
+                };
+                join(then_state, else_state)
+            }
+            ASTNode::CaseStmt(c) => {
+                self.expr(&c.value, &state);
+                self.calls(&c.value, &mut state);
+                // Without an else the statement can run no branch
+                let mut after = match &c.else_stmt {
+                    Some(e) => self.stmt(e, state.clone()),
+                    None => state.clone(),
+                };
+                for b in c.branches.iter() {
+                    let branch = self.stmt(&b.body, state.clone());
+                    after = join(after, branch);
+                }
+                after
+            }
+            ASTNode::ElseStmt(e) => self.stmt(&e.block, state),
+            ASTNode::WhileStmt(w) => {
+                self.expr(&w.guard, &state);
+                self.calls(&w.guard, &mut state);
+                // The body might never run, what it assigns is not
+                // definitely assigned after the loop
+                self.body(&w.block, state.clone());
+                state
+            }
+            ASTNode::RepeatStmt(r) => {
+                // The body runs at least once, the loop ends after it
+                // or at a break
+                let (end, breaks, continues) = self.body(&r.block, state);
+                let mut end = join(end, continues);
+                self.expr(&r.guard, &end);
+                self.calls(&r.guard, &mut end);
+                join(end, breaks)
+            }
+            ASTNode::BreakStmt(_) => {
+                if let Some((breaks, _)) = self.loops.last_mut() {
+                    *breaks = join(breaks.take(), state);
+                }
+                None
+            }
+            ASTNode::ContinueStmt(_) => {
+                if let Some((_, continues)) = self.loops.last_mut() {
+                    *continues = join(continues.take(), state);
+                }
+                None
+            }
+            ASTNode::ForStmt(l) => {
+                self.expr(&l.start, &state);
+                self.expr(&l.end, &state);
+                self.calls(&l.start, &mut state);
+                self.calls(&l.end, &mut state);
+                self.assign(&l.variable, &mut state);
+                // The control variable always gets the start, the rest
+                // is like in a while loop
+                self.body(&l.block, state.clone());
+                state
+            }
+            ASTNode::ReturnStmt(r) => {
+                if let Some(value) = &r.value {
+                    self.expr(value, &state);
+                }
+                None
+            }
+            ASTNode::PrintStmt(p) => {
+                for n in p.args.iter().flat_map(|a| a.nodes()) {
+                    self.expr(n, &state);
+                    self.calls(n, &mut state);
+                }
+                state
+            }
+            ASTNode::AssertStmt(a) => {
+                self.expr(&a.expr, &state);
+                self.calls(&a.expr, &mut state);
+                state
+            }
+            ASTNode::ProcedureCallStmt(p) => {
+                self.args(&p.target, &p.args, &state);
+                self.calls(node, &mut state);
+                state
+            }
+            other => {
+                self.expr(other, &state);
+                self.calls(other, &mut state);
+                state
+            }
+        }
+    }
+}
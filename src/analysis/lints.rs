@@ -0,0 +1,476 @@
+use std::collections::HashMap;
+
+use log::trace;
+
+use super::{cfg::Cfg, flow};
+use crate::{
+    core::{
+        ast::*,
+        errors::Warning,
+        lints::{Lint, LintConfig, LintLevel},
+        symbol_table::{Symbol, SymbolTable, SymbolType},
+    },
+    scanner::position::Position,
+};
+
+/// What the linter knows about a declared variable or parameter
+struct VarInfo {
+    name: String,
+    position: Position,
+    is_param: bool,
+    read: bool,
+}
+
+/// Walks the AST of a program keeping track of declarations and uses
+/// of variables, scope by scope, collecting the warnings of the lints
+/// that are not allowed by the configuration
+struct Linter<'a> {
+    config: &'a LintConfig,
+    scopes: Vec<Vec<VarInfo>>,
+    warnings: Vec<Warning>,
+    /// Parameters of every subprogram, by lowercase name
+    params: HashMap<String, SymbolTable>,
+}
+
+/// Runs all the lints on the given program, returns the diagnostics
+/// sorted by position
+pub fn check(program: &ProgramNode, config: &LintConfig) -> Vec<Warning> {
+    let mut linter = Linter {
+        config,
+        scopes: Vec::new(),
+        warnings: Vec::new(),
+        params: subprogram_params(program),
+    };
+    linter.push_scope();
+    for global in program.globals.iter() {
+        linter.declare(&global.id.lexeme, global.position, false);
+    }
+    for f in program.functions.iter() {
+        linter.lint_function(f);
+    }
+    for p in program.procedures.iter() {
+        linter.lint_procedure(p);
+    }
+    linter.lint_node(&ASTNode::Block(program.main_block.clone()));
+    linter.pop_scope();
+    for graph in Cfg::build_program(program) {
+        for position in graph.unreachable_code() {
+            linter.warn(
+                Lint::UnreachableCode,
+                position,
+                "unreachable code".to_string(),
+            );
+        }
+    }
+    linter
+        .warnings
+        .extend(flow::uninitialized_reads(program, config));
+    linter
+        .warnings
+        .sort_by_key(|w| (w.position.line, w.position.col));
+    linter.warnings
+}
+
+/// True if the node, or any node nested in it, is a return or a break
+/// statement
+fn contains_exit(node: &ASTNode) -> bool {
+    match node {
+        ASTNode::ReturnStmt(_) | ASTNode::BreakStmt(_) => true,
+        ASTNode::Block(b) => b.statements.iter().any(contains_exit),
+        ASTNode::IfStmt(i) => {
+            contains_exit(&i.then) || i.else_stmt.as_ref().is_some_and(|e| contains_exit(e))
+        }
+        ASTNode::CaseStmt(c) => {
+            c.branches.iter().any(|b| contains_exit(&b.body))
+                || c.else_stmt.as_ref().is_some_and(|e| contains_exit(e))
+        }
+        ASTNode::ElseStmt(e) => contains_exit(&e.block),
+        ASTNode::WhileStmt(w) => contains_exit(&w.block),
+        ASTNode::ForStmt(l) => contains_exit(&l.block),
+        ASTNode::RepeatStmt(r) => contains_exit(&r.block),
+        _ => false,
+    }
+}
+
+/// Collects the names of the variables read by an expression, None if
+/// the expression calls a function (whose result can change at each
+/// evaluation)
+fn guard_variables(node: &ASTNode, names: &mut Vec<String>) -> Option<()> {
+    match node {
+        ASTNode::VarName(v) => names.push(v.id.lexeme.to_lowercase()),
+        ASTNode::ArrayRef(a) => {
+            names.push(a.array.lexeme.to_lowercase());
+            for index in a.indexes.iter() {
+                guard_variables(index, names)?;
+            }
+        }
+        ASTNode::BinaryExpression(b) => {
+            guard_variables(&b.left, names)?;
+            guard_variables(&b.right, names)?;
+        }
+        ASTNode::UnaryExpression(u) => guard_variables(&u.expression, names)?,
+        ASTNode::FieldRef(f) => guard_variables(&f.record, names)?,
+        ASTNode::FunctionCallStmt(_) => return None,
+        _ => {}
+    }
+    Some(())
+}
+
+/// Name of the variable an assignment writes to
+fn target_name(target: &ASTNode) -> Option<String> {
+    match target {
+        ASTNode::VarName(v) => Some(v.id.lexeme.to_lowercase()),
+        ASTNode::ArrayRef(a) => Some(a.array.lexeme.to_lowercase()),
+        ASTNode::FieldRef(f) => target_name(&f.record),
+        _ => None,
+    }
+}
+
+/// Parameters of the target of a call without an argument, the
+/// variables captured by a nested subprogram
+fn captured<'p>(
+    params: &'p HashMap<String, SymbolTable>,
+    target: &str,
+    args: &[(String, ASTNode)],
+) -> impl Iterator<Item = &'p Symbol> {
+    let explicit = args.len();
+    params
+        .get(&target.to_lowercase())
+        .into_iter()
+        .flat_map(move |p| p.iter().skip(explicit))
+}
+
+/// Parameters of every subprogram, by lowercase name
+pub(super) fn subprogram_params(program: &ProgramNode) -> HashMap<String, SymbolTable> {
+    program
+        .functions
+        .iter()
+        .map(|f| (f.name.to_lowercase(), f.args.clone()))
+        .chain(
+            program
+                .procedures
+                .iter()
+                .map(|p| (p.name.to_lowercase(), p.args.clone())),
+        )
+        .collect()
+}
+
+/// Collects the variables a call can change: the arguments given to
+/// `var` parameters and the captured variables of a nested subprogram
+fn call_assignments(
+    params: &HashMap<String, SymbolTable>,
+    target: &str,
+    args: &[(String, ASTNode)],
+    names: &mut Vec<String>,
+) {
+    if let Some(declared) = params.get(&target.to_lowercase()) {
+        for (param, (_, arg)) in declared.iter().zip(args.iter()) {
+            if param.s_type == SymbolType::VarParam {
+                names.extend(target_name(arg));
+            }
+        }
+    }
+    names.extend(
+        captured(params, target, args)
+            .filter(|c| c.s_type != SymbolType::Param)
+            .map(|c| c.name.to_lowercase()),
+    );
+    args.iter()
+        .for_each(|(_, a)| assigned_variables(a, params, names));
+}
+
+/// Collects the names of the variables written by a statement, the
+/// subprograms it calls (in expressions too) can change their `var`
+/// arguments and their captured variables
+pub(super) fn assigned_variables(
+    node: &ASTNode,
+    params: &HashMap<String, SymbolTable>,
+    names: &mut Vec<String>,
+) {
+    let mut walk = |node: &ASTNode| assigned_variables(node, params, names);
+    match node {
+        ASTNode::VarReassignment(v) => {
+            names.extend(target_name(&v.variable_to_reassign));
+            assigned_variables(&v.variable_to_reassign, params, names);
+            assigned_variables(&v.new_value, params, names);
+        }
+        ASTNode::ReadStmt(r) => {
+            names.extend(target_name(&r.variable_to_read_in));
+            assigned_variables(&r.variable_to_read_in, params, names);
+        }
+        ASTNode::Block(b) => b.statements.iter().for_each(walk),
+        ASTNode::IfStmt(i) => {
+            walk(&i.guard);
+            walk(&i.then);
+            if let Some(e) = &i.else_stmt {
+                walk(e);
+            }
+        }
+        ASTNode::CaseStmt(c) => {
+            walk(&c.value);
+            c.branches.iter().for_each(|b| walk(&b.body));
+            if let Some(e) = &c.else_stmt {
+                walk(e);
+            }
+        }
+        ASTNode::ElseStmt(e) => walk(&e.block),
+        ASTNode::WhileStmt(w) => {
+            walk(&w.guard);
+            walk(&w.block);
+        }
+        ASTNode::ForStmt(l) => {
+            names.extend(target_name(&l.variable));
+            assigned_variables(&l.start, params, names);
+            assigned_variables(&l.end, params, names);
+            assigned_variables(&l.block, params, names);
+        }
+        ASTNode::RepeatStmt(r) => {
+            walk(&r.block);
+            walk(&r.guard);
+        }
+        ASTNode::PrintStmt(p) => p.args.iter().flat_map(|a| a.nodes()).for_each(walk),
+        ASTNode::AssertStmt(a) => walk(&a.expr),
+        ASTNode::ReturnStmt(r) => {
+            if let Some(value) = &r.value {
+                walk(value);
+            }
+        }
+        ASTNode::ArrayRef(a) => a.indexes.iter().for_each(walk),
+        ASTNode::FieldRef(f) => walk(&f.record),
+        ASTNode::BinaryExpression(b) => {
+            walk(&b.left);
+            walk(&b.right);
+        }
+        ASTNode::UnaryExpression(u) => walk(&u.expression),
+        ASTNode::ProcedureCallStmt(p) => call_assignments(params, &p.target, &p.args, names),
+        ASTNode::FunctionCallStmt(f) => call_assignments(params, &f.target, &f.args, names),
+        _ => {}
+    }
+}
+
+impl<'a> Linter<'a> {
+    fn warn(&mut self, lint: Lint, position: Position, description: String) {
+        let level = self.config.level(lint);
+        if level != LintLevel::Allow {
+            trace!("lint {} at {}: {}", lint, position, description);
+            self.warnings.push(Warning {
+                position,
+                lint,
+                level,
+                description,
+            });
+        }
+    }
+
+    fn declare(&mut self, name: &str, position: Position, is_param: bool) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.push(VarInfo {
+                name: name.to_lowercase(),
+                position,
+                is_param,
+                read: false,
+            });
+        }
+    }
+
+    fn lookup(&mut self, name: &str) -> Option<&mut VarInfo> {
+        let name = name.to_lowercase();
+        self.scopes
+            .iter_mut()
+            .rev()
+            .find_map(|scope| scope.iter_mut().find(|v| v.name == name))
+    }
+
+    fn read(&mut self, name: &str) {
+        if let Some(var) = self.lookup(name) {
+            var.read = true;
+        }
+    }
+
+    /// The caller sees what is written in a `var` parameter, it is a
+    /// use of the parameter
+    fn write(&mut self, target: &ASTNode) {
+        if let ASTNode::VarName(v) = target {
+            if v.s_type == SymbolType::VarParam {
+                self.read(&v.id.lexeme);
+            }
+        }
+    }
+
+    fn push_scope(&mut self) {
+        self.scopes.push(Vec::new());
+    }
+
+    fn pop_scope(&mut self) {
+        for var in self.scopes.pop().unwrap_or_default() {
+            if var.read {
+                continue;
+            }
+            if var.is_param {
+                self.warn(
+                    Lint::UnusedParameter,
+                    var.position,
+                    format!("parameter `{}` is never used", var.name),
+                );
+            } else {
+                self.warn(
+                    Lint::UnusedVariable,
+                    var.position,
+                    format!("variable `{}` is never used", var.name),
+                );
+            }
+        }
+    }
+
+    /// Declares the parameters written in the source, the captured
+    /// variables are checked where they are declared
+    fn declare_params(&mut self, args: &SymbolTable, captured: usize) {
+        for arg in args.iter().take(args.len() - captured) {
+            self.declare(&arg.name, arg.position, true);
+        }
+    }
+
+    fn lint_function(&mut self, f: &FunctionDeclNode) {
+        self.push_scope();
+        self.declare_params(&f.args, f.captured);
+        self.lint_node(&f.block);
+        self.pop_scope();
+    }
+
+    fn lint_procedure(&mut self, p: &ProcedureDeclNode) {
+        self.push_scope();
+        self.declare_params(&p.args, p.captured);
+        self.lint_node(&p.block);
+        self.pop_scope();
+    }
+
+    /// The captured variables of a nested subprogram are read by its
+    /// calls
+    fn lint_call(&mut self, target: &str, args: &[(String, ASTNode)]) {
+        args.iter().for_each(|(_, a)| self.lint_node(a));
+        let names: Vec<String> = captured(&self.params, target, args)
+            .map(|c| c.name.clone())
+            .collect();
+        names.iter().for_each(|name| self.read(name));
+    }
+
+    fn lint_block(&mut self, block: &BlockNode) {
+        self.push_scope();
+        for stmt in block.statements.iter() {
+            self.lint_node(stmt);
+        }
+        self.pop_scope();
+    }
+
+    /// Lints the guard of a while or of a repeat loop against its body
+    fn lint_guard(&mut self, position: Position, guard: &ASTNode, body: &ASTNode) {
+        let mut read = Vec::new();
+        if guard_variables(guard, &mut read).is_some() && !read.is_empty() && !contains_exit(body) {
+            let mut written = Vec::new();
+            assigned_variables(body, &self.params, &mut written);
+            if !read.iter().any(|name| written.contains(name)) {
+                self.warn(
+                    Lint::UnchangingLoopGuard,
+                    position,
+                    "none of the variables in the loop guard is assigned in the loop body"
+                        .to_string(),
+                );
+            }
+        }
+    }
+
+    fn lint_node(&mut self, node: &ASTNode) {
+        match node {
+            ASTNode::Block(b) => self.lint_block(b),
+            ASTNode::VariableDecl(decl) => self.declare(&decl.id.lexeme, decl.position, false),
+            ASTNode::VarName(v) => match v.s_type {
+                SymbolType::Function | SymbolType::Procedure => {}
+                _ => self.read(&v.id.lexeme),
+            },
+            ASTNode::ArrayRef(a) => {
+                self.read(&a.array.lexeme);
+                a.indexes.iter().for_each(|i| self.lint_node(i));
+            }
+            ASTNode::FieldRef(f) => self.lint_node(&f.record),
+            ASTNode::VarReassignment(v) => {
+                self.lint_node(&v.new_value);
+                self.write(&v.variable_to_reassign);
+                if let target @ (ASTNode::ArrayRef(_) | ASTNode::FieldRef(_)) =
+                    v.variable_to_reassign.as_ref()
+                {
+                    self.lint_node(target);
+                }
+            }
+            ASTNode::ReadStmt(r) => {
+                self.write(&r.variable_to_read_in);
+                if let target @ (ASTNode::ArrayRef(_) | ASTNode::FieldRef(_)) =
+                    r.variable_to_read_in.as_ref()
+                {
+                    self.lint_node(target);
+                }
+            }
+            ASTNode::BinaryExpression(b) => {
+                self.lint_node(&b.left);
+                self.lint_node(&b.right);
+            }
+            ASTNode::UnaryExpression(u) => self.lint_node(&u.expression),
+            ASTNode::PrintStmt(p) => p
+                .args
+                .iter()
+                .flat_map(|a| a.nodes())
+                .for_each(|n| self.lint_node(n)),
+            ASTNode::AssertStmt(a) => self.lint_node(&a.expr),
+            ASTNode::ReturnStmt(r) => {
+                if let Some(value) = &r.value {
+                    self.lint_node(value);
+                }
+            }
+            ASTNode::FunctionCallStmt(f) => self.lint_call(&f.target, &f.args),
+            ASTNode::ProcedureCallStmt(p) => self.lint_call(&p.target, &p.args),
+            ASTNode::IfStmt(i) => {
+                self.lint_node(&i.guard);
+                self.lint_node(&i.then);
+                if let Some(e) = &i.else_stmt {
+                    self.lint_node(e);
+                }
+            }
+            ASTNode::CaseStmt(c) => {
+                self.lint_node(&c.value);
+                c.branches.iter().for_each(|b| self.lint_node(&b.body));
+                if let Some(e) = &c.else_stmt {
+                    self.lint_node(e);
+                }
+            }
+            ASTNode::ElseStmt(e) => self.lint_node(&e.block),
+            ASTNode::WhileStmt(w) => {
+                self.lint_guard(w.position, &w.guard, &w.block);
+                self.lint_node(&w.guard);
+                self.lint_node(&w.block);
+            }
+            ASTNode::RepeatStmt(r) => {
+                self.lint_guard(r.position, &r.guard, &r.block);
+                self.lint_node(&r.block);
+                self.lint_node(&r.guard);
+            }
+            ASTNode::ForStmt(l) => {
+                // the loop reads its control variable, to compare it
+                // with the end
+                self.lint_node(&l.variable);
+                self.lint_node(&l.start);
+                self.lint_node(&l.end);
+                self.lint_node(&l.block);
+            }
+            ASTNode::FunctionDecl(f) => self.lint_function(f),
+            ASTNode::ProcedureDecl(p) => self.lint_procedure(p),
+            ASTNode::Program(_)
+            | ASTNode::ProgramName(_)
+            | ASTNode::RecordDecl(_)
+            | ASTNode::ConstDecl(_)
+            | ASTNode::ForwardDecl(_)
+            | ASTNode::Literal(_)
+            | ASTNode::BreakStmt(_)
+            | ASTNode::ContinueStmt(_)
+            | ASTNode::EofStmt(_) => {}
+        }
+    }
+}
@@ -0,0 +1,4 @@
+pub mod calls;
+pub mod cfg;
+pub mod flow;
+pub mod lints;
@@ -1,17 +1,71 @@
 use log::trace;
 
-use crate::core::ast::ArrayRefExpr;
+use crate::core::{ast::ArrayRefExpr, types::Type};
 
 use super::Compiler;
 
 impl Compiler {
     /// Compiles an array reference, leaving in the right register its
-    /// value
+    /// value. A row of a nested array is a pointer to its first element
     pub fn compile_array_ref(&mut self, arr: ArrayRefExpr) {
         trace!("compiling array reference");
-        self.compile_ast(arr.index.as_ref().clone());
-        let r_type = arr.r_type.internal().to_c_type();
-        let name = format!("{}_{}", self.scope, arr.array.lexeme);
-        self.emit(format!("last_{} = {}[last_int];", r_type, name));
+        let offset = self.element_offset(&arr);
+        let r_type = Compiler::type_for_last(arr.r_type.clone());
+        let name = self.array_name(&arr);
+        match arr.r_type {
+            Type::Array(..) => self.emit(format!("last_{} = {} + {};", r_type, name, offset)),
+            Type::Simple(_) => self.emit(format!("last_{} = {}[{}];", r_type, name, offset)),
+        }
+    }
+
+    /// Computes (and checks) the indexes of the reference in a
+    /// temporary, and returns the position of the element in the
+    /// array. Nested arrays are laid out one row after the other
+    pub fn element_offset(&mut self, arr: &ArrayRefExpr) -> String {
+        let label = self.advance_label();
+        let offset = format!("tmp_{}", label);
+        let mut dimension = arr.array_type.clone();
+        for (i, index) in arr.indexes.iter().enumerate() {
+            self.compile_ast(index.clone());
+            if i == 0 {
+                self.check_index(arr, "last_int", &Compiler::array_len(&self.array_name(arr)));
+                self.emit_declaration(&offset, format!("int {} = last_int;", offset));
+            } else {
+                let size = dimension.size().unwrap_or(0);
+                self.check_index(arr, "last_int", &size.to_string());
+                self.emit(format!("{} = {} * {} + last_int;", offset, offset, size));
+            }
+            dimension = match dimension.element() {
+                Some(element) => element.clone(),
+                None => break,
+            };
+        }
+        match dimension.cells() {
+            1 => offset,
+            cells => format!("{} * {}", offset, cells),
+        }
+    }
+
+    /// Name of the referenced array in the current scope
+    pub fn array_name(&self, arr: &ArrayRefExpr) -> String {
+        self.variable(&arr.array.lexeme)
+    }
+
+    /// Name of the variable with the length of the array, -1 when it
+    /// is not known. It only exists with bounds checks
+    pub fn array_len(name: &str) -> String {
+        format!("{}_len", name)
+    }
+
+    /// Checks the index (already computed in `index`) against the
+    /// length of its dimension, if bounds checks are on
+    pub fn check_index(&mut self, arr: &ArrayRefExpr, index: &str, length: &str) {
+        if !self.options.bounds_checks {
+            return;
+        }
+        self.emit(format!(
+            "mpl_check_index({}, {}, \"{}\", {}, {});",
+            index, length, arr.array.lexeme, arr.position.line, arr.position.col
+        ));
     }
 }
@@ -2,13 +2,27 @@ use log::trace;
 
 use crate::core::ast::AssertStmtNode;
 
-use super::Compiler;
+use super::{AssertMode, Compiler};
 
 impl Compiler {
-    /// Compiles an assert node, runs `assert` from c std
+    /// Compiles an assert node, by default it runs `assert` from c
+    /// std. The output is flushed first, abort would drop what is still
+    /// buffered
     pub fn compile_assert(&mut self, node: AssertStmtNode) {
         trace!("compiling assert stmt");
+        if self.options.asserts == AssertMode::Off {
+            return;
+        }
         self.compile_ast(node.expr.as_ref().clone());
-        self.emit("assert(last_bool);".to_string());
+        match self.options.asserts {
+            AssertMode::Report => self.emit(format!(
+                "if (!last_bool) mpl_assert_failed({}, {});",
+                node.position.line, node.position.col
+            )),
+            _ => {
+                self.emit("if (!last_bool) fflush(stdout);".to_string());
+                self.emit("assert(last_bool);".to_string());
+            }
+        }
     }
 }
@@ -0,0 +1,63 @@
+use log::trace;
+
+use crate::core::{
+    ast::FunctionCallNode,
+    builtins::Builtin,
+    types::{SimpleType, Type},
+};
+
+use super::Compiler;
+
+impl Compiler {
+    /// Compiles the call of a builtin to the call of its helper in the
+    /// runtime, the arguments are evaluated first as for the other
+    /// functions
+    pub fn compile_builtin_call(&mut self, builtin: Builtin, f: FunctionCallNode) {
+        trace!("Compiling call of builtin {}", builtin.name());
+        let label = self.advance_label();
+        let mut args = vec![];
+        for (i, (_, arg)) in f.args.iter().enumerate() {
+            self.compile_ast(arg.clone());
+            let name = format!("arg_{}_{}", label, i);
+            self.emit_declaration(
+                &name,
+                format!(
+                    "{} {} = last_{};",
+                    arg.r_type().to_c_type(),
+                    name,
+                    Compiler::type_for_last(arg.r_type())
+                ),
+            );
+            args.push(name);
+        }
+        let args = args.join(", ");
+        let number = match f.r_type {
+            Type::Simple(SimpleType::Real) => "real",
+            _ => "int",
+        };
+        let call = match builtin {
+            Builtin::Sqrt => format!("sqrt({})", args),
+            Builtin::Abs | Builtin::Min | Builtin::Max => {
+                format!("mpl_{}_{}({})", builtin.name(), number, args)
+            }
+            // the helpers that can fail report where the call is
+            Builtin::Chr
+            | Builtin::StrToInt
+            | Builtin::Random
+            | Builtin::Trunc
+            | Builtin::Round => format!(
+                "mpl_{}({}, {}, {})",
+                builtin.name(),
+                args,
+                f.position.line,
+                f.position.col
+            ),
+            _ => format!("mpl_{}({})", builtin.name(), args),
+        };
+        self.emit(format!(
+            "last_{} = {};",
+            Compiler::type_for_last(f.r_type),
+            call
+        ));
+    }
+}
@@ -0,0 +1,89 @@
+use log::trace;
+
+use crate::core::{
+    ast::CaseStmtNode,
+    types::{SimpleType, Type},
+};
+
+use super::Compiler;
+
+impl Compiler {
+    /// Compiles a case statement, an int value goes through a C switch
+    /// (a jump table for the C compiler), a string value is compared
+    /// with the labels one after the other
+    pub fn compile_case(&mut self, node: CaseStmtNode) {
+        trace!("compiling case statement");
+        let label = self.advance_label();
+        let (endcase, endcase_ptr) = (
+            format!("endcase_{}", label),
+            format!("endcase_ptr_{}", label),
+        );
+        let (otherwise, otherwise_ptr) = match node.else_stmt {
+            Some(_) => (format!("else_{}", label), format!("else_ptr_{}", label)),
+            None => (endcase.clone(), endcase_ptr.clone()),
+        };
+        let branches: Vec<(String, String)> = (0..node.branches.len())
+            .map(|i| {
+                (
+                    format!("case_{}_{}", label, i),
+                    format!("case_ptr_{}_{}", label, i),
+                )
+            })
+            .collect();
+        self.emit_label_ptr(&endcase_ptr, &endcase);
+        if node.else_stmt.is_some() {
+            self.emit_label_ptr(&otherwise_ptr, &otherwise);
+        }
+        for (branch, branch_ptr) in branches.iter() {
+            self.emit_label_ptr(branch_ptr, branch);
+        }
+
+        self.compile_ast(node.value.as_ref().clone());
+        let value = format!("case_value_{}", label);
+        if node.value.r_type() == Type::Simple(SimpleType::String) {
+            self.emit_declaration(&value, format!("char* {} = last_str;", value));
+            for (b, (branch, branch_ptr)) in node.branches.iter().zip(branches.iter()) {
+                for l in b.labels.iter() {
+                    self.emit(format!(
+                        "if (strcmp({}, {}) == 0) {}",
+                        value,
+                        l.to_c_lit(),
+                        self.goto(branch_ptr, branch)
+                    ));
+                }
+            }
+        } else {
+            self.emit_declaration(&value, format!("int {} = last_int;", value));
+            self.emit(format!("switch ({}) {{", value));
+            for (b, (branch, branch_ptr)) in node.branches.iter().zip(branches.iter()) {
+                let labels: Vec<String> = b
+                    .labels
+                    .iter()
+                    .map(|l| format!("case {}:", l.to_c_lit()))
+                    .collect();
+                self.emit(format!(
+                    "{} {}",
+                    labels.join(" "),
+                    self.goto(branch_ptr, branch)
+                ));
+            }
+            self.emit(format!(
+                "default: {}",
+                self.goto(&otherwise_ptr, &otherwise)
+            ));
+            self.emit("}".to_string());
+        }
+        self.emit_goto(&otherwise_ptr, &otherwise);
+
+        for (b, (branch, _)) in node.branches.iter().zip(branches) {
+            self.emit_label(branch);
+            self.compile_ast(b.body.as_ref().clone());
+            self.emit_goto(&endcase_ptr, &endcase);
+        }
+        if let Some(else_stmt) = node.else_stmt {
+            self.emit_label(otherwise);
+            self.compile_ast(else_stmt.as_ref().clone());
+        }
+        self.emit_label(endcase);
+    }
+}
@@ -1,6 +1,10 @@
-use crate::core::{
-    ast::{ASTNode, BinaryExprNode, BinaryExprType, LiteralExprNode, UnaryExprNode},
-    types::{SimpleType, Type},
+use crate::{
+    core::{
+        ast::{ASTNode, BinaryExprNode, BinaryExprType, LiteralExprNode, UnaryExprNode},
+        objects::Object,
+        types::{SimpleType, Type},
+    },
+    interpreter::Interpreter,
 };
 
 use super::Compiler;
@@ -10,30 +14,33 @@ impl Compiler {
     /// for everything
     pub fn compile_expression(&mut self, expr: BinaryExprNode) {
         let label = self.advance_label();
-        self.emit(format!(
-            "{} left_arm_{};",
-            expr.left.as_ref().clone().r_type().to_c_type(),
-            label
-        ));
-        self.emit(format!(
-            "{} right_arm_{};",
-            expr.right.as_ref().clone().r_type().to_c_type(),
-            label
-        ));
+        let (left, right) = (
+            format!("left_arm_{}", label),
+            format!("right_arm_{}", label),
+        );
+        self.emit_declaration(
+            &left,
+            format!("{} {};", expr.left.r_type().to_c_type(), left),
+        );
+        self.emit_declaration(
+            &right,
+            format!("{} {};", expr.right.r_type().to_c_type(), right),
+        );
 
         self.compile_ast(expr.left.as_ref().clone());
         self.emit(format!(
             "left_arm_{} = last_{};",
             label,
-            expr.left.as_ref().clone().r_type().to_c_type()
+            Compiler::type_for_last(expr.left.r_type())
         ));
 
         self.compile_ast(expr.right.as_ref().clone());
         self.emit(format!(
             "right_arm_{} = last_{};",
             label,
-            expr.right.as_ref().clone().r_type().to_c_type()
+            Compiler::type_for_last(expr.right.r_type())
         ));
+        let strings = expr.left.r_type() == Type::Simple(SimpleType::String);
         match expr.clone().r_type {
             Type::Simple(s) => match s {
                 SimpleType::Int | SimpleType::Real => match expr.op_type {
@@ -41,7 +48,7 @@ impl Compiler {
                     | BinaryExprType::Subtraction
                     | BinaryExprType::Multiplication
                     | BinaryExprType::Division => self.emit(format!(
-                        "last_{} = right_arm_{} {} left_arm_{};",
+                        "last_{} = left_arm_{} {} right_arm_{};",
                         expr.r_type.to_c_type(),
                         label,
                         expr.op.lexeme,
@@ -54,8 +61,14 @@ impl Compiler {
                     ),
                 },
                 SimpleType::String => match expr.op_type {
+                    // a new buffer, the operands may still be in use
                     BinaryExprType::Addition => {
-                        self.emit(format!("strcat(right_arm_{}, left_arm_{});", label, label))
+                        self.emit(format!(
+                            "last_str = malloc(strlen(left_arm_{}) + strlen(right_arm_{}) + 1);",
+                            label, label
+                        ));
+                        self.emit(format!("strcpy(last_str, left_arm_{});", label));
+                        self.emit(format!("strcat(last_str, right_arm_{});", label));
                     }
                     _ => self.push_c_error(
                         ASTNode::BinaryExpression(expr.clone()),
@@ -70,14 +83,24 @@ impl Compiler {
                     BinaryExprType::LogicGreaterThan
                     | BinaryExprType::LogicGreaterThanEQ
                     | BinaryExprType::LogicLessThan
-                    | BinaryExprType::LogicLessThanEQ => self.emit(format!(
-                        "last_bool = left_arm_{} {} right_arm_{};",
-                        label, expr.op.lexeme, label
-                    )),
-                    BinaryExprType::LogicEQ => self.emit(format!(
-                        "last_bool = left_arm_{} == right_arm_{};",
-                        label, label
-                    )),
+                    | BinaryExprType::LogicLessThanEQ
+                    | BinaryExprType::LogicEQ => {
+                        let op = match expr.op_type {
+                            BinaryExprType::LogicEQ => "==",
+                            _ => expr.op.lexeme.as_str(),
+                        };
+                        if strings {
+                            self.emit(format!(
+                                "last_bool = strcmp(left_arm_{}, right_arm_{}) {} 0;",
+                                label, label, op
+                            ))
+                        } else {
+                            self.emit(format!(
+                                "last_bool = left_arm_{} {} right_arm_{};",
+                                label, op, label
+                            ))
+                        }
+                    }
                     BinaryExprType::LogicAND => self.emit(format!(
                         "last_bool = left_arm_{} && right_arm_{};",
                         label, label
@@ -95,8 +118,12 @@ impl Compiler {
                     ASTNode::BinaryExpression(expr),
                     "Binary expressions between void oprands are not allowed",
                 ),
+                SimpleType::Record(_) => self.push_c_error(
+                    ASTNode::BinaryExpression(expr),
+                    "Binary expressions between records are not allowed",
+                ),
             },
-            Type::Array(_) => self.push_c_error(
+            Type::Array(..) => self.push_c_error(
                 ASTNode::BinaryExpression(expr),
                 "Binary expressions between arrays are not allowed",
             ),
@@ -104,7 +131,7 @@ impl Compiler {
     }
 
     pub fn compile_lit(&mut self, expr: LiteralExprNode) {
-        match expr.r_type {
+        match expr.r_type.clone() {
             Type::Simple(s) => match s {
                 SimpleType::Int | SimpleType::Bool | SimpleType::Real => self.emit(format!(
                     "last_{} = {};",
@@ -112,14 +139,21 @@ impl Compiler {
                     expr.value.to_c_lit()
                 )),
                 SimpleType::String => {
-                    self.emit("last_str = malloc(128 * sizeof(char));".to_string());
-                    self.emit(format!("strcpy(last_str, \"{}\");", expr.to_c_lit()));
+                    let length = match &expr.value {
+                        Object::String(s) => s.len(),
+                        _ => 0,
+                    };
+                    self.emit(format!("last_str = malloc({} * sizeof(char));", length + 1));
+                    self.emit(format!("strcpy(last_str, {});", expr.to_c_lit()));
                 }
                 SimpleType::Void => {
                     self.push_c_error(ASTNode::Literal(expr), "literal of type void?")
                 }
+                SimpleType::Record(_) => {
+                    self.push_c_error(ASTNode::Literal(expr), "records have no literals")
+                }
             },
-            Type::Array(a) => match a {
+            Type::Array(a, _) => match a.internal() {
                 SimpleType::Void => {
                     self.push_c_error(ASTNode::Literal(expr), "Found array of void expressions")
                 }
@@ -137,6 +171,46 @@ impl Compiler {
         }
     }
 
+    /// Whether the expression is made only of literals
+    pub fn is_constant(expr: &ASTNode) -> bool {
+        match expr {
+            ASTNode::Literal(l) => !matches!(l.value, Object::Array(_)),
+            ASTNode::BinaryExpression(b) => {
+                Compiler::is_constant(&b.left) && Compiler::is_constant(&b.right)
+            }
+            ASTNode::UnaryExpression(u) => Compiler::is_constant(&u.expression),
+            _ => false,
+        }
+    }
+
+    /// Compiles a constant expression to its value, computed by the
+    /// interpreter so that it is the one the program would get. The
+    /// ones failing (divisions by zero) or with no C literal (infinity,
+    /// nan) are left to the program
+    pub fn compile_constant(&mut self, expr: ASTNode) {
+        let mut output = Vec::new();
+        let value = Interpreter::new(&mut std::io::empty(), &mut output).eval(&expr);
+        match (value, expr) {
+            (Ok(Object::Real(r)), expr) if !r.is_finite() => self.compile_folded(expr),
+            (Ok(value), expr) => self.compile_lit(LiteralExprNode {
+                position: expr.position(),
+                value,
+                r_type: expr.r_type(),
+            }),
+            (Err(_), expr) => self.compile_folded(expr),
+        }
+    }
+
+    /// Compiles the operation at the top of a constant expression as
+    /// it is, its operands still get folded
+    fn compile_folded(&mut self, expr: ASTNode) {
+        match expr {
+            ASTNode::BinaryExpression(b) => self.compile_expression(b),
+            ASTNode::UnaryExpression(u) => self.compile_unary(u),
+            other => self.compile_ast(other),
+        }
+    }
+
     pub fn compile_unary(&mut self, expr: UnaryExprNode) {
         self.compile_ast(expr.expression.as_ref().clone());
         self.emit("last_bool = !last_bool;".to_string());
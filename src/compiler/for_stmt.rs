@@ -0,0 +1,64 @@
+use crate::core::ast::{ASTNode, ForStmtNode};
+
+use super::{Compiler, LoopLabels};
+
+impl Compiler {
+    /// Compiles a for loop, the bounds go in temporaries so that they
+    /// are evaluated once. The loop stops after the iteration where
+    /// the variable is the end, so that it never steps over the int
+    /// range
+    pub fn compile_for(&mut self, node: ForStmtNode) {
+        let label = self.advance_label();
+        let variable = match node.variable.as_ref() {
+            ASTNode::VarName(v) => self.variable(&v.id.lexeme),
+            other => {
+                self.push_c_error(other.clone(), "Expected variable name as for loop control");
+                return;
+            }
+        };
+        let (guard, endfor) = (format!("for_{}", label), format!("endfor_{}", label));
+        let (guard_ptr, endfor_ptr) = (
+            format!("for_ptr_{}", label),
+            format!("endfor_ptr_{}", label),
+        );
+        let (step, step_ptr) = (format!("step_{}", label), format!("step_ptr_{}", label));
+        let end = format!("for_end_{}", label);
+        let (compare, direction) = match node.downto {
+            false => ("<", "+"),
+            true => (">", "-"),
+        };
+        self.emit_label_ptr(&endfor_ptr, &endfor);
+        self.emit_label_ptr(&guard_ptr, &guard);
+        self.emit_label_ptr(&step_ptr, &step);
+        self.compile_ast(node.start.as_ref().clone());
+        let start = format!("for_start_{}", label);
+        self.emit_declaration(&start, format!("int {} = last_int;", start));
+        self.compile_ast(node.end.as_ref().clone());
+        self.emit_declaration(&end, format!("int {} = last_int;", end));
+        self.emit(format!("{} = {};", variable, start));
+        self.emit(format!(
+            "if ({} {} {}) {}",
+            end,
+            compare,
+            variable,
+            self.goto(&endfor_ptr, &endfor)
+        ));
+        self.emit_label(guard.clone());
+        self.loops.push(LoopLabels {
+            next: (step_ptr, step.clone()),
+            exit: (endfor_ptr.clone(), endfor.clone()),
+        });
+        self.compile_ast(node.block.as_ref().clone());
+        self.loops.pop();
+        self.emit_label(step);
+        self.emit(format!(
+            "if ({} == {}) {}",
+            variable,
+            end,
+            self.goto(&endfor_ptr, &endfor)
+        ));
+        self.emit(format!("{} = {} {} 1;", variable, variable, direction));
+        self.emit_goto(&guard_ptr, &guard);
+        self.emit_label(endfor);
+    }
+}
@@ -1,11 +1,13 @@
 use log::trace;
 
 use crate::core::{
-    ast::{FunctionCallNode, FunctionDeclNode},
+    ast::{ASTNode, FunctionCallNode, FunctionDeclNode},
+    builtins::Builtin,
+    symbol_table::{Symbol, SymbolTable, SymbolType},
     types::{SimpleType, Type},
 };
 
-use super::Compiler;
+use super::{Compiler, Dialect};
 
 impl Compiler {
     /// Returns the name of the register with the return value for the
@@ -20,28 +22,89 @@ impl Compiler {
         format!("{}_return_ptr", name)
     }
 
-    /// Compiles a function declaration
-    pub fn compile_function(&mut self, f: FunctionDeclNode) {
-        self.scope = f.name.clone();
-        trace!("Compiling function declaration");
-        for sym in f.args.iter() {
+    /// Declares the parameters and the return registers of a
+    /// subprogram. Every subprogram is declared before the first body,
+    /// so that they can call each other in any order
+    pub fn declare_subprogram(&mut self, name: &str, args: &SymbolTable, r_type: Option<&Type>) {
+        self.frame.clear();
+        for sym in args.iter() {
+            let param = format!("{}_{}", name, sym.name);
+            self.emit_declaration(&param, format!("{} {};", sym.r_type.to_c_type(), param));
+            if let Type::Array(..) = sym.r_type {
+                self.declare_array_len(&format!("{}_{}", name, sym.name.to_lowercase()), -1);
+            }
+        }
+        if let Some(r_type) = r_type {
             self.emit(format!(
-                "{} {}_{};",
-                sym.r_type.to_c_type(),
-                f.name,
-                sym.name
+                "{} {};",
+                r_type.to_c_type(),
+                Compiler::f_ret_value(name.to_string())
             ));
         }
-        self.emit(format!(
-            "{} {};",
-            f.r_type.to_c_type(),
-            Compiler::f_ret_value(f.name.clone())
-        ));
-        self.emit(format!("void* {};", Compiler::f_ret_ptr(f.name.clone())));
-        self.emit(format!("void* fptr_{} = &&f_{};", f.name, f.name));
+        self.declare_ret_ptr(name);
+        let frame = std::mem::take(&mut self.frame);
+        self.frames.insert(name.to_string(), frame);
+    }
+
+    /// Compiles a function declaration, its registers are already
+    /// declared by `declare_subprogram`
+    pub fn compile_function(&mut self, f: FunctionDeclNode) {
+        self.scope = f.name.clone();
+        self.frame = self.frames.get(&f.name).cloned().unwrap_or_default();
+        trace!("Compiling function declaration");
         self.emit_label(format!("f_{}", f.name));
         self.compile_ast(*f.block);
-        self.emit(format!("goto *{};", Compiler::f_ret_ptr(f.name)))
+        self.emit_return(&f.name);
+    }
+
+    /// Declares the return register of the subprogram, the address of
+    /// the call site in GNU C and its label number in C99
+    pub fn declare_ret_ptr(&mut self, name: &str) {
+        match self.options.dialect {
+            Dialect::Gnu => {
+                let ptr = Compiler::f_ret_ptr(name.to_string());
+                self.emit_declaration(&ptr, format!("void* {};", ptr));
+                self.emit(format!("void* fptr_{} = &&f_{};", name, name));
+            }
+            Dialect::C99 => {
+                self.call_sites.entry(name.to_string()).or_default();
+                let ptr = Compiler::f_ret_ptr(name.to_string());
+                self.emit_declaration(&ptr, format!("int {};", ptr));
+            }
+        }
+    }
+
+    /// Jumps back to the caller of the subprogram
+    pub fn emit_return(&mut self, name: &str) {
+        match self.options.dialect {
+            Dialect::Gnu => self.emit(format!("goto *{};", Compiler::f_ret_ptr(name.to_string()))),
+            Dialect::C99 => self.emit(format!("goto {}_return;", name)),
+        }
+    }
+
+    /// Stores the call site in the return register of the target and
+    /// jumps to it, the call returns to `ret_{label}`
+    pub fn emit_call(&mut self, label: usize, target: &str) {
+        match self.options.dialect {
+            Dialect::Gnu => self.emit(format!(
+                "{} = &&ret_{};",
+                Compiler::f_ret_ptr(target.to_string()),
+                label
+            )),
+            Dialect::C99 => {
+                self.call_sites
+                    .entry(target.to_string())
+                    .or_default()
+                    .push(label);
+                self.emit(format!(
+                    "{} = {};",
+                    Compiler::f_ret_ptr(target.to_string()),
+                    label
+                ));
+            }
+        }
+        self.emit(format!("goto f_{};", target));
+        self.emit_label(format!("ret_{}", label));
     }
 
     /// Returns the correct type suffix for register addressing
@@ -51,33 +114,128 @@ impl Compiler {
                 SimpleType::String => "str".to_string(),
                 _ => a.to_c_type(),
             },
-            Type::Array(a) => match a {
+            Type::Array(a, _) => match a.internal() {
                 SimpleType::String => "str_arr".to_string(),
-                _ => format!("{}_arr", a.to_c_type()),
+                simple => format!("{}_arr", simple.to_c_type()),
             },
         }
     }
 
+    /// Parameters of the target without an argument, the variables
+    /// captured by a nested subprogram
+    fn captured_params(&self, target: &str, args: &[(String, ASTNode)]) -> Vec<Symbol> {
+        self.params
+            .get(target)
+            .map(|params| params.iter().skip(args.len()).cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Tells if the call can get back to the subprogram being
+    /// compiled, its frame is then saved before the call
+    fn recursive(&self, target: &str) -> bool {
+        self.scope != "main" && self.calls.reaches(target, &self.scope)
+    }
+
+    /// Evaluates the arguments of a call and stores them in the
+    /// parameters of the target. All of them are evaluated before the
+    /// first is stored, an argument can call the same subprogram. The
+    /// captured variables are passed by name. Before a recursive call
+    /// the frame of the caller is pushed on the runtime stack
+    pub fn compile_call_args(&mut self, label: usize, target: &str, args: &[(String, ASTNode)]) {
+        for (i, (_, arg)) in args.iter().enumerate() {
+            self.compile_ast(arg.clone());
+            let name = format!("arg_{}_{}", label, i);
+            self.emit_declaration(
+                &name,
+                format!(
+                    "{} {} = last_{};",
+                    arg.r_type().to_c_type(),
+                    name,
+                    Compiler::type_for_last(arg.r_type())
+                ),
+            );
+        }
+        if self.recursive(target) {
+            for variable in self.frame.clone() {
+                self.emit(format!("mpl_push(&{0}, sizeof {0});", variable));
+            }
+        }
+        for (i, (name, arg)) in args.iter().enumerate() {
+            self.emit(format!("{}_{} = arg_{}_{};", target, name, label, i));
+            if let Type::Array(..) = arg.r_type() {
+                let length = self.length_of(arg);
+                self.set_array_len(&format!("{}_{}", target, name.to_lowercase()), &length);
+            }
+        }
+        for param in self.captured_params(target, args) {
+            let variable = self.variable(&param.name);
+            self.emit(format!("{}_{} = {};", target, param.name, variable));
+            if let Type::Array(..) = param.r_type {
+                let length = Compiler::array_len(&variable);
+                self.set_array_len(&format!("{}_{}", target, param.name), &length);
+            }
+        }
+    }
+
+    /// After the call returned, pops the frame saved by
+    /// `compile_call_args` and copies the `var` parameters of the
+    /// target back in the variables they were given as
+    pub fn finish_call(&mut self, label: usize, target: &str, args: &[(String, ASTNode)]) {
+        let params = self.params.get(target).cloned().unwrap_or_default();
+        // the variables and the values they get back, with their C type
+        let mut copies = vec![];
+        for (name, arg) in args.iter() {
+            let by_reference = params
+                .get(name.clone())
+                .is_some_and(|p| p.s_type == SymbolType::VarParam);
+            if let (true, ASTNode::VarName(v)) = (by_reference, arg) {
+                let variable = self.variable(&v.id.lexeme);
+                let value = format!("{}_{}", target, name);
+                copies.push((variable.clone(), value, arg.r_type().to_c_type()));
+                if let (Type::Array(..), true) = (arg.r_type(), self.options.bounds_checks) {
+                    let length =
+                        Compiler::array_len(&format!("{}_{}", target, name.to_lowercase()));
+                    copies.push((Compiler::array_len(&variable), length, "int".to_string()));
+                }
+            }
+        }
+        for param in self.captured_params(target, args) {
+            if param.s_type == SymbolType::VarParam {
+                let variable = self.variable(&param.name);
+                let value = format!("{}_{}", target, param.name);
+                copies.push((variable, value, param.r_type.to_c_type()));
+            }
+        }
+        if self.recursive(target) {
+            // the parameters of the target can be the ones popped
+            for (i, (_, value, c_type)) in copies.iter_mut().enumerate() {
+                let back = format!("back_{}_{}", label, i);
+                self.emit(format!("{} {} = {};", c_type, back, value));
+                *value = back;
+            }
+            for variable in self.frame.clone().iter().rev() {
+                self.emit(format!("mpl_pop(&{0}, sizeof {0});", variable));
+            }
+        }
+        for (variable, value, _) in copies {
+            self.emit(format!("{} = {};", variable, value));
+        }
+    }
+
     /// Compiles a function call
     pub fn compile_function_call(&mut self, f: FunctionCallNode) {
+        if let Some(builtin) = Builtin::from_name(&f.target) {
+            return self.compile_builtin_call(builtin, f);
+        }
         trace!("Compiling function call");
         let label = self.advance_label();
-        for arg in f.args.iter() {
-            self.compile_ast(arg.1.clone());
-            let r_type = Compiler::type_for_last(arg.1.r_type());
-            self.emit(format!("{}_{} = last_{};", f.target, arg.0, r_type));
-        }
-        self.emit(format!(
-            "{} = &&ret_{};",
-            Compiler::f_ret_ptr(f.clone().target),
-            label
-        ));
-        self.emit(format!("goto f_{};", f.target));
-        self.emit_label(format!("ret_{}", label));
+        self.compile_call_args(label, &f.target, &f.args);
+        self.emit_call(label, &f.target);
         self.emit(format!(
             "last_{} = {};",
             Compiler::type_for_last(f.r_type),
-            Compiler::f_ret_value(f.target)
+            Compiler::f_ret_value(f.target.clone())
         ));
+        self.finish_call(label, &f.target, &f.args);
     }
 }
@@ -10,16 +10,18 @@ impl Compiler {
         trace!("compiling if statement");
         let cur = self.advance_label();
 
-        self.emit(format!("void* thenptr_{} = &&then_{};", cur, cur));
-        self.emit(format!("void* endifptr_{} = &&endif_{};", cur, cur));
+        let (then, endif) = (format!("then_{}", cur), format!("endif_{}", cur));
+        let (thenptr, endifptr) = (format!("thenptr_{}", cur), format!("endifptr_{}", cur));
+        self.emit_label_ptr(&thenptr, &then);
+        self.emit_label_ptr(&endifptr, &endif);
 
         self.compile_ast(node.guard.as_ref().clone());
-        self.emit(format!("if (last_bool) goto *thenptr_{};", cur));
+        self.emit(format!("if (last_bool) {}", self.goto(&thenptr, &then)));
 
         if let Some(else_node) = node.else_stmt {
             self.compile_ast(else_node.as_ref().clone());
         }
-        self.emit(format!("goto *endifptr_{};", cur));
+        self.emit_goto(&endifptr, &endif);
         self.emit_label(format!("then_{}", cur));
         self.compile_ast(node.then.as_ref().clone());
         self.emit_label(format!("endif_{}", cur));
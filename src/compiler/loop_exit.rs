@@ -0,0 +1,18 @@
+use super::Compiler;
+
+impl Compiler {
+    /// Jumps to the end of the innermost loop
+    pub fn compile_break(&mut self) {
+        if let Some(labels) = self.loops.last().cloned() {
+            self.emit_goto(&labels.exit.0, &labels.exit.1);
+        }
+    }
+
+    /// Jumps to what comes after the body of the innermost loop: the
+    /// guard of a while, the step of a for, the guard of a repeat
+    pub fn compile_continue(&mut self) {
+        if let Some(labels) = self.loops.last().cloned() {
+            self.emit_goto(&labels.next.0, &labels.next.1);
+        }
+    }
+}
@@ -1,29 +1,49 @@
 mod array;
 mod assert;
 mod block;
+mod builtins;
+mod case_stmt;
 mod expression;
+mod for_stmt;
 mod funcions;
 mod if_stmt;
+mod loop_exit;
+mod options;
 mod print;
 mod procedures;
 mod program;
 mod program_name;
 mod read;
+mod record;
+mod repeat_stmt;
 mod return_stmt;
+mod runtime;
+mod source_map;
 mod var;
 mod while_stmt;
 
+pub use options::{AssertMode, CompileOptions, Dialect, OptLevel, RuntimeMode, RUNTIME_HEADER};
+pub use runtime::header as runtime_header;
+pub use source_map::{source_map_json, LineMapping};
+
 use crate::{
+    analysis::{calls::CallGraph, flow, lints},
     core::{
         ast::ASTNode,
-        errors::{CompilationError, SyntaxError},
+        errors::{CompilationError, SyntaxError, Warning},
+        lints::LintLevel,
+        objects::c_string,
+        symbol_table::SymbolTable,
+        types::RecordType,
     },
     parser::Parser,
+    scanner::position::Position,
 };
 use std::{
-    borrow::Borrow,
+    collections::{HashMap, HashSet},
     fs::File,
     io::{Error, Read},
+    sync::Arc,
 };
 
 #[derive(Debug)]
@@ -32,21 +52,59 @@ pub enum State {
     Error,
 }
 
+/// Where `continue` and `break` jump in a loop, each target is the
+/// pointer to the label and the label
+#[derive(Debug, Clone)]
+struct LoopLabels {
+    next: (String, String),
+    exit: (String, String),
+}
+
 #[derive(Debug)]
 pub struct Compiler {
     pub c_errors: Vec<CompilationError>,
     pub s_errors: Vec<SyntaxError>,
+    pub warnings: Vec<Warning>,
+    pub options: CompileOptions,
     pub state: State,
     source: String,
     raw_instructions: String,
     pub scope: String,
     pub label: usize,
+    /// Parameters of every function and procedure, by name
+    params: HashMap<String, SymbolTable>,
+    /// Record types of the program, their structs go before `main`
+    records: Vec<Arc<RecordType>>,
+    /// Labels of the calls to every subprogram, the returns dispatch
+    /// on them in the C99 dialect
+    call_sites: HashMap<String, Vec<usize>>,
+    /// Loops being compiled, the innermost last
+    loops: Vec<LoopLabels>,
+    /// Position of the node being compiled, recorded by `emit`
+    position: Option<Position>,
+    /// Position every line of `raw_instructions` comes from
+    positions: Vec<Option<Position>>,
+    source_map: Vec<LineMapping>,
+    /// Global variables of the program, lowercase
+    globals: HashSet<String>,
+    calls: CallGraph,
+    /// C variables declared for the subprogram being compiled, saved
+    /// around its recursive calls
+    frame: Vec<String>,
+    /// Parameters and return pointer of every subprogram, its frame
+    /// before the body
+    frames: HashMap<String, Vec<String>>,
 }
 
 impl Compiler {
     pub fn insert_header(&mut self) {
-        self.source = "#include <stdio.h>\n#include <stdlib.h>\n#include <string.h>\n#include <stdbool.h>\n#include <assert.h>\nint main(){\n\n".to_string()
-            + &self.source;
+        let typedefs: String = self
+            .records
+            .iter()
+            .map(|r| Compiler::record_typedef(r) + "\n")
+            .collect();
+        self.source =
+            runtime::prelude(&self.options) + &typedefs + "int main(){\n\n" + &self.source;
     }
 
     pub fn insert_footer(&mut self) {
@@ -55,32 +113,159 @@ impl Compiler {
 
     pub fn emit(&mut self, instr: String) {
         self.raw_instructions = format!("{}    {}\n", self.raw_instructions, instr);
+        self.positions.push(self.position);
     }
 
+    /// Emits a label, in C99 it is followed by an empty statement
+    /// since a declaration can not be labelled
     pub fn emit_label(&mut self, label: String) {
-        self.raw_instructions = format!("{}{}:\n", self.raw_instructions, label);
+        let label = match self.options.dialect {
+            Dialect::Gnu => format!("{}:", label),
+            Dialect::C99 => format!("{}:;", label),
+        };
+        self.raw_instructions = format!("{}{}\n", self.raw_instructions, label);
+        self.positions.push(self.position);
+    }
+
+    /// Emits the declaration of a C variable, in a subprogram the
+    /// variable is part of its frame
+    pub fn emit_declaration(&mut self, name: &str, declaration: String) {
+        self.frame.push(name.to_string());
+        self.emit(declaration);
+    }
+
+    /// Stores the address of the label in `ptr` for `emit_goto`, only
+    /// GNU C has label addresses
+    pub fn emit_label_ptr(&mut self, ptr: &str, label: &str) {
+        if self.options.dialect == Dialect::Gnu {
+            self.emit(format!("void* {} = &&{};", ptr, label));
+        }
+    }
+
+    /// Jump to the label, through the pointer stored by
+    /// `emit_label_ptr` in GNU C
+    pub fn goto(&self, ptr: &str, label: &str) -> String {
+        match self.options.dialect {
+            Dialect::Gnu => format!("goto *{};", ptr),
+            Dialect::C99 => format!("goto {};", label),
+        }
     }
 
+    pub fn emit_goto(&mut self, ptr: &str, label: &str) {
+        self.emit(self.goto(ptr, label));
+    }
+
+    /// Puts together the translation, mapping its lines to the
+    /// positions they were generated for. With `#line` directives the
+    /// instructions of a source line are joined on a single line, so
+    /// that the lines after a directive keep matching the source
     pub fn gen_source(&mut self) {
         self.source = String::new();
         self.insert_header();
-        self.source += self.raw_instructions.borrow();
+        self.source_map.clear();
+        let name = c_string(&self.options.source_name);
+        // lines of the body, with the position of their first instruction
+        let mut body: Vec<(String, Option<Position>)> = vec![];
+        for (instr, position) in self.raw_instructions.lines().zip(self.positions.iter()) {
+            match (body.last_mut(), position) {
+                (Some((text, Some(last))), Some(position))
+                    if self.options.line_directives && last.line == position.line =>
+                {
+                    text.push(' ');
+                    text.push_str(instr.trim_start());
+                }
+                _ => body.push((instr.to_string(), *position)),
+            }
+        }
+        let mut c_line = self.source.matches('\n').count();
+        // source line the C compiler gives to the last line written
+        let mut reported: Option<i64> = None;
+        for (text, position) in body {
+            if let Some(position) = position {
+                if self.options.line_directives && reported.map(|l| l + 1) != Some(position.line) {
+                    self.source += &format!("#line {} {}\n", position.line, name);
+                    c_line += 1;
+                    reported = Some(position.line - 1);
+                }
+                self.source_map.push(LineMapping {
+                    c_line: c_line + 1,
+                    position,
+                });
+            }
+            self.source += &text;
+            self.source.push('\n');
+            c_line += 1;
+            reported = reported.map(|l| l + 1);
+        }
+        if self.options.dialect == Dialect::C99 {
+            self.source += &self.return_dispatch();
+        }
         self.insert_footer();
     }
 
+    /// The returns of every subprogram in C99, each one jumps back to
+    /// the call site stored in its return register
+    fn return_dispatch(&self) -> String {
+        let mut targets: Vec<_> = self.call_sites.iter().collect();
+        targets.sort();
+        let mut dispatch = String::new();
+        for (target, labels) in targets {
+            dispatch += &format!("{}_return:;\n", target);
+            dispatch += &format!("    switch ({}) {{\n", Compiler::f_ret_ptr(target.clone()));
+            for label in labels {
+                dispatch += &format!("    case {}: goto ret_{};\n", label, label);
+            }
+            dispatch += "    }\n    abort();\n";
+        }
+        dispatch
+    }
+
     pub fn get_source(&self) -> String {
         self.source.clone()
     }
 
+    /// Lines of the translation with the position they come from, set
+    /// up by `gen_source`
+    pub fn source_map(&self) -> &[LineMapping] {
+        &self.source_map
+    }
+
     pub fn new() -> Compiler {
+        Compiler::with_options(CompileOptions::default())
+    }
+
+    pub fn with_options(options: CompileOptions) -> Compiler {
         Compiler {
             c_errors: vec![],
             s_errors: vec![],
+            warnings: vec![],
+            options,
             state: State::Sane,
             source: String::new(),
             raw_instructions: String::new(),
             scope: "main".to_string(),
             label: 0,
+            params: HashMap::new(),
+            records: vec![],
+            call_sites: HashMap::new(),
+            loops: vec![],
+            position: None,
+            positions: vec![],
+            source_map: vec![],
+            globals: HashSet::new(),
+            calls: CallGraph::default(),
+            frame: vec![],
+            frames: HashMap::new(),
+        }
+    }
+
+    /// Name of the variable in the C translation, the globals are
+    /// shared by every scope
+    pub fn variable(&self, name: &str) -> String {
+        let name = name.to_lowercase();
+        match self.globals.contains(&name) {
+            true => format!("global_{}", name),
+            false => format!("{}_{}", self.scope, name),
         }
     }
 
@@ -91,21 +276,37 @@ impl Compiler {
     }
 
     pub fn compile_ast(&mut self, ast: ASTNode) {
+        let outer = self.position;
+        let position = ast.position();
+        if position.line > 0 {
+            self.position = Some(position);
+        }
         match ast {
             ASTNode::Program(p) => self.compile_program(p),
             ASTNode::ProgramName(node) => self.compile_program_name(node),
             ASTNode::FunctionDecl(f_node) => self.compile_function(f_node),
             ASTNode::ProcedureDecl(p_node) => self.compile_procedure(p_node),
             ASTNode::Block(b_node) => self.compile_block(b_node),
+            ASTNode::BinaryExpression(_) | ASTNode::UnaryExpression(_)
+                if self.options.opt_level >= OptLevel::O1 && Compiler::is_constant(&ast) =>
+            {
+                self.compile_constant(ast)
+            }
             ASTNode::BinaryExpression(exp_node) => self.compile_expression(exp_node),
             ASTNode::VarName(var_name) => self.compile_var_name(var_name),
             ASTNode::ArrayRef(a_ref_node) => self.compile_array_ref(a_ref_node),
+            ASTNode::FieldRef(f_ref_node) => self.compile_field_ref(f_ref_node),
             ASTNode::Literal(lit) => self.compile_lit(lit),
             ASTNode::UnaryExpression(expr) => self.compile_unary(expr),
             ASTNode::VarReassignment(ass) => self.compile_var_assignment(ass),
             ASTNode::VariableDecl(decl) => self.compile_var_decl(decl),
             ASTNode::WhileStmt(while_stmt) => self.compile_while(while_stmt),
+            ASTNode::ForStmt(for_stmt) => self.compile_for(for_stmt),
+            ASTNode::RepeatStmt(repeat) => self.compile_repeat(repeat),
+            ASTNode::BreakStmt(_) => self.compile_break(),
+            ASTNode::ContinueStmt(_) => self.compile_continue(),
             ASTNode::IfStmt(if_stmt) => self.compile_if(if_stmt),
+            ASTNode::CaseStmt(case) => self.compile_case(case),
             ASTNode::ElseStmt(else_stmt) => self.compile_else(else_stmt),
             ASTNode::PrintStmt(prnt) => self.compile_print(prnt),
             ASTNode::ReadStmt(read) => self.compile_read(read),
@@ -113,21 +314,66 @@ impl Compiler {
             ASTNode::FunctionCallStmt(fn_call) => self.compile_function_call(fn_call),
             ASTNode::ProcedureCallStmt(proc_call) => self.compile_procedure_call(proc_call),
             ASTNode::ReturnStmt(ret) => self.compile_return(ret),
-            ASTNode::EofStmt(_) => {}
+            // the structs are in the header, the constants are
+            // replaced by their values and every subprogram is
+            // declared before the first one
+            ASTNode::RecordDecl(_)
+            | ASTNode::ConstDecl(_)
+            | ASTNode::ForwardDecl(_)
+            | ASTNode::EofStmt(_) => {}
         }
+        self.position = outer;
     }
 
-    /// Compile is the main compilation function. It parses the source
-    /// file and if finds some syntactic errors it returns them
-    pub fn compile(&mut self, source: String) -> Result<(), Vec<SyntaxError>> {
-        let mut parser = Parser::new(source);
-        match parser.parse() {
-            Ok(ast) => {
-                self.compile_ast(ast);
-                Ok(())
+    /// Parses the source and checks it without generating any code:
+    /// if it finds some syntactic errors it returns them, otherwise
+    /// checks the returns of the subprograms and the for loops, then
+    /// runs the lints (configured by `lints` and by the pragmas in the
+    /// source). The errors of the checks leave the compiler in an error state
+    pub fn analyse(&mut self, source: String) -> Result<ASTNode, Vec<SyntaxError>> {
+        let mut parser = Parser::new(source.clone());
+        let ast = parser.parse()?;
+        let mut errs = Vec::new();
+        for pragma in parser.pragmas() {
+            if let Err(msg) = self.options.lints.apply_pragma(&pragma.lexeme) {
+                let line = source
+                    .lines()
+                    .nth((pragma.position.line - 1) as usize)
+                    .unwrap_or_default();
+                errs.push(SyntaxError::new(pragma.position, line.to_string(), msg));
             }
-            Err(errs) => Err(errs),
         }
+        if !errs.is_empty() {
+            return Err(errs);
+        }
+        if let ASTNode::Program(program) = &ast {
+            for err in flow::check_returns(program)
+                .into_iter()
+                .chain(flow::check_for_loops(program))
+            {
+                self.state = State::Error;
+                self.c_errors.push(err);
+            }
+            self.push_warnings(lints::check(program, &self.options.lints));
+        }
+        Ok(ast)
+    }
+
+    /// Compile is the main compilation function, it analyses the
+    /// source and compiles the resulting AST
+    pub fn compile(&mut self, source: String) -> Result<(), Vec<SyntaxError>> {
+        let ast = self.analyse(source)?;
+        self.compile_ast(ast);
+        Ok(())
+    }
+
+    /// Stores the given lint diagnostics, if any of them is denied
+    /// the compilation is considered unsuccessful
+    pub fn push_warnings(&mut self, warnings: Vec<Warning>) {
+        if warnings.iter().any(|w| w.level == LintLevel::Deny) {
+            self.state = State::Error;
+        }
+        self.warnings.extend(warnings);
     }
 
     /// This function compiles, taking its content as source and
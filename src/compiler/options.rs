@@ -0,0 +1,135 @@
+use std::str::FromStr;
+
+use crate::core::lints::LintConfig;
+
+/// Flavour of C the translation is written in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Dialect {
+    /// GNU C, jumps go through label addresses (`&&label`)
+    #[default]
+    Gnu,
+    /// Standard C99, every jump is a plain `goto` and the returns of
+    /// the subprograms dispatch on the call site with a `switch`
+    C99,
+}
+
+/// What a failed `assert` does in the translation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AssertMode {
+    /// `assert` of the C library, removed by `NDEBUG`
+    #[default]
+    Abort,
+    /// Reports the position of the assert on stderr and exits with 1,
+    /// like the interpreter does
+    Report,
+    /// Asserts are not compiled at all
+    Off,
+}
+
+/// How much work goes into the translation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum OptLevel {
+    /// Every expression is translated as written
+    #[default]
+    O0,
+    /// Expressions made only of literals are computed at compile time
+    O1,
+}
+
+/// Where the runtime helpers (bounds checks, assert reports) go
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RuntimeMode {
+    /// Defined at the top of the translation
+    #[default]
+    Inline,
+    /// Included from `RUNTIME_HEADER`, the header is part of the output
+    Header,
+}
+
+impl FromStr for Dialect {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "gnu" => Ok(Dialect::Gnu),
+            "c99" => Ok(Dialect::C99),
+            other => Err(format!("Unknown dialect: {}", other)),
+        }
+    }
+}
+
+impl FromStr for AssertMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "abort" => Ok(AssertMode::Abort),
+            "report" => Ok(AssertMode::Report),
+            "off" => Ok(AssertMode::Off),
+            other => Err(format!("Unknown assert mode: {}", other)),
+        }
+    }
+}
+
+impl FromStr for OptLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "0" => Ok(OptLevel::O0),
+            "1" => Ok(OptLevel::O1),
+            other => Err(format!("Unknown optimisation level: {}", other)),
+        }
+    }
+}
+
+impl FromStr for RuntimeMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "inline" => Ok(RuntimeMode::Inline),
+            "header" => Ok(RuntimeMode::Header),
+            other => Err(format!("Unknown runtime mode: {}", other)),
+        }
+    }
+}
+
+/// Name of the header with the runtime, for `RuntimeMode::Header`
+pub const RUNTIME_HEADER: &str = "miniplc_runtime.h";
+
+/// How a program gets checked and compiled
+#[derive(Debug, Clone)]
+pub struct CompileOptions {
+    /// Levels of the lints, the pragmas in the source apply on top
+    pub lints: LintConfig,
+    pub dialect: Dialect,
+    /// Checks every array index against the length of the array
+    pub bounds_checks: bool,
+    pub asserts: AssertMode,
+    pub opt_level: OptLevel,
+    pub runtime: RuntimeMode,
+    /// Emits `#line` directives pointing at the MiniPascal source
+    pub line_directives: bool,
+    /// Name of the source in the `#line` directives and in the source
+    /// map
+    pub source_name: String,
+    /// Size of the buffers strings are read in, terminator included
+    pub string_buffer: usize,
+}
+
+impl Default for CompileOptions {
+    fn default() -> Self {
+        CompileOptions {
+            lints: LintConfig::new(),
+            dialect: Dialect::default(),
+            bounds_checks: false,
+            asserts: AssertMode::default(),
+            opt_level: OptLevel::default(),
+            runtime: RuntimeMode::default(),
+            line_directives: false,
+            source_name: "input.mpl".to_string(),
+            string_buffer: 128,
+        }
+    }
+}
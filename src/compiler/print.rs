@@ -1,35 +1,88 @@
 use crate::core::{
-    ast::PrintStmtNode,
+    ast::{PrintStmtNode, WriteArg},
     types::{SimpleType, Type},
 };
 
 use super::Compiler;
 
 impl Compiler {
-    /// Compiles a print statement
+    /// Compiles a `write` or `writeln` statement, each value is printed
+    /// before the next one is evaluated
     pub fn compile_print(&mut self, node: PrintStmtNode) {
-        let child = node.to_print.as_ref();
+        for arg in node.args.iter() {
+            self.compile_write_arg(arg);
+        }
+        if node.newline {
+            self.emit("printf(\"\\n\");".to_string());
+        }
+    }
+
+    /// Prints a value with `printf`, padded to its width and with the
+    /// decimals of its precision
+    fn compile_write_arg(&mut self, arg: &WriteArg) {
+        let child = &arg.value;
         self.compile_ast(child.clone());
-        match child.r_type() {
-            Type::Simple(t) => match t {
-                SimpleType::Int => self.emit("printf(\"%d\\n\", last_int);".to_string()),
-                SimpleType::String => self.emit("printf(\"%s\\n\", last_str);".to_string()),
-                SimpleType::Bool => self.emit("printf(\"%d\\n\", last_bool);".to_string()),
-                SimpleType::Real => self.emit("printf(\"%e\\n\", last_double);".to_string()),
-                SimpleType::Void => {
-                    self.push_c_error(child.clone(), "Unable to print a void expression");
-                }
-            },
-            Type::Array(t) => self.emit(format!(
-                "printf(\"array of {}\");",
-                match t {
-                    SimpleType::Int => "int",
-                    SimpleType::Real => "reals",
-                    SimpleType::String => "string",
-                    SimpleType::Bool => "bool",
-                    SimpleType::Void => "void",
-                }
-            )),
+        let r_type = child.r_type();
+        let mut register = format!("last_{}", Compiler::type_for_last(r_type.clone()));
+        // the width and the precision are computed in the same registers
+        let label = self.advance_label();
+        if arg.width.is_some() && matches!(r_type, Type::Simple(_)) {
+            let stashed = format!("write_{}", label);
+            self.emit_declaration(
+                &stashed,
+                format!("{} {} = {};", r_type.to_c_type(), stashed, register),
+            );
+            register = stashed;
+        }
+        let (conversion, printed) = match &r_type {
+            Type::Simple(SimpleType::Int) => ("d", register),
+            Type::Simple(SimpleType::Real) if arg.precision.is_some() => ("f", register),
+            Type::Simple(SimpleType::Real) => ("s", format!("mpl_real({})", register)),
+            Type::Simple(SimpleType::String) => ("s", register),
+            Type::Simple(SimpleType::Bool) => ("s", format!("{} ? \"true\" : \"false\"", register)),
+            Type::Simple(SimpleType::Void) => {
+                return self.push_c_error(child.clone(), "Unable to print a void expression");
+            }
+            Type::Simple(SimpleType::Record(_)) => {
+                return self.push_c_error(child.clone(), "Unable to print a whole record");
+            }
+            Type::Array(t, _) => (
+                "s",
+                format!(
+                    "\"array of {}\"",
+                    match t.internal() {
+                        SimpleType::Int => "int",
+                        SimpleType::Real => "reals",
+                        SimpleType::String => "string",
+                        SimpleType::Bool => "bool",
+                        SimpleType::Void => "void",
+                        SimpleType::Record(_) => "records",
+                    }
+                ),
+            ),
+        };
+        let mut format = "%".to_string();
+        let mut args = vec![];
+        if let Some(width) = &arg.width {
+            self.compile_ast(width.clone());
+            let width = format!("width_{}", label);
+            self.emit_declaration(&width, format!("int {} = last_int;", width));
+            format += "*";
+            args.push(format!("width_{}", label));
+        }
+        if let Some(precision) = &arg.precision {
+            self.compile_ast(precision.clone());
+            let precision = format!("precision_{}", label);
+            self.emit_declaration(&precision, format!("int {} = last_int;", precision));
+            format += ".*";
+            args.push(format!("precision_{0} < 0 ? 0 : precision_{0}", label));
         }
+        args.push(printed);
+        self.emit(format!(
+            "printf(\"{}{}\", {});",
+            format,
+            conversion,
+            args.join(", ")
+        ));
     }
 }
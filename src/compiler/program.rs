@@ -1,12 +1,14 @@
-use crate::core::ast::ProgramNode;
+use crate::{analysis::calls::CallGraph, core::ast::ProgramNode};
 
 use super::Compiler;
 
 impl Compiler {
     /// Function that compiles a program node, firstly sets up the
-    /// required registers, compiles all the functions and procedures
-    /// and finally compiles the main block
+    /// required registers, the globals and the registers of the
+    /// subprograms, compiles all the functions and procedures and
+    /// finally compiles the main block
     pub fn compile_program(&mut self, node: ProgramNode) {
+        self.calls = CallGraph::build(&node);
         // Registers
         self.emit("int last_int;".to_string());
         self.emit("double last_double;".to_string());
@@ -14,10 +16,32 @@ impl Compiler {
         self.emit("char* last_str;".to_string());
         self.emit("int* last_int_arr;".to_string());
         self.emit("double* last_double_arr;".to_string());
-        self.emit("bool last_bool_arr;".to_string());
+        self.emit("bool* last_bool_arr;".to_string());
         self.emit("char** last_str_arr;".to_string());
-        self.emit("void* main_block_ptr = &&main_block;".to_string());
-        self.emit("goto *main_block_ptr;".to_string());
+        for r in node.records.iter() {
+            self.emit(format!("{} last_{};", r.r_type.c_name(), r.r_type.c_name()));
+            self.emit(format!(
+                "{}* last_{}_arr;",
+                r.r_type.c_name(),
+                r.r_type.c_name()
+            ));
+            self.records.push(r.r_type.clone());
+        }
+        // globals are declared before any jump
+        for g in node.globals.iter() {
+            self.globals.insert(g.id.lexeme.to_lowercase());
+            self.compile_var_decl(g.clone());
+        }
+        for f in node.functions.iter() {
+            self.params.insert(f.name.clone(), f.args.clone());
+            self.declare_subprogram(&f.name, &f.args, Some(&f.r_type));
+        }
+        for p in node.procedures.iter() {
+            self.params.insert(p.name.clone(), p.args.clone());
+            self.declare_subprogram(&p.name, &p.args, None);
+        }
+        self.emit_label_ptr("main_block_ptr", "main_block");
+        self.emit_goto("main_block_ptr", "main_block");
 
         for f in node.functions.iter() {
             self.compile_function(f.clone());
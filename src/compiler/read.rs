@@ -8,21 +8,11 @@ use super::Compiler;
 impl Compiler {
     pub fn compile_read(&mut self, node: ReadStmtNode) {
         let where_to_read = match node.variable_to_read_in.as_ref().clone() {
-            ASTNode::VarName(inode) => match inode.r_type {
-                Type::Simple(s) => match s {
-                    SimpleType::String => format!("{}_{}", self.scope, inode.id.lexeme),
-                    _ => format!("&{}_{}", self.scope, inode.id.lexeme),
-                },
-                Type::Array(_) => {
-                    self.push_c_error(ASTNode::ReadStmt(node.clone()), "Unable to read into array");
-                    "".to_string()
-                }
-            },
-            ASTNode::ArrayRef(inode) => {
-                self.compile_ast(inode.index.as_ref().clone());
-                match inode.r_type {
-                    Type::Simple(_) => format!("{}[last_int]", inode.array.lexeme),
-                    Type::Array(_) => {
+            target @ (ASTNode::VarName(_) | ASTNode::ArrayRef(_) | ASTNode::FieldRef(_)) => {
+                match target.r_type() {
+                    Type::Simple(SimpleType::String) => self.lvalue(&target),
+                    Type::Simple(_) => format!("&{}", self.lvalue(&target)),
+                    Type::Array(..) => {
                         self.push_c_error(
                             ASTNode::ReadStmt(node.clone()),
                             "Unable to read into array",
@@ -52,8 +42,15 @@ impl Compiler {
                     );
                     "%d"
                 }
+                SimpleType::Record(_) => {
+                    self.push_c_error(
+                        ASTNode::ReadStmt(node.clone()),
+                        "Trying to read into a whole record",
+                    );
+                    "%d"
+                }
             },
-            Type::Array(_s) => {
+            Type::Array(..) => {
                 self.push_c_error(
                     ASTNode::ReadStmt(node.clone()),
                     "Trying to read into whole array",
@@ -61,6 +58,17 @@ impl Compiler {
                 "%d"
             }
         };
-        self.emit(format!("scanf(\"{}\", {});", how_to_read, where_to_read));
+        if how_to_read == "%s" {
+            // strings are read in a fresh buffer, the terminator takes
+            // the last byte
+            let size = self.options.string_buffer.max(2);
+            self.emit(format!(
+                "{} = malloc({} * sizeof(char));",
+                where_to_read, size
+            ));
+            self.emit(format!("scanf(\"%{}s\", {});", size - 1, where_to_read));
+        } else {
+            self.emit(format!("scanf(\"{}\", {});", how_to_read, where_to_read));
+        }
     }
 }
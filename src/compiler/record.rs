@@ -0,0 +1,99 @@
+use log::trace;
+
+use crate::core::{
+    ast::{ASTNode, FieldRefNode},
+    types::{RecordType, SimpleType, Type},
+};
+
+use super::Compiler;
+
+impl Compiler {
+    /// The struct a record maps to, its members are the fields in
+    /// declaration order
+    pub fn record_typedef(record: &RecordType) -> String {
+        let mut typedef = "typedef struct {\n".to_string();
+        for (i, (_, f_type)) in record.fields.iter().enumerate() {
+            typedef += &format!("    {} {};\n", f_type.to_c_type(), record.c_field(i));
+        }
+        typedef + &format!("}} {};\n", record.c_name())
+    }
+
+    /// Initial value of a variable of the type, records get the
+    /// initial value of every field
+    pub fn c_default(s_type: &SimpleType) -> String {
+        match s_type {
+            SimpleType::Real => "0.0".to_string(),
+            SimpleType::String => "\"\"".to_string(),
+            SimpleType::Bool => "false".to_string(),
+            SimpleType::Record(r) => format!(
+                "{{{}}}",
+                r.fields
+                    .iter()
+                    .map(|(_, t)| Compiler::c_default(&t.internal()))
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ),
+            _ => "0".to_string(),
+        }
+    }
+
+    /// Tells if a value of the type has a string in it, which can't be
+    /// left zeroed
+    pub fn holds_string(s_type: &SimpleType) -> bool {
+        match s_type {
+            SimpleType::String => true,
+            SimpleType::Record(r) => r
+                .fields
+                .iter()
+                .any(|(_, t)| Compiler::holds_string(&t.internal())),
+            _ => false,
+        }
+    }
+
+    /// Compiles the access to a field, the record is computed in its
+    /// register and the field is copied in the register of its type
+    pub fn compile_field_ref(&mut self, node: FieldRefNode) {
+        trace!("compiling field reference");
+        let record = node.record.r_type();
+        self.compile_ast(*node.record);
+        if let Type::Simple(SimpleType::Record(r)) = &record {
+            self.emit(format!(
+                "last_{} = last_{}.{};",
+                Compiler::type_for_last(node.r_type),
+                Compiler::type_for_last(record.clone()),
+                r.c_field(node.index)
+            ));
+        }
+    }
+
+    /// The C lvalue of the target of an assignment or of a read, the
+    /// indexes in it are computed (and checked) first, in temporaries
+    pub fn lvalue(&mut self, target: &ASTNode) -> String {
+        match target {
+            ASTNode::VarName(v) => self.variable(&v.id.lexeme),
+            ASTNode::ArrayRef(a) => {
+                let offset = self.element_offset(a);
+                format!("{}[{}]", self.array_name(a), offset)
+            }
+            ASTNode::FieldRef(f) => {
+                let record = self.lvalue(&f.record);
+                match f.record.r_type() {
+                    Type::Simple(SimpleType::Record(r)) => {
+                        format!("{}.{}", record, r.c_field(f.index))
+                    }
+                    _ => {
+                        self.push_c_error(
+                            target.clone(),
+                            "Field of something that is not a record",
+                        );
+                        String::new()
+                    }
+                }
+            }
+            other => {
+                self.push_c_error(other.clone(), "Unknown variable to reassign");
+                String::new()
+            }
+        }
+    }
+}
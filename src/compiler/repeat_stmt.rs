@@ -0,0 +1,38 @@
+use crate::core::ast::RepeatStmtNode;
+
+use super::{Compiler, LoopLabels};
+
+impl Compiler {
+    /// Compiles a repeat loop, the guard is checked after the body and
+    /// a `continue` jumps to it
+    pub fn compile_repeat(&mut self, node: RepeatStmtNode) {
+        let label = self.advance_label();
+        let (repeat, until, endrepeat) = (
+            format!("repeat_{}", label),
+            format!("until_{}", label),
+            format!("endrepeat_{}", label),
+        );
+        let (repeat_ptr, until_ptr, endrepeat_ptr) = (
+            format!("repeat_ptr_{}", label),
+            format!("until_ptr_{}", label),
+            format!("endrepeat_ptr_{}", label),
+        );
+        self.emit_label_ptr(&endrepeat_ptr, &endrepeat);
+        self.emit_label_ptr(&until_ptr, &until);
+        self.emit_label_ptr(&repeat_ptr, &repeat);
+        self.emit_label(repeat.clone());
+        self.loops.push(LoopLabels {
+            next: (until_ptr, until.clone()),
+            exit: (endrepeat_ptr, endrepeat.clone()),
+        });
+        self.compile_ast(node.block.as_ref().clone());
+        self.loops.pop();
+        self.emit_label(until);
+        self.compile_ast(node.guard.as_ref().clone());
+        self.emit(format!(
+            "if (!last_bool) {}",
+            self.goto(&repeat_ptr, &repeat)
+        ));
+        self.emit_label(endrepeat);
+    }
+}
@@ -3,6 +3,9 @@ use crate::core::ast::ReturnStmtNode;
 use super::Compiler;
 
 impl Compiler {
+    /// Compiles a return, storing the value (if any) in the return
+    /// register of the current subprogram and jumping back to the
+    /// caller, a return in the main block ends the program
     pub fn compile_return(&mut self, expr: ReturnStmtNode) {
         if let Some(var) = expr.value {
             self.compile_ast(var.as_ref().clone());
@@ -11,8 +14,11 @@ impl Compiler {
                 Compiler::f_ret_value(self.scope.clone()),
                 Compiler::type_for_last(var.r_type())
             ));
+        }
+        if self.scope == "main" {
+            self.emit("return 0;".to_string());
         } else {
-            self.emit("return;".to_string());
+            self.emit_return(&self.scope.clone());
         }
     }
 }
@@ -0,0 +1,194 @@
+use super::{AssertMode, CompileOptions, RuntimeMode, RUNTIME_HEADER};
+use crate::interpreter::builtins::RANDOM_SEED;
+
+/// Helpers the translation calls at run time, they report errors the
+/// way the interpreter does. The output is flushed first, `exit`
+/// would not lose it but the order with stderr would be off
+const RUNTIME: &str = r#"static inline void mpl_check_index(int index, int length, const char* array, int line, int col) {
+    if (length >= 0 && (index < 0 || index >= length)) {
+        fflush(stdout);
+        fprintf(stderr, "Evaluation error in %d:%d > Index %d out of bounds for array `%s` of length %d\n", line, col, index, array, length);
+        exit(1);
+    }
+}
+static inline void mpl_assert_failed(int line, int col) {
+    fflush(stdout);
+    fprintf(stderr, "Evaluation error in %d:%d > Assertion failed\n", line, col);
+    exit(1);
+}
+static inline int mpl_length(const char* s) {
+    return (int)strlen(s);
+}
+static inline char* mpl_substr(const char* s, int start, int count) {
+    int length = (int)strlen(s);
+    start = start < 0 ? 0 : start > length ? length : start;
+    count = count < 0 ? 0 : count > length - start ? length - start : count;
+    char* result = malloc(count + 1);
+    memcpy(result, s + start, count);
+    result[count] = '\0';
+    return result;
+}
+static inline int mpl_ord(const char* s) {
+    return (unsigned char)s[0];
+}
+static inline char* mpl_chr(int code, int line, int col) {
+    if (code < 1 || code > 127) {
+        fflush(stdout);
+        fprintf(stderr, "Evaluation error in %d:%d > chr(%d) is not an ASCII character\n", line, col, code);
+        exit(1);
+    }
+    char* result = malloc(2);
+    result[0] = (char)code;
+    result[1] = '\0';
+    return result;
+}
+static inline int mpl_abs_int(int x) {
+    return x < 0 ? (int)(0u - (unsigned)x) : x;
+}
+static inline double mpl_abs_real(double x) {
+    return fabs(x);
+}
+static inline int mpl_min_int(int a, int b) {
+    return a < b ? a : b;
+}
+static inline double mpl_min_real(double a, double b) {
+    return a < b ? a : b;
+}
+static inline int mpl_max_int(int a, int b) {
+    return a < b ? b : a;
+}
+static inline double mpl_max_real(double a, double b) {
+    return a < b ? b : a;
+}
+static inline char* mpl_inttostr(int n) {
+    char* result = malloc(12);
+    sprintf(result, "%d", n);
+    return result;
+}
+/* an optional sign and digits, nothing around them */
+static inline int mpl_strtoint(const char* s, int line, int col) {
+    const char* digits = s + (s[0] == '+' || s[0] == '-');
+    bool valid = *digits != '\0';
+    for (const char* c = digits; *c != '\0'; c++) {
+        valid = valid && *c >= '0' && *c <= '9';
+    }
+    long long value = valid ? strtoll(s, NULL, 10) : 0;
+    if (!valid || value < INT_MIN || value > INT_MAX) {
+        fflush(stdout);
+        fprintf(stderr, "Evaluation error in %d:%d > `%s` is not an int\n", line, col, s);
+        exit(1);
+    }
+    return (int)value;
+}
+/* the frames of the subprograms, saved around their recursive calls */
+static char* mpl_stack;
+static size_t mpl_stack_top, mpl_stack_capacity;
+static inline void mpl_push(const void* value, size_t size) {
+    if (mpl_stack_top + size > mpl_stack_capacity) {
+        mpl_stack_capacity = 2 * (mpl_stack_top + size);
+        mpl_stack = realloc(mpl_stack, mpl_stack_capacity);
+    }
+    memcpy(mpl_stack + mpl_stack_top, value, size);
+    mpl_stack_top += size;
+}
+static inline void mpl_pop(void* value, size_t size) {
+    mpl_stack_top -= size;
+    memcpy(value, mpl_stack + mpl_stack_top, size);
+}
+/* the fewest digits that read back as the same number, with a point
+   between 1e-5 and 1e15, as the interpreter prints the reals */
+static inline const char* mpl_real(double value) {
+    static char text[64];
+    if (!isfinite(value)) {
+        snprintf(text, sizeof text, "%f", value);
+        return text;
+    }
+    int digits = 0;
+    for (; digits < 16; digits++) {
+        snprintf(text, sizeof text, "%.*e", digits, value);
+        if (strtod(text, NULL) == value) {
+            break;
+        }
+    }
+    snprintf(text, sizeof text, "%.*e", digits, value);
+    int exponent = atoi(strchr(text, 'e') + 1);
+    if (exponent >= -5 && exponent < 15) {
+        if (digits > exponent) {
+            snprintf(text, sizeof text, "%.*f", digits - exponent, value);
+        } else {
+            snprintf(text, sizeof text, "%.0f.0", value);
+        }
+    }
+    return text;
+}
+/* converting a real out of the range of int is undefined behaviour */
+static inline int mpl_to_int(const char* builtin, double x, double whole, int line, int col) {
+    if (!(whole >= INT_MIN && whole <= INT_MAX)) {
+        fflush(stdout);
+        fprintf(stderr, "Evaluation error in %d:%d > %s(%s) does not fit in an int\n", line, col, builtin, mpl_real(x));
+        exit(1);
+    }
+    return (int)whole;
+}
+static inline int mpl_trunc(double x, int line, int col) {
+    return mpl_to_int("trunc", x, trunc(x), line, col);
+}
+static inline int mpl_round(double x, int line, int col) {
+    return mpl_to_int("round", x, round(x), line, col);
+}
+"#;
+
+/// The xorshift generator behind `random`, it starts from the seed
+/// of the interpreter
+fn random() -> String {
+    format!(
+        r#"static inline int mpl_random(int bound, int line, int col) {{
+    static uint32_t state = {}u;
+    if (bound <= 0) {{
+        fflush(stdout);
+        fprintf(stderr, "Evaluation error in %d:%d > random(%d) needs a positive bound\n", line, col, bound);
+        exit(1);
+    }}
+    state ^= state << 13;
+    state ^= state >> 17;
+    state ^= state << 5;
+    return (int)(state % (uint32_t)bound);
+}}
+"#,
+        RANDOM_SEED
+    )
+}
+
+const INCLUDES: &[&str] = &[
+    "stdio.h",
+    "stdlib.h",
+    "string.h",
+    "stdbool.h",
+    "stdint.h",
+    "limits.h",
+    "math.h",
+];
+
+/// Content of `RUNTIME_HEADER`
+pub fn header() -> String {
+    let mut header = "#ifndef MINIPLC_RUNTIME_H\n#define MINIPLC_RUNTIME_H\n".to_string();
+    for include in INCLUDES {
+        header += &format!("#include <{}>\n", include);
+    }
+    header + RUNTIME + &random() + "#endif\n"
+}
+
+/// Everything the translation starts with, before `main`
+pub fn prelude(options: &CompileOptions) -> String {
+    let mut prelude = String::new();
+    for include in INCLUDES {
+        prelude += &format!("#include <{}>\n", include);
+    }
+    if options.asserts == AssertMode::Abort {
+        prelude += "#include <assert.h>\n";
+    }
+    match options.runtime {
+        RuntimeMode::Inline => prelude + RUNTIME + &random(),
+        RuntimeMode::Header => prelude + &format!("#include \"{}\"\n", RUNTIME_HEADER),
+    }
+}
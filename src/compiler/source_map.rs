@@ -0,0 +1,27 @@
+use serde_json::{json, Value};
+
+use crate::scanner::position::Position;
+
+/// A line of the translation together with the position of the node
+/// it was generated for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineMapping {
+    /// Line of the C source, starting from 1
+    pub c_line: usize,
+    pub position: Position,
+}
+
+/// The source map as JSON, the lines of the translation are in order
+pub fn source_map_json(source_name: &str, mappings: &[LineMapping]) -> Value {
+    json!({
+        "source": source_name,
+        "lines": mappings
+            .iter()
+            .map(|m| json!({
+                "c_line": m.c_line,
+                "line": m.position.line,
+                "col": m.position.col,
+            }))
+            .collect::<Vec<_>>(),
+    })
+}
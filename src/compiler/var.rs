@@ -1,6 +1,7 @@
 use crate::core::{
     ast::{ASTNode, VarNameNode, VarReassignmentExprNode, VariableDeclNode},
     symbol_table::SymbolType,
+    types::Type,
 };
 
 use super::Compiler;
@@ -10,13 +11,17 @@ impl Compiler {
         match expr.variable_to_reassign.as_ref().clone() {
             ASTNode::VarName(node) => match node.s_type {
                 SymbolType::Var | SymbolType::VarParam | SymbolType::Param | SymbolType::Arr => {
-                    let name = format!("{}_{}", self.scope, node.id.lexeme.to_lowercase());
+                    let name = self.variable(&node.id.lexeme);
                     self.compile_ast(expr.new_value.as_ref().clone());
                     self.emit(format!(
                         "{} = last_{};",
                         name,
                         Compiler::type_for_last(expr.new_value.r_type())
                     ));
+                    if let Type::Array(..) = expr.new_value.r_type() {
+                        let length = self.length_of(&expr.new_value);
+                        self.set_array_len(&name, &length);
+                    }
                 }
                 _ => {
                     self.push_c_error(
@@ -25,12 +30,9 @@ impl Compiler {
                     );
                 }
             },
-            ASTNode::ArrayRef(node) => {
-                let label = self.advance_label();
-                self.compile_ast(node.index.as_ref().clone());
-                self.emit(format!("int tmp_{} = last_int;", label));
+            target @ (ASTNode::ArrayRef(_) | ASTNode::FieldRef(_)) => {
+                let name = self.lvalue(&target);
                 self.compile_ast(expr.new_value.as_ref().clone());
-                let name = format!("{}_{}[tmp_{}]", self.scope, node.array.lexeme, label);
                 self.emit(format!(
                     "{} = last_{};",
                     name,
@@ -46,19 +48,79 @@ impl Compiler {
         }
     }
 
+    /// Declares the variable, zeroed like the interpreter does it,
+    /// arrays with a known size get their elements allocated (and
+    /// zeroed, empty strings for the strings)
     pub fn compile_var_decl(&mut self, expr: VariableDeclNode) {
-        self.emit(format!(
-            "{} {}_{};",
-            expr.var_type.to_c_type(),
-            self.scope,
-            expr.id.lexeme.to_lowercase()
-        ));
+        let name = self.variable(&expr.id.lexeme);
+        if let Type::Array(..) = expr.var_type {
+            self.declare_array_len(&name, expr.size.map(|s| s as i64).unwrap_or(-1));
+        }
+        match (&expr.var_type, expr.size) {
+            (Type::Array(t, _), Some(size)) => {
+                let cells = size * t.cells();
+                let internal = t.internal();
+                self.emit_declaration(
+                    &name,
+                    format!(
+                        "{} {} = calloc({}, sizeof({}));",
+                        expr.var_type.to_c_type(),
+                        name,
+                        cells,
+                        internal.to_c_type()
+                    ),
+                );
+                // a zeroed string is NULL, the cells holding one get
+                // the same default as a scalar
+                if Compiler::holds_string(&internal) {
+                    let label = self.advance_label();
+                    self.emit(format!(
+                        "for (int i_{0} = 0; i_{0} < {1}; i_{0}++) {2}[i_{0}] = ({3}){4};",
+                        label,
+                        cells,
+                        name,
+                        internal.to_c_type(),
+                        Compiler::c_default(&internal)
+                    ));
+                }
+            }
+            (Type::Simple(t), _) => self.emit_declaration(
+                &name,
+                format!("{} {} = {};", t.to_c_type(), name, Compiler::c_default(t)),
+            ),
+            _ => self.emit_declaration(&name, format!("{} {};", expr.var_type.to_c_type(), name)),
+        }
+    }
+
+    /// Declares the length of the array, for the bounds checks
+    pub fn declare_array_len(&mut self, name: &str, length: i64) {
+        if self.options.bounds_checks {
+            let length_name = Compiler::array_len(name);
+            self.emit_declaration(&length_name, format!("int {} = {};", length_name, length));
+        }
+    }
+
+    /// Updates the length of the array after it got a new value
+    pub fn set_array_len(&mut self, name: &str, length: &str) {
+        if self.options.bounds_checks {
+            self.emit(format!("{} = {};", Compiler::array_len(name), length));
+        }
+    }
+
+    /// Length of an array expression, only known when it is a variable
+    /// or a row of a nested array
+    pub fn length_of(&self, expr: &ASTNode) -> String {
+        match expr {
+            ASTNode::VarName(v) => Compiler::array_len(&self.variable(&v.id.lexeme)),
+            ASTNode::ArrayRef(a) => a.r_type.size().map(|s| s as i64).unwrap_or(-1).to_string(),
+            _ => "-1".to_string(),
+        }
     }
 
     pub fn compile_var_name(&mut self, expr: VarNameNode) {
         let name = match expr.s_type {
             SymbolType::Var | SymbolType::VarParam | SymbolType::Param | SymbolType::Arr => {
-                format!("{}_{}", self.scope, expr.id.lexeme.to_lowercase())
+                self.variable(&expr.id.lexeme)
             }
             _ => {
                 self.push_c_error(
@@ -1,20 +1,30 @@
 use crate::core::ast::WhileStmtNode;
 
-use super::Compiler;
+use super::{Compiler, LoopLabels};
 
 impl Compiler {
     pub fn compile_while(&mut self, node: WhileStmtNode) {
         let label = self.advance_label();
+        let (guard, endwhile) = (format!("guard_{}", label), format!("endwhile_{}", label));
+        let (guard_ptr, endwhile_ptr) = (
+            format!("guard_ptr_{}", label),
+            format!("endwhile_ptr_{}", label),
+        );
+        self.emit_label_ptr(&endwhile_ptr, &endwhile);
+        self.emit_label_ptr(&guard_ptr, &guard);
+        self.emit_label(guard.clone());
+        self.compile_ast(node.guard.as_ref().clone());
         self.emit(format!(
-            "void* endwhile_ptr_{} = &&endwhile_{};",
-            label, label
+            "if (!last_bool) {}",
+            self.goto(&endwhile_ptr, &endwhile)
         ));
-        self.emit(format!("void* guard_ptr_{} = &&guard_{};", label, label));
-        self.emit_label(format!("guard_{}", label));
-        self.compile_ast(node.guard.as_ref().clone());
-        self.emit(format!("if (!last_bool) goto *endwhile_ptr_{};", label));
+        self.loops.push(LoopLabels {
+            next: (guard_ptr.clone(), guard.clone()),
+            exit: (endwhile_ptr, endwhile),
+        });
         self.compile_ast(node.block.as_ref().clone());
-        self.emit(format!("goto *guard_ptr_{};", label));
+        self.loops.pop();
+        self.emit_goto(&guard_ptr, &guard);
         self.emit_label(format!("endwhile_{}", label));
     }
 }
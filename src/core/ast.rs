@@ -1,11 +1,11 @@
-use std::fmt::Display;
+use std::{fmt::Display, sync::Arc};
 
 use crate::core::token::*;
 use crate::scanner::position::Position;
 
 use super::objects::Object;
-use super::symbol_table::{SymbolTable, SymbolType};
-use super::types::{SimpleType, Type};
+use super::symbol_table::{Symbol, SymbolTable, SymbolType};
+use super::types::{RecordType, SimpleType, Type};
 
 #[derive(Clone, Debug)]
 pub enum ASTNode {
@@ -13,22 +13,31 @@ pub enum ASTNode {
     ProgramName(ProgramNameNode),
     FunctionDecl(FunctionDeclNode),
     ProcedureDecl(ProcedureDeclNode),
+    RecordDecl(RecordDeclNode),
     Block(BlockNode),
 
     // Expressions
     BinaryExpression(BinaryExprNode),
     VarName(VarNameNode),
     ArrayRef(ArrayRefExpr),
+    FieldRef(FieldRefNode),
     Literal(LiteralExprNode),
     UnaryExpression(UnaryExprNode),
     VarReassignment(VarReassignmentExprNode),
 
     // Declarations
     VariableDecl(VariableDeclNode),
+    ConstDecl(ConstDeclNode),
+    ForwardDecl(ForwardDeclNode),
 
     // Statements
     WhileStmt(WhileStmtNode),
+    ForStmt(ForStmtNode),
+    RepeatStmt(RepeatStmtNode),
+    BreakStmt(BreakStmtNode),
+    ContinueStmt(ContinueStmtNode),
     IfStmt(IfStmtNode),
+    CaseStmt(CaseStmtNode),
     ElseStmt(ElseStmtNode),
     PrintStmt(PrintStmtNode),
     ReadStmt(ReadStmtNode),
@@ -48,17 +57,20 @@ impl ASTNode {
             ASTNode::ProgramName(_) => Type::Simple(SimpleType::Void),
             ASTNode::FunctionDecl(_) => Type::Simple(SimpleType::Void),
             ASTNode::ProcedureDecl(_) => Type::Simple(SimpleType::Void),
+            ASTNode::RecordDecl(_) => Type::Simple(SimpleType::Void),
+            ASTNode::ConstDecl(_) => Type::Simple(SimpleType::Void),
+            ASTNode::ForwardDecl(_) => Type::Simple(SimpleType::Void),
             ASTNode::Block(_) => Type::Simple(SimpleType::Void),
-            ASTNode::BinaryExpression(e) => e.r_type,
-            ASTNode::VarName(i) => i.r_type,
-            ASTNode::Literal(l) => l.r_type,
-            ASTNode::UnaryExpression(u) => u.r_type,
+            ASTNode::BinaryExpression(e) => e.r_type.clone(),
+            ASTNode::VarName(i) => i.r_type.clone(),
+            ASTNode::Literal(l) => l.r_type.clone(),
+            ASTNode::UnaryExpression(u) => u.r_type.clone(),
             ASTNode::VarReassignment(_) => Type::Simple(SimpleType::Void),
             ASTNode::VariableDecl(_) => Type::Simple(SimpleType::Void),
             ASTNode::PrintStmt(_) => Type::Simple(SimpleType::Void),
             ASTNode::ReadStmt(_) => Type::Simple(SimpleType::Void),
             ASTNode::AssertStmt(_) => Type::Simple(SimpleType::Void),
-            ASTNode::FunctionCallStmt(f) => f.r_type,
+            ASTNode::FunctionCallStmt(f) => f.r_type.clone(),
             ASTNode::ProcedureCallStmt(_) => Type::Simple(SimpleType::Void),
             ASTNode::ReturnStmt(r) => match r.clone().value {
                 Some(v) => v.r_type(),
@@ -66,8 +78,14 @@ impl ASTNode {
             },
             ASTNode::EofStmt(_) => Type::Simple(SimpleType::Void),
             ASTNode::WhileStmt(_) => Type::Simple(SimpleType::Void),
-            ASTNode::ArrayRef(a) => a.r_type,
+            ASTNode::ForStmt(_) => Type::Simple(SimpleType::Void),
+            ASTNode::RepeatStmt(_) => Type::Simple(SimpleType::Void),
+            ASTNode::BreakStmt(_) => Type::Simple(SimpleType::Void),
+            ASTNode::ContinueStmt(_) => Type::Simple(SimpleType::Void),
+            ASTNode::ArrayRef(a) => a.r_type.clone(),
+            ASTNode::FieldRef(f) => f.r_type.clone(),
             ASTNode::IfStmt(_) => Type::Simple(SimpleType::Void),
+            ASTNode::CaseStmt(_) => Type::Simple(SimpleType::Void),
             ASTNode::ElseStmt(_) => Type::Simple(SimpleType::Void),
         }
     }
@@ -78,16 +96,25 @@ impl ASTNode {
             ASTNode::ProgramName(_node) => Position::new(0, 0, 0),
             ASTNode::FunctionDecl(node) => node.position,
             ASTNode::ProcedureDecl(node) => node.position,
+            ASTNode::RecordDecl(node) => node.position,
+            ASTNode::ConstDecl(node) => node.position,
+            ASTNode::ForwardDecl(node) => node.position,
             ASTNode::Block(node) => node.position,
             ASTNode::BinaryExpression(node) => node.position,
             ASTNode::VarName(node) => node.position,
             ASTNode::ArrayRef(node) => node.position,
+            ASTNode::FieldRef(node) => node.position,
             ASTNode::Literal(node) => node.position,
             ASTNode::UnaryExpression(node) => node.position,
             ASTNode::VarReassignment(node) => node.position,
             ASTNode::VariableDecl(node) => node.position,
             ASTNode::WhileStmt(node) => node.position,
+            ASTNode::ForStmt(node) => node.position,
+            ASTNode::RepeatStmt(node) => node.position,
+            ASTNode::BreakStmt(node) => node.position,
+            ASTNode::ContinueStmt(node) => node.position,
             ASTNode::IfStmt(node) => node.position,
+            ASTNode::CaseStmt(node) => node.position,
             ASTNode::ElseStmt(node) => node.position,
             ASTNode::PrintStmt(node) => node.position,
             ASTNode::ReadStmt(node) => node.position,
@@ -107,9 +134,39 @@ pub struct ProgramNode {
     pub program_name: ProgramNameNode,
     pub functions: Box<[FunctionDeclNode]>,
     pub procedures: Box<[ProcedureDeclNode]>,
+    pub records: Box<[RecordDeclNode]>,
+    /// Variables declared outside of any subprogram, visible in all of
+    /// them
+    pub globals: Box<[VariableDeclNode]>,
+    pub constants: Box<[ConstDeclNode]>,
     pub main_block: BlockNode,
 }
 
+/// Node to rapresent the declaration of a record type, the type has
+/// the fields
+#[derive(Clone, Debug)]
+pub struct RecordDeclNode {
+    pub position: Position,
+    pub r_type: Arc<RecordType>,
+}
+
+/// Node to rapresent the declaration of a global constant, its value
+/// is computed by the parser
+#[derive(Clone, Debug)]
+pub struct ConstDeclNode {
+    pub position: Position,
+    pub id: Token,
+    pub value: LiteralExprNode,
+}
+
+/// Node to rapresent a `forward` declaration of a function or of a
+/// procedure, the symbol has its signature
+#[derive(Clone, Debug)]
+pub struct ForwardDeclNode {
+    pub position: Position,
+    pub symbol: Symbol,
+}
+
 /// Node that rapresents a binary expression (both binary and integer)
 #[derive(Clone, Debug)]
 pub struct BinaryExprNode {
@@ -152,12 +209,28 @@ pub struct VarNameNode {
     pub s_type: SymbolType,
 }
 
-/// Node to rapresent a reference to an array
+/// Node to rapresent a reference to an array, with an index for each
+/// dimension it goes through: `m[i][j]`. With less indexes than the
+/// dimensions of the array it is a whole row
 #[derive(Clone, Debug)]
 pub struct ArrayRefExpr {
     pub position: Position,
     pub array: Token,
-    pub index: Box<ASTNode>,
+    /// Type of the whole array, its nested sizes lay the elements out
+    pub array_type: Type,
+    pub indexes: Vec<ASTNode>,
+    pub r_type: Type,
+}
+
+/// Node to rapresent the access to a field of a record, the record
+/// is a variable, an array element, a function call or a field
+#[derive(Clone, Debug)]
+pub struct FieldRefNode {
+    pub position: Position,
+    pub record: Box<ASTNode>,
+    pub field: Token,
+    /// Position of the field in the record
+    pub index: usize,
     pub r_type: Type,
 }
 
@@ -190,27 +263,54 @@ pub struct VariableDeclNode {
     pub position: Position,
     pub id: Token,
     pub var_type: Type,
-    pub value: Option<ExpressionStmtNode>,
+    /// Number of elements, for arrays
+    pub size: Option<usize>,
 }
 
-/// Node to rapresent an expression, both unary and binary, when the
-/// necessary elements to understand what to do are given by the
-/// context
+/// Node to rapresent a while statement
 #[derive(Clone, Debug)]
-pub struct ExpressionStmtNode {
+pub struct WhileStmtNode {
     pub position: Position,
-    pub child: Box<ASTNode>,
-    pub r_type: Type,
+    pub guard: Box<ASTNode>,
+    pub block: Box<ASTNode>,
 }
 
-/// Node to rapresent a while statement
+/// Node to rapresent a for statement, the bounds are evaluated once
+/// before the first iteration
 #[derive(Clone, Debug)]
-pub struct WhileStmtNode {
+pub struct ForStmtNode {
     pub position: Position,
-    pub guard: Box<ASTNode>,
+    /// The control variable, an int
+    pub variable: Box<ASTNode>,
+    pub start: Box<ASTNode>,
+    pub end: Box<ASTNode>,
+    /// `downto` instead of `to`
+    pub downto: bool,
     pub block: Box<ASTNode>,
 }
 
+/// Node to rapresent a repeat statement, the body runs before the
+/// guard is checked and the loop ends when the guard holds
+#[derive(Clone, Debug)]
+pub struct RepeatStmtNode {
+    pub position: Position,
+    pub block: Box<ASTNode>,
+    pub guard: Box<ASTNode>,
+}
+
+/// Node to rapresent a break out of the innermost loop
+#[derive(Clone, Debug)]
+pub struct BreakStmtNode {
+    pub position: Position,
+}
+
+/// Node to rapresent a jump to the next iteration of the innermost
+/// loop
+#[derive(Clone, Debug)]
+pub struct ContinueStmtNode {
+    pub position: Position,
+}
+
 /// Node to rapresent a read statement
 #[derive(Clone, Debug)]
 pub struct ReadStmtNode {
@@ -226,13 +326,35 @@ pub struct VarReassignmentExprNode {
     pub new_value: Box<ASTNode>,
 }
 
-/// Node to rapresent a print statement
+/// Node to rapresent a `write` or `writeln` statement, the values
+/// are printed one after the other
 #[derive(Clone, Debug)]
 pub struct PrintStmtNode {
-    pub to_print: Box<ASTNode>,
+    pub args: Box<[WriteArg]>,
+    /// `writeln` ends the line after the values
+    pub newline: bool,
     pub position: Position,
 }
 
+/// Value given to `write`, `x:8:2` pads it to 8 characters (on the
+/// right when negative) and prints a real with 2 decimals
+#[derive(Clone, Debug)]
+pub struct WriteArg {
+    pub value: ASTNode,
+    pub width: Option<ASTNode>,
+    pub precision: Option<ASTNode>,
+}
+
+impl WriteArg {
+    /// The value, its width and its precision, in the order they are
+    /// evaluated
+    pub fn nodes(&self) -> impl Iterator<Item = &ASTNode> {
+        std::iter::once(&self.value)
+            .chain(self.width.iter())
+            .chain(self.precision.iter())
+    }
+}
+
 /// Node to rapresent a assert statement
 #[derive(Clone, Debug)]
 pub struct AssertStmtNode {
@@ -245,6 +367,9 @@ pub struct FunctionDeclNode {
     pub name: String,
     pub position: Position,
     pub args: SymbolTable,
+    /// How many of the args are variables captured from the enclosing
+    /// subprograms, they come last
+    pub captured: usize,
     pub block: Box<ASTNode>,
     pub r_type: Type,
 }
@@ -262,6 +387,9 @@ pub struct ProcedureDeclNode {
     pub name: String,
     pub position: Position,
     pub args: SymbolTable,
+    /// How many of the args are variables captured from the enclosing
+    /// subprograms, they come last
+    pub captured: usize,
     pub block: Box<ASTNode>,
 }
 
@@ -287,6 +415,25 @@ pub struct IfStmtNode {
     pub else_stmt: Option<Box<ASTNode>>,
 }
 
+/// Node to rapresent a case statement, the branch with a label equal
+/// to the value runs, the else branch (an `ElseStmt`) if none has it
+#[derive(Debug, Clone)]
+pub struct CaseStmtNode {
+    pub position: Position,
+    pub value: Box<ASTNode>,
+    pub branches: Box<[CaseBranch]>,
+    pub else_stmt: Option<Box<ASTNode>>,
+}
+
+/// Branch of a case statement, its labels are int or string literals
+/// of the type of the value
+#[derive(Debug, Clone)]
+pub struct CaseBranch {
+    pub position: Position,
+    pub labels: Box<[LiteralExprNode]>,
+    pub body: Box<ASTNode>,
+}
+
 #[derive(Debug, Clone)]
 pub struct ElseStmtNode {
     pub position: Position,
@@ -339,8 +486,17 @@ impl Display for ASTNode {
             ASTNode::ProcedureCallStmt(_) => write!(f, "procedure call"),
             ASTNode::ReturnStmt(_) => write!(f, "return statement"),
             ASTNode::WhileStmt(_) => write!(f, "while loop"),
+            ASTNode::ForStmt(_) => write!(f, "for loop"),
+            ASTNode::RepeatStmt(_) => write!(f, "repeat loop"),
+            ASTNode::BreakStmt(_) => write!(f, "break"),
+            ASTNode::ContinueStmt(_) => write!(f, "continue"),
             ASTNode::ArrayRef(_) => write!(f, "array reference"),
+            ASTNode::FieldRef(_) => write!(f, "field reference"),
+            ASTNode::RecordDecl(r) => write!(f, "record declaration of {}", r.r_type.name),
+            ASTNode::ConstDecl(c) => write!(f, "constant declaration of {}", c.id.lexeme),
+            ASTNode::ForwardDecl(d) => write!(f, "forward declaration of {}", d.symbol.name),
             ASTNode::IfStmt(_) => write!(f, "If statement"),
+            ASTNode::CaseStmt(_) => write!(f, "Case statement"),
             ASTNode::ElseStmt(_) => write!(f, "else statement"),
         }
     }
@@ -0,0 +1,200 @@
+//! Functions every program can call without declaring them, they are
+//! in the global symbol table before parsing starts
+
+use std::fmt::Display;
+
+use super::{
+    symbol_table::{Symbol, SymbolTable, SymbolType},
+    types::{SimpleType, Type},
+};
+use crate::scanner::position::Position;
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Builtin {
+    Length,
+    Substr,
+    Ord,
+    Chr,
+    Abs,
+    Sqrt,
+    Trunc,
+    Round,
+    IntToStr,
+    StrToInt,
+    Min,
+    Max,
+    Random,
+}
+
+pub const BUILTINS: [Builtin; 13] = [
+    Builtin::Length,
+    Builtin::Substr,
+    Builtin::Ord,
+    Builtin::Chr,
+    Builtin::Abs,
+    Builtin::Sqrt,
+    Builtin::Trunc,
+    Builtin::Round,
+    Builtin::IntToStr,
+    Builtin::StrToInt,
+    Builtin::Min,
+    Builtin::Max,
+    Builtin::Random,
+];
+
+const INT: Type = Type::Simple(SimpleType::Int);
+const REAL: Type = Type::Simple(SimpleType::Real);
+const STRING: Type = Type::Simple(SimpleType::String);
+
+/// Kind of value a parameter takes, a real one also takes an int
+#[derive(PartialEq, Clone, Copy, Debug)]
+enum Param {
+    Int,
+    Real,
+    String,
+    /// An int or a real, the result is a real if any of them is
+    Number,
+}
+
+impl Param {
+    fn accepts(&self, t: &Type) -> bool {
+        match self {
+            Param::Int => *t == INT,
+            Param::Real | Param::Number => *t == INT || *t == REAL,
+            Param::String => *t == STRING,
+        }
+    }
+}
+
+impl Builtin {
+    /// Name in the programs, in lowercase
+    pub fn name(&self) -> &'static str {
+        match self {
+            Builtin::Length => "length",
+            Builtin::Substr => "substr",
+            Builtin::Ord => "ord",
+            Builtin::Chr => "chr",
+            Builtin::Abs => "abs",
+            Builtin::Sqrt => "sqrt",
+            Builtin::Trunc => "trunc",
+            Builtin::Round => "round",
+            Builtin::IntToStr => "inttostr",
+            Builtin::StrToInt => "strtoint",
+            Builtin::Min => "min",
+            Builtin::Max => "max",
+            Builtin::Random => "random",
+        }
+    }
+
+    /// The builtin with the given name, ignoring the case
+    pub fn from_name(name: &str) -> Option<Builtin> {
+        BUILTINS
+            .iter()
+            .find(|b| b.name().eq_ignore_ascii_case(name))
+            .copied()
+    }
+
+    /// Tells if every call with the same arguments gives the same
+    /// value, only those can compute a constant
+    pub fn is_pure(&self) -> bool {
+        *self != Builtin::Random
+    }
+
+    /// Number of the builtin in the bytecode
+    pub fn code(&self) -> u8 {
+        BUILTINS.iter().position(|b| b == self).unwrap_or(0) as u8
+    }
+
+    pub fn from_code(code: u8) -> Option<Builtin> {
+        BUILTINS.get(code as usize).copied()
+    }
+
+    /// Names and kinds of the parameters
+    fn params(&self) -> &'static [(&'static str, Param)] {
+        match self {
+            Builtin::Length | Builtin::Ord | Builtin::StrToInt => &[("s", Param::String)],
+            Builtin::Substr => &[
+                ("s", Param::String),
+                ("start", Param::Int),
+                ("count", Param::Int),
+            ],
+            Builtin::Chr | Builtin::IntToStr => &[("n", Param::Int)],
+            Builtin::Random => &[("bound", Param::Int)],
+            Builtin::Abs => &[("x", Param::Number)],
+            Builtin::Sqrt | Builtin::Trunc | Builtin::Round => &[("x", Param::Real)],
+            Builtin::Min | Builtin::Max => &[("a", Param::Number), ("b", Param::Number)],
+        }
+    }
+
+    /// Names of the parameters, for the call nodes
+    pub fn param_names(&self) -> Vec<String> {
+        self.params().iter().map(|(n, _)| n.to_string()).collect()
+    }
+
+    /// Type of the result of a call with arguments of the given types,
+    /// None if they don't fit the parameters
+    pub fn r_type(&self, args: &[Type]) -> Option<Type> {
+        let params = self.params();
+        if args.len() != params.len() || params.iter().zip(args).any(|((_, p), a)| !p.accepts(a)) {
+            return None;
+        }
+        // an int and a real give a real, as the C conversions do
+        let number = match args.contains(&REAL) {
+            true => REAL,
+            false => INT,
+        };
+        Some(match self {
+            Builtin::Length | Builtin::Ord | Builtin::StrToInt | Builtin::Random => INT,
+            Builtin::Trunc | Builtin::Round => INT,
+            Builtin::Substr | Builtin::Chr | Builtin::IntToStr => STRING,
+            Builtin::Sqrt => REAL,
+            Builtin::Abs | Builtin::Min | Builtin::Max => number,
+        })
+    }
+
+    /// The symbol of the builtin in the global table
+    pub fn symbol(&self) -> Symbol {
+        Symbol {
+            name: self.name().to_string(),
+            s_type: SymbolType::Builtin,
+            r_type: Type::Simple(SimpleType::Void),
+            position: Position::new(0, 0, 0),
+            args: None,
+        }
+    }
+}
+
+/// The way the builtin is called, with the kinds of its parameters
+impl Display for Builtin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let params: Vec<String> = self
+            .params()
+            .iter()
+            .map(|(name, param)| {
+                let kind = match param {
+                    Param::Int => "int",
+                    Param::Real => "real",
+                    Param::String => "string",
+                    Param::Number => "number",
+                };
+                format!("{}: {}", name, kind)
+            })
+            .collect();
+        let result = match self {
+            Builtin::Substr | Builtin::Chr | Builtin::IntToStr => "string",
+            Builtin::Sqrt => "real",
+            Builtin::Abs | Builtin::Min | Builtin::Max => "number",
+            _ => "int",
+        };
+        write!(f, "{}({}): {}", self.name(), params.join(", "), result)
+    }
+}
+
+/// The global table every program starts with
+pub fn symbols() -> SymbolTable {
+    let mut table = SymbolTable::new();
+    for builtin in BUILTINS.iter() {
+        table.push(builtin.symbol());
+    }
+    table
+}
@@ -0,0 +1,566 @@
+use std::fmt::Write;
+
+use serde_json::{json, Map, Value};
+
+use super::{
+    ast::*,
+    errors::SyntaxError,
+    objects::Object,
+    pretty::type_source,
+    symbol_table::{Symbol, SymbolTable, SymbolType},
+    token::Token,
+    types::Type,
+};
+use crate::scanner::position::Position;
+
+/// Name used for a symbol kind in the dumps
+pub fn symbol_kind(s_type: SymbolType) -> &'static str {
+    match s_type {
+        SymbolType::Function => "function",
+        SymbolType::Procedure => "procedure",
+        SymbolType::Var => "var",
+        SymbolType::VarParam => "var-param",
+        SymbolType::Param => "param",
+        SymbolType::Arr => "array",
+        SymbolType::Record => "record",
+        SymbolType::Const => "const",
+        SymbolType::Builtin => "builtin",
+    }
+}
+
+fn position(p: Position) -> Value {
+    json!({ "line": p.line, "col": p.col })
+}
+
+/// A token as scanned, the kind is the name of the `Kind` variant
+pub fn token(t: &Token) -> Value {
+    json!({
+        "kind": format!("{:?}", t.kind),
+        "lexeme": t.lexeme,
+        "position": position(t.position),
+    })
+}
+
+/// An error found by the scanner, in the same shape as the tokens
+pub fn scan_error(e: &SyntaxError) -> Value {
+    json!({
+        "error": e.description,
+        "position": position(e.position),
+    })
+}
+
+fn r_type(t: &Type) -> Value {
+    Value::String(type_source(t))
+}
+
+fn object(value: &Object) -> Value {
+    match value {
+        Object::Int(i) => json!(i),
+        Object::Real(r) => json!(r),
+        Object::String(s) => json!(s),
+        Object::Bool(b) => json!(b),
+        Object::Array(a) => Value::Array(a.iter().map(object).collect()),
+        Object::Record(r, fields) => Value::Object(
+            r.fields
+                .iter()
+                .zip(fields.iter())
+                .map(|((name, _), value)| (name.clone(), object(value)))
+                .collect(),
+        ),
+    }
+}
+
+fn symbol(s: &Symbol) -> Value {
+    node(
+        "Symbol",
+        vec![
+            ("name", json!(s.name)),
+            ("kind", json!(symbol_kind(s.s_type))),
+            ("type", r_type(&s.r_type)),
+            ("position", position(s.position)),
+        ],
+    )
+}
+
+fn symbols(table: &SymbolTable) -> Value {
+    Value::Array(table.iter().map(symbol).collect())
+}
+
+fn arguments(args: &[(String, ASTNode)]) -> Value {
+    Value::Array(
+        args.iter()
+            .map(|(param, value)| {
+                node(
+                    "Arg",
+                    vec![("param", json!(param)), ("value", to_json(value))],
+                )
+            })
+            .collect(),
+    )
+}
+
+/// Builds a node object, the `node` key always comes first and keeps
+/// the name of the variant
+fn node(name: &str, fields: Vec<(&str, Value)>) -> Value {
+    let mut map = Map::new();
+    map.insert("node".to_string(), Value::String(name.to_string()));
+    for (key, value) in fields {
+        map.insert(key.to_string(), value);
+    }
+    Value::Object(map)
+}
+
+fn record(r: &RecordDeclNode) -> Value {
+    node(
+        "Record",
+        vec![
+            ("name", json!(r.r_type.name)),
+            ("position", position(r.position)),
+            (
+                "fields",
+                Value::Array(
+                    r.r_type
+                        .fields
+                        .iter()
+                        .map(|(name, t)| json!({ "name": name, "type": r_type(t) }))
+                        .collect(),
+                ),
+            ),
+        ],
+    )
+}
+
+fn constant(c: &ConstDeclNode) -> Value {
+    node(
+        "Const",
+        vec![
+            ("name", json!(c.id.lexeme)),
+            ("position", position(c.position)),
+            ("type", r_type(&c.value.r_type)),
+            ("value", object(&c.value.value)),
+        ],
+    )
+}
+
+fn function(f: &FunctionDeclNode) -> Value {
+    node(
+        "Function",
+        vec![
+            ("name", json!(f.name)),
+            ("position", position(f.position)),
+            ("type", r_type(&f.r_type)),
+            ("params", symbols(&f.args)),
+            ("body", to_json(&f.block)),
+        ],
+    )
+}
+
+fn procedure(p: &ProcedureDeclNode) -> Value {
+    node(
+        "Procedure",
+        vec![
+            ("name", json!(p.name)),
+            ("position", position(p.position)),
+            ("params", symbols(&p.args)),
+            ("body", to_json(&p.block)),
+        ],
+    )
+}
+
+fn block(b: &BlockNode) -> Value {
+    node(
+        "Block",
+        vec![
+            ("position", position(b.position)),
+            ("symbols", symbols(&b.context)),
+            (
+                "statements",
+                Value::Array(b.statements.iter().map(to_json).collect()),
+            ),
+        ],
+    )
+}
+
+/// Serializes the whole tree, with types, positions and the kind of
+/// every symbol referenced, in a representation that only changes
+/// when the AST does
+pub fn to_json(ast: &ASTNode) -> Value {
+    match ast {
+        ASTNode::Program(p) => node(
+            "Program",
+            vec![
+                ("name", json!(p.program_name.name.lexeme)),
+                (
+                    "records",
+                    Value::Array(p.records.iter().map(record).collect()),
+                ),
+                (
+                    "constants",
+                    Value::Array(p.constants.iter().map(constant).collect()),
+                ),
+                (
+                    "globals",
+                    Value::Array(
+                        p.globals
+                            .iter()
+                            .map(|g| to_json(&ASTNode::VariableDecl(g.clone())))
+                            .collect(),
+                    ),
+                ),
+                (
+                    "functions",
+                    Value::Array(p.functions.iter().map(function).collect()),
+                ),
+                (
+                    "procedures",
+                    Value::Array(p.procedures.iter().map(procedure).collect()),
+                ),
+                ("main", block(&p.main_block)),
+            ],
+        ),
+        ASTNode::ProgramName(p) => node(
+            "ProgramName",
+            vec![
+                ("name", json!(p.name.lexeme)),
+                ("position", position(p.name.position)),
+            ],
+        ),
+        ASTNode::RecordDecl(r) => record(r),
+        ASTNode::ConstDecl(c) => constant(c),
+        ASTNode::ForwardDecl(d) => node(
+            "Forward",
+            vec![
+                ("name", json!(d.symbol.name)),
+                ("kind", json!(symbol_kind(d.symbol.s_type))),
+                ("position", position(d.position)),
+                ("type", r_type(&d.symbol.r_type)),
+                (
+                    "params",
+                    d.symbol.args.as_deref().map(symbols).unwrap_or_default(),
+                ),
+            ],
+        ),
+        ASTNode::FunctionDecl(f) => function(f),
+        ASTNode::ProcedureDecl(p) => procedure(p),
+        ASTNode::Block(b) => block(b),
+        ASTNode::BinaryExpression(b) => node(
+            "Binary",
+            vec![
+                ("position", position(b.position)),
+                ("op", json!(b.op_type.to_string())),
+                ("type", r_type(&b.r_type)),
+                ("left", to_json(&b.left)),
+                ("right", to_json(&b.right)),
+            ],
+        ),
+        ASTNode::VarName(v) => node(
+            "Variable",
+            vec![
+                ("position", position(v.position)),
+                ("name", json!(v.id.lexeme)),
+                ("type", r_type(&v.r_type)),
+                ("symbol", json!(symbol_kind(v.s_type))),
+            ],
+        ),
+        ASTNode::ArrayRef(a) => node(
+            "ArrayRef",
+            vec![
+                ("position", position(a.position)),
+                ("array", json!(a.array.lexeme)),
+                ("type", r_type(&a.r_type)),
+                (
+                    "indexes",
+                    Value::Array(a.indexes.iter().map(to_json).collect()),
+                ),
+            ],
+        ),
+        ASTNode::FieldRef(f) => node(
+            "FieldRef",
+            vec![
+                ("position", position(f.position)),
+                ("field", json!(f.field.lexeme)),
+                ("type", r_type(&f.r_type)),
+                ("record", to_json(&f.record)),
+            ],
+        ),
+        ASTNode::Literal(l) => node(
+            "Literal",
+            vec![
+                ("position", position(l.position)),
+                ("type", r_type(&l.r_type)),
+                ("value", object(&l.value)),
+            ],
+        ),
+        ASTNode::UnaryExpression(u) => node(
+            "Unary",
+            vec![
+                ("position", position(u.position)),
+                ("op", json!(u.operand.lexeme)),
+                ("type", r_type(&u.r_type)),
+                ("operand", to_json(&u.expression)),
+            ],
+        ),
+        ASTNode::VarReassignment(v) => node(
+            "Assign",
+            vec![
+                ("position", position(v.position)),
+                ("target", to_json(&v.variable_to_reassign)),
+                ("value", to_json(&v.new_value)),
+            ],
+        ),
+        ASTNode::VariableDecl(v) => {
+            let mut fields = vec![
+                ("position", position(v.position)),
+                ("name", json!(v.id.lexeme)),
+                ("type", r_type(&v.var_type)),
+            ];
+            if let Some(size) = v.size {
+                fields.push(("size", json!(size)));
+            }
+            node("VarDecl", fields)
+        }
+        ASTNode::WhileStmt(w) => node(
+            "While",
+            vec![
+                ("position", position(w.position)),
+                ("guard", to_json(&w.guard)),
+                ("body", to_json(&w.block)),
+            ],
+        ),
+        ASTNode::ForStmt(l) => node(
+            "For",
+            vec![
+                ("position", position(l.position)),
+                ("variable", to_json(&l.variable)),
+                ("start", to_json(&l.start)),
+                ("end", to_json(&l.end)),
+                ("downto", json!(l.downto)),
+                ("body", to_json(&l.block)),
+            ],
+        ),
+        ASTNode::RepeatStmt(r) => node(
+            "Repeat",
+            vec![
+                ("position", position(r.position)),
+                ("body", to_json(&r.block)),
+                ("guard", to_json(&r.guard)),
+            ],
+        ),
+        ASTNode::BreakStmt(b) => node("Break", vec![("position", position(b.position))]),
+        ASTNode::ContinueStmt(c) => node("Continue", vec![("position", position(c.position))]),
+        ASTNode::IfStmt(i) => {
+            let mut fields = vec![
+                ("position", position(i.position)),
+                ("guard", to_json(&i.guard)),
+                ("then", to_json(&i.then)),
+            ];
+            if let Some(e) = &i.else_stmt {
+                fields.push(("else", to_json(e)));
+            }
+            node("If", fields)
+        }
+        ASTNode::CaseStmt(c) => {
+            let branches = c
+                .branches
+                .iter()
+                .map(|b| {
+                    json!({
+                        "position": position(b.position),
+                        "labels": b
+                            .labels
+                            .iter()
+                            .map(|l| to_json(&ASTNode::Literal(l.clone())))
+                            .collect::<Vec<Value>>(),
+                        "body": to_json(&b.body),
+                    })
+                })
+                .collect::<Vec<Value>>();
+            let mut fields = vec![
+                ("position", position(c.position)),
+                ("value", to_json(&c.value)),
+                ("branches", Value::Array(branches)),
+            ];
+            if let Some(e) = &c.else_stmt {
+                fields.push(("else", to_json(e)));
+            }
+            node("Case", fields)
+        }
+        ASTNode::ElseStmt(e) => node(
+            "Else",
+            vec![
+                ("position", position(e.position)),
+                ("body", to_json(&e.block)),
+            ],
+        ),
+        ASTNode::PrintStmt(p) => {
+            let values = p
+                .args
+                .iter()
+                .map(|a| {
+                    let mut fields = vec![("value", to_json(&a.value))];
+                    if let Some(width) = &a.width {
+                        fields.push(("width", to_json(width)));
+                    }
+                    if let Some(precision) = &a.precision {
+                        fields.push(("precision", to_json(precision)));
+                    }
+                    node("PrintArg", fields)
+                })
+                .collect::<Vec<Value>>();
+            node(
+                "Print",
+                vec![
+                    ("position", position(p.position)),
+                    ("newline", Value::Bool(p.newline)),
+                    ("values", Value::Array(values)),
+                ],
+            )
+        }
+        ASTNode::ReadStmt(r) => node(
+            "Read",
+            vec![
+                ("position", position(r.position)),
+                ("target", to_json(&r.variable_to_read_in)),
+            ],
+        ),
+        ASTNode::AssertStmt(a) => node(
+            "Assert",
+            vec![
+                ("position", position(a.position)),
+                ("condition", to_json(&a.expr)),
+            ],
+        ),
+        ASTNode::FunctionCallStmt(f) => node(
+            "FunctionCall",
+            vec![
+                ("position", position(f.position)),
+                ("name", json!(f.target)),
+                ("type", r_type(&f.r_type)),
+                ("args", arguments(&f.args)),
+            ],
+        ),
+        ASTNode::ProcedureCallStmt(p) => node(
+            "ProcedureCall",
+            vec![
+                ("position", position(p.position)),
+                ("name", json!(p.target)),
+                ("args", arguments(&p.args)),
+            ],
+        ),
+        ASTNode::ReturnStmt(r) => {
+            let mut fields = vec![("position", position(r.token.position))];
+            if let Some(value) = &r.value {
+                fields.push(("value", to_json(value)));
+            }
+            node("Return", fields)
+        }
+        ASTNode::EofStmt(e) => node("Eof", vec![("position", position(e.eof.position))]),
+    }
+}
+
+fn is_position(value: &Value) -> bool {
+    matches!(value, Value::Object(map) if map.len() == 2 && map.contains_key("line") && map.contains_key("col"))
+}
+
+fn short_position(value: &Value) -> String {
+    format!("{}:{}", value["line"], value["col"])
+}
+
+/// True for values printed inline: scalars, positions and non empty
+/// arrays of scalars (e.g. array literals)
+fn is_leaf(value: &Value) -> bool {
+    match value {
+        Value::Object(_) => is_position(value),
+        Value::Array(a) => !a.is_empty() && a.iter().all(|v| !v.is_object() && !v.is_array()),
+        _ => true,
+    }
+}
+
+fn sexpr_leaf(value: &Value) -> String {
+    match value {
+        Value::Array(a) => format!(
+            "({})",
+            a.iter().map(sexpr_leaf).collect::<Vec<String>>().join(" ")
+        ),
+        other if is_position(other) => short_position(other),
+        other => other.to_string(),
+    }
+}
+
+fn sexpr_value(value: &Value, indent: usize, out: &mut String) {
+    let pad = "  ".repeat(indent);
+    match value {
+        Value::Object(map) => {
+            let head = map.get("node").and_then(Value::as_str).unwrap_or("");
+            out.push('(');
+            out.push_str(head);
+            for (key, field) in map.iter().filter(|(k, _)| *k != "node") {
+                if is_leaf(field) {
+                    let _ = write!(out, " :{} {}", key, sexpr_leaf(field));
+                } else {
+                    let _ = write!(out, "\n{}  :{} ", pad, key);
+                    sexpr_value(field, indent + 1, out);
+                }
+            }
+            out.push(')');
+        }
+        Value::Array(items) => {
+            out.push('(');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    let _ = write!(out, "\n{} ", pad);
+                }
+                sexpr_value(item, indent, out);
+            }
+            out.push(')');
+        }
+        other => out.push_str(&sexpr_leaf(other)),
+    }
+}
+
+/// S-expression rendering of a tree built by `to_json`
+pub fn to_sexpr(tree: &Value) -> String {
+    let mut out = String::new();
+    sexpr_value(tree, 0, &mut out);
+    out + "\n"
+}
+
+/// Adds a graph node for the object and the edges to its children,
+/// returns the name of the node
+fn dot_value(value: &Value, next: &mut usize, out: &mut String) -> String {
+    let name = format!("n{}", next);
+    *next += 1;
+    let escape = |s: &str| s.replace('\\', "\\\\").replace('"', "\\\"");
+    let mut label = String::new();
+    let mut edges = Vec::new();
+    match value {
+        Value::Object(map) => {
+            label += map.get("node").and_then(Value::as_str).unwrap_or("");
+            for (key, field) in map.iter().filter(|(k, _)| *k != "node") {
+                if is_leaf(field) {
+                    let _ = write!(label, "\\n{}: {}", key, escape(&sexpr_leaf(field)));
+                } else if let Value::Array(items) = field {
+                    for (i, item) in items.iter().enumerate() {
+                        edges.push((format!("{}[{}]", key, i), item));
+                    }
+                } else {
+                    edges.push((key.to_string(), field));
+                }
+            }
+        }
+        other => label += &escape(&sexpr_leaf(other)),
+    }
+    let _ = writeln!(out, "  {} [label=\"{}\"];", name, label);
+    for (key, child) in edges {
+        let child_name = dot_value(child, next, out);
+        let _ = writeln!(out, "  {} -> {} [label=\"{}\"];", name, child_name, key);
+    }
+    name
+}
+
+/// Graphviz rendering of a tree built by `to_json`
+pub fn to_dot(tree: &Value) -> String {
+    let mut out = "digraph ast {\n  node [shape=box, fontname=\"monospace\"];\n".to_string();
+    dot_value(tree, &mut 0, &mut out);
+    out + "}\n"
+}
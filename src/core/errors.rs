@@ -1,3 +1,4 @@
+use crate::core::lints::{Lint, LintLevel};
 use crate::scanner::position::Position;
 use colored::Colorize;
 use std::fmt;
@@ -25,6 +26,16 @@ pub struct CompilationError {
     pub description: String,
 }
 
+/// Diagnostic produced by a lint, it is only an error if the lint
+/// level is `deny`
+#[derive(Debug, Clone)]
+pub struct Warning {
+    pub position: Position,
+    pub lint: Lint,
+    pub level: LintLevel,
+    pub description: String,
+}
+
 impl SyntaxError {
     pub fn new(position_: Position, raw_line_: String, description_: String) -> SyntaxError {
         SyntaxError {
@@ -72,6 +83,23 @@ impl MiniPLError for CompilationError {
     }
 }
 
+impl MiniPLError for Warning {
+    fn get_error(&self) -> String {
+        let header = match self.level {
+            LintLevel::Deny => "Error".red().bold(),
+            _ => "Warning".yellow().bold(),
+        };
+        format! {
+            "{} in {}:{} > {} [{}]\n",
+            header,
+            self.position.line,
+            self.position.col,
+            self.description.bold(),
+            self.lint
+        }
+    }
+}
+
 impl fmt::Display for SyntaxError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}", self.get_error())
@@ -89,3 +117,9 @@ impl fmt::Display for CompilationError {
         write!(f, "{}", self.get_error())
     }
 }
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.get_error())
+    }
+}
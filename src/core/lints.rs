@@ -0,0 +1,152 @@
+use std::{collections::HashMap, fmt::Display, str::FromStr};
+
+/// Named checks that produce warnings instead of hard errors, each of
+/// them can be allowed, reported as a warning or turned into an error
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Lint {
+    UnusedVariable,
+    UnusedParameter,
+    UninitializedRead,
+    UnreachableCode,
+    UnchangingLoopGuard,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LintLevel {
+    Allow,
+    Warn,
+    Deny,
+}
+
+/// Lints that became hard errors, their names are still accepted
+/// (and do nothing) so that old command lines and pragmas keep working
+pub const REMOVED: [&str; 1] = ["missing-return"];
+
+impl Lint {
+    pub const ALL: [Lint; 5] = [
+        Lint::UnusedVariable,
+        Lint::UnusedParameter,
+        Lint::UninitializedRead,
+        Lint::UnreachableCode,
+        Lint::UnchangingLoopGuard,
+    ];
+
+    /// Name used to refer to the lint on the command line and in
+    /// pragmas
+    pub fn name(&self) -> &'static str {
+        match self {
+            Lint::UnusedVariable => "unused-variable",
+            Lint::UnusedParameter => "unused-parameter",
+            Lint::UninitializedRead => "uninitialized-read",
+            Lint::UnreachableCode => "unreachable-code",
+            Lint::UnchangingLoopGuard => "unchanging-loop-guard",
+        }
+    }
+}
+
+impl FromStr for Lint {
+    type Err = String;
+
+    /// Both `unused-variable` and `unused_variable` are accepted
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s.trim().replace('_', "-").to_lowercase();
+        Lint::ALL
+            .iter()
+            .find(|lint| lint.name() == name)
+            .copied()
+            .ok_or(format!("Unknown lint: {}", s.trim()))
+    }
+}
+
+impl Display for Lint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for LintLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "allow" => Ok(LintLevel::Allow),
+            "warn" => Ok(LintLevel::Warn),
+            "deny" => Ok(LintLevel::Deny),
+            other => Err(format!("Unknown lint level: {}", other)),
+        }
+    }
+}
+
+/// Level of every lint, all of them warn unless configured otherwise
+#[derive(Debug, Clone)]
+pub struct LintConfig {
+    levels: HashMap<Lint, LintLevel>,
+}
+
+impl LintConfig {
+    pub fn new() -> LintConfig {
+        LintConfig {
+            levels: Lint::ALL
+                .iter()
+                .map(|lint| (*lint, LintLevel::Warn))
+                .collect(),
+        }
+    }
+
+    pub fn level(&self, lint: Lint) -> LintLevel {
+        self.levels[&lint]
+    }
+
+    pub fn set(&mut self, lint: Lint, level: LintLevel) {
+        self.levels.insert(lint, level);
+    }
+
+    /// Sets the level of the named lint, the special name `warnings`
+    /// selects all of them
+    pub fn set_by_name(&mut self, name: &str, level: LintLevel) -> Result<(), String> {
+        if REMOVED.contains(&name.trim().replace('_', "-").to_lowercase().as_str()) {
+            return Ok(());
+        }
+        if name.trim() == "warnings" {
+            for lint in Lint::ALL {
+                self.set(lint, level);
+            }
+        } else {
+            self.set(name.parse()?, level);
+        }
+        Ok(())
+    }
+
+    /// Applies a pragma comment of the form
+    /// `pragma allow(unused-variable) deny(unreachable-code, ...)`,
+    /// the `pragma` keyword already stripped
+    pub fn apply_pragma(&mut self, pragma: &str) -> Result<(), String> {
+        let mut rest = pragma.trim();
+        if rest.is_empty() {
+            return Err("Empty pragma".to_string());
+        }
+        while !rest.is_empty() {
+            let open = rest
+                .find('(')
+                .ok_or(format!("Expected `(` in pragma: {}", rest))?;
+            let close = rest
+                .find(')')
+                .ok_or(format!("Expected `)` in pragma: {}", rest))?;
+            if close < open {
+                return Err(format!("Malformed pragma: {}", rest));
+            }
+            let level: LintLevel = rest[..open].parse()?;
+            for name in rest[open + 1..close].split(',') {
+                self.set_by_name(name, level)?;
+            }
+            rest = rest[close + 1..].trim_start();
+        }
+        Ok(())
+    }
+}
+
+impl Default for LintConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
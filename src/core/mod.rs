@@ -1,10 +1,18 @@
 #[cfg(not(tarpaulin_include))]
 pub mod ast;
 #[cfg(not(tarpaulin_include))]
-pub(crate) mod errors;
+pub mod builtins;
+#[cfg(not(tarpaulin_include))]
+pub mod dump;
+#[cfg(not(tarpaulin_include))]
+pub mod errors;
+#[cfg(not(tarpaulin_include))]
+pub mod lints;
 #[cfg(not(tarpaulin_include))]
 pub mod objects;
 #[cfg(not(tarpaulin_include))]
+pub mod pretty;
+#[cfg(not(tarpaulin_include))]
 pub mod symbol_table;
 #[cfg(not(tarpaulin_include))]
 pub mod token;
@@ -1,4 +1,6 @@
-use std::{fmt::Display, str::FromStr};
+use std::{fmt::Display, str::FromStr, sync::Arc};
+
+use super::types::RecordType;
 
 #[derive(Debug, Clone)]
 pub enum Object {
@@ -7,13 +9,15 @@ pub enum Object {
     String(String),
     Bool(bool),
     Array(Box<[Object]>),
+    /// Value of a record, with a value for each field of the type
+    Record(Arc<RecordType>, Box<[Object]>),
 }
 
 impl Object {
     pub fn to_c_lit(&self) -> String {
         match self {
             Object::Int(i) => i.to_string(),
-            Object::String(s) => s.clone(),
+            Object::String(s) => c_string(s),
             Object::Bool(b) => {
                 if *b {
                     "true".to_string()
@@ -21,7 +25,7 @@ impl Object {
                     "false".to_string()
                 }
             }
-            Object::Array(a) => {
+            Object::Array(a) | Object::Record(_, a) => {
                 let mut to_out = "{".to_string();
                 for el in a.iter() {
                     to_out = format!("{}{},", to_out, el.to_c_lit());
@@ -30,9 +34,31 @@ impl Object {
                 to_out += "}";
                 to_out
             }
-            Object::Real(r) => r.to_string(),
+            // the debug format always has a dot or an exponent, so C
+            // reads it as a double
+            Object::Real(r) => format!("{:?}", r),
+        }
+    }
+}
+
+/// Quotes the string as a C string literal, control characters with
+/// no short escape are written with three octal digits so that a digit
+/// following them is not taken in
+pub fn c_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            '\r' => out.push_str("\\r"),
+            c if c.is_ascii_control() => out.push_str(&format!("\\{:03o}", c as u8)),
+            c => out.push(c),
         }
     }
+    out.push('"');
+    out
 }
 
 impl Display for Object {
@@ -42,6 +68,7 @@ impl Display for Object {
             Object::String(v) => v.to_string(),
             Object::Bool(v) => v.to_string(),
             Object::Array(_a) => String::from_str("string").unwrap(),
+            Object::Record(r, _) => r.name.clone(),
             Object::Real(v) => v.to_string(),
         };
         let typ = match self {
@@ -49,6 +76,7 @@ impl Display for Object {
             Object::String(_) => String::from_str("string"),
             Object::Bool(_) => String::from_str("bool"),
             Object::Array(_) => String::from_str("array"),
+            Object::Record(_, _) => String::from_str("record"),
             Object::Real(_) => String::from_str("real"),
         }
         .unwrap();
@@ -0,0 +1,135 @@
+use super::{
+    ast::*,
+    objects::Object,
+    types::{SimpleType, Type},
+};
+
+/// Returns a type as it would be written in a MiniPL source, arrays
+/// with their size when it is known
+pub fn type_source(r_type: &Type) -> String {
+    let simple = |s: &SimpleType| match s {
+        SimpleType::Int => "int".to_string(),
+        SimpleType::Real => "real".to_string(),
+        SimpleType::String => "string".to_string(),
+        SimpleType::Bool => "bool".to_string(),
+        SimpleType::Void => "void".to_string(),
+        SimpleType::Record(r) => r.name.clone(),
+    };
+    match r_type {
+        Type::Simple(s) => simple(s),
+        Type::Array(e, Some(size)) => format!("array [{}] of {}", size, type_source(e)),
+        Type::Array(e, None) => format!("array of {}", type_source(e)),
+    }
+}
+
+fn literal(value: &Object) -> String {
+    match value {
+        Object::Int(i) => i.to_string(),
+        Object::Real(r) => format!("{:?}", r),
+        Object::String(s) => format!("{:?}", s),
+        Object::Bool(b) => b.to_string(),
+        Object::Array(a) => format!(
+            "[{}]",
+            a.iter().map(literal).collect::<Vec<String>>().join(", ")
+        ),
+        Object::Record(r, fields) => format!(
+            "{}({})",
+            r.name,
+            fields
+                .iter()
+                .map(literal)
+                .collect::<Vec<String>>()
+                .join(", ")
+        ),
+    }
+}
+
+fn arguments(args: &[(String, ASTNode)]) -> String {
+    args.iter()
+        .map(|(_, a)| expression(a))
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+/// Returns an expression as it would be written in a MiniPL source,
+/// nested binary expressions are wrapped in parenthesis
+pub fn expression(node: &ASTNode) -> String {
+    let operand = |node: &ASTNode| match node {
+        ASTNode::BinaryExpression(_) => format!("({})", expression(node)),
+        other => expression(other),
+    };
+    match node {
+        ASTNode::BinaryExpression(b) => {
+            format!("{} {} {}", operand(&b.left), b.op.lexeme, operand(&b.right))
+        }
+        ASTNode::UnaryExpression(u) => format!("!{}", operand(&u.expression)),
+        ASTNode::VarName(v) => v.id.lexeme.clone(),
+        ASTNode::ArrayRef(a) => format!(
+            "{}{}",
+            a.array.lexeme,
+            a.indexes
+                .iter()
+                .map(|i| format!("[{}]", expression(i)))
+                .collect::<String>()
+        ),
+        ASTNode::FieldRef(f) => format!("{}.{}", expression(&f.record), f.field.lexeme),
+        ASTNode::Literal(l) => literal(&l.value),
+        ASTNode::FunctionCallStmt(f) => format!("{}({})", f.target, arguments(&f.args)),
+        other => other.to_string(),
+    }
+}
+
+/// Returns a single statement in one line, without the trailing
+/// semicolon; compound statements are summarised by their header
+pub fn statement(node: &ASTNode) -> String {
+    match node {
+        ASTNode::VariableDecl(v) => format!("var {}: {}", v.id.lexeme, type_source(&v.var_type)),
+        ASTNode::RecordDecl(r) => format!(
+            "type {} = record {} end",
+            r.r_type.name,
+            r.r_type
+                .fields
+                .iter()
+                .map(|(name, t)| format!("{}: {}", name, type_source(t)))
+                .collect::<Vec<String>>()
+                .join("; ")
+        ),
+        ASTNode::ConstDecl(c) => format!("const {} = {}", c.id.lexeme, literal(&c.value.value)),
+        ASTNode::VarReassignment(v) => format!(
+            "{} := {}",
+            expression(&v.variable_to_reassign),
+            expression(&v.new_value)
+        ),
+        ASTNode::ReadStmt(r) => format!("read({})", expression(&r.variable_to_read_in)),
+        ASTNode::PrintStmt(p) => format!(
+            "{}({})",
+            if p.newline { "writeln" } else { "write" },
+            p.args
+                .iter()
+                .map(|a| a.nodes().map(expression).collect::<Vec<String>>().join(":"))
+                .collect::<Vec<String>>()
+                .join(", ")
+        ),
+        ASTNode::AssertStmt(a) => format!("assert({})", expression(&a.expr)),
+        ASTNode::ProcedureCallStmt(p) => format!("{}({})", p.target, arguments(&p.args)),
+        ASTNode::ReturnStmt(r) => match &r.value {
+            Some(value) => format!("return {}", expression(value)),
+            None => "return".to_string(),
+        },
+        ASTNode::IfStmt(i) => format!("if {} then", expression(&i.guard)),
+        ASTNode::CaseStmt(c) => format!("case {} of", expression(&c.value)),
+        ASTNode::WhileStmt(w) => format!("while {} do", expression(&w.guard)),
+        ASTNode::ForStmt(l) => format!(
+            "for {} := {} {} {} do",
+            expression(&l.variable),
+            expression(&l.start),
+            if l.downto { "downto" } else { "to" },
+            expression(&l.end)
+        ),
+        ASTNode::RepeatStmt(r) => format!("repeat ... until {}", expression(&r.guard)),
+        ASTNode::BreakStmt(_) => "break".to_string(),
+        ASTNode::ContinueStmt(_) => "continue".to_string(),
+        ASTNode::Block(_) => "begin ... end".to_string(),
+        other => expression(other),
+    }
+}
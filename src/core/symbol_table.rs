@@ -12,6 +12,12 @@ pub enum SymbolType {
     VarParam,
     Param,
     Arr,
+    /// A record type, declared with `type`
+    Record,
+    /// A global constant, its uses are replaced by its value
+    Const,
+    /// A function of the language, see `core::builtins`
+    Builtin,
 }
 
 #[derive(Clone, Debug)]
@@ -23,7 +29,7 @@ pub struct Symbol {
     pub args: Option<Box<SymbolTable>>,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Default)]
 pub struct SymbolTable {
     pub symbols: Vec<Symbol>,
 }
@@ -46,13 +52,17 @@ impl SymbolTable {
         }
     }
 
-    pub fn iter(&self) -> core::slice::Iter<Symbol> {
+    pub fn iter(&self) -> core::slice::Iter<'_, Symbol> {
         self.symbols.iter()
     }
 
     pub fn len(&self) -> usize {
         self.symbols.len()
     }
+
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
 }
 
 impl Display for Symbol {
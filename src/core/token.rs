@@ -47,7 +47,8 @@ pub enum Kind {
     If,        // if
     Then,      // then
     Else,      // else
-    Print,     // print
+    Print,     // writeln
+    Write,     // write
     Read,      // read
     True,      // true
     Var,       // var
@@ -62,6 +63,18 @@ pub enum Kind {
     Of,        // array [<int>] *of* int
     Return,    // return value
     While,     // While (expr) do; block
+    For,       // for i := a to b do; block
+    To,        // for i := a *to* b
+    Downto,    // for i := a *downto* b
+    Repeat,    // repeat stmts until (expr)
+    Until,     // repeat stmts *until* (expr)
+    Break,     // break out of a loop
+    Continue,  // next iteration of a loop
+    Case,      // case (expr) of labels: stmt; ... end
+    Type,      // type Name = record ... end
+    Record,    // type Name = *record* fields end
+    Const,     // const Name = value
+    Forward,   // forward
 
     // End Of Files And Whitespaces
     Eof,     // End of File
@@ -125,7 +138,8 @@ impl fmt::Display for Kind {
                 Kind::If => "if",
                 Kind::Then => "then",
                 Kind::Else => "else",
-                Kind::Print => "print",
+                Kind::Print => "writeln",
+                Kind::Write => "write",
                 Kind::Read => "read",
                 Kind::True => "true",
                 Kind::Var => "var",
@@ -145,6 +159,18 @@ impl fmt::Display for Kind {
                 Kind::Of => "of",
                 Kind::Return => "return",
                 Kind::While => "while",
+                Kind::For => "for",
+                Kind::To => "to",
+                Kind::Downto => "downto",
+                Kind::Repeat => "repeat",
+                Kind::Until => "until",
+                Kind::Break => "break",
+                Kind::Continue => "continue",
+                Kind::Case => "case",
+                Kind::Type => "type",
+                Kind::Record => "record",
+                Kind::Const => "const",
+                Kind::Forward => "forward",
             }
         )
     }
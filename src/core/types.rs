@@ -1,24 +1,60 @@
-use std::fmt::Display;
+use std::{fmt::Display, sync::Arc};
 
-#[derive(PartialEq, Debug, Clone, Copy)]
+#[derive(PartialEq, Debug, Clone)]
 pub enum SimpleType {
     Int,
     Real,
     String,
     Bool,
     Void,
+    /// Record declared at global scope with `type`
+    Record(Arc<RecordType>),
+}
+
+/// A record type, its fields are in declaration order
+#[derive(PartialEq, Debug)]
+pub struct RecordType {
+    pub name: String,
+    pub fields: Vec<(String, Type)>,
+}
+
+impl RecordType {
+    /// Index and type of the field with the given name (ignoring the
+    /// case, as for every identifier)
+    pub fn field(&self, name: &str) -> Option<(usize, &Type)> {
+        self.fields
+            .iter()
+            .enumerate()
+            .find(|(_, (n, _))| n.eq_ignore_ascii_case(name))
+            .map(|(i, (_, t))| (i, t))
+    }
+
+    /// Name of the C struct the record maps to
+    pub fn c_name(&self) -> String {
+        format!("rec_{}", self.name.to_lowercase())
+    }
+
+    /// Name of the member of the C struct for the field
+    pub fn c_field(&self, index: usize) -> String {
+        format!("f_{}", self.fields[index].0.to_lowercase())
+    }
 }
 
 impl SimpleType {
-    pub fn to_c_type(self) -> String {
+    pub fn to_c_type(&self) -> String {
         match self {
             SimpleType::Int => "int".to_string(),
             SimpleType::String => "char*".to_string(),
             SimpleType::Bool => "bool".to_string(),
             SimpleType::Void => "void".to_string(),
             SimpleType::Real => "double".to_string(),
+            SimpleType::Record(r) => r.c_name(),
         }
     }
+
+    pub fn is_record(&self) -> bool {
+        matches!(self, SimpleType::Record(_))
+    }
 }
 
 impl Display for SimpleType {
@@ -32,38 +68,106 @@ impl Display for SimpleType {
                 SimpleType::Bool => "bool",
                 SimpleType::Void => "void",
                 SimpleType::Real => "double",
+                SimpleType::Record(r) => &r.name,
             }
         )
     }
 }
 
-#[derive(PartialEq, Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 pub enum Type {
     Simple(SimpleType),
-    Array(SimpleType),
+    /// Array of elements of the type, which can be arrays themselves,
+    /// with its size when it is known
+    Array(Box<Type>, Option<usize>),
+}
+
+/// The size of an array is not part of its type, arrays of any size
+/// can be assigned to each other, but the size of the nested ones is:
+/// it is what lays the elements out
+impl PartialEq for Type {
+    fn eq(&self, other: &Type) -> bool {
+        match (self, other) {
+            (Type::Array(a, _), Type::Array(b, _)) => a.same_layout(b),
+            (Type::Simple(a), Type::Simple(b)) => a == b,
+            _ => false,
+        }
+    }
 }
 
 impl Display for Type {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Type::Simple(t) => write!(f, "{}", t),
-            Type::Array(a) => write!(f, "array of {}", a),
+            Type::Array(a, _) => write!(f, "array of {}", a.element_display()),
         }
     }
 }
 
 impl Type {
+    /// The type of the elements at the bottom of the array, the type
+    /// itself if it is not an array
     pub fn internal(&self) -> SimpleType {
         match self {
-            Type::Simple(s) => *s,
-            Type::Array(s) => *s,
+            Type::Simple(s) => s.clone(),
+            Type::Array(s, _) => s.internal(),
+        }
+    }
+
+    /// The type of the elements of an array
+    pub fn element(&self) -> Option<&Type> {
+        match self {
+            Type::Simple(_) => None,
+            Type::Array(e, _) => Some(e),
+        }
+    }
+
+    /// The declared size of an array
+    pub fn size(&self) -> Option<usize> {
+        match self {
+            Type::Simple(_) => None,
+            Type::Array(_, size) => *size,
+        }
+    }
+
+    /// Number of dimensions, 0 if it is not an array
+    pub fn dimensions(&self) -> usize {
+        match self {
+            Type::Simple(_) => 0,
+            Type::Array(e, _) => 1 + e.dimensions(),
+        }
+    }
+
+    /// How many values of the `internal` type a value of the type
+    /// holds, the elements of arrays are laid out one row after the
+    /// other
+    pub fn cells(&self) -> usize {
+        match self {
+            Type::Simple(_) => 1,
+            Type::Array(e, size) => size.unwrap_or(0) * e.cells(),
+        }
+    }
+
+    /// Equality including the sizes of the arrays
+    fn same_layout(&self, other: &Type) -> bool {
+        match (self, other) {
+            (Type::Array(a, n), Type::Array(b, m)) => n == m && a.same_layout(b),
+            _ => self == other,
+        }
+    }
+
+    fn element_display(&self) -> String {
+        match self {
+            Type::Array(e, Some(size)) => format!("array [{}] of {}", size, e.element_display()),
+            other => other.to_string(),
         }
     }
 
-    pub fn to_c_type(self) -> String {
+    /// Arrays are a flat block of their `internal` type
+    pub fn to_c_type(&self) -> String {
         match self {
             Type::Simple(s) => s.to_c_type(),
-            Type::Array(a) => format!("{}*", a.to_c_type()),
+            Type::Array(_, _) => format!("{}*", self.internal().to_c_type()),
         }
     }
 }
@@ -0,0 +1,439 @@
+use std::{
+    collections::BTreeSet,
+    io::{BufRead, Write},
+};
+
+use crate::{
+    core::{
+        ast::{ASTNode, ProgramNode},
+        errors::EvaluationError,
+        objects::Object,
+        symbol_table::{Symbol, SymbolTable, SymbolType},
+        types::{SimpleType, Type},
+    },
+    interpreter::{statement::format_real, Hook, Interpreter},
+    parser::Parser,
+    scanner::position::Position,
+};
+
+/// Shown by `help`
+const HELP: &str = "\
+break LINE (b)      stop when a statement of the line is about to run
+delete LINE (d)     remove the breakpoint of the line
+step (s)            run the next statement, going into calls
+next (n)            run the next statement, going over calls
+finish (f)          run until the current call returns
+continue (c)        run until a breakpoint or a watch stops the program
+print EXPR (p)      show the value of an expression
+locals              show the variables in scope, innermost first
+backtrace (bt)      show the running calls
+watch EXPR (w)      stop whenever the value of the expression changes
+unwatch N           remove the watch with the given number
+list (l)            show the source around the current line
+quit (q)            stop the program
+An empty line repeats the last command";
+
+/// Description of the error the program stops with on `quit`
+pub const QUIT: &str = "Stopped by the debugger";
+
+/// When the program stops next
+#[derive(Debug, Clone, Copy)]
+enum Mode {
+    Step,
+    /// At the next statement with at most this many calls running
+    Next(usize),
+    /// At the next statement with less than this many calls running
+    Finish(usize),
+    Continue,
+}
+
+#[derive(Debug)]
+struct Watch {
+    expression: String,
+    /// Value at the last check in its frame
+    value: String,
+    /// Calls running when the watch was set, it is only checked when
+    /// they are the running ones again
+    frame: Vec<String>,
+}
+
+/// Names of the running calls, outermost first
+fn frame(interpreter: &Interpreter) -> Vec<String> {
+    interpreter
+        .call_stack()
+        .iter()
+        .map(|call| call.name.clone())
+        .collect()
+}
+
+/// Interactive debugger over the interpreter, it is its `Hook`. The
+/// program stops before its first statement and then as the commands
+/// read from `commands` say, everything the debugger prints goes to
+/// `out`. At the end of the commands the program runs to the end
+pub struct Debugger<'a> {
+    functions: SymbolTable,
+    source: Vec<String>,
+    commands: &'a mut dyn BufRead,
+    out: &'a mut dyn Write,
+    breakpoints: BTreeSet<i64>,
+    watches: Vec<Watch>,
+    mode: Mode,
+    last_command: String,
+    line: i64,
+}
+
+impl<'a> Debugger<'a> {
+    pub fn new(
+        program: &ProgramNode,
+        source: &str,
+        commands: &'a mut dyn BufRead,
+        out: &'a mut dyn Write,
+    ) -> Debugger<'a> {
+        let mut functions = SymbolTable::new();
+        for f in program.functions.iter() {
+            functions.push(Symbol {
+                name: f.name.clone(),
+                s_type: SymbolType::Function,
+                r_type: f.r_type.clone(),
+                position: f.position,
+                args: Some(Box::new(f.args.clone())),
+            });
+        }
+        Debugger {
+            functions,
+            source: source.lines().map(|l| l.to_string()).collect(),
+            commands,
+            out,
+            breakpoints: BTreeSet::new(),
+            watches: vec![],
+            mode: Mode::Step,
+            last_command: String::new(),
+            line: 0,
+        }
+    }
+
+    /// Runs the program under the debugger, stopping on `quit` is not
+    /// an error
+    pub fn run(
+        &mut self,
+        program: &ProgramNode,
+        input: &mut dyn BufRead,
+        output: &mut dyn Write,
+    ) -> Result<(), EvaluationError> {
+        let mut interpreter = Interpreter::new(input, output);
+        interpreter.set_hook(self);
+        match interpreter.run(program) {
+            Err(e) if e.description == QUIT => Ok(()),
+            result => result,
+        }
+    }
+
+    /// Symbols of the variables in scope and of the functions, for the
+    /// parser of the expressions
+    fn symbols(&self, interpreter: &Interpreter) -> SymbolTable {
+        let mut symbols = SymbolTable::new();
+        let globals = std::iter::once(interpreter.globals());
+        for scope in interpreter.scopes().iter().rev().chain(globals) {
+            let mut names: Vec<_> = scope.iter().collect();
+            names.sort_by(|a, b| a.0.cmp(b.0));
+            for (name, value) in names {
+                let r_type = type_of(value);
+                symbols.push(Symbol {
+                    name: name.clone(),
+                    s_type: match r_type {
+                        Type::Array(..) => SymbolType::Arr,
+                        Type::Simple(_) => SymbolType::Var,
+                    },
+                    r_type,
+                    // not known at run time, hints leave it out
+                    position: Position::new(0, 0, 0),
+                    args: None,
+                });
+            }
+        }
+        for f in self.functions.iter() {
+            symbols.push(f.clone());
+        }
+        symbols
+    }
+
+    /// Parses and evaluates the expression in the current scope
+    fn evaluate(&self, interpreter: &mut Interpreter, expression: &str) -> Result<String, String> {
+        let mut parser = Parser::with_symbols(expression.to_string(), self.symbols(interpreter));
+        let ast = parser.parse_only_expression().map_err(|errs| {
+            errs.iter()
+                .map(|e| e.description.clone())
+                .collect::<Vec<_>>()
+                .join(", ")
+        })?;
+        interpreter
+            .eval(&ast)
+            .map(|value| show(&value))
+            .map_err(|e| e.description)
+    }
+
+    /// Updates the values of the watches set in the running frame,
+    /// telling which ones changed. A watch that fails to evaluate
+    /// keeps its value
+    fn check_watches(&mut self, interpreter: &mut Interpreter) -> Vec<String> {
+        let mut changes = vec![];
+        let running = frame(interpreter);
+        for i in 0..self.watches.len() {
+            if self.watches[i].frame != running {
+                continue;
+            }
+            let value = match self.evaluate(interpreter, &self.watches[i].expression) {
+                Ok(value) => value,
+                Err(_) => continue,
+            };
+            let watch = &mut self.watches[i];
+            if watch.value != value {
+                changes.push(format!(
+                    "Watch {}: {}\nOld value = {}\nNew value = {}",
+                    i + 1,
+                    watch.expression,
+                    watch.value,
+                    value
+                ));
+                watch.value = value;
+            }
+        }
+        changes
+    }
+
+    fn source_line(&self, line: i64) -> &str {
+        usize::try_from(line - 1)
+            .ok()
+            .and_then(|i| self.source.get(i))
+            .map(|l| l.as_str())
+            .unwrap_or_default()
+    }
+
+    /// Reads and runs commands until one of them resumes the program
+    fn prompt(&mut self, interpreter: &mut Interpreter) -> Result<(), EvaluationError> {
+        loop {
+            let _ = write!(self.out, "(mpdb) ");
+            let _ = self.out.flush();
+            let mut command = String::new();
+            if self.commands.read_line(&mut command).unwrap_or(0) == 0 {
+                // no more commands, the program runs to its end
+                let _ = writeln!(self.out);
+                self.breakpoints.clear();
+                self.watches.clear();
+                self.mode = Mode::Continue;
+                return Ok(());
+            }
+            let mut command = command.trim().to_string();
+            if command.is_empty() {
+                command = self.last_command.clone();
+            }
+            self.last_command = command.clone();
+            let (name, arg) = command
+                .split_once(char::is_whitespace)
+                .map(|(n, a)| (n, a.trim()))
+                .unwrap_or((command.as_str(), ""));
+            let depth = interpreter.call_stack().len();
+            let reply = match name {
+                "" => continue,
+                "s" | "step" => return self.resume(Mode::Step),
+                "n" | "next" => return self.resume(Mode::Next(depth)),
+                "c" | "continue" => return self.resume(Mode::Continue),
+                "f" | "finish" if depth == 0 => "Not in a function or procedure".to_string(),
+                "f" | "finish" => return self.resume(Mode::Finish(depth)),
+                "q" | "quit" => {
+                    return Err(EvaluationError {
+                        position: Position::new(0, self.line, 0),
+                        description: QUIT.to_string(),
+                    })
+                }
+                "b" | "break" => match arg.parse::<i64>() {
+                    Ok(line) if line >= 1 && line as usize <= self.source.len() => {
+                        self.breakpoints.insert(line);
+                        format!(
+                            "Breakpoint at line {}: {}",
+                            line,
+                            self.source_line(line).trim()
+                        )
+                    }
+                    _ => format!("No line `{}` in the program", arg),
+                },
+                "d" | "delete" => match arg.parse::<i64>() {
+                    Ok(line) if self.breakpoints.remove(&line) => {
+                        format!("Deleted the breakpoint at line {}", line)
+                    }
+                    _ => format!("No breakpoint at line `{}`", arg),
+                },
+                "p" | "print" => match self.evaluate(interpreter, arg) {
+                    Ok(value) => format!("{} = {}", arg, value),
+                    Err(e) => e,
+                },
+                "locals" => self.locals(interpreter),
+                "bt" | "backtrace" => backtrace(interpreter, self.line),
+                "w" | "watch" => match self.evaluate(interpreter, arg) {
+                    Ok(value) => {
+                        self.watches.push(Watch {
+                            expression: arg.to_string(),
+                            value: value.clone(),
+                            frame: frame(interpreter),
+                        });
+                        format!("Watch {}: {} = {}", self.watches.len(), arg, value)
+                    }
+                    Err(e) => e,
+                },
+                "unwatch" => match arg.parse::<usize>() {
+                    Ok(n) if n >= 1 && n <= self.watches.len() => {
+                        let watch = self.watches.remove(n - 1);
+                        format!("Removed watch {}: {}", n, watch.expression)
+                    }
+                    _ => format!("No watch `{}`", arg),
+                },
+                "l" | "list" => self.list(),
+                "h" | "help" => HELP.to_string(),
+                other => format!("Unknown command `{}`, try `help`", other),
+            };
+            let _ = writeln!(self.out, "{}", reply);
+        }
+    }
+
+    fn resume(&mut self, mode: Mode) -> Result<(), EvaluationError> {
+        self.mode = mode;
+        Ok(())
+    }
+
+    /// Variables in scope, innermost block first and globals last
+    fn locals(&self, interpreter: &Interpreter) -> String {
+        let mut lines = vec![];
+        let globals = std::iter::once(interpreter.globals());
+        for scope in interpreter.scopes().iter().rev().chain(globals) {
+            let mut names: Vec<_> = scope.iter().collect();
+            names.sort_by(|a, b| a.0.cmp(b.0));
+            for (name, value) in names {
+                lines.push(format!("{} = {}", name, show(value)));
+            }
+        }
+        if lines.is_empty() {
+            "No variables".to_string()
+        } else {
+            lines.join("\n")
+        }
+    }
+
+    /// Five lines of source on each side of the current one
+    fn list(&self) -> String {
+        let first = (self.line - 5).max(1);
+        let last = (self.line + 5).min(self.source.len() as i64);
+        (first..=last)
+            .map(|line| {
+                format!(
+                    "{}{:>4} {}",
+                    if line == self.line { "=>" } else { "  " },
+                    line,
+                    self.source_line(line)
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+impl Hook for Debugger<'_> {
+    fn statement(
+        &mut self,
+        interpreter: &mut Interpreter<'_>,
+        node: &ASTNode,
+    ) -> Result<(), EvaluationError> {
+        let line = node.position().line;
+        if line <= 0 {
+            return Ok(());
+        }
+        let depth = interpreter.call_stack().len();
+        let changes = self.check_watches(interpreter);
+        let at_breakpoint = self.breakpoints.contains(&line);
+        let stop = !changes.is_empty()
+            || at_breakpoint
+            || match self.mode {
+                Mode::Step => true,
+                Mode::Next(d) => depth <= d,
+                Mode::Finish(d) => depth < d,
+                Mode::Continue => false,
+            };
+        self.line = line;
+        if !stop {
+            return Ok(());
+        }
+        let _ = interpreter.flush();
+        for change in changes {
+            let _ = writeln!(self.out, "{}", change);
+        }
+        if at_breakpoint {
+            let _ = writeln!(self.out, "Breakpoint at line {}", line);
+        }
+        let text = self.source_line(line).to_string();
+        let _ = writeln!(self.out, "{:>4} {}", line, text);
+        self.prompt(interpreter)
+    }
+}
+
+/// Running calls, innermost first, with the line running in each
+fn backtrace(interpreter: &Interpreter, line: i64) -> String {
+    let calls = interpreter.call_stack();
+    let mut frames = vec![];
+    let mut line = line;
+    for (i, call) in calls.iter().enumerate().rev() {
+        frames.push(format!(
+            "#{} {} at line {}",
+            calls.len() - i - 1,
+            call.name,
+            line
+        ));
+        line = call.position.line;
+    }
+    frames.push(format!("#{} main block at line {}", calls.len(), line));
+    frames.join("\n")
+}
+
+/// Type of the value, arrays of unknown elements are taken as ints
+fn type_of(value: &Object) -> Type {
+    let simple = |value: &Object| match value {
+        Object::Real(_) => SimpleType::Real,
+        Object::String(_) => SimpleType::String,
+        Object::Bool(_) => SimpleType::Bool,
+        Object::Record(r, _) => SimpleType::Record(r.clone()),
+        _ => SimpleType::Int,
+    };
+    match value {
+        Object::Array(elements) => Type::Array(
+            Box::new(
+                elements
+                    .first()
+                    .map(type_of)
+                    .unwrap_or(Type::Simple(SimpleType::Int)),
+            ),
+            Some(elements.len()),
+        ),
+        other => Type::Simple(simple(other)),
+    }
+}
+
+/// The value as the debugger shows it
+pub fn show(value: &Object) -> String {
+    match value {
+        Object::Int(i) => i.to_string(),
+        Object::Real(r) => format_real(*r),
+        Object::String(s) => format!("{:?}", s),
+        Object::Bool(b) => b.to_string(),
+        Object::Array(elements) => format!(
+            "[{}]",
+            elements.iter().map(show).collect::<Vec<_>>().join(", ")
+        ),
+        Object::Record(r, fields) => format!(
+            "{{{}}}",
+            r.fields
+                .iter()
+                .zip(fields.iter())
+                .map(|((name, _), value)| format!("{}: {}", name, show(value)))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    }
+}
@@ -0,0 +1,902 @@
+use crate::core::types::SimpleType;
+
+/// Small deterministic random generator (splitmix64), the same seed
+/// always gives the same program
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        Rng(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Random number in `0..n`
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n.max(1) as u64) as usize
+    }
+
+    /// True with the given probability, in percent
+    pub fn chance(&mut self, percent: usize) -> bool {
+        self.below(100) < percent
+    }
+
+    pub fn pick<'a, T>(&mut self, items: &'a [T]) -> &'a T {
+        &items[self.below(items.len())]
+    }
+}
+
+const TYPES: [SimpleType; 4] = [
+    SimpleType::Int,
+    SimpleType::Real,
+    SimpleType::String,
+    SimpleType::Bool,
+];
+
+/// Ends of string literals that are easy to get wrong in the C
+/// translation
+const SUFFIXES: [&str; 4] = [" ", "\\n", "\\t", "%d"];
+
+/// Deepest expression the generator writes
+const MAX_EXPR_DEPTH: usize = 4;
+
+/// Deepest nesting of loops
+const MAX_LOOPS: usize = 2;
+
+/// Depth the calls from outside give to a recursive subprogram
+const MAX_RECURSION: usize = 3;
+
+/// Calls a recursive subprogram makes to itself, never in a loop, so
+/// that a call makes at most a few dozen others
+const MAX_SELF_CALLS: usize = 2;
+
+/// Type of a generated variable or parameter
+#[derive(Clone, PartialEq)]
+enum Shape {
+    Simple(SimpleType),
+    /// Index of the record type in `Generator::records`
+    Record(usize),
+    /// Element type and size
+    Array(Box<Shape>, usize),
+}
+
+#[derive(Clone)]
+struct Variable {
+    name: String,
+    shape: Shape,
+    /// Loop counters and depths can not be assigned, the loops and the
+    /// recursions have to end
+    writable: bool,
+}
+
+#[derive(Clone)]
+struct Param {
+    name: String,
+    shape: Shape,
+    by_reference: bool,
+}
+
+#[derive(Clone)]
+struct Subprogram {
+    name: String,
+    params: Vec<Param>,
+    r_type: Option<Shape>,
+    /// The first parameter is then a depth, the subprogram returns
+    /// when it is 0 and calls itself with one less
+    recursive: bool,
+}
+
+struct Record {
+    name: String,
+    fields: Vec<(String, Shape)>,
+}
+
+/// A generated program together with the input it reads
+pub struct Generated {
+    pub source: String,
+    pub stdin: String,
+}
+
+/// Writes random well typed programs, in the subset of the language
+/// that both the interpreter and the C translation support: arrays
+/// indexed by constants in their bounds, records, recursion bounded by
+/// a depth, integer divisions by values that can not be zero and loops
+/// that always end
+pub struct Generator {
+    rng: Rng,
+    out: String,
+    stdin: String,
+    indent: usize,
+    scopes: Vec<Vec<Variable>>,
+    records: Vec<Record>,
+    functions: Vec<Subprogram>,
+    procedures: Vec<Subprogram>,
+    /// The subprogram being written, None in the main block
+    current: Option<Subprogram>,
+    /// Calls the current subprogram made to itself
+    self_calls: usize,
+    loops: usize,
+    names: usize,
+    /// String variables the expression being written can still read,
+    /// with one per expression strings grow linearly in loops and not
+    /// exponentially
+    string_reads: usize,
+}
+
+fn type_name(s_type: &SimpleType) -> &'static str {
+    match s_type {
+        SimpleType::Int => "int",
+        SimpleType::Real => "real",
+        SimpleType::String => "string",
+        SimpleType::Bool => "bool",
+        SimpleType::Void => "void",
+        SimpleType::Record(_) => unreachable!("the records are shapes of their own"),
+    }
+}
+
+/// Binary expressions used as operands get parenthesis, the generator
+/// does not have to care about precedence
+fn operand(expr: String, atomic: bool) -> String {
+    if atomic {
+        expr
+    } else {
+        format!("({})", expr)
+    }
+}
+
+impl Generator {
+    pub fn new(seed: u64) -> Generator {
+        Generator {
+            rng: Rng::new(seed),
+            out: String::new(),
+            stdin: String::new(),
+            indent: 0,
+            scopes: vec![],
+            records: vec![],
+            functions: vec![],
+            procedures: vec![],
+            current: None,
+            self_calls: 0,
+            loops: 0,
+            names: 0,
+            string_reads: 0,
+        }
+    }
+
+    /// Generates the program for the given seed
+    pub fn program(seed: u64) -> Generated {
+        let mut generator = Generator::new(seed);
+        generator.line(&format!("program generated{};", seed));
+        for _ in 0..generator.rng.below(3) {
+            generator.record();
+        }
+        for _ in 0..generator.rng.below(3) {
+            generator.function();
+        }
+        for _ in 0..generator.rng.below(3) {
+            generator.procedure();
+        }
+        generator.current = None;
+        generator.scopes = vec![vec![]];
+        generator.line("begin");
+        generator.indent += 1;
+        let statements = 3 + generator.rng.below(10);
+        generator.statements(statements, true);
+        generator.indent -= 1;
+        generator.line("end");
+        Generated {
+            source: generator.out,
+            stdin: generator.stdin,
+        }
+    }
+
+    fn line(&mut self, text: &str) {
+        self.out.push_str(&"  ".repeat(self.indent));
+        self.out.push_str(text);
+        self.out.push('\n');
+    }
+
+    fn fresh(&mut self, prefix: &str) -> String {
+        self.names += 1;
+        format!("{}{}", prefix, self.names)
+    }
+
+    fn random_type(&mut self) -> SimpleType {
+        self.rng.pick(&TYPES).clone()
+    }
+
+    fn shape_name(&self, shape: &Shape) -> String {
+        match shape {
+            Shape::Simple(s_type) => type_name(s_type).to_string(),
+            Shape::Record(r) => self.records[*r].name.clone(),
+            Shape::Array(element, size) => {
+                format!("array [{}] of {}", size, self.shape_name(element))
+            }
+        }
+    }
+
+    /// Mostly simple types, sometimes records and arrays of them, or
+    /// of arrays
+    fn random_shape(&mut self) -> Shape {
+        let record = |g: &mut Generator| Shape::Record(g.rng.below(g.records.len()));
+        match self.rng.below(10) {
+            0 | 1 if !self.records.is_empty() => record(self),
+            2 | 3 => {
+                let element = match self.rng.below(4) {
+                    0 if !self.records.is_empty() => record(self),
+                    1 => Shape::Array(
+                        Box::new(Shape::Simple(self.random_type())),
+                        2 + self.rng.below(2),
+                    ),
+                    _ => Shape::Simple(self.random_type()),
+                };
+                Shape::Array(Box::new(element), 2 + self.rng.below(3))
+            }
+            _ => Shape::Simple(self.random_type()),
+        }
+    }
+
+    /// Declares a record type, its fields can be records declared
+    /// before it but not arrays
+    fn record(&mut self) {
+        let name = self.fresh("R");
+        let fields: Vec<(String, Shape)> = (0..1 + self.rng.below(3))
+            .map(|_| {
+                let shape = match !self.records.is_empty() && self.rng.chance(20) {
+                    true => Shape::Record(self.rng.below(self.records.len())),
+                    false => Shape::Simple(self.random_type()),
+                };
+                (self.fresh("x"), shape)
+            })
+            .collect();
+        let declared: Vec<String> = fields
+            .iter()
+            .map(|(field, shape)| format!("{}: {}", field, self.shape_name(shape)))
+            .collect();
+        self.line(&format!(
+            "type {} = record {} end;",
+            name,
+            declared.join("; ")
+        ));
+        self.records.push(Record { name, fields });
+    }
+
+    /// Whole variables of the shape, the only ones that can be given
+    /// to a var parameter
+    fn variables(&self, shape: &Shape, writable: bool) -> Vec<Variable> {
+        self.scopes
+            .iter()
+            .flatten()
+            .filter(|v| v.shape == *shape && (v.writable || !writable))
+            .cloned()
+            .collect()
+    }
+
+    /// Collects in `out` the ways to reach a value of the wanted shape
+    /// from `prefix`, which has the given shape: itself, its fields and
+    /// its elements, at random indexes in the bounds
+    fn paths(&mut self, prefix: String, shape: &Shape, wanted: &Shape, out: &mut Vec<String>) {
+        if shape == wanted {
+            out.push(prefix.clone());
+        }
+        match shape {
+            Shape::Simple(_) => {}
+            Shape::Record(r) => {
+                for (field, f_shape) in self.records[*r].fields.clone() {
+                    self.paths(format!("{}.{}", prefix, field), &f_shape, wanted, out);
+                }
+            }
+            Shape::Array(element, size) => {
+                let index = self.rng.below(*size);
+                self.paths(format!("{}[{}]", prefix, index), element, wanted, out);
+            }
+        }
+    }
+
+    /// The variables, fields and elements holding a value of the shape
+    fn places(&mut self, wanted: &Shape, writable: bool) -> Vec<String> {
+        let variables: Vec<Variable> = self
+            .scopes
+            .iter()
+            .flatten()
+            .filter(|v| v.writable || !writable)
+            .cloned()
+            .collect();
+        let mut out = vec![];
+        for v in variables {
+            self.paths(v.name, &v.shape, wanted, &mut out);
+        }
+        out
+    }
+
+    fn declare(&mut self, name: String, shape: Shape, writable: bool) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.push(Variable {
+                name,
+                shape,
+                writable,
+            });
+        }
+    }
+
+    /// Parameters of a subprogram, arrays are always passed by
+    /// reference and never written with var
+    fn params(&mut self, by_reference: bool, recursive: bool) -> Vec<Param> {
+        let mut params = vec![];
+        if recursive {
+            params.push(Param {
+                name: self.fresh("d"),
+                shape: Shape::Simple(SimpleType::Int),
+                by_reference: false,
+            });
+        }
+        for _ in 0..1 + self.rng.below(3) {
+            let shape = self.random_shape();
+            let by_reference =
+                by_reference && !matches!(shape, Shape::Array(..)) && self.rng.chance(40);
+            params.push(Param {
+                name: self.fresh("a"),
+                shape,
+                by_reference,
+            });
+        }
+        params
+    }
+
+    fn header(&self, params: &[Param]) -> String {
+        params
+            .iter()
+            .map(|p| {
+                format!(
+                    "{}{} : {}",
+                    if p.by_reference { "var " } else { "" },
+                    p.name,
+                    self.shape_name(&p.shape)
+                )
+            })
+            .collect::<Vec<String>>()
+            .join(", ")
+    }
+
+    /// Writes the beginning of the body, with the end of the recursion
+    /// first, functions returning a record get a variable to return
+    fn subprogram_body(&mut self, subprogram: Subprogram) {
+        self.scopes = vec![subprogram
+            .params
+            .iter()
+            .enumerate()
+            .map(|(i, p)| Variable {
+                name: p.name.clone(),
+                shape: p.shape.clone(),
+                writable: i > 0 || !subprogram.recursive,
+            })
+            .collect()];
+        self.current = None;
+        self.self_calls = 0;
+        self.line("begin");
+        self.indent += 1;
+        if let Some(shape @ Shape::Record(_)) = &subprogram.r_type {
+            let name = self.fresh("v");
+            self.line(&format!("var {} : {};", name, self.shape_name(shape)));
+            self.declare(name, shape.clone(), true);
+        }
+        if subprogram.recursive {
+            let value = match &subprogram.r_type {
+                Some(shape) => format!(" {}", self.value(shape).unwrap_or_default()),
+                None => String::new(),
+            };
+            self.line(&format!("if {} <= 0 then", subprogram.params[0].name));
+            self.indent += 1;
+            self.line(&format!("return{};", value));
+            self.indent -= 1;
+        }
+        self.current = Some(subprogram);
+        let statements = 1 + self.rng.below(5);
+        self.statements(statements, false);
+    }
+
+    fn function(&mut self) {
+        let name = self.fresh("f");
+        let recursive = self.rng.chance(30);
+        let params = self.params(false, recursive);
+        let r_type = match !self.records.is_empty() && self.rng.chance(20) {
+            true => Shape::Record(self.rng.below(self.records.len())),
+            false => Shape::Simple(self.random_type()),
+        };
+        self.line(&format!(
+            "function {}({}) : {};",
+            name,
+            self.header(&params),
+            self.shape_name(&r_type)
+        ));
+        let function = Subprogram {
+            name,
+            params,
+            r_type: Some(r_type.clone()),
+            recursive,
+        };
+        self.subprogram_body(function.clone());
+        let value = self.value(&r_type).unwrap_or_default();
+        self.line(&format!("return {};", value));
+        self.indent -= 1;
+        self.line("end");
+        self.functions.push(function);
+    }
+
+    fn procedure(&mut self) {
+        let name = self.fresh("p");
+        let recursive = self.rng.chance(30);
+        let params = self.params(true, recursive);
+        self.line(&format!("procedure {}({});", name, self.header(&params)));
+        let procedure = Subprogram {
+            name,
+            params,
+            r_type: None,
+            recursive,
+        };
+        self.subprogram_body(procedure.clone());
+        self.indent -= 1;
+        self.line("end");
+        self.procedures.push(procedure);
+    }
+
+    /// Tells if the subprogram can call itself here
+    fn can_recurse(&self, subprogram: &Subprogram) -> bool {
+        subprogram.recursive && self.loops == 0 && self.self_calls < MAX_SELF_CALLS
+    }
+
+    fn is_current(&self, subprogram: &Subprogram) -> bool {
+        self.current
+            .as_ref()
+            .is_some_and(|c| c.name == subprogram.name)
+    }
+
+    /// Arguments of a call, the depth of a recursive call is one less
+    /// than the current one, and a small constant from the outside.
+    /// None when no variable can be given to a parameter
+    fn arguments(&mut self, callee: &Subprogram, depth: usize) -> Option<Vec<String>> {
+        let mut args = vec![];
+        for (i, p) in callee.params.iter().enumerate() {
+            if i == 0 && callee.recursive {
+                args.push(match self.is_current(callee) {
+                    true => format!("{} - 1", p.name),
+                    false => self.rng.below(MAX_RECURSION + 1).to_string(),
+                });
+                continue;
+            }
+            match (&p.shape, p.by_reference) {
+                (Shape::Simple(s_type), false) => args.push(self.expression(s_type, depth)),
+                (shape, by_reference) => {
+                    let candidates = match by_reference {
+                        true => self
+                            .variables(shape, true)
+                            .into_iter()
+                            .map(|v| v.name)
+                            .collect(),
+                        false => self.places(shape, false),
+                    };
+                    if candidates.is_empty() {
+                        return None;
+                    }
+                    args.push(self.rng.pick(&candidates).clone());
+                }
+            }
+        }
+        Some(args)
+    }
+
+    /// Writes `count` statements, `top` tells if they run exactly once
+    /// (reads are only written there, so that the input is consumed
+    /// exactly)
+    fn statements(&mut self, count: usize, top: bool) {
+        for _ in 0..count {
+            self.statement(top);
+        }
+    }
+
+    fn statement(&mut self, top: bool) {
+        match self.rng.below(100) {
+            0..=19 => {
+                let shape = self.random_shape();
+                let name = self.fresh("v");
+                self.line(&format!("var {} : {};", name, self.shape_name(&shape)));
+                self.declare(name, shape, true);
+            }
+            20..=39 => {
+                if let Some(assignment) = self.assignment() {
+                    self.line(&format!("{};", assignment));
+                }
+            }
+            40..=59 => {
+                let write = self.write();
+                self.line(&format!("{};", write));
+            }
+            60..=65 => self.if_statement(),
+            66..=69 => self.case_statement(),
+            70..=73 if self.loops < MAX_LOOPS => self.while_loop(),
+            74..=76 if self.loops < MAX_LOOPS => self.for_loop(),
+            77..=79 if self.loops < MAX_LOOPS => self.repeat_loop(),
+            80..=87 => self.procedure_call_statement(),
+            88..=94 if top => self.read(),
+            95 => {
+                let condition = self.expression(&SimpleType::Bool, 0);
+                self.line(&format!("assert({});", condition));
+            }
+            96 | 97 if self.loops > 0 => {
+                let condition = self.expression(&SimpleType::Bool, 0);
+                self.line(&format!("if {} then", condition));
+                let exit = match self.rng.chance(50) {
+                    true => "break;",
+                    false => "continue;",
+                };
+                self.indent += 1;
+                self.line(exit);
+                self.indent -= 1;
+            }
+            _ => {
+                let write = self.write();
+                self.line(&format!("{};", write));
+            }
+        }
+    }
+
+    /// A `writeln` of one to three values, some of them padded to a
+    /// width and some reals with a precision
+    fn write(&mut self) -> String {
+        let args: Vec<String> = (0..1 + self.rng.below(3))
+            .map(|_| {
+                let s_type = self.random_type();
+                let value = self.expression(&s_type, 0);
+                match self.rng.below(10) {
+                    0 | 1 => format!("{}:{}", value, self.rng.below(12)),
+                    2 if s_type == SimpleType::Real => {
+                        format!("{}:{}:{}", value, self.rng.below(12), self.rng.below(6))
+                    }
+                    _ => value,
+                }
+            })
+            .collect();
+        format!("writeln({})", args.join(", "))
+    }
+
+    /// A statement that fits in the branch of an if
+    fn simple_statement(&mut self) -> String {
+        if self.rng.chance(50) {
+            if let Some(assignment) = self.assignment() {
+                return assignment;
+            }
+        }
+        self.write()
+    }
+
+    /// Assignment of a simple value or of a whole record
+    fn assignment(&mut self) -> Option<String> {
+        let shape = match !self.records.is_empty() && self.rng.chance(15) {
+            true => Shape::Record(self.rng.below(self.records.len())),
+            false => Shape::Simple(self.random_type()),
+        };
+        let targets = self.places(&shape, true);
+        if targets.is_empty() {
+            return None;
+        }
+        let target = self.rng.pick(&targets).clone();
+        let value = self.value(&shape)?;
+        Some(format!("{} := {}", target, value))
+    }
+
+    /// An expression for the simple types, a record from a variable or
+    /// from a call otherwise
+    fn value(&mut self, shape: &Shape) -> Option<String> {
+        if let Shape::Simple(s_type) = shape {
+            return Some(self.expression(s_type, 0));
+        }
+        self.string_reads = 1;
+        if self.rng.chance(30) {
+            if let Some(call) = self.function_call(shape, 0) {
+                return Some(call);
+            }
+        }
+        let candidates = self.places(shape, false);
+        match candidates.is_empty() {
+            true => None,
+            false => Some(self.rng.pick(&candidates).clone()),
+        }
+    }
+
+    fn if_statement(&mut self) {
+        let guard = self.expression(&SimpleType::Bool, 0);
+        self.line(&format!("if {} then", guard));
+        self.indent += 1;
+        let then = self.simple_statement();
+        self.line(&format!("{};", then));
+        self.indent -= 1;
+        if self.rng.chance(50) {
+            self.line("else");
+            self.indent += 1;
+            let otherwise = self.simple_statement();
+            self.line(&format!("{};", otherwise));
+            self.indent -= 1;
+        }
+    }
+
+    fn case_statement(&mut self) {
+        let s_type = match self.rng.chance(70) {
+            true => SimpleType::Int,
+            false => SimpleType::String,
+        };
+        let value = self.expression(&s_type, 0);
+        self.line(&format!("case {} of", value));
+        self.indent += 1;
+        // small labels, so that the value hits them sometimes
+        let mut used: Vec<String> = Vec::new();
+        for _ in 0..1 + self.rng.below(4) {
+            let mut labels = Vec::new();
+            for _ in 0..1 + self.rng.below(3) {
+                let label = match s_type {
+                    SimpleType::Int => self.rng.below(10).to_string(),
+                    _ => format!("\"{}\"", self.word()),
+                };
+                if !used.contains(&label) {
+                    used.push(label.clone());
+                    labels.push(label);
+                }
+            }
+            if labels.is_empty() {
+                continue;
+            }
+            let body = self.simple_statement();
+            self.line(&format!("{}: {};", labels.join(", "), body));
+        }
+        self.indent -= 1;
+        if self.rng.chance(50) {
+            self.line("else");
+            self.indent += 1;
+            let otherwise = self.simple_statement();
+            self.line(&format!("{};", otherwise));
+            self.indent -= 1;
+        }
+        self.line("end");
+    }
+
+    fn while_loop(&mut self) {
+        let counter = self.fresh("i");
+        self.line(&format!("var {} : int;", counter));
+        self.line(&format!("{} := 0;", counter));
+        self.declare(counter.clone(), Shape::Simple(SimpleType::Int), false);
+        let times = 1 + self.rng.below(4);
+        self.line(&format!("while {} < {} do", counter, times));
+        self.line("begin");
+        self.indent += 1;
+        self.line(&format!("{} := {} + 1;", counter, counter));
+        self.scopes.push(vec![]);
+        self.loops += 1;
+        let statements = 1 + self.rng.below(4);
+        self.statements(statements, false);
+        self.loops -= 1;
+        self.scopes.pop();
+        self.indent -= 1;
+        self.line("end");
+    }
+
+    fn repeat_loop(&mut self) {
+        let counter = self.fresh("i");
+        self.line(&format!("var {} : int;", counter));
+        self.line(&format!("{} := 0;", counter));
+        self.declare(counter.clone(), Shape::Simple(SimpleType::Int), false);
+        let times = 1 + self.rng.below(4);
+        self.line("repeat");
+        self.indent += 1;
+        self.line(&format!("{} := {} + 1;", counter, counter));
+        self.scopes.push(vec![]);
+        self.loops += 1;
+        let statements = 1 + self.rng.below(4);
+        self.statements(statements, false);
+        self.loops -= 1;
+        self.scopes.pop();
+        self.indent -= 1;
+        self.line(&format!("until {} >= {};", counter, times));
+    }
+
+    fn for_loop(&mut self) {
+        let counter = self.fresh("i");
+        self.line(&format!("var {} : int;", counter));
+        self.declare(counter.clone(), Shape::Simple(SimpleType::Int), false);
+        let start = self.rng.below(4);
+        let (direction, end) = match self.rng.chance(50) {
+            true => ("to", start + self.rng.below(4)),
+            false => ("downto", start.saturating_sub(self.rng.below(4))),
+        };
+        self.line(&format!(
+            "for {} := {} {} {} do",
+            counter, start, direction, end
+        ));
+        self.line("begin");
+        self.indent += 1;
+        self.scopes.push(vec![]);
+        self.loops += 1;
+        let statements = 1 + self.rng.below(4);
+        self.statements(statements, false);
+        self.loops -= 1;
+        self.scopes.pop();
+        self.indent -= 1;
+        self.line("end");
+    }
+
+    /// Call of a procedure defined before the current subprogram, or
+    /// of the current one if it is recursive
+    fn procedure_call_statement(&mut self) {
+        let mut callable = self.procedures.clone();
+        if let Some(current) = self.current.clone() {
+            if current.r_type.is_none() && self.can_recurse(&current) {
+                callable.push(current);
+            }
+        }
+        if callable.is_empty() {
+            return;
+        }
+        let procedure = self.rng.pick(&callable).clone();
+        let Some(args) = self.arguments(&procedure, 0) else {
+            return;
+        };
+        if self.is_current(&procedure) {
+            self.self_calls += 1;
+        }
+        self.line(&format!("{}({});", procedure.name, args.join(", ")));
+    }
+
+    fn read(&mut self) {
+        let s_type = self
+            .rng
+            .pick(&[SimpleType::Int, SimpleType::Real, SimpleType::String])
+            .clone();
+        let targets = self.places(&Shape::Simple(s_type.clone()), true);
+        if targets.is_empty() {
+            return;
+        }
+        let target = self.rng.pick(&targets).clone();
+        let word = match s_type {
+            SimpleType::Int => self.rng.below(1000).to_string(),
+            SimpleType::Real => self.real_literal(),
+            _ => self.word(),
+        };
+        self.stdin.push_str(&word);
+        self.stdin.push('\n');
+        self.line(&format!("read({});", target));
+    }
+
+    fn word(&mut self) -> String {
+        let letters = ["a", "b", "x", "yz", "Q", "0"];
+        (0..1 + self.rng.below(3))
+            .map(|_| *self.rng.pick(&letters))
+            .collect()
+    }
+
+    fn real_literal(&mut self) -> String {
+        format!("{}.{}", self.rng.below(100), self.rng.below(100))
+    }
+
+    fn literal(&mut self, s_type: &SimpleType) -> String {
+        match s_type {
+            SimpleType::Int => match self.rng.below(10) {
+                0 => "2147483647".to_string(),
+                _ => self.rng.below(100).to_string(),
+            },
+            SimpleType::Real => self.real_literal(),
+            SimpleType::String => {
+                let mut s = self.word();
+                if self.rng.chance(10) {
+                    let suffix = *self.rng.pick(&SUFFIXES);
+                    s.push_str(suffix);
+                }
+                format!("\"{}\"", s)
+            }
+            _ => self.rng.pick(&["true", "false"]).to_string(),
+        }
+    }
+
+    /// Random expression of the given type, together with whether it
+    /// can be used as an operand without parenthesis
+    fn term(&mut self, s_type: &SimpleType, depth: usize) -> (String, bool) {
+        if depth >= MAX_EXPR_DEPTH || self.rng.chance(30) {
+            let candidates = self.places(&Shape::Simple(s_type.clone()), false);
+            let readable = *s_type != SimpleType::String || self.string_reads > 0;
+            if readable && !candidates.is_empty() && self.rng.chance(60) {
+                if *s_type == SimpleType::String {
+                    self.string_reads -= 1;
+                }
+                return (self.rng.pick(&candidates).clone(), true);
+            }
+            return (self.literal(s_type), true);
+        }
+        if self.rng.chance(10) {
+            if let Some(call) = self.function_call(&Shape::Simple(s_type.clone()), depth) {
+                return (call, true);
+            }
+        }
+        let sub = |g: &mut Generator, s_type: &SimpleType| {
+            let (e, atomic) = g.term(s_type, depth + 1);
+            operand(e, atomic)
+        };
+        let expr = match s_type {
+            SimpleType::Int | SimpleType::Real => {
+                let left = sub(self, s_type);
+                match self.rng.below(4) {
+                    0 => format!("{} + {}", left, sub(self, s_type)),
+                    1 => format!("{} - {}", left, sub(self, s_type)),
+                    2 => format!("{} * {}", left, sub(self, s_type)),
+                    // x * x + 1 is never zero, not even after a wrap
+                    // around, and never -1
+                    _ => {
+                        let divisor = sub(self, s_type);
+                        let one = if *s_type == SimpleType::Int {
+                            "1"
+                        } else {
+                            "1.0"
+                        };
+                        format!("{} / ({} * {} + {})", left, divisor, divisor, one)
+                    }
+                }
+            }
+            SimpleType::String => format!("{} + {}", sub(self, s_type), sub(self, s_type)),
+            _ => match self.rng.below(4) {
+                0 => format!("{} and {}", sub(self, s_type), sub(self, s_type)),
+                1 => format!("!{}", sub(self, s_type)),
+                2 => {
+                    let compared = self.random_type();
+                    format!("{} = {}", sub(self, &compared), sub(self, &compared))
+                }
+                _ => {
+                    let compared = self.random_type();
+                    let op = *self.rng.pick(&["<", "<=", ">", ">="]);
+                    format!("{} {} {}", sub(self, &compared), op, sub(self, &compared))
+                }
+            },
+        };
+        (expr, false)
+    }
+
+    fn expression(&mut self, s_type: &SimpleType, depth: usize) -> String {
+        if depth == 0 {
+            self.string_reads = 1;
+        }
+        self.term(s_type, depth).0
+    }
+
+    /// Call of a function giving a value of the shape, as its result
+    /// or as a field of it. Only the functions defined before the
+    /// current subprogram can be called, and the current one if it is
+    /// recursive
+    fn function_call(&mut self, wanted: &Shape, depth: usize) -> Option<String> {
+        let mut callable = self.functions.clone();
+        if let Some(current) = self.current.clone() {
+            if current.r_type.is_some() && self.can_recurse(&current) {
+                callable.push(current);
+            }
+        }
+        let mut calls = vec![];
+        for function in callable {
+            let mut fields = vec![];
+            if let Some(r_type) = &function.r_type {
+                self.paths(String::new(), r_type, wanted, &mut fields);
+            }
+            if let Some(field) = fields.first() {
+                calls.push((function, field.clone()));
+            }
+        }
+        if calls.is_empty() {
+            return None;
+        }
+        let (function, field) = self.rng.pick(&calls).clone();
+        let args = self.arguments(&function, depth + 1)?;
+        if self.is_current(&function) {
+            self.self_calls += 1;
+        }
+        Some(format!("{}({}){}", function.name, args.join(", "), field))
+    }
+}
@@ -0,0 +1,162 @@
+pub mod generator;
+
+use std::{
+    fs,
+    io::Write,
+    path::Path,
+    process::{Command, Stdio},
+};
+
+use crate::{
+    compiler::{runtime_header, CompileOptions, Compiler, RuntimeMode, State, RUNTIME_HEADER},
+    core::{ast::ASTNode, lints::LintLevel},
+    interpreter::{Interpreter, STACK_SIZE},
+    vm::{self, Module, Vm},
+};
+
+/// What running a program looked like from the outside
+#[derive(Debug, PartialEq, Eq)]
+pub struct Outcome {
+    pub stdout: String,
+    pub success: bool,
+}
+
+/// Result of running a program through every backend
+#[derive(Debug)]
+pub enum Verdict {
+    Agree,
+    /// The compiler refused the program, with its diagnostics
+    Rejected(Vec<String>),
+    /// The C compiler refused the translation, with its output
+    Unbuildable(String),
+    Diverge {
+        interpreter: Outcome,
+        vm: Outcome,
+        native: Outcome,
+    },
+}
+
+/// Runs the program with the interpreter
+pub fn interpret(ast: ASTNode, stdin: &str) -> Outcome {
+    let stdin = stdin.to_string();
+    let (stdout, success) = std::thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(move || {
+            let mut input = stdin.as_bytes();
+            let mut output = Vec::new();
+            let success = match ast {
+                ASTNode::Program(program) => Interpreter::new(&mut input, &mut output)
+                    .run(&program)
+                    .is_ok(),
+                _ => false,
+            };
+            (output, success)
+        })
+        .and_then(|thread| {
+            thread
+                .join()
+                .map_err(|_| std::io::Error::other("the interpreter panicked"))
+        })
+        .unwrap_or_else(|e| (e.to_string().into_bytes(), false));
+    Outcome {
+        stdout: String::from_utf8_lossy(&stdout).to_string(),
+        success,
+    }
+}
+
+/// Runs the bytecode in the virtual machine
+pub fn virtual_machine(module: &Module, stdin: &str) -> Outcome {
+    let mut input = stdin.as_bytes();
+    let mut output = Vec::new();
+    let success = Vm::new(module, &mut input, &mut output).run().is_ok();
+    Outcome {
+        stdout: String::from_utf8_lossy(&output).to_string(),
+        success,
+    }
+}
+
+/// Builds the C translation with `cc` in `work` and runs it, signed
+/// overflow is made to wrap around as it does in the interpreter
+pub fn native(c_source: &str, stdin: &str, work: &Path, name: &str) -> Result<Outcome, String> {
+    let c_file = work.join(format!("{}.c", name));
+    let binary = work.join(name);
+    fs::write(&c_file, c_source).map_err(|e| e.to_string())?;
+    let built = Command::new("cc")
+        .args(["-w", "-fwrapv", "-o"])
+        .arg(&binary)
+        .arg(&c_file)
+        .arg("-lm")
+        .output()
+        .map_err(|e| format!("unable to run cc: {}", e))?;
+    if !built.status.success() {
+        return Err(String::from_utf8_lossy(&built.stderr).to_string());
+    }
+    let mut child = Command::new(&binary)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| format!("unable to run {}: {}", binary.display(), e))?;
+    // the program may exit before reading all of it
+    let _ = child
+        .stdin
+        .take()
+        .map(|mut input| input.write_all(stdin.as_bytes()));
+    let output = child.wait_with_output().map_err(|e| e.to_string())?;
+    Ok(Outcome {
+        stdout: String::from_utf8_lossy(&output.stdout).to_string(),
+        success: output.status.success(),
+    })
+}
+
+/// Compiles the program with the given options and runs it through
+/// the interpreter, through the bytecode in the virtual machine and
+/// through the C translation, on the same input. Lints are all turned
+/// off, only errors reject a program
+pub fn check(
+    source: &str,
+    stdin: &str,
+    work: &Path,
+    name: &str,
+    options: &CompileOptions,
+) -> Verdict {
+    let mut compiler = Compiler::with_options(options.clone());
+    let _ = compiler
+        .options
+        .lints
+        .set_by_name("warnings", LintLevel::Allow);
+    let ast = match compiler.analyse(source.to_string()) {
+        Ok(ast) => ast,
+        Err(errs) => return Verdict::Rejected(errs.iter().map(|e| e.to_string()).collect()),
+    };
+    compiler.compile_ast(ast.clone());
+    if let State::Error = compiler.state {
+        return Verdict::Rejected(compiler.c_errors.iter().map(|e| e.to_string()).collect());
+    }
+    let module = match &ast {
+        ASTNode::Program(program) => vm::compile(program),
+        _ => Err(vec![]),
+    };
+    let module = match module {
+        Ok(module) => module,
+        Err(errs) => return Verdict::Rejected(errs.iter().map(|e| e.to_string()).collect()),
+    };
+    compiler.gen_source();
+    if options.runtime == RuntimeMode::Header {
+        if let Err(e) = fs::write(work.join(RUNTIME_HEADER), runtime_header()) {
+            return Verdict::Unbuildable(e.to_string());
+        }
+    }
+
+    let interpreter = interpret(ast, stdin);
+    let vm = virtual_machine(&module, stdin);
+    match native(&compiler.get_source(), stdin, work, name) {
+        Err(e) => Verdict::Unbuildable(e),
+        Ok(native) if native == interpreter && vm == interpreter => Verdict::Agree,
+        Ok(native) => Verdict::Diverge {
+            interpreter,
+            vm,
+            native,
+        },
+    }
+}
@@ -0,0 +1,136 @@
+use crate::{
+    core::{builtins::Builtin, errors::EvaluationError, objects::Object},
+    scanner::position::Position,
+};
+
+use super::{expression::type_error, statement::format_real};
+
+/// State `random` starts from, the same in every backend so that a
+/// program draws the same numbers everywhere
+pub const RANDOM_SEED: u32 = 2463534242;
+
+/// Next number of the xorshift generator behind `random`
+fn next_random(state: &mut u32) -> u32 {
+    *state ^= *state << 13;
+    *state ^= *state >> 17;
+    *state ^= *state << 5;
+    *state
+}
+
+fn int(value: &Object, position: Position) -> Result<i32, EvaluationError> {
+    match value {
+        Object::Int(i) => Ok(*i as i32),
+        _ => Err(type_error(position, "builtin argument is not an int")),
+    }
+}
+
+/// An int argument given to a real parameter is converted
+fn real(value: &Object, position: Position) -> Result<f64, EvaluationError> {
+    match value {
+        Object::Int(i) => Ok(*i as f64),
+        Object::Real(r) => Ok(*r),
+        _ => Err(type_error(position, "builtin argument is not a number")),
+    }
+}
+
+fn string(value: &Object, position: Position) -> Result<&str, EvaluationError> {
+    match value {
+        Object::String(s) => Ok(s),
+        _ => Err(type_error(position, "builtin argument is not a string")),
+    }
+}
+
+fn error(position: Position, description: String) -> EvaluationError {
+    EvaluationError {
+        position,
+        description,
+    }
+}
+
+/// Calls a builtin on the values of its arguments, shared with the
+/// virtual machine. Strings are handled byte by byte, as in C
+pub fn call(
+    builtin: Builtin,
+    args: &[Object],
+    random: &mut u32,
+    position: Position,
+) -> Result<Object, EvaluationError> {
+    let arg = |i: usize| {
+        args.get(i)
+            .ok_or(type_error(position, "missing builtin argument"))
+    };
+    // min, max and abs give a real as soon as one argument is real
+    let reals = args.iter().any(|a| matches!(a, Object::Real(_)));
+    Ok(match builtin {
+        Builtin::Length => Object::Int(string(arg(0)?, position)?.len() as i64),
+        Builtin::Substr => {
+            let bytes = string(arg(0)?, position)?.as_bytes();
+            let start = int(arg(1)?, position)?.clamp(0, bytes.len() as i32) as usize;
+            let count = int(arg(2)?, position)?.clamp(0, (bytes.len() - start) as i32) as usize;
+            Object::String(String::from_utf8_lossy(&bytes[start..start + count]).to_string())
+        }
+        Builtin::Ord => Object::Int(string(arg(0)?, position)?.bytes().next().unwrap_or(0) as i64),
+        Builtin::Chr => match int(arg(0)?, position)? {
+            code @ 1..=127 => Object::String((code as u8 as char).to_string()),
+            code => {
+                return Err(error(
+                    position,
+                    format!("chr({}) is not an ASCII character", code),
+                ))
+            }
+        },
+        Builtin::Abs if reals => Object::Real(real(arg(0)?, position)?.abs()),
+        Builtin::Abs => Object::Int(int(arg(0)?, position)?.wrapping_abs() as i64),
+        Builtin::Sqrt => Object::Real(real(arg(0)?, position)?.sqrt()),
+        Builtin::Trunc | Builtin::Round => {
+            let x = real(arg(0)?, position)?;
+            let whole = match builtin {
+                Builtin::Trunc => x.trunc(),
+                _ => x.round(),
+            };
+            // `as` would saturate, in C the conversion is undefined
+            if !(i32::MIN as f64..=i32::MAX as f64).contains(&whole) {
+                return Err(error(
+                    position,
+                    format!(
+                        "{}({}) does not fit in an int",
+                        builtin.name(),
+                        format_real(x)
+                    ),
+                ));
+            }
+            Object::Int(whole as i64)
+        }
+        Builtin::IntToStr => Object::String(int(arg(0)?, position)?.to_string()),
+        Builtin::StrToInt => {
+            let s = string(arg(0)?, position)?;
+            match s.parse::<i32>() {
+                Ok(i) => Object::Int(i as i64),
+                Err(_) => return Err(error(position, format!("`{}` is not an int", s))),
+            }
+        }
+        Builtin::Min | Builtin::Max if reals => {
+            let (a, b) = (real(arg(0)?, position)?, real(arg(1)?, position)?);
+            Object::Real(match (builtin, a < b) {
+                (Builtin::Min, true) | (Builtin::Max, false) => a,
+                _ => b,
+            })
+        }
+        Builtin::Min | Builtin::Max => {
+            let (a, b) = (int(arg(0)?, position)?, int(arg(1)?, position)?);
+            Object::Int(match (builtin, a < b) {
+                (Builtin::Min, true) | (Builtin::Max, false) => a,
+                _ => b,
+            } as i64)
+        }
+        Builtin::Random => match int(arg(0)?, position)? {
+            bound if bound > 0 => Object::Int((next_random(random) % bound as u32) as i64),
+            bound => {
+                return Err(error(
+                    position,
+                    format!("random({}) needs a positive bound", bound),
+                ))
+            }
+        },
+    })
+}
@@ -0,0 +1,214 @@
+use crate::{
+    core::{
+        ast::{ASTNode, BinaryExprNode, BinaryExprType},
+        builtins::Builtin,
+        errors::EvaluationError,
+        objects::Object,
+    },
+    scanner::position::Position,
+};
+
+use super::{builtins, Interpreter};
+
+pub fn type_error(position: Position, what: &str) -> EvaluationError {
+    EvaluationError {
+        position,
+        description: format!("Type error: {}", what),
+    }
+}
+
+/// Integers behave like the `int` of the C translation, 32 bits
+/// wrapping around on overflow
+fn int_op(
+    op: &BinaryExprType,
+    l: i64,
+    r: i64,
+    position: Position,
+) -> Result<Object, EvaluationError> {
+    let (l, r) = (l as i32, r as i32);
+    let value = match op {
+        BinaryExprType::Addition => l.wrapping_add(r),
+        BinaryExprType::Subtraction => l.wrapping_sub(r),
+        BinaryExprType::Multiplication => l.wrapping_mul(r),
+        BinaryExprType::Division if r == 0 => {
+            return Err(EvaluationError {
+                position,
+                description: "Division by zero".to_string(),
+            })
+        }
+        BinaryExprType::Division => l.wrapping_div(r),
+        _ => return Err(type_error(position, "not an arithmetic operator")),
+    };
+    Ok(Object::Int(value as i64))
+}
+
+fn real_op(
+    op: &BinaryExprType,
+    l: f64,
+    r: f64,
+    position: Position,
+) -> Result<Object, EvaluationError> {
+    Ok(Object::Real(match op {
+        BinaryExprType::Addition => l + r,
+        BinaryExprType::Subtraction => l - r,
+        BinaryExprType::Multiplication => l * r,
+        BinaryExprType::Division => l / r,
+        _ => return Err(type_error(position, "not an arithmetic operator")),
+    }))
+}
+
+/// Compares two values of the same simple type, None if they can not
+/// be compared
+fn compare(l: &Object, r: &Object) -> Option<std::cmp::Ordering> {
+    match (l, r) {
+        (Object::Int(l), Object::Int(r)) => Some(l.cmp(r)),
+        (Object::Real(l), Object::Real(r)) => l.partial_cmp(r),
+        (Object::String(l), Object::String(r)) => Some(l.cmp(r)),
+        (Object::Bool(l), Object::Bool(r)) => Some(l.cmp(r)),
+        _ => None,
+    }
+}
+
+/// Applies a binary operator to two values, shared with the virtual
+/// machine so that both behave the same
+pub fn binary(
+    op: &BinaryExprType,
+    left: Object,
+    right: Object,
+    position: Position,
+) -> Result<Object, EvaluationError> {
+    match op {
+        BinaryExprType::Addition
+        | BinaryExprType::Subtraction
+        | BinaryExprType::Multiplication
+        | BinaryExprType::Division => match (left, right) {
+            (Object::Int(l), Object::Int(r)) => int_op(op, l, r, position),
+            (Object::Real(l), Object::Real(r)) => real_op(op, l, r, position),
+            (Object::String(l), Object::String(r)) if matches!(op, BinaryExprType::Addition) => {
+                Ok(Object::String(l + &r))
+            }
+            _ => Err(type_error(
+                position,
+                "invalid operands for an arithmetic operator",
+            )),
+        },
+        BinaryExprType::LogicAND => match (left, right) {
+            (Object::Bool(l), Object::Bool(r)) => Ok(Object::Bool(l && r)),
+            _ => Err(type_error(position, "`and` between non bool values")),
+        },
+        op => {
+            let ordering = compare(&left, &right)
+                .ok_or(type_error(position, "comparing values of different types"))?;
+            Ok(Object::Bool(match op {
+                BinaryExprType::LogicEQ => ordering.is_eq(),
+                BinaryExprType::LogicGreaterThan => ordering.is_gt(),
+                BinaryExprType::LogicGreaterThanEQ => ordering.is_ge(),
+                BinaryExprType::LogicLessThan => ordering.is_lt(),
+                _ => ordering.is_le(),
+            }))
+        }
+    }
+}
+
+impl<'a> Interpreter<'a> {
+    /// Evaluates an expression, the parser already checked the types
+    /// so a mismatch here is a bug in the parser
+    pub fn eval(&mut self, node: &ASTNode) -> Result<Object, EvaluationError> {
+        match node {
+            ASTNode::Literal(l) => Ok(l.value.clone()),
+            ASTNode::VarName(v) => Ok(self.variable(&v.id.lexeme, v.position)?.clone()),
+            ASTNode::ArrayRef(a) => {
+                let mut indexes = vec![];
+                for index in a.indexes.iter() {
+                    indexes.push(self.eval_index(index)?);
+                }
+                let mut value: &Object = self.variable(&a.array.lexeme, a.position)?;
+                for index in indexes {
+                    value = match value {
+                        Object::Array(elements) => elements.get(index).ok_or(EvaluationError {
+                            position: a.position,
+                            description: format!(
+                                "Index {} out of bounds for array `{}` of length {}",
+                                index,
+                                a.array.lexeme,
+                                elements.len()
+                            ),
+                        })?,
+                        _ => {
+                            return Err(type_error(
+                                a.position,
+                                "indexing something that is not an array",
+                            ))
+                        }
+                    };
+                }
+                Ok(value.clone())
+            }
+            ASTNode::FieldRef(f) => match self.eval(&f.record)? {
+                Object::Record(_, mut fields) if f.index < fields.len() => {
+                    Ok(std::mem::replace(&mut fields[f.index], Object::Int(0)))
+                }
+                _ => Err(type_error(
+                    f.position,
+                    "field of something that is not a record",
+                )),
+            },
+            ASTNode::UnaryExpression(u) => match self.eval(&u.expression)? {
+                Object::Bool(b) => Ok(Object::Bool(!b)),
+                _ => Err(type_error(u.position, "`!` applied to a non bool value")),
+            },
+            ASTNode::BinaryExpression(b) => self.eval_binary(b),
+            ASTNode::FunctionCallStmt(f) if Builtin::from_name(&f.target).is_some() => {
+                let mut args = vec![];
+                for (_, arg) in f.args.iter() {
+                    args.push(self.eval(arg)?);
+                }
+                let builtin = Builtin::from_name(&f.target).expect("a builtin");
+                builtins::call(builtin, &args, &mut self.random, f.position)
+            }
+            ASTNode::FunctionCallStmt(f) => {
+                let function = self
+                    .functions
+                    .get(&f.target.to_lowercase())
+                    .cloned()
+                    .ok_or(EvaluationError {
+                        position: f.position,
+                        description: format!("Unknown function `{}`", f.target),
+                    })?;
+                self.call(
+                    &function.name,
+                    f.position,
+                    &function.args,
+                    &function.block,
+                    &f.args,
+                )?
+                .ok_or(EvaluationError {
+                    position: f.position,
+                    description: format!("Function `{}` ended without a return", f.target),
+                })
+            }
+            other => Err(type_error(
+                other.position(),
+                &format!("{} is not an expression", other),
+            )),
+        }
+    }
+
+    /// Evaluates an array index, negative indexes are out of bounds
+    pub fn eval_index(&mut self, node: &ASTNode) -> Result<usize, EvaluationError> {
+        match self.eval(node)? {
+            Object::Int(i) if i >= 0 => Ok(i as usize),
+            Object::Int(i) => Err(EvaluationError {
+                position: node.position(),
+                description: format!("Negative array index {}", i),
+            }),
+            _ => Err(type_error(node.position(), "array index is not an int")),
+        }
+    }
+
+    fn eval_binary(&mut self, b: &BinaryExprNode) -> Result<Object, EvaluationError> {
+        let left = self.eval(&b.left)?;
+        let right = self.eval(&b.right)?;
+        binary(&b.op_type, left, right, b.position)
+    }
+}
@@ -0,0 +1,277 @@
+pub mod builtins;
+pub mod expression;
+pub mod statement;
+
+use std::{
+    collections::{HashMap, VecDeque},
+    io::{BufRead, Write},
+};
+
+use crate::{
+    core::{
+        ast::{ASTNode, FunctionDeclNode, ProcedureDeclNode, ProgramNode},
+        errors::EvaluationError,
+        objects::Object,
+        symbol_table::{SymbolTable, SymbolType},
+        types::{SimpleType, Type},
+    },
+    scanner::position::Position,
+};
+
+/// Deepest chain of calls before the interpreter gives up with a
+/// stack overflow error
+pub const MAX_DEPTH: usize = 10_000;
+
+/// Stack to give to the thread running the interpreter, deep recursion
+/// in the program means deep recursion in the interpreter
+pub const STACK_SIZE: usize = 1 << 30;
+
+/// What a statement asks the interpreter to do next
+#[derive(Debug)]
+pub enum Flow {
+    Next,
+    Return(Option<Object>),
+    /// Leave the innermost loop
+    Break,
+    /// Go to the next iteration of the innermost loop
+    Continue,
+}
+
+/// Variables of a subprogram activation, one map per nested block
+type Frame = Vec<HashMap<String, Object>>;
+
+/// A running call of a function or procedure
+#[derive(Debug, Clone)]
+pub struct Activation {
+    pub name: String,
+    /// Where it was called from
+    pub position: Position,
+}
+
+/// Observer of the execution (the debugger, the profiler). The
+/// interpreter is handed to it so that it can look at the variables
+/// and evaluate expressions, the hook is not called for what it does
+/// with it
+pub trait Hook {
+    /// Called before every statement, an error stops the program
+    fn statement(
+        &mut self,
+        interpreter: &mut Interpreter<'_>,
+        node: &ASTNode,
+    ) -> Result<(), EvaluationError>;
+
+    /// Called when a subprogram starts, its activation is on top of
+    /// the call stack
+    fn call(&mut self, _interpreter: &mut Interpreter<'_>) {}
+
+    /// Called when a subprogram ends, its activation is still on top
+    /// of the call stack
+    fn ret(&mut self, _interpreter: &mut Interpreter<'_>) {}
+}
+
+/// Tree walking interpreter, executes the AST directly with the same
+/// observable behaviour as the C translation: same output format,
+/// same input parsing. Used when no C compiler is around and as a
+/// reference for the backends
+pub struct Interpreter<'a> {
+    functions: HashMap<String, FunctionDeclNode>,
+    procedures: HashMap<String, ProcedureDeclNode>,
+    frames: Vec<Frame>,
+    /// Global variables, visible from every frame
+    globals: HashMap<String, Object>,
+    input: &'a mut dyn BufRead,
+    output: &'a mut dyn Write,
+    words: VecDeque<String>,
+    calls: Vec<Activation>,
+    hook: Option<&'a mut dyn Hook>,
+    /// State of the generator behind `random`
+    random: u32,
+}
+
+/// Value a variable of the given type holds before any assignment
+pub fn default_value(r_type: &Type, size: Option<usize>) -> Object {
+    let simple = |s: &SimpleType| match s {
+        SimpleType::Int | SimpleType::Void => Object::Int(0),
+        SimpleType::Real => Object::Real(0.0),
+        SimpleType::String => Object::String(String::new()),
+        SimpleType::Bool => Object::Bool(false),
+        SimpleType::Record(r) => Object::Record(
+            r.clone(),
+            r.fields
+                .iter()
+                .map(|(_, t)| default_value(t, None))
+                .collect(),
+        ),
+    };
+    match r_type {
+        Type::Simple(s) => simple(s),
+        Type::Array(e, _) => {
+            Object::Array(vec![default_value(e, e.size()); size.unwrap_or(0)].into_boxed_slice())
+        }
+    }
+}
+
+impl<'a> Interpreter<'a> {
+    pub fn new(input: &'a mut dyn BufRead, output: &'a mut dyn Write) -> Interpreter<'a> {
+        Interpreter {
+            functions: HashMap::new(),
+            procedures: HashMap::new(),
+            frames: vec![vec![HashMap::new()]],
+            globals: HashMap::new(),
+            input,
+            output,
+            words: VecDeque::new(),
+            calls: vec![],
+            hook: None,
+            random: builtins::RANDOM_SEED,
+        }
+    }
+
+    /// Sets the hook called during the execution
+    pub fn set_hook(&mut self, hook: &'a mut dyn Hook) {
+        self.hook = Some(hook);
+    }
+
+    /// Running calls, the innermost last
+    pub fn call_stack(&self) -> &[Activation] {
+        &self.calls
+    }
+
+    /// Variables visible in the running subprogram (or in the main
+    /// block), one map per nested block, the innermost last
+    pub fn scopes(&self) -> &[HashMap<String, Object>] {
+        self.frames.last().map(|f| f.as_slice()).unwrap_or_default()
+    }
+
+    /// Global variables of the program
+    pub fn globals(&self) -> &HashMap<String, Object> {
+        &self.globals
+    }
+
+    /// Flushes what the program wrote so far
+    pub fn flush(&mut self) -> std::io::Result<()> {
+        self.output.flush()
+    }
+
+    /// Runs the hook, if any, with the interpreter
+    fn with_hook<T>(&mut self, f: impl FnOnce(&mut dyn Hook, &mut Self) -> T) -> Option<T> {
+        let hook = self.hook.take()?;
+        let result = f(&mut *hook, self);
+        self.hook = Some(hook);
+        Some(result)
+    }
+
+    /// Runs the main block of the program
+    pub fn run(&mut self, program: &ProgramNode) -> Result<(), EvaluationError> {
+        for f in program.functions.iter() {
+            self.functions.insert(f.name.to_lowercase(), f.clone());
+        }
+        for p in program.procedures.iter() {
+            self.procedures.insert(p.name.to_lowercase(), p.clone());
+        }
+        for g in program.globals.iter() {
+            self.globals.insert(
+                g.id.lexeme.to_lowercase(),
+                default_value(&g.var_type, g.size),
+            );
+        }
+        let result = self.exec(&ASTNode::Block(program.main_block.clone()));
+        let flushed = self.output.flush();
+        result?;
+        flushed.map_err(|e| EvaluationError {
+            position: Position::new(0, 0, 0),
+            description: format!("Unable to write the output: {}", e),
+        })
+    }
+
+    fn frame(&mut self) -> &mut Frame {
+        self.frames
+            .last_mut()
+            .expect("the interpreter always has a frame")
+    }
+
+    fn declare(&mut self, name: &str, value: Object) {
+        if let Some(scope) = self.frame().last_mut() {
+            scope.insert(name.to_lowercase(), value);
+        }
+    }
+
+    fn variable(&mut self, name: &str, position: Position) -> Result<&mut Object, EvaluationError> {
+        let name = name.to_lowercase();
+        self.frames
+            .last_mut()
+            .and_then(|frame| {
+                frame
+                    .iter_mut()
+                    .rev()
+                    .find_map(|scope| scope.get_mut(&name))
+            })
+            .or(self.globals.get_mut(&name))
+            .ok_or(EvaluationError {
+                position,
+                description: format!("Unknown variable `{}`", name),
+            })
+    }
+
+    /// Runs a subprogram with the given arguments, `var` parameters
+    /// and arrays (passed as pointers by the C translation) are copied
+    /// back in the caller variables they came from when the call ends
+    fn call(
+        &mut self,
+        name: &str,
+        position: Position,
+        params: &SymbolTable,
+        block: &ASTNode,
+        args: &[(String, ASTNode)],
+    ) -> Result<Option<Object>, EvaluationError> {
+        if self.frames.len() >= MAX_DEPTH {
+            return Err(EvaluationError {
+                position,
+                description: "Stack overflow, too many nested calls".to_string(),
+            });
+        }
+        let mut scope = HashMap::new();
+        for (param, (_, arg)) in params.iter().zip(args.iter()) {
+            scope.insert(param.name.to_lowercase(), self.eval(arg)?);
+        }
+        // the variables captured by a nested subprogram have no
+        // argument, the caller passes its own
+        for param in params.iter().skip(args.len()) {
+            let value = self.variable(&param.name, position)?.clone();
+            scope.insert(param.name.to_lowercase(), value);
+        }
+        self.frames.push(vec![scope]);
+        self.calls.push(Activation {
+            name: name.to_string(),
+            position,
+        });
+        self.with_hook(|hook, interpreter| hook.call(interpreter));
+        let flow = self.exec(block);
+        self.with_hook(|hook, interpreter| hook.ret(interpreter));
+        self.calls.pop();
+        let mut frame = self.frames.pop().unwrap_or_default();
+        let returned = match flow? {
+            Flow::Return(value) => value,
+            Flow::Next | Flow::Break | Flow::Continue => None,
+        };
+        for (i, param) in params.iter().enumerate() {
+            let by_reference =
+                param.s_type == SymbolType::VarParam || matches!(param.r_type, Type::Array(..));
+            if !by_reference {
+                continue;
+            }
+            let variable = match args.get(i) {
+                Some((_, ASTNode::VarName(v))) => &v.id.lexeme,
+                Some(_) => continue,
+                None => &param.name,
+            };
+            let value = frame
+                .first_mut()
+                .and_then(|scope| scope.remove(&param.name.to_lowercase()));
+            if let Some(value) = value {
+                *self.variable(variable, position)? = value;
+            }
+        }
+        Ok(returned)
+    }
+}
@@ -0,0 +1,413 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    io::BufRead,
+};
+
+use crate::{
+    core::{
+        ast::{ASTNode, ReadStmtNode},
+        errors::EvaluationError,
+        objects::Object,
+        types::{SimpleType, Type},
+    },
+    scanner::position::Position,
+};
+
+use super::{default_value, expression::type_error, Flow, Interpreter};
+
+/// Text of a real that is not a number or is infinite, as `printf`
+/// shows it
+fn non_finite(value: f64) -> String {
+    match value {
+        // glibc shows the sign of NaNs too
+        v if v.is_nan() && v.is_sign_negative() => "-nan".to_string(),
+        v if v.is_nan() => "nan".to_string(),
+        v if v > 0.0 => "inf".to_string(),
+        _ => "-inf".to_string(),
+    }
+}
+
+/// Formats a real with the fewest digits that read back as the same
+/// number, with a point and in scientific notation only below 1e-5
+/// and from 1e15 on. The C translation does the same with `printf`
+pub fn format_real(value: f64) -> String {
+    if !value.is_finite() {
+        return non_finite(value);
+    }
+    let digits = (0..16)
+        .find(|d| format!("{:.*e}", d, value).parse::<f64>() == Ok(value))
+        .unwrap_or(16);
+    let formatted = format!("{:.*e}", digits, value);
+    let (mantissa, exponent) = formatted.split_once('e').unwrap_or((&formatted, "0"));
+    let exponent: i32 = exponent.parse().unwrap_or(0);
+    if (-5..15).contains(&exponent) {
+        let decimals = (digits as i32 - exponent).max(0) as usize;
+        let fixed = format!("{:.*}", decimals, value);
+        return match decimals {
+            0 => fixed + ".0",
+            _ => fixed,
+        };
+    }
+    format!(
+        "{}e{}{:02}",
+        mantissa,
+        if exponent < 0 { '-' } else { '+' },
+        exponent.abs()
+    )
+}
+
+/// Text printed by `write` for the value, as the C translation prints
+/// it: padded to the width with spaces, on the right when the width is
+/// negative, a real with a precision has that many decimals
+pub fn format_value(value: &Object, width: Option<i64>, precision: Option<i64>) -> String {
+    let text = match value {
+        Object::Int(i) => i.to_string(),
+        Object::Real(r) if !r.is_finite() => non_finite(*r),
+        Object::Real(r) => match precision {
+            Some(p) => format!("{:.*}", p.max(0) as usize, r),
+            None => format_real(*r),
+        },
+        Object::String(s) => s.clone(),
+        Object::Bool(b) => b.to_string(),
+        Object::Array(a) => format!(
+            "array of {}",
+            match a.first() {
+                Some(Object::Real(_)) => "reals",
+                Some(Object::String(_)) => "string",
+                Some(Object::Bool(_)) => "bool",
+                Some(Object::Record(..)) => "records",
+                _ => "int",
+            }
+        ),
+        // the parser does not let a whole record be printed
+        Object::Record(..) => value.to_string(),
+    };
+    // as in C the width counts bytes
+    let padding =
+        " ".repeat((width.unwrap_or(0).unsigned_abs() as usize).saturating_sub(text.len()));
+    match width {
+        Some(w) if w < 0 => text + &padding,
+        _ => padding + &text,
+    }
+}
+
+/// Reads the next whitespace separated word from the input, like
+/// `scanf` does, `words` keeps the rest of the line
+pub fn next_word(
+    input: &mut dyn BufRead,
+    words: &mut VecDeque<String>,
+    position: Position,
+) -> Result<String, EvaluationError> {
+    while words.is_empty() {
+        let mut line = String::new();
+        let read = input.read_line(&mut line).map_err(|e| EvaluationError {
+            position,
+            description: format!("Unable to read the input: {}", e),
+        })?;
+        if read == 0 {
+            return Err(EvaluationError {
+                position,
+                description: "Reached the end of the input while reading".to_string(),
+            });
+        }
+        words.extend(line.split_whitespace().map(|w| w.to_string()));
+    }
+    Ok(words.pop_front().unwrap_or_default())
+}
+
+/// Parses a word of the input as a value of the given type
+pub fn parse_word(
+    word: String,
+    r_type: Type,
+    position: Position,
+) -> Result<Object, EvaluationError> {
+    let invalid = |what: &str| EvaluationError {
+        position,
+        description: format!("Expected {} in the input, found `{}`", what, word),
+    };
+    Ok(match r_type {
+        Type::Simple(SimpleType::Int) => {
+            Object::Int(word.parse::<i32>().map_err(|_| invalid("an int"))? as i64)
+        }
+        Type::Simple(SimpleType::Real) => {
+            Object::Real(word.parse::<f64>().map_err(|_| invalid("a real"))?)
+        }
+        Type::Simple(SimpleType::Bool) => match word.as_str() {
+            "true" => Object::Bool(true),
+            "false" => Object::Bool(false),
+            w => Object::Bool(w.parse::<i32>().map_err(|_| invalid("a bool"))? != 0),
+        },
+        Type::Simple(SimpleType::String) => Object::String(word),
+        _ => {
+            return Err(EvaluationError {
+                position,
+                description: "Unable to read into an array".to_string(),
+            })
+        }
+    })
+}
+
+pub fn io_error(position: Position, e: std::io::Error) -> EvaluationError {
+    EvaluationError {
+        position,
+        description: format!("Unable to write the output: {}", e),
+    }
+}
+
+impl<'a> Interpreter<'a> {
+    /// Executes a statement
+    pub fn exec(&mut self, node: &ASTNode) -> Result<Flow, EvaluationError> {
+        if !matches!(node, ASTNode::Block(_) | ASTNode::ElseStmt(_)) {
+            self.with_hook(|hook, interpreter| hook.statement(interpreter, node))
+                .transpose()?;
+        }
+        match node {
+            ASTNode::Block(b) => {
+                self.frame().push(HashMap::new());
+                let mut flow = Ok(Flow::Next);
+                for stmt in b.statements.iter() {
+                    flow = self.exec(stmt);
+                    if !matches!(flow, Ok(Flow::Next)) {
+                        break;
+                    }
+                }
+                self.frame().pop();
+                flow
+            }
+            ASTNode::VariableDecl(v) => {
+                self.declare(&v.id.lexeme, default_value(&v.var_type, v.size));
+                Ok(Flow::Next)
+            }
+            ASTNode::VarReassignment(v) => {
+                let value = self.eval(&v.new_value)?;
+                self.assign(&v.variable_to_reassign, value)?;
+                Ok(Flow::Next)
+            }
+            ASTNode::IfStmt(i) => match self.eval(&i.guard)? {
+                Object::Bool(true) => self.exec(&i.then),
+                _ => match &i.else_stmt {
+                    Some(e) => self.exec(e),
+                    None => Ok(Flow::Next),
+                },
+            },
+            ASTNode::CaseStmt(c) => {
+                let value = self.eval(&c.value)?;
+                let branch = c.branches.iter().find(|b| {
+                    b.labels.iter().any(|l| match (&l.value, &value) {
+                        (Object::Int(a), Object::Int(b)) => a == b,
+                        (Object::String(a), Object::String(b)) => a == b,
+                        _ => false,
+                    })
+                });
+                match (branch, &c.else_stmt) {
+                    (Some(b), _) => self.exec(&b.body),
+                    (None, Some(e)) => self.exec(e),
+                    (None, None) => Ok(Flow::Next),
+                }
+            }
+            ASTNode::ElseStmt(e) => self.exec(&e.block),
+            ASTNode::WhileStmt(w) => {
+                while let Object::Bool(true) = self.eval(&w.guard)? {
+                    match self.exec(&w.block)? {
+                        Flow::Return(value) => return Ok(Flow::Return(value)),
+                        Flow::Break => break,
+                        Flow::Next | Flow::Continue => {}
+                    }
+                }
+                Ok(Flow::Next)
+            }
+            ASTNode::RepeatStmt(r) => loop {
+                match self.exec(&r.block)? {
+                    Flow::Return(value) => return Ok(Flow::Return(value)),
+                    Flow::Break => return Ok(Flow::Next),
+                    Flow::Next | Flow::Continue => {}
+                }
+                if let Object::Bool(true) = self.eval(&r.guard)? {
+                    return Ok(Flow::Next);
+                }
+            },
+            ASTNode::BreakStmt(_) => Ok(Flow::Break),
+            ASTNode::ContinueStmt(_) => Ok(Flow::Continue),
+            ASTNode::ForStmt(l) => {
+                let start = self.eval(&l.start)?;
+                let end = self.eval(&l.end)?;
+                let (mut i, end) = match (start, end) {
+                    (Object::Int(start), Object::Int(end)) => (start, end),
+                    _ => {
+                        return Err(EvaluationError {
+                            position: l.position,
+                            description: "The bounds of a for loop must be ints".to_string(),
+                        })
+                    }
+                };
+                self.assign(&l.variable, Object::Int(i))?;
+                while (!l.downto && i <= end) || (l.downto && i >= end) {
+                    match self.exec(&l.block)? {
+                        Flow::Return(value) => return Ok(Flow::Return(value)),
+                        Flow::Break => break,
+                        Flow::Next | Flow::Continue => {}
+                    }
+                    if i == end {
+                        break;
+                    }
+                    i += if l.downto { -1 } else { 1 };
+                    self.assign(&l.variable, Object::Int(i))?;
+                }
+                Ok(Flow::Next)
+            }
+            ASTNode::PrintStmt(p) => {
+                // each value is printed before the next one is evaluated
+                for arg in p.args.iter() {
+                    let value = self.eval(&arg.value)?;
+                    let width = self.eval_format(&arg.width)?;
+                    let precision = self.eval_format(&arg.precision)?;
+                    let text = format_value(&value, width, precision);
+                    self.output
+                        .write_all(text.as_bytes())
+                        .map_err(|e| io_error(p.position, e))?;
+                }
+                if p.newline {
+                    self.output
+                        .write_all(b"\n")
+                        .map_err(|e| io_error(p.position, e))?;
+                }
+                Ok(Flow::Next)
+            }
+            ASTNode::ReadStmt(r) => {
+                self.read(r)?;
+                Ok(Flow::Next)
+            }
+            ASTNode::AssertStmt(a) => match self.eval(&a.expr)? {
+                Object::Bool(true) => Ok(Flow::Next),
+                _ => Err(EvaluationError {
+                    position: a.position,
+                    description: "Assertion failed".to_string(),
+                }),
+            },
+            ASTNode::ProcedureCallStmt(p) => {
+                let procedure = self
+                    .procedures
+                    .get(&p.target.to_lowercase())
+                    .cloned()
+                    .ok_or(EvaluationError {
+                        position: p.position,
+                        description: format!("Unknown procedure `{}`", p.target),
+                    })?;
+                self.call(
+                    &procedure.name,
+                    p.position,
+                    &procedure.args,
+                    &procedure.block,
+                    &p.args,
+                )?;
+                Ok(Flow::Next)
+            }
+            ASTNode::FunctionCallStmt(_) => {
+                self.eval(node)?;
+                Ok(Flow::Next)
+            }
+            ASTNode::ReturnStmt(r) => Ok(Flow::Return(match &r.value {
+                Some(value) => Some(self.eval(value)?),
+                None => None,
+            })),
+            ASTNode::EofStmt(_) => Ok(Flow::Next),
+            other => Err(EvaluationError {
+                position: other.position(),
+                description: format!("Unable to execute {}", other),
+            }),
+        }
+    }
+
+    /// The variable an assignment stores in, with the fields to follow
+    /// in it (the innermost last). The indexes of an array element are
+    /// evaluated here, once
+    fn place(
+        &mut self,
+        target: &ASTNode,
+        fields: &mut Vec<usize>,
+    ) -> Result<(String, Position, Vec<usize>), EvaluationError> {
+        match target {
+            ASTNode::VarName(v) => Ok((v.id.lexeme.clone(), v.position, vec![])),
+            ASTNode::ArrayRef(a) => {
+                let mut indexes = vec![];
+                for index in a.indexes.iter() {
+                    indexes.push(self.eval_index(index)?);
+                }
+                Ok((a.array.lexeme.clone(), a.position, indexes))
+            }
+            ASTNode::FieldRef(f) => {
+                let place = self.place(&f.record, fields)?;
+                fields.push(f.index);
+                Ok(place)
+            }
+            other => Err(EvaluationError {
+                position: other.position(),
+                description: "Assignment to something that is not a variable".to_string(),
+            }),
+        }
+    }
+
+    /// Stores a value in a variable, in an element of an array or in
+    /// a field of a record
+    fn assign(&mut self, target: &ASTNode, value: Object) -> Result<(), EvaluationError> {
+        let mut fields = vec![];
+        let (name, position, indexes) = self.place(target, &mut fields)?;
+        let mut stored = self.variable(&name, position)?;
+        for index in indexes {
+            stored = match stored {
+                Object::Array(elements) => {
+                    let len = elements.len();
+                    elements.get_mut(index).ok_or(EvaluationError {
+                        position,
+                        description: format!(
+                            "Index {} out of bounds for array `{}` of length {}",
+                            index, name, len
+                        ),
+                    })?
+                }
+                _ => {
+                    return Err(EvaluationError {
+                        position,
+                        description: format!("`{}` is not an array", name),
+                    })
+                }
+            };
+        }
+        for field in fields {
+            stored = match stored {
+                Object::Record(_, values) if field < values.len() => &mut values[field],
+                _ => {
+                    return Err(type_error(
+                        position,
+                        "field of something that is not a record",
+                    ))
+                }
+            };
+        }
+        *stored = value;
+        Ok(())
+    }
+
+    /// Reads a word of the input into the target, parsed according to
+    /// the type of the target
+    fn read(&mut self, node: &ReadStmtNode) -> Result<(), EvaluationError> {
+        let target = node.variable_to_read_in.as_ref();
+        let word = next_word(self.input, &mut self.words, node.position)?;
+        let value = parse_word(word, target.r_type(), node.position)?;
+        self.assign(target, value)
+    }
+
+    /// Value of the width or the precision of a printed value
+    fn eval_format(&mut self, format: &Option<ASTNode>) -> Result<Option<i64>, EvaluationError> {
+        match format {
+            Some(f) => match self.eval(f)? {
+                Object::Int(i) => Ok(Some(i)),
+                _ => Err(type_error(
+                    f.position(),
+                    "the width of a value is not an int",
+                )),
+            },
+            None => Ok(None),
+        }
+    }
+}
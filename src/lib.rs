@@ -0,0 +1,159 @@
+//! Compiler for the MiniPascal language, from source to C.
+//!
+//! The stable API is what is re-exported here: the [`Scanner`], the
+//! [`Parser`] and the AST it builds, the [`SymbolTable`], the
+//! [`Compiler`], the [`Interpreter`] and the error types, together
+//! with the convenience functions [`parse_str`], [`check_str`] and
+//! [`compile_str`]. The modules are public for the tools built on
+//! top of the compiler, but their content may change between versions.
+//! Every pass recurses on the tree: programs with long expressions
+//! need a thread with a stack of [`interpreter::STACK_SIZE`], as the
+//! `miniplc` binary uses
+//!
+//! ```no_run
+//! let output = miniplc::compile_str(
+//!     "program hello; begin writeln(\"hi\"); end",
+//!     &miniplc::CompileOptions::default(),
+//! );
+//! match output {
+//!     Ok(output) => print!("{}", output.c_source),
+//!     Err(diagnostics) => eprint!("{}", diagnostics),
+//! }
+//! ```
+
+pub mod analysis;
+pub mod compiler;
+pub mod core;
+pub mod debugger;
+pub mod difftest;
+pub mod interpreter;
+pub mod parser;
+pub mod profiler;
+pub mod scanner;
+mod tests;
+pub mod vm;
+
+use std::fmt;
+
+pub use crate::compiler::{
+    source_map_json, AssertMode, CompileOptions, Compiler, Dialect, LineMapping, OptLevel,
+    RuntimeMode, State, RUNTIME_HEADER,
+};
+pub use crate::core::ast::{ASTNode, ProgramNode};
+pub use crate::core::errors::{
+    CompilationError, EvaluationError, MiniPLError, SyntaxError, Warning,
+};
+pub use crate::core::lints::{Lint, LintConfig, LintLevel};
+pub use crate::core::symbol_table::SymbolTable;
+pub use crate::interpreter::Interpreter;
+pub use crate::parser::Parser;
+pub use crate::scanner::Scanner;
+
+/// A program that went through all the checks
+#[derive(Debug, Clone)]
+pub struct Checked {
+    pub program: ProgramNode,
+    pub warnings: Vec<Warning>,
+}
+
+/// Result of a successful compilation
+#[derive(Debug, Clone)]
+pub struct Output {
+    /// The C translation of the program
+    pub c_source: String,
+    /// Content of `RUNTIME_HEADER`, if the translation includes it
+    pub runtime_header: Option<String>,
+    /// Lines of `c_source` with the position in the source they come
+    /// from, `source_map_json` gives it as JSON
+    pub source_map: Vec<LineMapping>,
+    pub warnings: Vec<Warning>,
+}
+
+/// Everything that went wrong with a program, the warnings are
+/// included since the denied lints are among them
+#[derive(Debug, Clone, Default)]
+pub struct Diagnostics {
+    pub syntax: Vec<SyntaxError>,
+    pub compilation: Vec<CompilationError>,
+    pub warnings: Vec<Warning>,
+}
+
+impl fmt::Display for Diagnostics {
+    /// Warnings first, then the errors, one per line
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for warning in self.warnings.iter() {
+            writeln!(f, "{}", warning)?;
+        }
+        for err in self.syntax.iter() {
+            writeln!(f, "{}", err)?;
+        }
+        for err in self.compilation.iter() {
+            writeln!(f, "{}", err)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Diagnostics {}
+
+/// Parses the source, without any check other than the syntactic ones
+pub fn parse_str(src: &str) -> Result<ProgramNode, Vec<SyntaxError>> {
+    match Parser::new(src.to_string()).parse()? {
+        ASTNode::Program(program) => Ok(program),
+        _ => unreachable!("the parser always returns a program"),
+    }
+}
+
+/// Parses the source and runs every check on it (returns of the
+/// subprograms and lints) without generating any code
+pub fn check_str(src: &str, options: &CompileOptions) -> Result<Checked, Diagnostics> {
+    let mut compiler = Compiler::with_options(options.clone());
+    let (ast, compiler) = analyse(src, &mut compiler)?;
+    match ast {
+        ASTNode::Program(program) => Ok(Checked {
+            program,
+            warnings: compiler.warnings.clone(),
+        }),
+        _ => unreachable!("the parser always returns a program"),
+    }
+}
+
+/// Checks the source and translates it to C
+pub fn compile_str(src: &str, options: &CompileOptions) -> Result<Output, Diagnostics> {
+    let mut compiler = Compiler::with_options(options.clone());
+    let (ast, compiler) = analyse(src, &mut compiler)?;
+    compiler.compile_ast(ast);
+    if let State::Error = compiler.state {
+        return Err(diagnostics(compiler, vec![]));
+    }
+    compiler.gen_source();
+    Ok(Output {
+        c_source: compiler.get_source(),
+        runtime_header: match options.runtime {
+            RuntimeMode::Inline => None,
+            RuntimeMode::Header => Some(compiler::runtime_header()),
+        },
+        source_map: compiler.source_map().to_vec(),
+        warnings: compiler.warnings.clone(),
+    })
+}
+
+/// Runs `Compiler::analyse`, turning every failure into diagnostics
+fn analyse<'a>(
+    src: &str,
+    compiler: &'a mut Compiler,
+) -> Result<(ASTNode, &'a mut Compiler), Diagnostics> {
+    match compiler.analyse(src.to_string()) {
+        Ok(ast) if matches!(compiler.state, State::Sane) => Ok((ast, compiler)),
+        Ok(_) => Err(diagnostics(compiler, vec![])),
+        Err(errs) => Err(diagnostics(compiler, errs)),
+    }
+}
+
+fn diagnostics(compiler: &Compiler, syntax: Vec<SyntaxError>) -> Diagnostics {
+    Diagnostics {
+        syntax,
+        compilation: compiler.c_errors.clone(),
+        warnings: compiler.warnings.clone(),
+    }
+}
@@ -1,33 +1,627 @@
-mod compiler;
-mod core;
-mod parser;
-mod scanner;
-
-use clap::Parser;
-use compiler::Compiler;
-use std::fs::File;
+use clap::{ArgEnum, CommandFactory, ErrorKind, Parser, Subcommand};
+use miniplc::{
+    analysis::cfg::{self, Cfg},
+    core::{dump, token::Kind},
+    debugger::Debugger,
+    difftest::{self, generator::Generator, Outcome, Verdict},
+    interpreter::STACK_SIZE,
+    profiler::Profiler,
+    vm::{self, Vm},
+    ASTNode, AssertMode, CompileOptions, Dialect, Interpreter, LintLevel, OptLevel, RuntimeMode,
+    Scanner, RUNTIME_HEADER,
+};
+use std::fs::{self, File};
 use std::io::Error;
-use std::io::Write;
+use std::io::{self, BufWriter, Write};
 
 /// Compiler for the MiniPascal language
 #[derive(Parser, Debug)]
 #[clap(author, version, about, long_about = None)]
 struct Args {
+    #[clap(subcommand)]
+    command: Option<Command>,
+
     /// File to compile
     #[clap(short, long)]
-    file: String,
+    file: Option<String>,
 
     /// Output file name
     #[clap(short, long, default_value = "out.c")]
     output: String,
 
     /// Verbosity of the application
-    #[clap(short, parse(from_occurrences))]
+    #[clap(short, global = true, parse(from_occurrences))]
     verbose: usize,
+
+    /// Lints to ignore (`warnings` selects all of them)
+    #[clap(short = 'A', long = "allow", value_name = "LINT", global = true)]
+    allow: Vec<String>,
+
+    /// Lints to report as warnings
+    #[clap(short = 'W', long = "warn", value_name = "LINT", global = true)]
+    warn: Vec<String>,
+
+    /// Lints to report as errors, failing the compilation
+    #[clap(short = 'D', long = "deny", value_name = "LINT", global = true)]
+    deny: Vec<String>,
+
+    /// Flavour of C to generate
+    #[clap(long, default_value = "gnu", possible_values = ["gnu", "c99"])]
+    dialect: Dialect,
+
+    /// Check every array index against the length of the array
+    #[clap(long)]
+    bounds_checks: bool,
+
+    /// What a failed assert does
+    #[clap(long, default_value = "abort", possible_values = ["abort", "report", "off"])]
+    asserts: AssertMode,
+
+    /// Optimisation level, 1 computes the constant expressions
+    #[clap(short = 'O', long = "opt-level", default_value = "0", possible_values = ["0", "1"])]
+    opt_level: OptLevel,
+
+    /// Where the runtime helpers go, `header` writes them in
+    /// `miniplc_runtime.h` next to the output
+    #[clap(long, default_value = "inline", possible_values = ["inline", "header"])]
+    runtime: RuntimeMode,
+
+    /// Emit `#line` directives pointing at the MiniPascal source
+    #[clap(long)]
+    line_directives: bool,
+
+    /// Write a JSON map from the lines of the output to the positions
+    /// in the source
+    #[clap(long, value_name = "FILE")]
+    source_map: Option<String>,
+
+    /// Size of the buffers strings are read in
+    #[clap(long, value_name = "BYTES", default_value = "128")]
+    string_buffer: usize,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Print the control flow graph of the main block and of every
+    /// function and procedure
+    Cfg {
+        /// Output format
+        #[clap(long, arg_enum, default_value = "dot")]
+        format: CfgFormat,
+
+        /// Source file
+        file: String,
+    },
+    /// Print the abstract syntax tree, with types, positions and
+    /// symbol kinds
+    Ast {
+        /// Output format
+        #[clap(long, arg_enum, default_value = "json")]
+        format: AstFormat,
+
+        /// Source file
+        file: String,
+    },
+    /// Check the program and run it with the interpreter, without
+    /// going through C
+    Run {
+        /// Run the bytecode in the virtual machine instead of walking
+        /// the AST
+        #[clap(long)]
+        vm: bool,
+
+        /// Count the statements run on every line and in every
+        /// function and procedure, and the time spent on them
+        #[clap(long, conflicts_with = "vm")]
+        profile: bool,
+
+        /// Format of the profile, `collapsed` is the input of
+        /// flamegraph tools
+        #[clap(long, arg_enum, default_value = "text", requires = "profile")]
+        profile_format: ProfileFormat,
+
+        /// Where the profile goes, stderr by default
+        #[clap(long, value_name = "FILE", requires = "profile")]
+        profile_output: Option<String>,
+
+        /// Source file
+        file: String,
+    },
+    /// Check the program and compile it to bytecode for `exec`
+    Bytecode {
+        /// Output file name
+        #[clap(short, long, default_value = "out.mplc")]
+        output: String,
+
+        /// Print a listing of the instructions instead
+        #[clap(long)]
+        listing: bool,
+
+        /// Source file
+        file: String,
+    },
+    /// Run a bytecode file written by `bytecode` in the virtual
+    /// machine
+    Exec {
+        /// Bytecode file
+        file: String,
+    },
+    /// Run the program in the interpreter under an interactive
+    /// debugger, `help` lists its commands
+    Debug {
+        /// Input of the program, by default it shares the standard
+        /// input with the commands
+        #[clap(long)]
+        input: Option<String>,
+
+        /// Source file
+        file: String,
+    },
+    /// Print the tokens produced by the scanner, without parsing
+    Tokens {
+        /// Output format
+        #[clap(long, arg_enum, default_value = "text")]
+        format: TokensFormat,
+
+        /// Include whitespaces, line ends and comments
+        #[clap(long)]
+        trivia: bool,
+
+        /// Source file
+        file: String,
+    },
+    /// Run programs through the interpreter and through the C
+    /// translation (built with `cc`) and report where they disagree.
+    /// Without files, random programs are generated
+    Difftest {
+        /// Seed of the first generated program
+        #[clap(long, default_value = "0")]
+        seed: u64,
+
+        /// Number of programs to generate
+        #[clap(long, default_value = "100")]
+        count: u64,
+
+        /// Directory where the failing programs are kept, with their
+        /// input, they are only reported without it
+        #[clap(long)]
+        keep: Option<String>,
+
+        /// Print the programs instead of checking them
+        #[clap(long)]
+        print: bool,
+
+        /// Programs to check instead of generated ones, the input
+        /// comes from the `.stdin` file next to each of them
+        files: Vec<String>,
+    },
+}
+
+#[derive(ArgEnum, Clone, Debug)]
+enum CfgFormat {
+    Dot,
+    Text,
+}
+
+#[derive(ArgEnum, Clone, Debug)]
+enum TokensFormat {
+    Text,
+    Json,
+}
+
+#[derive(ArgEnum, Clone, Debug)]
+enum ProfileFormat {
+    Text,
+    Json,
+    Collapsed,
+}
+
+#[derive(ArgEnum, Clone, Debug)]
+enum AstFormat {
+    Json,
+    Sexpr,
+    Dot,
+}
+
+/// Parses the given file, printing the syntax errors and exiting if
+/// there are any
+#[cfg(not(tarpaulin_include))]
+fn parse_file(file: &str) -> Result<miniplc::ProgramNode, Error> {
+    match miniplc::parse_str(&fs::read_to_string(file)?) {
+        Ok(program) => Ok(program),
+        Err(errs) => {
+            for err in errs {
+                eprintln!("{}", err);
+            }
+            std::process::exit(1);
+        }
+    }
+}
+
+#[cfg(not(tarpaulin_include))]
+fn print_cfg(format: CfgFormat, file: &str) -> Result<(), Error> {
+    let graphs = Cfg::build_program(&parse_file(file)?);
+    match format {
+        CfgFormat::Dot => print!("{}", cfg::to_dot(&graphs)),
+        CfgFormat::Text => graphs.iter().for_each(|g| print!("{}", g.to_text())),
+    }
+    Ok(())
+}
+
+#[cfg(not(tarpaulin_include))]
+fn print_ast(format: AstFormat, file: &str) -> Result<(), Error> {
+    let tree = dump::to_json(&ASTNode::Program(parse_file(file)?));
+    match format {
+        AstFormat::Json => println!("{:#}", tree),
+        AstFormat::Sexpr => print!("{}", dump::to_sexpr(&tree)),
+        AstFormat::Dot => print!("{}", dump::to_dot(&tree)),
+    }
+    Ok(())
+}
+
+/// Scans the whole file, errors are reported where they happen and
+/// the scan goes on, the exit status is 1 if there were any
+#[cfg(not(tarpaulin_include))]
+fn print_tokens(format: TokensFormat, trivia: bool, file: &str) -> Result<(), Error> {
+    let mut scanner = Scanner::new(fs::read_to_string(file)?);
+    let mut entries = Vec::new();
+    let mut failed = false;
+    loop {
+        let next = if trivia {
+            scanner.next_token_with_trivia()
+        } else {
+            scanner.next_token()
+        };
+        let at_end = matches!(&next, Ok(t) if t.kind == Kind::Eof);
+        match format {
+            TokensFormat::Json => entries.push(match &next {
+                Ok(token) => dump::token(token),
+                Err(err) => dump::scan_error(err),
+            }),
+            TokensFormat::Text => match &next {
+                Ok(token) => println!(
+                    "{:<8} {:<14} {:?}",
+                    token.position.to_string(),
+                    format!("{:?}", token.kind),
+                    token.lexeme
+                ),
+                Err(err) => println!(
+                    "{:<8} {:<14} {}",
+                    err.position.to_string(),
+                    "error",
+                    err.description
+                ),
+            },
+        }
+        failed |= next.is_err();
+        if at_end {
+            break;
+        }
+    }
+    if let TokensFormat::Json = format {
+        println!("{:#}", serde_json::Value::Array(entries));
+    }
+    if failed {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+/// Checks the program, printing the diagnostics on stderr and exiting
+/// if there are errors
+#[cfg(not(tarpaulin_include))]
+fn checked(options: &CompileOptions, file: &str) -> Result<miniplc::ProgramNode, Error> {
+    match miniplc::check_str(&fs::read_to_string(file)?, options) {
+        Ok(checked) => {
+            checked.warnings.iter().for_each(|w| eprintln!("{}", w));
+            Ok(checked.program)
+        }
+        Err(diagnostics) => {
+            eprint!("{}", diagnostics);
+            std::process::exit(1);
+        }
+    }
 }
 
+/// Prints the errors on stderr and exits
+#[cfg(not(tarpaulin_include))]
+fn exit_with<E: std::fmt::Display, T>(errs: Vec<E>) -> T {
+    errs.iter().for_each(|e| eprintln!("{}", e));
+    std::process::exit(1)
+}
+
+/// Runs the program with the interpreter, diagnostics and runtime
+/// errors go to stderr so that stdout only has the program output
+#[cfg(not(tarpaulin_include))]
+fn run(options: &CompileOptions, file: &str) -> Result<(), Error> {
+    let program = checked(options, file)?;
+    let stdin = io::stdin();
+    let mut input = stdin.lock();
+    let mut output = BufWriter::new(io::stdout());
+    let result = Interpreter::new(&mut input, &mut output).run(&program);
+    if let Err(err) = result {
+        eprint!("{}", err);
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+/// Runs the program with the interpreter under the profiler, the
+/// profile is written at the end even if the program fails
+#[cfg(not(tarpaulin_include))]
+fn profile(
+    options: &CompileOptions,
+    file: &str,
+    format: ProfileFormat,
+    output: Option<String>,
+) -> Result<(), Error> {
+    let source = fs::read_to_string(file)?;
+    let program = checked(options, file)?;
+    let stdin = io::stdin();
+    let mut input = stdin.lock();
+    let mut out = BufWriter::new(io::stdout());
+    let mut profiler = Profiler::new(&program, &source);
+    let result = profiler.run(&program, &mut input, &mut out);
+    let report = match format {
+        ProfileFormat::Text => profiler.report(),
+        ProfileFormat::Json => format!("{:#}\n", profiler.to_json()),
+        ProfileFormat::Collapsed => profiler.collapsed(),
+    };
+    drop(out);
+    match output {
+        Some(path) => fs::write(path, report)?,
+        None => eprint!("{}", report),
+    }
+    if let Err(err) = result {
+        eprint!("{}", err);
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+/// Runs the bytecode in the virtual machine, like `run` does with the
+/// interpreter
+#[cfg(not(tarpaulin_include))]
+fn exec(module: &vm::Module) -> Result<(), Error> {
+    let stdin = io::stdin();
+    let mut input = stdin.lock();
+    let mut output = BufWriter::new(io::stdout());
+    if let Err(err) = Vm::new(module, &mut input, &mut output).run() {
+        eprint!("{}", err);
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+/// Compiles the program to bytecode, in `output` or as a listing on
+/// stdout
+#[cfg(not(tarpaulin_include))]
+fn bytecode(
+    options: &CompileOptions,
+    file: &str,
+    output: &str,
+    listing: bool,
+) -> Result<(), Error> {
+    let module = vm::compile(&checked(options, file)?).unwrap_or_else(exit_with);
+    if listing {
+        print!("{}", module);
+        return Ok(());
+    }
+    let mut out = BufWriter::new(File::create(output)?);
+    module.write(&mut out)?;
+    out.flush()
+}
+
+/// Reads the standard input a line at a time, so that the debugger
+/// commands and the input of the program can share it
+struct StdinLines {
+    line: Vec<u8>,
+    read: usize,
+}
+
+impl io::Read for StdinLines {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = io::BufRead::fill_buf(self)?;
+        let n = available.len().min(buf.len());
+        buf[..n].copy_from_slice(&available[..n]);
+        io::BufRead::consume(self, n);
+        Ok(n)
+    }
+}
+
+impl io::BufRead for StdinLines {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.read == self.line.len() {
+            let mut line = String::new();
+            io::stdin().read_line(&mut line)?;
+            self.line = line.into_bytes();
+            self.read = 0;
+        }
+        Ok(&self.line[self.read..])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.read = (self.read + amt).min(self.line.len());
+    }
+}
+
+/// Runs the program under the debugger, with the commands from the
+/// standard input
+#[cfg(not(tarpaulin_include))]
+fn debug(options: &CompileOptions, file: &str, input: Option<String>) -> Result<(), Error> {
+    let source = fs::read_to_string(file)?;
+    let program = match miniplc::check_str(&source, options) {
+        Ok(checked) => {
+            checked.warnings.iter().for_each(|w| eprintln!("{}", w));
+            checked.program
+        }
+        Err(diagnostics) => {
+            eprint!("{}", diagnostics);
+            std::process::exit(1);
+        }
+    };
+    let input = match input {
+        Some(path) => Some(fs::read_to_string(path)?),
+        None => None,
+    };
+    let new_line = || StdinLines {
+        line: vec![],
+        read: 0,
+    };
+    let (mut commands, mut shared) = (new_line(), new_line());
+    let mut file = input.as_deref().map(str::as_bytes);
+    let input: &mut dyn io::BufRead = match file.as_mut() {
+        Some(file) => file,
+        None => &mut shared,
+    };
+    let mut out = io::stdout();
+    let mut output = io::stdout();
+    let result =
+        Debugger::new(&program, &source, &mut commands, &mut out).run(&program, input, &mut output);
+    if let Err(err) = result {
+        eprint!("{}", err);
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+/// What a backend printed in a difftest report, with its exit status
+#[cfg(not(tarpaulin_include))]
+fn outcome(backend: &str, outcome: &Outcome) -> String {
+    format!(
+        "--- {} ({})\n{}",
+        backend,
+        if outcome.success {
+            "success"
+        } else {
+            "failure"
+        },
+        outcome.stdout
+    )
+}
+
+/// Checks the given programs, or the generated ones, with
+/// `difftest::check`. Failing programs are written in `keep`, if
+/// given, with their input, the exit status is 1 if there were any
+#[cfg(not(tarpaulin_include))]
+fn difftest(
+    seed: u64,
+    count: u64,
+    keep: Option<&str>,
+    print: bool,
+    files: Vec<String>,
+    options: &CompileOptions,
+) -> Result<(), Error> {
+    let work = std::env::temp_dir().join(format!("miniplc-difftest-{}", std::process::id()));
+    fs::create_dir_all(&work)?;
+    let programs: Vec<(String, String, String)> = if files.is_empty() {
+        (seed..seed.saturating_add(count))
+            .map(|seed| {
+                let generated = Generator::program(seed);
+                (format!("seed{}", seed), generated.source, generated.stdin)
+            })
+            .collect()
+    } else {
+        files
+            .iter()
+            .map(|file| {
+                let path = std::path::Path::new(file);
+                let name = path
+                    .file_stem()
+                    .map(|s| s.to_string_lossy().to_string())
+                    .unwrap_or_default();
+                let stdin = fs::read_to_string(path.with_extension("stdin")).unwrap_or_default();
+                Ok((name, fs::read_to_string(path)?, stdin))
+            })
+            .collect::<Result<_, Error>>()?
+    };
+
+    if print {
+        for (name, source, stdin) in programs.iter() {
+            println!("{{* {} *}}\n{}{{* input: {:?} *}}\n", name, source, stdin);
+        }
+        return Ok(());
+    }
+
+    let mut failures = 0;
+    for (name, source, stdin) in programs.iter() {
+        let report = match difftest::check(source, stdin, &work, name, options) {
+            Verdict::Agree => continue,
+            Verdict::Rejected(errs) => format!("rejected by the compiler\n{}", errs.join("\n")),
+            Verdict::Unbuildable(err) => format!("the C translation does not build\n{}", err),
+            Verdict::Diverge {
+                interpreter,
+                vm,
+                native,
+            } => format!(
+                "the backends disagree\n{}{}{}",
+                outcome("interpreter", &interpreter),
+                outcome("virtual machine", &vm),
+                outcome("C", &native)
+            ),
+        };
+        failures += 1;
+        println!("{}: {}", name, report);
+        if let Some(keep) = keep.map(std::path::Path::new) {
+            fs::create_dir_all(keep)?;
+            fs::write(keep.join(format!("{}.mpl", name)), source)?;
+            if !stdin.is_empty() {
+                fs::write(keep.join(format!("{}.stdin", name)), stdin)?;
+            }
+        }
+    }
+    let _ = fs::remove_dir_all(&work);
+    println!("{} programs, {} failures", programs.len(), failures);
+    if failures > 0 {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+/// Options with the lint levels and the code generation given on the
+/// command line
+#[cfg(not(tarpaulin_include))]
+fn configured_options(args: &Args) -> CompileOptions {
+    let mut options = CompileOptions {
+        dialect: args.dialect,
+        bounds_checks: args.bounds_checks,
+        asserts: args.asserts,
+        opt_level: args.opt_level,
+        runtime: args.runtime,
+        line_directives: args.line_directives,
+        source_name: args.file.clone().unwrap_or_default(),
+        string_buffer: args.string_buffer,
+        ..CompileOptions::default()
+    };
+    for (names, level) in [
+        (&args.allow, LintLevel::Allow),
+        (&args.warn, LintLevel::Warn),
+        (&args.deny, LintLevel::Deny),
+    ] {
+        for name in names {
+            if let Err(msg) = options.lints.set_by_name(name, level) {
+                eprintln!("{}", msg);
+                std::process::exit(2);
+            }
+        }
+    }
+    options
+}
+
+/// The parser, the checks, the backends and the interpreter all
+/// recurse on the tree, a long expression or a deep recursion in the
+/// program needs more than the stack of the main thread
 #[cfg(not(tarpaulin_include))]
 fn main() -> Result<(), Error> {
+    std::thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(compile)?
+        .join()
+        .expect("the compiler panicked")
+}
+
+#[cfg(not(tarpaulin_include))]
+fn compile() -> Result<(), Error> {
     let args = Args::parse();
 
     stderrlog::new()
@@ -36,23 +630,72 @@ fn main() -> Result<(), Error> {
         .init()
         .unwrap();
 
-    use compiler::State;
-
-    let mut compiler = Compiler::new();
-    let source = compiler.compile_file(args.file)?;
-    match compiler.state {
-        State::Sane => {
-            let mut output = File::create(args.output)?;
-            write!(output, "{}", source)?;
+    let options = configured_options(&args);
+    let file = match args.command {
+        Some(Command::Run { vm: true, file, .. }) => {
+            return exec(&vm::compile(&checked(&options, &file)?).unwrap_or_else(exit_with))
         }
-        State::Error => {
-            for synerr in compiler.s_errors {
-                println!("{}", synerr);
+        Some(Command::Run {
+            profile: true,
+            profile_format,
+            profile_output,
+            file,
+            ..
+        }) => return profile(&options, &file, profile_format, profile_output),
+        Some(Command::Run { file, .. }) => return run(&options, &file),
+        Some(Command::Bytecode {
+            output,
+            listing,
+            file,
+        }) => return bytecode(&options, &file, &output, listing),
+        Some(Command::Exec { file }) => {
+            let module = vm::Module::read(&mut io::BufReader::new(File::open(&file)?))
+                .unwrap_or_else(|e| exit_with(vec![format!("{}: {}", file, e)]));
+            return exec(&module);
+        }
+        Some(Command::Debug { input, file }) => return debug(&options, &file, input),
+        Some(Command::Difftest {
+            seed,
+            count,
+            keep,
+            print,
+            files,
+        }) => return difftest(seed, count, keep.as_deref(), print, files, &options),
+        Some(Command::Cfg { format, file }) => return print_cfg(format, &file),
+        Some(Command::Ast { format, file }) => return print_ast(format, &file),
+        Some(Command::Tokens {
+            format,
+            trivia,
+            file,
+        }) => return print_tokens(format, trivia, &file),
+        None => match args.file {
+            Some(file) => file,
+            None => Args::command()
+                .error(
+                    ErrorKind::MissingRequiredArgument,
+                    "a file to compile (--file) or a subcommand is required",
+                )
+                .exit(),
+        },
+    };
+
+    match miniplc::compile_str(&fs::read_to_string(file)?, &options) {
+        Ok(output) => {
+            output.warnings.iter().for_each(|w| eprintln!("{}", w));
+            let mut out = File::create(&args.output)?;
+            write!(out, "{}", output.c_source)?;
+            if let Some(header) = output.runtime_header {
+                let dir = std::path::Path::new(&args.output)
+                    .parent()
+                    .unwrap_or_else(|| std::path::Path::new(""));
+                fs::write(dir.join(RUNTIME_HEADER), header)?;
             }
-            for cerr in compiler.c_errors {
-                println!("{}", cerr);
+            if let Some(path) = &args.source_map {
+                let map = miniplc::source_map_json(&options.source_name, &output.source_map);
+                fs::write(path, format!("{:#}\n", map))?;
             }
         }
+        Err(diagnostics) => eprint!("{}", diagnostics),
     }
 
     Ok(())
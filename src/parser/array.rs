@@ -1,11 +1,14 @@
 use log::trace;
 
-use crate::core::{
-    ast::{ASTNode, LiteralExprNode},
-    errors::SyntaxError,
-    objects::Object,
-    token::Kind,
-    types::{SimpleType, Type},
+use crate::{
+    core::{
+        ast::{ASTNode, ArrayRefExpr, LiteralExprNode},
+        errors::SyntaxError,
+        objects::Object,
+        token::{Kind, Token},
+        types::{SimpleType, Type},
+    },
+    current_with_expected,
 };
 
 use super::Parser;
@@ -31,9 +34,9 @@ impl Parser {
             }
             for sym in symbols.iter() {
                 match sym {
-                    ASTNode::Literal(l) => match l.r_type {
+                    ASTNode::Literal(l) => match &l.r_type {
                         Type::Simple(t) => {
-                            if t == r_type {
+                            if *t == r_type {
                                 to_return.push(l.clone().value);
                             } else {
                                 errors.push(self.error_at_current(
@@ -56,11 +59,49 @@ impl Parser {
         if errors.is_empty() {
             Ok(ASTNode::Literal(LiteralExprNode {
                 position: initial.position,
+                r_type: Type::Array(Box::new(Type::Simple(r_type)), Some(to_return.len())),
                 value: Object::Array(to_return.into_boxed_slice()),
-                r_type: Type::Array(r_type),
             }))
         } else {
             Err(errors)
         }
     }
+
+    /// Parses the indexes of an element of the array `id`, `[<expr>]`
+    /// for each dimension it goes through. The current token is the
+    /// first `[` and is left on the last `]`
+    pub fn parse_indexes(
+        &mut self,
+        id: Token,
+        array_type: Type,
+    ) -> Result<ASTNode, Vec<SyntaxError>> {
+        trace!("parsing array indexes");
+        let mut indexes = vec![];
+        let mut r_type = array_type.clone();
+        loop {
+            r_type = match r_type.element() {
+                Some(element) => element.clone(),
+                None => {
+                    return Err(vec![self.error_at_current(&format!(
+                        "Too many indexes for `{}`, it has {} dimensions",
+                        id.lexeme,
+                        array_type.dimensions()
+                    ))])
+                }
+            };
+            indexes.push(self.parse_expression()?);
+            current_with_expected!(Kind::RightSquare, self, Ok(()))?;
+            if self.advance().kind != Kind::LeftSquare {
+                self.go_back();
+                break;
+            }
+        }
+        Ok(ASTNode::ArrayRef(ArrayRefExpr {
+            position: id.position,
+            array: id,
+            array_type,
+            indexes,
+            r_type,
+        }))
+    }
 }
@@ -10,17 +10,27 @@ use super::Parser;
 
 impl Parser {
     pub fn parse_block(&mut self) -> Result<ASTNode, Vec<SyntaxError>> {
+        self.parse_block_until(Kind::End)
+    }
+
+    /// Parses the statements of a block up to the token closing it,
+    /// which is left as the current token
+    pub fn parse_block_until(&mut self, close: Kind) -> Result<ASTNode, Vec<SyntaxError>> {
         trace!("parse block");
         let start = self.current.clone();
         let mut stmts: Vec<ASTNode> = Vec::new();
         let mut errors: Vec<SyntaxError> = Vec::new();
 
         self.advance();
-        while !self.matches(Kind::End) && !self.matches(Kind::Eof) {
+        while !self.matches(close) && !self.matches(Kind::Eof) {
             if !self.matches(Kind::Begin) {
                 self.go_back();
             }
             match self.parse_statement() {
+                // nested subprograms go with the global ones
+                Ok(
+                    ASTNode::FunctionDecl(_) | ASTNode::ProcedureDecl(_) | ASTNode::ForwardDecl(_),
+                ) => {}
                 Ok(stmt) => {
                     trace!("OK, statement read: {}", stmt);
                     stmts.push(stmt)
@@ -32,12 +42,18 @@ impl Parser {
                     }
                     self.syncronize();
                     trace!("New current: {}", self.current);
+                    // declarations are not statements, this block was
+                    // never closed, the global parsing takes it from
+                    // here
+                    if self.declaration_is_next() {
+                        break;
+                    }
                 }
             }
             self.advance();
         }
 
-        let symbols = self.context.pop().unwrap();
+        let symbols = self.context.pop().unwrap_or_default();
 
         if errors.is_empty() {
             Ok(ASTNode::Block(BlockNode {
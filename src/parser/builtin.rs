@@ -0,0 +1,58 @@
+use log::trace;
+
+use crate::{
+    advance_with_expected,
+    core::{
+        ast::{ASTNode, FunctionCallNode},
+        builtins::Builtin,
+        errors::SyntaxError,
+        symbol_table::{Symbol, SymbolType},
+        token::Kind,
+        types::Type,
+    },
+    current_with_expected,
+};
+
+use super::Parser;
+
+/// Where a symbol comes from, for the errors about declaring it again
+pub fn declared_at(symbol: &Symbol) -> String {
+    match symbol.s_type {
+        SymbolType::Builtin => "which is a builtin function".to_string(),
+        _ => format!("previously declared at {}", symbol.position),
+    }
+}
+
+impl Parser {
+    /// Parses the call of a builtin function, the current token is its
+    /// name. The types of the arguments pick the type of the result
+    pub fn parse_builtin_call(&mut self, builtin: Builtin) -> Result<ASTNode, Vec<SyntaxError>> {
+        trace!("parsing call of builtin {}", builtin.name());
+        let position = self.current.position;
+        advance_with_expected!(Kind::LeftParen, self, {
+            let params = self.parse_call_parameters()?;
+            let types: Vec<Type> = params.iter().map(|p| p.r_type()).collect();
+            match builtin.r_type(&types) {
+                Some(r_type) => current_with_expected!(
+                    Kind::RightParen,
+                    self,
+                    Ok(ASTNode::FunctionCallStmt(FunctionCallNode {
+                        position,
+                        args: builtin
+                            .param_names()
+                            .into_iter()
+                            .zip(params.iter().cloned())
+                            .collect(),
+                        target: builtin.name().to_string(),
+                        r_type,
+                    }))
+                ),
+                None => Err(vec![self.error_at_current(&format!(
+                    "Wrong arguments for `{}`, expected {}",
+                    builtin.name(),
+                    builtin
+                ))]),
+            }
+        })
+    }
+}
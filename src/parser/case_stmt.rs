@@ -0,0 +1,132 @@
+use std::collections::HashMap;
+
+use log::trace;
+
+use crate::{
+    core::{
+        ast::{ASTNode, CaseBranch, CaseStmtNode, ElseStmtNode},
+        errors::SyntaxError,
+        pretty,
+        token::Kind,
+        types::{SimpleType, Type},
+    },
+    current_with_expected,
+    scanner::position::Position,
+};
+
+use super::Parser;
+
+impl Parser {
+    /// Parses `case <expr> of <labels>: <stmt> ... [else <stmt>] end`,
+    /// the labels are int or string literals of the type of the value
+    /// and each of them can appear once
+    pub fn parse_case(&mut self) -> Result<ASTNode, Vec<SyntaxError>> {
+        trace!("parsing case statement");
+        let case_token = self.current.clone();
+        let value = self.parse_expression()?;
+        let value_type = value.r_type();
+        // errors in the labels and in the bodies do not stop the
+        // parsing of the statement, they are all reported at its end
+        let mut errors = Vec::new();
+        let valid = value_type == Type::Simple(SimpleType::Int)
+            || value_type == Type::Simple(SimpleType::String);
+        if !valid {
+            errors.push(self.error_at_current(&format!(
+                "Expected int or string value in case statement, found {}",
+                value_type
+            )));
+        }
+        current_with_expected!(Kind::Of, self, Ok(()))?;
+
+        let mut branches = Vec::new();
+        let mut else_stmt = None;
+        let mut seen: HashMap<String, Position> = HashMap::new();
+        loop {
+            match self.advance().kind {
+                Kind::End => break,
+                Kind::Else => {
+                    let else_token = self.current.clone();
+                    match self.parse_statement() {
+                        Ok(body) => {
+                            else_stmt = Some(Box::new(ASTNode::ElseStmt(ElseStmtNode {
+                                position: else_token.position,
+                                block: Box::new(body),
+                            })))
+                        }
+                        Err(errs) => {
+                            errors.extend(errs);
+                            self.syncronize();
+                            if self.matches(Kind::End) {
+                                break;
+                            }
+                        }
+                    }
+                    match self.advance().kind {
+                        Kind::End => break,
+                        other => return Err(self.unexpected_token_err(Kind::End, other)),
+                    }
+                }
+                _ => self.go_back(),
+            }
+            let mut labels = Vec::new();
+            loop {
+                let label = match self.parse_unary()? {
+                    ASTNode::Literal(l) => l,
+                    other => {
+                        return Err(vec![SyntaxError::new(
+                            other.position(),
+                            self.scanner.curr_line(),
+                            "Expected an int or string literal as case label".to_string(),
+                        )])
+                    }
+                };
+                let text = pretty::expression(&ASTNode::Literal(label.clone()));
+                if valid && label.r_type != value_type {
+                    errors.push(SyntaxError::new(
+                        label.position,
+                        self.scanner.curr_line(),
+                        format!(
+                            "Case label of type {} for a value of type {}",
+                            label.r_type, value_type
+                        ),
+                    ));
+                } else if let Some(first) = seen.insert(text.clone(), label.position) {
+                    errors.push(SyntaxError::new(
+                        label.position,
+                        self.scanner.curr_line(),
+                        format!("Duplicate case label `{}`, already used at {}", text, first),
+                    ));
+                }
+                labels.push(label);
+                match self.current.kind {
+                    Kind::Comma => continue,
+                    Kind::Colon => break,
+                    other => return Err(self.unexpected_token_err(Kind::Colon, other)),
+                }
+            }
+            match self.parse_statement() {
+                Ok(body) => branches.push(CaseBranch {
+                    position: labels[0].position,
+                    labels: labels.into_boxed_slice(),
+                    body: Box::new(body),
+                }),
+                Err(errs) => {
+                    errors.extend(errs);
+                    self.syncronize();
+                    if self.matches(Kind::End) {
+                        break;
+                    }
+                }
+            }
+        }
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+        Ok(ASTNode::CaseStmt(CaseStmtNode {
+            position: case_token.position,
+            value: Box::new(value),
+            branches: branches.into_boxed_slice(),
+            else_stmt,
+        }))
+    }
+}
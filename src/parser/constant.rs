@@ -0,0 +1,128 @@
+use log::trace;
+
+use crate::{
+    advance_with_expected,
+    core::{
+        ast::{ASTNode, ConstDeclNode, LiteralExprNode},
+        builtins::Builtin,
+        errors::SyntaxError,
+        symbol_table::{Symbol, SymbolType},
+        token::{Kind, Token},
+    },
+    current_with_expected,
+    interpreter::Interpreter,
+};
+
+use super::{builtin::declared_at, Parser};
+
+/// The first builtin in the expression whose calls don't always give
+/// the same value
+fn impure_call(expr: &ASTNode) -> Option<Builtin> {
+    match expr {
+        ASTNode::BinaryExpression(b) => impure_call(&b.left).or_else(|| impure_call(&b.right)),
+        ASTNode::UnaryExpression(u) => impure_call(&u.expression),
+        ASTNode::FunctionCallStmt(f) => Builtin::from_name(&f.target)
+            .filter(|b| !b.is_pure())
+            .or_else(|| f.args.iter().find_map(|(_, arg)| impure_call(arg))),
+        _ => None,
+    }
+}
+
+impl Parser {
+    /// Parses a constant declaration, `const <name> = <expression>;`.
+    /// The expression can only use literals, the constants declared
+    /// before and the builtins other than `random`, its value is
+    /// computed here and replaces every use
+    pub fn parse_const_decl(&mut self) -> Result<ASTNode, Vec<SyntaxError>> {
+        trace!("parsing constant declaration");
+        let position = self.current.position;
+        advance_with_expected!(Kind::Identifier, self, {
+            let id = self.current.clone();
+            if let Some(s) = self.get_symbol(id.lexeme.clone()) {
+                return Err(vec![self.error_at_current(&format!(
+                    "Declaration of an already declared symbol: {}, {}",
+                    id.lexeme,
+                    declared_at(&s)
+                ))]);
+            }
+            advance_with_expected!(Kind::Equal, self, {
+                let expr = self.parse_expression()?;
+                current_with_expected!(Kind::Semicolon, self, {
+                    if let Some(builtin) = impure_call(&expr) {
+                        return Err(vec![self.error_at_current(&format!(
+                            "The value of `{}` must be known at compile time: `{}` gives a different value at every call",
+                            id.lexeme,
+                            builtin.name()
+                        ))]);
+                    }
+                    let mut output = Vec::new();
+                    let value =
+                        match Interpreter::new(&mut std::io::empty(), &mut output).eval(&expr) {
+                            Ok(value) => value,
+                            Err(e) => {
+                                return Err(vec![self.error_at_current(&format!(
+                                    "The value of `{}` must be known at compile time: {}",
+                                    id.lexeme, e.description
+                                ))])
+                            }
+                        };
+                    self.context[0].push(Symbol {
+                        name: id.lexeme.clone(),
+                        s_type: SymbolType::Const,
+                        r_type: expr.r_type(),
+                        position: id.position,
+                        args: None,
+                    });
+                    let node = ConstDeclNode {
+                        position,
+                        value: LiteralExprNode {
+                            position: id.position,
+                            value,
+                            r_type: expr.r_type(),
+                        },
+                        id,
+                    };
+                    self.constants.push(node.clone());
+                    Ok(ASTNode::ConstDecl(node))
+                })
+            })
+        })
+    }
+
+    /// The value of a constant, where it is used
+    pub fn constant(&self, id: &Token) -> Option<LiteralExprNode> {
+        self.constants
+            .iter()
+            .find(|c| c.id.lexeme.eq_ignore_ascii_case(&id.lexeme))
+            .map(|c| LiteralExprNode {
+                position: id.position,
+                ..c.value.clone()
+            })
+    }
+
+    /// The global variable or constant a local declaration would hide,
+    /// locals and parameters can't have their names
+    pub fn hidden_global(&self, name: &str) -> Option<Symbol> {
+        self.context[0].get(name.to_string()).filter(|s| {
+            matches!(
+                s.s_type,
+                SymbolType::Var | SymbolType::Arr | SymbolType::Const
+            )
+        })
+    }
+
+    /// Error for a local declaration with the name of a global
+    pub fn global_hidden_err(&self, name: &str) -> SyntaxError {
+        let global = self.hidden_global(name);
+        let kind = match global.as_ref().map(|s| s.s_type) {
+            Some(SymbolType::Const) => "constant",
+            _ => "variable",
+        };
+        self.error_at_current(&format!(
+            "`{}` is already declared as a global {} at {}",
+            name,
+            kind,
+            global.map(|s| s.position.to_string()).unwrap_or_default()
+        ))
+    }
+}
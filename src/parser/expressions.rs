@@ -6,14 +6,29 @@ use crate::{
         errors::SyntaxError,
         objects::Object,
         symbol_table::SymbolType,
-        token::Kind,
+        token::{Kind, Token},
         types::{SimpleType, Type},
     },
-    parser::Parser,
+    parser::{Parser, MAX_NESTING},
 };
 
 impl Parser {
+    /// Parses an operand, failing instead of overflowing the stack on
+    /// expressions nested too deep
     pub fn parse_unary(&mut self) -> Result<ASTNode, Vec<SyntaxError>> {
+        if self.nesting == MAX_NESTING {
+            return Err(vec![self.error_at_current(&format!(
+                "Expression nested too deep, the limit is {} levels",
+                MAX_NESTING
+            ))]);
+        }
+        self.nesting += 1;
+        let unary = self.parse_operand();
+        self.nesting -= 1;
+        unary
+    }
+
+    fn parse_operand(&mut self) -> Result<ASTNode, Vec<SyntaxError>> {
         trace!("Ok, parsing a unary: {}", self.current.clone().lexeme);
         match self.advance().kind {
             Kind::Bang => {
@@ -35,10 +50,10 @@ impl Parser {
                 trace!("found identifier: {}", self.current.clone().lexeme);
                 match self.get_symbol(self.current.lexeme.clone()) {
                     Some(sym) => match sym.s_type {
-                        SymbolType::Function => {
+                        SymbolType::Function | SymbolType::Builtin => {
                             let to_return = self.parse_function_call()?;
                             self.advance();
-                            Ok(to_return)
+                            self.parse_fields(to_return)
                         }
                         SymbolType::Procedure => Err(vec![
 			    self.error_at_current(
@@ -48,12 +63,10 @@ impl Parser {
                             trace!("found identifier {}", self.current.clone().lexeme);
                             let sym = self.parse_symbol()?;
                             self.advance();
-                            Ok(sym)
+                            self.parse_fields(sym)
                         }
                     },
-                    None => Err(vec![self.error_at_current(
-                        format!("Unknown symbol in expression: {}", self.current.lexeme).as_str(),
-                    )]),
+                    None => Err(vec![self.unknown_symbol_err("symbol in expression")]),
                 }
             }
             Kind::True => {
@@ -68,7 +81,7 @@ impl Parser {
                 self.advance();
                 Ok(ASTNode::Literal(LiteralExprNode {
                     position: self.previous.position,
-                    value: Object::Bool(true),
+                    value: Object::Bool(false),
                     r_type: Type::Simple(SimpleType::Bool),
                 }))
             }
@@ -80,23 +93,36 @@ impl Parser {
                     r_type: Type::Simple(SimpleType::String),
                 }))
             }
-            Kind::LitInt => {
-                self.advance();
-                Ok(ASTNode::Literal(LiteralExprNode {
-                    position: self.previous.position,
-                    value: Object::Int(self.previous.clone().lexeme.parse::<i64>().unwrap()),
-                    r_type: Type::Simple(SimpleType::Int),
-                }))
-            }
-            Kind::LitReal => {
-                self.advance();
-                trace!("Real value: {}", self.previous.clone().lexeme);
-                Ok(ASTNode::Literal(LiteralExprNode {
-                    position: self.previous.position,
-                    value: Object::Real(self.previous.clone().lexeme.parse::<f64>().unwrap()),
-                    r_type: Type::Simple(SimpleType::Real),
-                }))
-            }
+            Kind::LitInt => match self.current.lexeme.parse::<i32>() {
+                Ok(value) => {
+                    self.advance();
+                    Ok(ASTNode::Literal(LiteralExprNode {
+                        position: self.previous.position,
+                        value: Object::Int(value as i64),
+                        r_type: Type::Simple(SimpleType::Int),
+                    }))
+                }
+                Err(_) => Err(vec![self.error_at_current(&format!(
+                    "Integer literal `{}` does not fit in an int (at most {})",
+                    self.current.lexeme,
+                    i32::MAX
+                ))]),
+            },
+            Kind::LitReal => match self.current.lexeme.parse::<f64>() {
+                Ok(value) => {
+                    self.advance();
+                    trace!("Real value: {}", self.previous.clone().lexeme);
+                    Ok(ASTNode::Literal(LiteralExprNode {
+                        position: self.previous.position,
+                        value: Object::Real(value),
+                        r_type: Type::Simple(SimpleType::Real),
+                    }))
+                }
+                Err(_) => Err(vec![self.error_at_current(&format!(
+                    "Invalid real literal `{}`",
+                    self.current.lexeme
+                ))]),
+            },
             Kind::LeftParen => match self.parse_expression() {
                 Ok(node) => match self.current.kind {
                     Kind::RightParen => {
@@ -117,155 +143,159 @@ impl Parser {
         }
     }
 
+    /// Builds a binary expression node, positioned at the current
+    /// token as the other nodes are
+    fn binary(
+        &self,
+        left: ASTNode,
+        op: Token,
+        op_type: BinaryExprType,
+        right: ASTNode,
+        r_type: Type,
+    ) -> ASTNode {
+        ASTNode::BinaryExpression(BinaryExprNode {
+            position: self.current.position,
+            left: Box::new(left),
+            op,
+            op_type,
+            right: Box::new(right),
+            r_type,
+        })
+    }
+
     fn parse_factor(&mut self) -> Result<ASTNode, Vec<SyntaxError>> {
         trace!("parse factor");
-        let left_factor = self.parse_unary()?;
-        match self.current.kind {
-            Kind::Slash | Kind::Star => {
-                let op = self.current.clone();
-                let right_factor = self.parse_unary()?;
-                if left_factor.r_type() == right_factor.r_type()
-                    && left_factor.r_type() == Type::Simple(SimpleType::Int)
-                {
-                    return Ok(ASTNode::BinaryExpression(BinaryExprNode {
-                        position: self.current.position,
-                        left: Box::new(left_factor),
-                        op: op.clone(),
-                        op_type: match op.kind {
-                            Kind::Slash => BinaryExprType::Division,
-                            Kind::Star => BinaryExprType::Multiplication,
-                            _ => panic!("Wtf? {:?}", op.kind),
-                        },
-                        right: Box::new(right_factor),
-                        r_type: Type::Simple(SimpleType::Int),
-                    }));
-                }
+        let mut left_factor = self.parse_unary()?;
+        while self.matches(Kind::Slash) || self.matches(Kind::Star) {
+            let op = self.current.clone();
+            let right_factor = self.parse_unary()?;
+            let r_type = left_factor.r_type();
+            if r_type != right_factor.r_type()
+                || !matches!(
+                    r_type,
+                    Type::Simple(SimpleType::Int) | Type::Simple(SimpleType::Real)
+                )
+            {
+                return Err(vec![
+                    self.error_at_current("Mismatching types in term parsing")
+                ]);
             }
-            _ => return Ok(left_factor),
+            let op_type = match op.kind {
+                Kind::Slash => BinaryExprType::Division,
+                _ => BinaryExprType::Multiplication,
+            };
+            left_factor = self.binary(left_factor, op, op_type, right_factor, r_type);
         }
-        Err(vec![
-            self.error_at_current("Mismatching types in term parsing")
-        ])
+        Ok(left_factor)
     }
 
     fn parse_term(&mut self) -> Result<ASTNode, Vec<SyntaxError>> {
         trace!("parse term");
-        let left_factor = self.parse_factor()?;
-        match self.current.kind {
-            Kind::Minus | Kind::Plus => {
-                let op = self.current.clone();
-                let right_factor = self.parse_factor()?;
-                if left_factor.r_type() == right_factor.r_type() {
-                    return Ok(ASTNode::BinaryExpression(BinaryExprNode {
-                        position: self.current.position,
-                        left: Box::new(left_factor.clone()),
-                        op: op.clone(),
-                        op_type: match op.kind {
-                            Kind::Minus => BinaryExprType::Subtraction,
-                            Kind::Plus => BinaryExprType::Addition,
-                            _ => panic!("Wtf? {:?}", op.kind),
-                        },
-                        r_type: left_factor.r_type(),
-                        right: Box::new(right_factor),
-                    }));
-                }
+        let mut left_factor = self.parse_factor()?;
+        while self.matches(Kind::Minus) || self.matches(Kind::Plus) {
+            let op = self.current.clone();
+            let right_factor = self.parse_factor()?;
+            let r_type = left_factor.r_type();
+            let valid = match r_type {
+                Type::Simple(SimpleType::Int) | Type::Simple(SimpleType::Real) => true,
+                Type::Simple(SimpleType::String) => op.kind == Kind::Plus,
+                _ => false,
+            };
+            if !valid || r_type != right_factor.r_type() {
+                return Err(vec![
+                    self.error_at_current("Mismatching types in term parsing")
+                ]);
             }
-            _ => return Ok(left_factor),
+            let op_type = match op.kind {
+                Kind::Minus => BinaryExprType::Subtraction,
+                _ => BinaryExprType::Addition,
+            };
+            left_factor = self.binary(left_factor, op, op_type, right_factor, r_type);
         }
-        Err(vec![
-            self.error_at_current("Mismatching types in term parsing")
-        ])
+        Ok(left_factor)
     }
 
     fn parse_comparison(&mut self) -> Result<ASTNode, Vec<SyntaxError>> {
         trace!("parse comparison");
-        let left_term = self.parse_term()?;
-        match self.current.kind {
-            Kind::Greater | Kind::GreaterEqual | Kind::Less | Kind::LessEqual => {
-                let op = self.current.clone();
-                let op_type = match op.kind {
-                    Kind::Greater => BinaryExprType::LogicGreaterThan,
-                    Kind::GreaterEqual => BinaryExprType::LogicGreaterThanEQ,
-                    Kind::Less => BinaryExprType::LogicLessThan,
-                    Kind::LessEqual => BinaryExprType::LogicLessThanEQ,
-                    _ => panic!("Wtf? {:?}", op.kind), // seems risky
-                                                       // but since
-                                                       // the arm
-                                                       // matched the
-                                                       // kind must be
-                                                       // one of these
-                };
-                let right_term = self.parse_term()?;
-                Ok(ASTNode::BinaryExpression(BinaryExprNode {
-                    position: self.current.position,
-                    left: Box::new(left_term),
-                    op,
-                    op_type,
-                    right: Box::new(right_term),
-                    r_type: Type::Simple(SimpleType::Bool),
-                }))
+        let mut left_term = self.parse_term()?;
+        loop {
+            let op_type = match self.current.kind {
+                Kind::Greater => BinaryExprType::LogicGreaterThan,
+                Kind::GreaterEqual => BinaryExprType::LogicGreaterThanEQ,
+                Kind::Less => BinaryExprType::LogicLessThan,
+                Kind::LessEqual => BinaryExprType::LogicLessThanEQ,
+                _ => return Ok(left_term),
+            };
+            let op = self.current.clone();
+            let right_term = self.parse_term()?;
+            if left_term.r_type() != right_term.r_type()
+                || !matches!(left_term.r_type(), Type::Simple(s) if s != SimpleType::Void && !s.is_record())
+            {
+                return Err(vec![
+                    self.error_at_current("mismatched types in comparison expression")
+                ]);
             }
-            _ => Ok(left_term),
+            left_term = self.binary(
+                left_term,
+                op,
+                op_type,
+                right_term,
+                Type::Simple(SimpleType::Bool),
+            );
         }
     }
 
     fn parse_equality(&mut self) -> Result<ASTNode, Vec<SyntaxError>> {
         trace!("parse equality");
-        let comp_left = self.parse_comparison()?;
-        if self.matches(Kind::Equal) {
+        let mut comp_left = self.parse_comparison()?;
+        while self.matches(Kind::Equal) {
             let equal = self.current.clone();
             let comp_right = self.parse_comparison()?;
-            if comp_left.r_type() == comp_right.r_type()
-                && comp_left.r_type() != Type::Simple(SimpleType::Void)
+            if comp_left.r_type() != comp_right.r_type()
+                || !matches!(comp_left.r_type(), Type::Simple(s) if s != SimpleType::Void && !s.is_record())
             {
-                return Ok(ASTNode::BinaryExpression(BinaryExprNode {
-                    position: self.current.position,
-                    left: Box::new(comp_left),
-                    op: equal,
-                    op_type: BinaryExprType::LogicEQ,
-                    right: Box::new(comp_right),
-                    r_type: Type::Simple(SimpleType::Bool),
-                }));
+                return Err(vec![
+                    self.error_at_current("mismatched types in equality expression")
+                ]);
             }
-        } else {
-            return Ok(comp_left);
+            comp_left = self.binary(
+                comp_left,
+                equal,
+                BinaryExprType::LogicEQ,
+                comp_right,
+                Type::Simple(SimpleType::Bool),
+            );
         }
-        Err(vec![self.error_at_current(
-            "mismatched types in equality expression",
-        )])
+        Ok(comp_left)
     }
 
     pub fn parse_expression(&mut self) -> Result<ASTNode, Vec<SyntaxError>> {
         trace!("parse expression");
-        let eq_left = self.parse_equality()?;
-
-        if self.matches(Kind::And) {
+        let mut eq_left = self.parse_equality()?;
+        while self.matches(Kind::And) {
             let and = self.current.clone();
             let eq_right = self.parse_equality()?;
-
-            if eq_left.r_type() == eq_right.r_type()
-                && eq_left.r_type() == Type::Simple(SimpleType::Bool)
+            if eq_left.r_type() != eq_right.r_type()
+                || eq_left.r_type() != Type::Simple(SimpleType::Bool)
             {
-                return Ok(ASTNode::BinaryExpression(BinaryExprNode {
-                    position: and.position,
-                    left: Box::new(eq_left),
-                    op: and,
-                    op_type: BinaryExprType::LogicAND,
-                    right: Box::new(eq_right),
-                    r_type: Type::Simple(SimpleType::Bool),
-                }));
+                return Err(vec![self.error_at_current(
+                    format!(
+                        "Expected type bool, found {:?}, {:?}",
+                        eq_left.r_type(),
+                        eq_right.r_type()
+                    )
+                    .as_str(),
+                )]);
             }
-
-            Err(vec![self.error_at_current(
-                format!(
-                    "Expected type bool, found {:?}, {:?}",
-                    eq_left.r_type(),
-                    eq_right.r_type()
-                )
-                .as_str(),
-            )])
-        } else {
-            Ok(eq_left)
+            eq_left = ASTNode::BinaryExpression(BinaryExprNode {
+                position: and.position,
+                left: Box::new(eq_left),
+                op: and,
+                op_type: BinaryExprType::LogicAND,
+                right: Box::new(eq_right),
+                r_type: Type::Simple(SimpleType::Bool),
+            });
         }
+        Ok(eq_left)
     }
 }
@@ -0,0 +1,93 @@
+use log::trace;
+
+use crate::{
+    advance_with_expected,
+    core::{
+        ast::{ASTNode, ForStmtNode, VarNameNode},
+        errors::SyntaxError,
+        symbol_table::{SymbolTable, SymbolType},
+        token::Kind,
+        types::{SimpleType, Type},
+    },
+    current_with_expected,
+};
+
+use super::Parser;
+
+impl Parser {
+    /// Parses `for <var> := <expr> to|downto <expr> do begin ... end`,
+    /// the control variable and the bounds must be ints
+    pub fn parse_for_loop(&mut self) -> Result<ASTNode, Vec<SyntaxError>> {
+        trace!("parsing for loop");
+        let for_token = self.current.clone();
+        let id = advance_with_expected!(Kind::Identifier, self, Ok(self.current.clone()))?;
+        self.capture(&id.lexeme, true);
+        // a subprogram called in the body could change a global, the
+        // rest of the loop is still parsed
+        if self.hidden_global(&id.lexeme).is_some() {
+            let error = self.error_at_current(&format!(
+                "`{}` is a global variable, a for loop control variable must be local",
+                id.lexeme
+            ));
+            self.push_error(&error);
+        }
+        let variable = match self.get_symbol(id.lexeme.clone()) {
+            Some(sym)
+                if matches!(
+                    sym.s_type,
+                    SymbolType::Var | SymbolType::VarParam | SymbolType::Param
+                ) && sym.r_type == Type::Simple(SimpleType::Int) =>
+            {
+                VarNameNode {
+                    position: id.position,
+                    id,
+                    r_type: sym.r_type,
+                    s_type: sym.s_type,
+                }
+            }
+            Some(_) => {
+                return Err(vec![self.error_at_current(
+                    "Expected an int variable as for loop control variable",
+                )])
+            }
+            None => return Err(vec![self.unknown_symbol_err("variable")]),
+        };
+        advance_with_expected!(Kind::ColonEqual, self, Ok(()))?;
+        let start = self.parse_expression()?;
+        if start.r_type() != Type::Simple(SimpleType::Int) {
+            return Err(vec![
+                self.error_at_current("Expected int expression as for loop start")
+            ]);
+        }
+        let downto = match self.current.kind {
+            Kind::To => false,
+            Kind::Downto => true,
+            other => return Err(self.unexpected_token_err(Kind::To, other)),
+        };
+        let end = self.parse_expression()?;
+        if end.r_type() != Type::Simple(SimpleType::Int) {
+            return Err(vec![
+                self.error_at_current("Expected int expression as for loop end")
+            ]);
+        }
+        current_with_expected!(
+            Kind::Do,
+            self,
+            advance_with_expected!(Kind::Begin, self, {
+                self.context.push(SymbolTable::new());
+                self.loops.push(Kind::For);
+                let block = self.parse_block();
+                self.loops.pop();
+                let block = block?;
+                Ok(ASTNode::ForStmt(ForStmtNode {
+                    position: for_token.position,
+                    variable: Box::new(ASTNode::VarName(variable)),
+                    start: Box::new(start),
+                    end: Box::new(end),
+                    downto,
+                    block: Box::new(block),
+                }))
+            })
+        )
+    }
+}
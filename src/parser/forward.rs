@@ -0,0 +1,117 @@
+use log::trace;
+
+use crate::{
+    advance_with_expected,
+    core::{
+        ast::{ASTNode, ForwardDeclNode},
+        errors::SyntaxError,
+        symbol_table::{Symbol, SymbolType},
+        token::Kind,
+    },
+};
+
+use super::{builtin::declared_at, Parser};
+
+/// True if the two subprograms take the same parameters, passed the
+/// same way, and return the same type
+fn same_signature(a: &Symbol, b: &Symbol) -> bool {
+    let params = |s: &Symbol| {
+        s.args
+            .as_ref()
+            .map(|a| a.symbols.clone())
+            .unwrap_or_default()
+    };
+    let (a_params, b_params) = (params(a), params(b));
+    a.s_type == b.s_type
+        && a.r_type == b.r_type
+        && a_params.len() == b_params.len()
+        && a_params.iter().zip(b_params.iter()).all(|(p, q)| {
+            p.name.eq_ignore_ascii_case(&q.name) && p.s_type == q.s_type && p.r_type == q.r_type
+        })
+}
+
+impl Parser {
+    /// Parses the rest of a `forward` declaration, the current token is
+    /// `forward`. The subprogram can be called from here on, its body
+    /// comes later with the same signature
+    pub fn parse_forward(&mut self, symbol: Symbol) -> Result<ASTNode, Vec<SyntaxError>> {
+        trace!("parsing forward declaration of {}", symbol.name);
+        let position = self.current.position;
+        advance_with_expected!(Kind::Semicolon, self, {
+            if let Some(s) = self.get_symbol(symbol.name.clone()) {
+                return Err(vec![self.error_at_current(&format!(
+                    "Declaration of an already declared symbol: {}, {}",
+                    symbol.name,
+                    declared_at(&s)
+                ))]);
+            }
+            self.subprogram_name(&symbol)?;
+            if let Some(table) = self.context.last_mut() {
+                table.push(symbol.clone());
+            }
+            self.forwards.push(symbol.clone());
+            Ok(ASTNode::ForwardDecl(ForwardDeclNode { position, symbol }))
+        })
+    }
+
+    /// Adds the subprogram about to be parsed to the current scope,
+    /// unless it was declared `forward` with the same signature.
+    /// Returns the symbol in scope, the forward one if any
+    pub fn declare_subprogram(&mut self, symbol: Symbol) -> Result<Symbol, Vec<SyntaxError>> {
+        let forward = self
+            .forwards
+            .iter()
+            .position(|f| f.name.eq_ignore_ascii_case(&symbol.name));
+        match forward {
+            Some(i) => {
+                let forward = self.forwards.remove(i);
+                if !same_signature(&forward, &symbol) {
+                    return Err(vec![self.error_at_current(&format!(
+                        "Declaration of `{}` does not match its forward declaration at {}",
+                        symbol.name, forward.position
+                    ))]);
+                }
+                Ok(forward)
+            }
+            None => {
+                let declared = self
+                    .context
+                    .last()
+                    .and_then(|table| table.get(symbol.name.clone()));
+                if let Some(s) = declared {
+                    return Err(vec![self.error_at_current(&format!(
+                        "Declaration of an already declared symbol: {}, {}",
+                        symbol.name,
+                        declared_at(&s)
+                    ))]);
+                }
+                if let Some(table) = self.context.last_mut() {
+                    table.push(symbol.clone());
+                }
+                Ok(symbol)
+            }
+        }
+    }
+
+    /// Errors for the `forward` declarations never followed by the
+    /// subprogram
+    pub fn undefined_forwards(&self) -> Vec<SyntaxError> {
+        self.forwards
+            .iter()
+            .map(|f| {
+                let kind = match f.s_type {
+                    SymbolType::Function => "Function",
+                    _ => "Procedure",
+                };
+                SyntaxError::new(
+                    f.position,
+                    self.scanner.line((f.position.line - 1) as usize),
+                    format!(
+                        "{} `{}` is declared forward but never defined",
+                        kind, f.name
+                    ),
+                )
+            })
+            .collect()
+    }
+}
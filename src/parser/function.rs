@@ -4,6 +4,7 @@ use crate::{
     advance_with_expected,
     core::{
         ast::{ASTNode, FunctionCallNode, FunctionDeclNode, ReturnStmtNode},
+        builtins::Builtin,
         errors::SyntaxError,
         symbol_table::{Symbol, SymbolTable, SymbolType},
         token::Kind,
@@ -28,7 +29,7 @@ impl Parser {
                             name: id.lexeme,
                             s_type: match r_type {
                                 Type::Simple(_) => SymbolType::VarParam,
-                                Type::Array(_) => SymbolType::Arr,
+                                Type::Array(..) => SymbolType::Arr,
                             },
                             r_type,
                             position: id.position,
@@ -45,7 +46,11 @@ impl Parser {
                     match self.advance().kind {
                         Kind::Comma | Kind::RightParen => Ok(Symbol {
                             name: id.lexeme,
-                            s_type: SymbolType::Param,
+                            // arrays can be indexed either way
+                            s_type: match r_type {
+                                Type::Simple(_) => SymbolType::Param,
+                                Type::Array(..) => SymbolType::Arr,
+                            },
                             r_type,
                             position: id.position,
                             args: None,
@@ -70,6 +75,12 @@ impl Parser {
             self.go_back();
             while !self.matches(Kind::RightParen) {
                 let param = self.parse_single_param()?;
+                if self.hidden_global(&param.name).is_some() {
+                    return Err(vec![self.global_hidden_err(&param.name)]);
+                }
+                if self.hidden_local(&param.name, self.context.len()).is_some() {
+                    return Err(vec![self.local_hidden_err(&param.name)]);
+                }
                 params.push(param);
             }
         }
@@ -89,34 +100,41 @@ impl Parser {
                     self,
                     advance_with_expected!(Kind::Colon, self, {
                         let r_type = self.parse_type()?;
-                        advance_with_expected!(
-                            Kind::Semicolon,
-                            self,
-                            advance_with_expected!(Kind::Begin, self, {
-                                trace!("Parsing function {} block", id.lexeme);
-                                let mut ctx = self.context.pop().unwrap();
-                                ctx.push(Symbol {
-                                    name: id.lexeme.clone(),
-                                    s_type: SymbolType::Function,
-                                    r_type,
-                                    position: id.position,
-                                    args: Some(Box::new(args.clone())),
-                                });
-                                self.context.push(ctx);
-                                self.context.push(args.clone());
-                                // Parse block after pushing funciton
-                                // name on to the stack, otherwise
-                                // recursion is not available
-                                let block = self.parse_block()?;
-                                Ok(ASTNode::FunctionDecl(FunctionDeclNode {
-                                    name: id.lexeme,
-                                    position: self.current.position,
-                                    args,
-                                    block: Box::new(block),
-                                    r_type,
-                                }))
-                            })
-                        )
+                        advance_with_expected!(Kind::Semicolon, self, {
+                            let symbol = Symbol {
+                                name: id.lexeme.clone(),
+                                s_type: SymbolType::Function,
+                                r_type: r_type.clone(),
+                                position: id.position,
+                                args: Some(Box::new(args.clone())),
+                            };
+                            match self.advance().kind {
+                                Kind::Forward => self.parse_forward(symbol),
+                                Kind::Begin => {
+                                    trace!("Parsing function {} block", id.lexeme);
+                                    let declared = self.declare_subprogram(symbol)?;
+                                    let name = self.subprogram_name(&declared)?;
+                                    let loops =
+                                        self.enter_subprogram(name.clone(), declared.position);
+                                    self.context.push(args.clone());
+                                    // Parse block after pushing funciton
+                                    // name on to the stack, otherwise
+                                    // recursion is not available
+                                    let block = self.parse_block();
+                                    self.exit_subprogram(loops);
+                                    let block = block?;
+                                    Ok(ASTNode::FunctionDecl(FunctionDeclNode {
+                                        name,
+                                        position: self.current.position,
+                                        args,
+                                        captured: 0,
+                                        block: Box::new(block),
+                                        r_type,
+                                    }))
+                                }
+                                other => Err(self.unexpected_token_err(Kind::Begin, other)),
+                            }
+                        })
                     })
                 )
             })
@@ -127,20 +145,23 @@ impl Parser {
         trace!("parsing call parameters");
         let mut params: Vec<ASTNode> = Vec::new();
         let mut errors: Vec<SyntaxError> = Vec::new();
-        trace!("Entering the loop");
         self.advance();
         if !self.matches(Kind::RightParen) {
             self.go_back();
-            while !self.matches(Kind::Comma) && !self.matches(Kind::RightParen) && !self.is_at_end()
-            {
+            loop {
                 match self.parse_expression() {
                     Ok(node) => params.push(node),
                     Err(e) => {
-                        for err in e {
-                            errors.push(err);
-                        }
+                        errors.extend(e);
+                        break;
                     }
                 }
+                if !self.matches(Kind::Comma) {
+                    break;
+                }
+            }
+            if errors.is_empty() && !self.matches(Kind::RightParen) {
+                errors.push(self.error_at_current("Expected `,` or `)` after a parameter"));
             }
         }
         if errors.is_empty() {
@@ -150,11 +171,37 @@ impl Parser {
         }
     }
 
+    /// Checks the argument given to a `var` parameter, only a whole
+    /// variable can be changed by the call
+    pub fn check_var_argument(
+        &mut self,
+        param: &Symbol,
+        arg: &ASTNode,
+    ) -> Result<(), Vec<SyntaxError>> {
+        match (&param.s_type, arg) {
+            (SymbolType::VarParam, ASTNode::VarName(v)) => {
+                self.capture(&v.id.lexeme, true);
+                Ok(())
+            }
+            (SymbolType::VarParam, _) => Err(vec![self.error_at_current(&format!(
+                "Expected a variable for the var parameter `{}`, fields and array elements can't be passed",
+                param.name
+            ))]),
+            _ => Ok(()),
+        }
+    }
+
     pub fn parse_function_call(&mut self) -> Result<ASTNode, Vec<SyntaxError>> {
         trace!("parsing function call");
         let f_name = self.current.clone();
         let f_exists = self.get_symbol(f_name.lexeme.clone());
         match f_exists {
+            Some(f_sym) if f_sym.s_type == SymbolType::Builtin => {
+                match Builtin::from_name(&f_sym.name) {
+                    Some(builtin) => self.parse_builtin_call(builtin),
+                    None => Err(vec![self.unknown_symbol_err("function")]),
+                }
+            }
             Some(f_sym) => {
                 if f_sym.s_type == SymbolType::Function {
                     advance_with_expected!(Kind::LeftParen, self, {
@@ -170,15 +217,17 @@ impl Parser {
                                             "Mismatching types in function call",
                                         )]);
                                     }
+                                    self.check_var_argument(a, b)?;
                                     args_with_lexeme.push((a.clone().name, b.clone()));
                                 }
+                                let target = self.call_target(f_sym.position, f_name.lexeme);
                                 current_with_expected!(
                                     Kind::RightParen,
                                     self,
                                     Ok(ASTNode::FunctionCallStmt(FunctionCallNode {
                                         position: f_name.position,
                                         args: args_with_lexeme.into_boxed_slice(),
-                                        target: f_name.lexeme,
+                                        target,
                                         r_type: f_sym.r_type
                                     }))
                                 )
@@ -199,9 +248,7 @@ impl Parser {
                     )])
                 }
             }
-            None => Err(vec![self.error_at_current(
-                format!("Unknown function \"{}\"", f_name.lexeme).as_str(),
-            )]),
+            None => Err(vec![self.unknown_symbol_err("function")]),
         }
     }
 
@@ -0,0 +1,39 @@
+use log::trace;
+
+use crate::{
+    advance_with_expected,
+    core::{
+        ast::{ASTNode, BreakStmtNode, ContinueStmtNode},
+        errors::SyntaxError,
+        token::Kind,
+    },
+};
+
+use super::Parser;
+
+impl Parser {
+    /// Parses `break;` or `continue;`, only allowed in the body of a
+    /// loop
+    pub fn parse_loop_exit(&mut self) -> Result<ASTNode, Vec<SyntaxError>> {
+        trace!("parsing {}", self.current.kind);
+        let token = self.current.clone();
+        if self.loops.is_empty() {
+            return Err(vec![self.error_at_current(&format!(
+                "`{}` outside of a loop",
+                token.lexeme
+            ))]);
+        }
+        advance_with_expected!(
+            Kind::Semicolon,
+            self,
+            Ok(match token.kind {
+                Kind::Break => ASTNode::BreakStmt(BreakStmtNode {
+                    position: token.position,
+                }),
+                _ => ASTNode::ContinueStmt(ContinueStmtNode {
+                    position: token.position,
+                }),
+            })
+        )
+    }
+}
@@ -1,29 +1,46 @@
 mod array;
 mod assert;
 mod block;
+mod builtin;
+mod case_stmt;
+mod constant;
 mod expressions;
+mod for_loop;
+mod forward;
 mod function;
 mod if_stmt;
+mod loop_exit;
 mod main;
+mod nested;
 mod print;
 mod procedure;
 mod read;
+mod record;
+mod repeat_loop;
 mod statements;
+mod suggestions;
 mod symbol;
 mod types;
 mod var_assignment;
 mod var_declaration;
 mod while_loop;
 
+use std::collections::HashMap;
+
 use log::trace;
 
 use crate::core::ast::*;
+use crate::core::builtins;
 use crate::core::errors::SyntaxError;
 use crate::core::symbol_table::{Symbol, SymbolTable};
 use crate::core::token::{Kind, Token};
 use crate::scanner::position::Position;
 use crate::scanner::Scanner;
 
+/// How deep parentheses, `!`, calls and indexes can be nested in an
+/// expression, every pass recurses on them
+pub const MAX_NESTING: usize = 64;
+
 #[derive(Debug)]
 pub struct Parser {
     scanner: Scanner,
@@ -33,6 +50,23 @@ pub struct Parser {
     // panic: bool,
     syntax_errors: Vec<SyntaxError>,
     context: Vec<SymbolTable>,
+    /// Loops being parsed, the innermost last: `break` and `continue`
+    /// need one
+    loops: Vec<Kind>,
+    /// Global constants declared so far, with their values
+    constants: Vec<ConstDeclNode>,
+    /// Subprograms declared `forward` and not defined yet
+    forwards: Vec<Symbol>,
+    /// Subprograms being parsed, the innermost last
+    subprograms: Vec<nested::Subprogram>,
+    /// Subprograms parsed so far
+    finished: Vec<nested::Subprogram>,
+    /// Name in the AST of every subprogram, by declaration position
+    names: HashMap<Position, String>,
+    /// Nested subprograms, parsed with the global ones
+    nested: Vec<ASTNode>,
+    /// Operands being parsed, at most `MAX_NESTING`
+    nesting: usize,
 }
 
 #[macro_export]
@@ -71,10 +105,41 @@ impl Parser {
             },
             next: None,
             syntax_errors: vec![],
-            context: vec![SymbolTable::new()],
+            context: vec![builtins::symbols()],
+            loops: vec![],
+            constants: vec![],
+            forwards: vec![],
+            subprograms: vec![],
+            finished: vec![],
+            names: HashMap::new(),
+            nested: vec![],
+            nesting: 0,
         }
     }
 
+    /// Parser for an expression on its own, over the given symbols
+    /// (the debugger uses it on the variables in scope)
+    pub fn with_symbols(src: String, symbols: SymbolTable) -> Parser {
+        let mut parser = Parser::new(src);
+        parser.context = vec![symbols];
+        parser
+    }
+
+    /// Parses the whole source as a single expression
+    pub fn parse_only_expression(&mut self) -> Result<ASTNode, Vec<SyntaxError>> {
+        let expr = self.parse_expression()?;
+        if !self.syntax_errors.is_empty() {
+            return Err(self.syntax_errors.clone());
+        }
+        if !self.is_at_end() {
+            return Err(vec![self.error_at_current(&format!(
+                "Unexpected `{}` after the expression",
+                self.current.lexeme
+            ))]);
+        }
+        Ok(expr)
+    }
+
     /// Main parse function, gives the source to the scanner and
     /// iteratively looks at tokens, returning an AST (fake, since is
     /// practically a parse tree) that rapresents the program, the
@@ -85,12 +150,20 @@ impl Parser {
         let mut program_name: Option<ProgramNameNode> = None;
         let mut procedures: Vec<ProcedureDeclNode> = Vec::new();
         let mut functions: Vec<FunctionDeclNode> = Vec::new();
+        let mut records: Vec<RecordDeclNode> = Vec::new();
+        let mut globals: Vec<VariableDeclNode> = Vec::new();
         let mut main_block: Option<BlockNode> = None;
         while !self.is_at_end() {
             match self.parse_global_statement() {
                 Ok(ASTNode::ProgramName(node)) => program_name = Some(node),
                 Ok(ASTNode::ProcedureDecl(node)) => procedures.push(node),
                 Ok(ASTNode::FunctionDecl(node)) => functions.push(node),
+                Ok(ASTNode::RecordDecl(node)) => records.push(node),
+                Ok(ASTNode::VariableDecl(node)) => globals.push(node),
+                // kept by the parser, their uses are already replaced
+                Ok(ASTNode::ConstDecl(_)) => {}
+                // the symbol is enough, the body comes later
+                Ok(ASTNode::ForwardDecl(_)) => {}
                 Ok(ASTNode::Block(node)) => main_block = Some(node),
                 Ok(ASTNode::EofStmt(_)) => break,
                 Ok(other) => self.syntax_errors.push(SyntaxError::new(
@@ -107,6 +180,32 @@ impl Parser {
             }
         }
 
+        let undefined = self.undefined_forwards();
+        self.syntax_errors.extend(undefined);
+        for node in std::mem::take(&mut self.nested) {
+            match node {
+                ASTNode::FunctionDecl(node) => functions.push(node),
+                ASTNode::ProcedureDecl(node) => procedures.push(node),
+                _ => {}
+            }
+        }
+        match self.captured_params() {
+            Ok(captured) => {
+                for f in functions.iter_mut() {
+                    if let Some(params) = captured.get(&f.name) {
+                        f.args.symbols.extend(params.symbols.iter().cloned());
+                        f.captured = params.symbols.len();
+                    }
+                }
+                for p in procedures.iter_mut() {
+                    if let Some(params) = captured.get(&p.name) {
+                        p.args.symbols.extend(params.symbols.iter().cloned());
+                        p.captured = params.symbols.len();
+                    }
+                }
+            }
+            Err(errors) => self.syntax_errors.extend(errors),
+        }
         if self.syntax_errors.is_empty() {
             if let Some(name) = program_name {
                 if let Some(main) = main_block {
@@ -114,6 +213,9 @@ impl Parser {
                         program_name: name,
                         functions: functions.into_boxed_slice(),
                         procedures: procedures.into_boxed_slice(),
+                        records: records.into_boxed_slice(),
+                        globals: globals.into_boxed_slice(),
+                        constants: self.constants.clone().into_boxed_slice(),
                         main_block: main,
                     }))
                 } else {
@@ -173,6 +275,15 @@ impl Parser {
         self.current = self.previous.clone();
     }
 
+    /// True if the token the parser went back from starts a function
+    /// or procedure declaration
+    pub fn declaration_is_next(&self) -> bool {
+        matches!(
+            self.next.as_ref().map(|t| t.kind),
+            Some(Kind::Function) | Some(Kind::Procedure)
+        )
+    }
+
     /// Utility function to push an error to the error stack
     pub fn push_error(&mut self, error: &SyntaxError) {
         self.syntax_errors.push(error.clone());
@@ -187,6 +298,11 @@ impl Parser {
         )
     }
 
+    /// Pragma comments found in the source, see `Scanner::pragmas`
+    pub fn pragmas(&self) -> &[Token] {
+        self.scanner.pragmas()
+    }
+
     pub fn get_symbol(&self, s_name: String) -> Option<Symbol> {
         for table in self.context.iter().rev() {
             if let Some(f) = table.get(s_name.clone()) {
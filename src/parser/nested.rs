@@ -0,0 +1,285 @@
+use std::collections::HashMap;
+
+use log::trace;
+
+use crate::{
+    core::{
+        ast::ASTNode,
+        errors::SyntaxError,
+        symbol_table::{Symbol, SymbolTable, SymbolType},
+        token::Kind,
+        types::Type,
+    },
+    scanner::position::Position,
+};
+
+use super::Parser;
+
+/// A variable of an enclosing subprogram used by a nested one
+#[derive(Debug, Clone)]
+pub struct Capture {
+    pub symbol: Symbol,
+    /// Subprogram declaring it
+    pub owner: String,
+    /// True if the nested subprogram changes it
+    pub written: bool,
+}
+
+/// A function or procedure, with what it needs from the enclosing ones
+#[derive(Debug, Clone)]
+pub struct Subprogram {
+    /// Name in the AST, the nested ones are prefixed by the name of
+    /// the enclosing subprogram
+    pub name: String,
+    pub position: Position,
+    /// Enclosing subprograms, the outermost first
+    pub ancestors: Vec<String>,
+    /// Index of its parameters table in the context
+    pub base: usize,
+    pub captured: Vec<Capture>,
+    /// Subprograms it calls
+    pub calls: Vec<String>,
+    /// True once a subprogram is declared in it
+    pub has_nested: bool,
+}
+
+impl Subprogram {
+    /// Adds the variable to the captured ones, true if it is new or
+    /// now written
+    fn capture(&mut self, capture: &Capture) -> bool {
+        let name = &capture.symbol.name;
+        match self.captured.iter_mut().find(|c| c.symbol.name == *name) {
+            Some(c) if capture.written && !c.written => {
+                c.written = true;
+                true
+            }
+            Some(_) => false,
+            None => {
+                self.captured.push(capture.clone());
+                true
+            }
+        }
+    }
+
+    /// The captured variables as parameters, passed as `var` if they
+    /// are changed (arrays always are)
+    fn hidden_params(&self) -> SymbolTable {
+        let mut params = SymbolTable::new();
+        for c in self.captured.iter() {
+            params.push(Symbol {
+                s_type: match (&c.symbol.r_type, c.written) {
+                    (Type::Array(..), _) => SymbolType::Arr,
+                    (_, true) => SymbolType::VarParam,
+                    (_, false) => SymbolType::Param,
+                },
+                ..c.symbol.clone()
+            });
+        }
+        params
+    }
+}
+
+impl Parser {
+    /// Name of the subprogram in the AST: the declared one, prefixed
+    /// by the enclosing subprogram when nested
+    pub fn subprogram_name(&mut self, symbol: &Symbol) -> Result<String, Vec<SyntaxError>> {
+        if let Some(name) = self.names.get(&symbol.position) {
+            return Ok(name.clone());
+        }
+        let name = match self.subprograms.last() {
+            Some(parent) => format!("{}_{}", parent.name, symbol.name),
+            None => symbol.name.clone(),
+        };
+        if self.names.values().any(|n| n.eq_ignore_ascii_case(&name)) {
+            return Err(vec![self.error_at_current(&format!(
+                "`{}` clashes with the name given to a nested subprogram, rename one of them",
+                symbol.name
+            ))]);
+        }
+        self.names.insert(symbol.position, name.clone());
+        Ok(name)
+    }
+
+    /// Name in the AST of the called subprogram, declared at the given
+    /// position
+    pub fn call_target(&mut self, position: Position, lexeme: String) -> String {
+        let target = self.names.get(&position).cloned().unwrap_or(lexeme);
+        if let Some(caller) = self.subprograms.last_mut() {
+            if !caller.calls.contains(&target) {
+                caller.calls.push(target.clone());
+            }
+        }
+        target
+    }
+
+    /// Starts the body of a subprogram, before its parameters are
+    /// pushed. `break` and `continue` can't leave it
+    pub fn enter_subprogram(&mut self, name: String, position: Position) -> Vec<Kind> {
+        trace!("entering subprogram {}", name);
+        self.subprograms.push(Subprogram {
+            name,
+            position,
+            ancestors: self.subprograms.iter().map(|s| s.name.clone()).collect(),
+            base: self.context.len(),
+            captured: vec![],
+            calls: vec![],
+            has_nested: false,
+        });
+        std::mem::take(&mut self.loops)
+    }
+
+    /// Ends the body of a subprogram, given back the loops around it
+    pub fn exit_subprogram(&mut self, loops: Vec<Kind>) {
+        self.loops = loops;
+        if let Some(subprogram) = self.subprograms.pop() {
+            self.finished.push(subprogram);
+        }
+    }
+
+    /// Parses a function or procedure declared in the body of another
+    /// one, it is moved with the global ones once parsed
+    pub fn parse_nested_subprogram(&mut self) -> Result<ASTNode, Vec<SyntaxError>> {
+        match self.subprograms.last_mut() {
+            Some(parent) => parent.has_nested = true,
+            None => {
+                return Err(vec![self.error_at_current(
+                    "Functions and procedures can only be declared globally or in other functions and procedures",
+                )])
+            }
+        }
+        let decl = match self.current.kind {
+            Kind::Function => self.parse_function()?,
+            _ => self.parse_procedure()?,
+        };
+        if let ASTNode::FunctionDecl(_) | ASTNode::ProcedureDecl(_) = decl {
+            self.nested.push(decl.clone());
+        }
+        Ok(decl)
+    }
+
+    /// Records the use of a variable: if it belongs to an enclosing
+    /// subprogram the nested ones in between capture it
+    pub fn capture(&mut self, name: &str, written: bool) {
+        if self.subprograms.len() < 2 {
+            return;
+        }
+        let found = self
+            .context
+            .iter()
+            .enumerate()
+            .rev()
+            .find_map(|(i, table)| table.get(name.to_string()).map(|s| (i, s)));
+        let (index, symbol) = match found {
+            Some((i, s)) if i > 0 => (i, s),
+            _ => return,
+        };
+        if !matches!(
+            symbol.s_type,
+            SymbolType::Var | SymbolType::VarParam | SymbolType::Param | SymbolType::Arr
+        ) {
+            return;
+        }
+        let owner = match self.subprograms.iter().rev().find(|s| s.base <= index) {
+            Some(owner) => owner.name.clone(),
+            None => return,
+        };
+        let capture = Capture {
+            symbol: Symbol {
+                name: symbol.name.to_lowercase(),
+                ..symbol
+            },
+            owner,
+            written,
+        };
+        for s in self.subprograms.iter_mut().filter(|s| s.base > index) {
+            s.capture(&capture);
+        }
+    }
+
+    /// A variable of an enclosing scope with the given name, which
+    /// a new declaration would hide. Variables are not hidden in
+    /// nested subprograms and around them, their captured variables
+    /// are passed by name
+    pub fn hidden_local(&self, name: &str, tables: usize) -> Option<Symbol> {
+        let nested =
+            self.subprograms.len() >= 2 || self.subprograms.last().is_some_and(|s| s.has_nested);
+        if !nested {
+            return None;
+        }
+        self.context
+            .iter()
+            .take(tables)
+            .skip(1)
+            .filter_map(|t| t.get(name.to_string()))
+            .find(|s| {
+                matches!(
+                    s.s_type,
+                    SymbolType::Var | SymbolType::VarParam | SymbolType::Param | SymbolType::Arr
+                )
+            })
+    }
+
+    pub fn local_hidden_err(&self, name: &str) -> SyntaxError {
+        let position = self
+            .hidden_local(name, self.context.len())
+            .map(|s| s.position.to_string())
+            .unwrap_or_default();
+        self.error_at_current(&format!(
+            "`{}` is already declared at {}, nested subprograms can't hide it",
+            name, position
+        ))
+    }
+
+    /// Computes the variables every nested subprogram needs, the ones
+    /// it uses and the ones needed by the subprograms it calls, which
+    /// it passes on. They become its last parameters
+    pub fn captured_params(&mut self) -> Result<HashMap<String, SymbolTable>, Vec<SyntaxError>> {
+        let mut subprograms: HashMap<String, Subprogram> = self
+            .finished
+            .drain(..)
+            .map(|s| (s.name.clone(), s))
+            .collect();
+        let names: Vec<String> = subprograms.keys().cloned().collect();
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for name in names.iter() {
+                let inherited: Vec<Capture> = subprograms[name]
+                    .calls
+                    .iter()
+                    .filter_map(|callee| subprograms.get(callee))
+                    .flat_map(|callee| callee.captured.iter().cloned())
+                    .filter(|c| subprograms[name].ancestors.contains(&c.owner))
+                    .collect();
+                if let Some(s) = subprograms.get_mut(name) {
+                    for c in inherited.iter() {
+                        changed |= s.capture(c);
+                    }
+                }
+            }
+        }
+        let mut errors = vec![];
+        let mut params = HashMap::new();
+        for s in subprograms.values() {
+            for c in s.captured.iter() {
+                if c.symbol.position.char_number > s.position.char_number {
+                    errors.push(SyntaxError::new(
+                        s.position,
+                        self.scanner.line((s.position.line - 1) as usize),
+                        format!(
+                            "`{}` needs `{}`, which is declared after it at {}",
+                            s.name, c.symbol.name, c.symbol.position
+                        ),
+                    ));
+                }
+            }
+            if !s.captured.is_empty() {
+                params.insert(s.name.clone(), s.hidden_params());
+            }
+        }
+        match errors.is_empty() {
+            true => Ok(params),
+            false => Err(errors),
+        }
+    }
+}
@@ -1,35 +1,92 @@
 use log::trace;
 
 use crate::{
-    advance_with_expected,
     core::{
-        ast::{ASTNode, PrintStmtNode},
+        ast::{ASTNode, PrintStmtNode, WriteArg},
         errors::SyntaxError,
         token::Kind,
+        types::{SimpleType, Type},
     },
     current_with_expected,
     parser::Parser,
 };
 
 impl Parser {
-    /// Parses a print statement returning the corresponding ASTNode
+    /// Parses a `write` or `writeln` statement returning the
+    /// corresponding ASTNode, the values are optional as in
+    /// `writeln;`
     pub fn parse_print(&mut self) -> Result<ASTNode, Vec<SyntaxError>> {
         trace!("Parsing print statement");
         let position = self.current.clone().position;
-        advance_with_expected!(Kind::LeftParen, self, {
-            let expr = self.parse_expression()?;
-            current_with_expected!(
-                Kind::RightParen,
-                self,
-                advance_with_expected!(
-                    Kind::Semicolon,
-                    self,
-                    Ok(ASTNode::PrintStmt(PrintStmtNode {
-                        to_print: Box::new(expr),
-                        position,
-                    }))
-                )
-            )
+        let newline = self.matches(Kind::Print);
+        let mut args = vec![];
+        self.advance();
+        if self.matches(Kind::LeftParen) {
+            self.advance();
+            if !self.matches(Kind::RightParen) {
+                self.go_back();
+                loop {
+                    args.push(self.parse_write_arg()?);
+                    if !self.matches(Kind::Comma) {
+                        break;
+                    }
+                }
+            }
+            current_with_expected!(Kind::RightParen, self, Ok(()))?;
+            self.advance();
+        }
+        current_with_expected!(
+            Kind::Semicolon,
+            self,
+            Ok(ASTNode::PrintStmt(PrintStmtNode {
+                args: args.into_boxed_slice(),
+                newline,
+                position,
+            }))
+        )
+    }
+
+    /// Parses a value to print with its optional width and precision,
+    /// `value:width:precision`
+    fn parse_write_arg(&mut self) -> Result<WriteArg, Vec<SyntaxError>> {
+        let value = self.parse_expression()?;
+        let r_type = value.r_type();
+        if let Type::Simple(SimpleType::Record(r)) = r_type {
+            return Err(vec![self.error_at_current(&format!(
+                "Unable to print a whole record of type {}, print its fields one by one",
+                r.name
+            ))]);
+        }
+        let width = match self.matches(Kind::Colon) {
+            true => Some(self.parse_format("width")?),
+            false => None,
+        };
+        let precision = match width.is_some() && self.matches(Kind::Colon) {
+            true => Some(self.parse_format("precision")?),
+            false => None,
+        };
+        if precision.is_some() && r_type != Type::Simple(SimpleType::Real) {
+            return Err(vec![self.error_at_current(&format!(
+                "Expected a real to print with a precision, found {}",
+                r_type
+            ))]);
+        }
+        Ok(WriteArg {
+            value,
+            width,
+            precision,
         })
     }
+
+    /// Parses the width or the precision after a `:`, an int
+    fn parse_format(&mut self, what: &str) -> Result<ASTNode, Vec<SyntaxError>> {
+        let format = self.parse_expression()?;
+        match format.r_type() {
+            Type::Simple(SimpleType::Int) => Ok(format),
+            other => Err(vec![self.error_at_current(&format!(
+                "Expected an int as the {} of a printed value, found {}",
+                what, other
+            ))]),
+        }
+    }
 }
@@ -24,31 +24,37 @@ impl Parser {
                 current_with_expected!(
                     Kind::RightParen,
                     self,
-                    advance_with_expected!(
-                        Kind::Semicolon,
-                        self,
-                        advance_with_expected!(Kind::Begin, self, {
-                            trace!("procedure {}, args: {}", id.lexeme, args);
-                            let mut ctx = self.context.pop().unwrap();
-                            ctx.push(Symbol {
-                                name: id.lexeme.clone(),
-                                s_type: SymbolType::Procedure,
-                                r_type: Type::Simple(SimpleType::Void),
-                                position: id.position,
-                                args: Some(Box::new(args.clone())),
-                            });
-                            self.context.push(ctx);
-                            self.context.push(args.clone());
-                            let block = self.parse_block()?;
-                            trace!("block parsed");
-                            Ok(ASTNode::ProcedureDecl(ProcedureDeclNode {
-                                position: self.current.position,
-                                name: id.lexeme,
-                                args,
-                                block: Box::new(block),
-                            }))
-                        })
-                    )
+                    advance_with_expected!(Kind::Semicolon, self, {
+                        let symbol = Symbol {
+                            name: id.lexeme.clone(),
+                            s_type: SymbolType::Procedure,
+                            r_type: Type::Simple(SimpleType::Void),
+                            position: id.position,
+                            args: Some(Box::new(args.clone())),
+                        };
+                        match self.advance().kind {
+                            Kind::Forward => self.parse_forward(symbol),
+                            Kind::Begin => {
+                                trace!("procedure {}, args: {}", id.lexeme, args);
+                                let declared = self.declare_subprogram(symbol)?;
+                                let name = self.subprogram_name(&declared)?;
+                                let loops = self.enter_subprogram(name.clone(), declared.position);
+                                self.context.push(args.clone());
+                                let block = self.parse_block();
+                                self.exit_subprogram(loops);
+                                let block = block?;
+                                trace!("block parsed");
+                                Ok(ASTNode::ProcedureDecl(ProcedureDeclNode {
+                                    position: self.current.position,
+                                    name,
+                                    args,
+                                    captured: 0,
+                                    block: Box::new(block),
+                                }))
+                            }
+                            other => Err(self.unexpected_token_err(Kind::Begin, other)),
+                        }
+                    })
                 )
             })
         })
@@ -72,8 +78,10 @@ impl Parser {
                                     self.error_at_current("Mismatching types in procedure call")
                                 ]);
                             }
+                            self.check_var_argument(a, b)?;
                             args_with_lexeme.push((a.clone().name, b.clone()));
                         }
+                        let target = self.call_target(f_sym.position, f_name.lexeme);
                         current_with_expected!(
                             Kind::RightParen,
                             self,
@@ -83,7 +91,7 @@ impl Parser {
                                 Ok(ASTNode::ProcedureCallStmt(ProcedureCallNode {
                                     position: f_name.position,
                                     args: args_with_lexeme.into_boxed_slice(),
-                                    target: f_name.lexeme,
+                                    target,
                                 }))
                             )
                         )
@@ -99,9 +107,7 @@ impl Parser {
                 }
             })
         } else {
-            Err(vec![self.error_at_current(
-                format!("Unknown procedure {}", f_name.lexeme).as_str(),
-            )])
+            Err(vec![self.unknown_symbol_err("procedure")])
         }
     }
 }
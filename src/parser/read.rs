@@ -6,7 +6,9 @@ use crate::{
         ast::{ASTNode, ReadStmtNode},
         errors::SyntaxError,
         token::Kind,
+        types::{SimpleType, Type},
     },
+    current_with_expected,
     parser::Parser,
 };
 
@@ -17,8 +19,23 @@ impl Parser {
         let pos = self.current.clone().position;
         advance_with_expected!(Kind::LeftParen, self, {
             self.advance();
-            let where_to_read = self.parse_symbol()?;
-            advance_with_expected!(
+            self.capture(&self.current.lexeme.clone(), true);
+            let symbol = self.parse_symbol()?;
+            if let ASTNode::Literal(_) = symbol {
+                return Err(vec![self.error_at_current(&format!(
+                    "`{}` is a constant, it cannot be read",
+                    self.current.lexeme
+                ))]);
+            }
+            self.advance();
+            let where_to_read = self.parse_fields(symbol)?;
+            if let Type::Simple(SimpleType::Record(r)) = where_to_read.r_type() {
+                return Err(vec![self.error_at_current(&format!(
+                    "Unable to read a whole record of type {}, read its fields one by one",
+                    r.name
+                ))]);
+            }
+            current_with_expected!(
                 Kind::RightParen,
                 self,
                 advance_with_expected!(
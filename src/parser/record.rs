@@ -0,0 +1,148 @@
+use std::sync::Arc;
+
+use log::trace;
+
+use crate::{
+    advance_with_expected,
+    core::{
+        ast::{ASTNode, FieldRefNode, RecordDeclNode},
+        errors::SyntaxError,
+        symbol_table::{Symbol, SymbolType},
+        token::Kind,
+        types::{RecordType, SimpleType, Type},
+    },
+    current_with_expected,
+};
+
+use super::{builtin::declared_at, Parser};
+
+impl Parser {
+    /// Parses the fields of a record up to its `end`, the current
+    /// token is the first one after `record`
+    fn parse_record_fields(&mut self, name: &str) -> Result<Vec<(String, Type)>, Vec<SyntaxError>> {
+        let mut fields: Vec<(String, Type)> = Vec::new();
+        while !self.matches(Kind::End) {
+            current_with_expected!(Kind::Identifier, self, Ok(()))?;
+            let field = self.current.clone();
+            if fields
+                .iter()
+                .any(|(f, _)| f.eq_ignore_ascii_case(&field.lexeme))
+            {
+                return Err(vec![self.error_at_current(&format!(
+                    "Duplicate field `{}` in record {}",
+                    field.lexeme, name
+                ))]);
+            }
+            advance_with_expected!(Kind::Colon, self, Ok(()))?;
+            let f_type = self.parse_type()?;
+            if let Type::Array(..) = f_type {
+                return Err(vec![self.error_at_current(&format!(
+                    "Field `{}` of record {} is an array, fields can only be simple types or records",
+                    field.lexeme, name
+                ))]);
+            }
+            fields.push((field.lexeme, f_type));
+            // the semicolon is optional after the last field
+            match self.advance().kind {
+                Kind::Semicolon => {
+                    self.advance();
+                }
+                Kind::End => {}
+                other => return Err(self.unexpected_token_err(Kind::Semicolon, other)),
+            }
+        }
+        if fields.is_empty() {
+            return Err(vec![
+                self.error_at_current(&format!("Record {} has no fields", name))
+            ]);
+        }
+        Ok(fields)
+    }
+
+    /// Parses a record type declaration, `type <name> = record <field>:
+    /// <type>; ... end;`, the type is declared in the global scope
+    pub fn parse_record_decl(&mut self) -> Result<ASTNode, Vec<SyntaxError>> {
+        trace!("parsing record declaration");
+        let position = self.current.position;
+        advance_with_expected!(Kind::Identifier, self, {
+            let id = self.current.clone();
+            if let Some(s) = self.get_symbol(id.lexeme.clone()) {
+                return Err(vec![self.error_at_current(&format!(
+                    "Declaration of an already declared symbol: {}, {}",
+                    id.lexeme,
+                    declared_at(&s)
+                ))]);
+            }
+            advance_with_expected!(
+                Kind::Equal,
+                self,
+                advance_with_expected!(Kind::Record, self, {
+                    self.advance();
+                    let fields = match self.parse_record_fields(&id.lexeme) {
+                        Ok(fields) => fields,
+                        Err(errors) => {
+                            // skips the rest of the declaration, its
+                            // fields are not statements
+                            while !self.matches(Kind::End) && !self.is_at_end() {
+                                self.advance();
+                            }
+                            self.advance();
+                            return Err(errors);
+                        }
+                    };
+                    advance_with_expected!(Kind::Semicolon, self, {
+                        let r_type = Arc::new(RecordType {
+                            name: id.lexeme.clone(),
+                            fields,
+                        });
+                        self.context[0].push(Symbol {
+                            name: id.lexeme,
+                            s_type: SymbolType::Record,
+                            r_type: Type::Simple(SimpleType::Record(r_type.clone())),
+                            position: id.position,
+                            args: None,
+                        });
+                        Ok(ASTNode::RecordDecl(RecordDeclNode { position, r_type }))
+                    })
+                })
+            )
+        })
+    }
+
+    /// Parses the field accesses after a value, `.<field>` as many
+    /// times as they are. The current token is the one after the
+    /// value and is left on the one after the last field
+    pub fn parse_fields(&mut self, mut node: ASTNode) -> Result<ASTNode, Vec<SyntaxError>> {
+        while self.matches(Kind::Dot) {
+            let record = match node.r_type() {
+                Type::Simple(SimpleType::Record(r)) => r,
+                other => {
+                    return Err(vec![self.error_at_current(&format!(
+                        "Field access on a value of type {}, which is not a record",
+                        other
+                    ))])
+                }
+            };
+            advance_with_expected!(Kind::Identifier, self, Ok(()))?;
+            let field = self.current.clone();
+            let (index, r_type) = match record.field(&field.lexeme) {
+                Some((index, r_type)) => (index, r_type.clone()),
+                None => {
+                    return Err(vec![self.error_at_current(&format!(
+                        "Record {} has no field `{}`",
+                        record.name, field.lexeme
+                    ))])
+                }
+            };
+            node = ASTNode::FieldRef(FieldRefNode {
+                position: node.position(),
+                record: Box::new(node),
+                field,
+                index,
+                r_type,
+            });
+            self.advance();
+        }
+        Ok(node)
+    }
+}
@@ -0,0 +1,45 @@
+use log::trace;
+
+use crate::{
+    core::{
+        ast::{ASTNode, RepeatStmtNode},
+        errors::SyntaxError,
+        symbol_table::SymbolTable,
+        token::Kind,
+        types::SimpleType,
+    },
+    current_with_expected,
+};
+
+use super::Parser;
+
+impl Parser {
+    /// Parses `repeat <stmts> until <expr>;`, the statements are a
+    /// block of their own even without `begin` and `end`
+    pub fn parse_repeat_loop(&mut self) -> Result<ASTNode, Vec<SyntaxError>> {
+        trace!("parsing repeat loop");
+        let repeat_token = self.current.clone();
+        self.context.push(SymbolTable::new());
+        self.loops.push(Kind::Repeat);
+        let block = self.parse_block_until(Kind::Until);
+        self.loops.pop();
+        let block = block?;
+        current_with_expected!(Kind::Until, self, {
+            let guard = self.parse_expression()?;
+            if guard.r_type().internal() != SimpleType::Bool {
+                return Err(vec![self.error_at_current(
+                    "Expected boolean expression as repeat loop guard",
+                )]);
+            }
+            current_with_expected!(
+                Kind::Semicolon,
+                self,
+                Ok(ASTNode::RepeatStmt(RepeatStmtNode {
+                    position: repeat_token.position,
+                    block: Box::new(block),
+                    guard: Box::new(guard),
+                }))
+            )
+        })
+    }
+}
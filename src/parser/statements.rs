@@ -25,11 +25,25 @@ impl Parser {
         match self.advance().kind {
             Kind::Function => self.parse_function(),
             Kind::Procedure => self.parse_procedure(),
+            Kind::Type => self.parse_record_decl(),
+            Kind::Var | Kind::Const => {
+                if self.context[0]
+                    .iter()
+                    .any(|s| matches!(s.s_type, SymbolType::Function | SymbolType::Procedure))
+                {
+                    return Err(vec![self.error_at_current(
+                        "Global declarations must come before the functions and procedures",
+                    )]);
+                }
+                match self.current.kind {
+                    Kind::Var => self.parse_var_declaration(),
+                    _ => self.parse_const_decl(),
+                }
+            }
             Kind::Begin => {
+                // the block pops its own table when it ends
                 self.context.push(SymbolTable::new());
-                let block = self.parse_main_block()?;
-                self.context.pop();
-                Ok(block)
+                self.parse_main_block()
             }
             Kind::Program => self.parse_program_name(),
             Kind::Eof => Ok(ASTNode::EofStmt(EofNode {
@@ -55,23 +69,26 @@ impl Parser {
             Kind::Var => self.parse_var_declaration(),
             Kind::Identifier => match self.get_symbol(self.current.lexeme.clone()) {
                 Some(s) => match s.s_type {
-                    SymbolType::Function => {
+                    SymbolType::Function | SymbolType::Builtin => {
                         let node = self.parse_function_call()?;
                         advance_with_expected!(Kind::Semicolon, self, Ok(node))
                     }
                     SymbolType::Procedure => self.parse_procedure_call(),
                     _ => self.parse_var_assignment(),
                 },
-                None => Err(vec![self.error_at_current(
-                    format!("Symbol not found: {}", self.current.lexeme).as_str(),
-                )]),
+                None => Err(vec![self.unknown_symbol_err("symbol")]),
             },
             Kind::Read => self.parse_read(),
-            Kind::Print => self.parse_print(),
+            Kind::Print | Kind::Write => self.parse_print(),
             Kind::Assert => self.parse_assert(),
             Kind::Return => self.parse_return(),
             Kind::While => self.parse_while_loop(),
+            Kind::For => self.parse_for_loop(),
+            Kind::Repeat => self.parse_repeat_loop(),
+            Kind::Break | Kind::Continue => self.parse_loop_exit(),
             Kind::If => self.parse_if(),
+            Kind::Case => self.parse_case(),
+            Kind::Function | Kind::Procedure => self.parse_nested_subprogram(),
             other => Err(vec![
                 self.error_at_current(&format!("Unexpected token: {}", other))
             ]),
@@ -105,10 +122,17 @@ impl Parser {
         expected_token: Kind,
         found_token: Kind,
     ) -> Vec<SyntaxError> {
+        let hint = match found_token {
+            Kind::Identifier => self.keyword_hint(expected_token, &self.current.lexeme),
+            _ => String::new(),
+        };
         vec![SyntaxError {
             position: self.current.position,
             raw_line: self.scanner.curr_line(),
-            description: format!("Expected token: {}, found {}", expected_token, found_token),
+            description: format!(
+                "Expected token: {}, found {}{}",
+                expected_token, found_token, hint
+            ),
         }]
     }
 }
@@ -0,0 +1,97 @@
+use strsim::osa_distance;
+
+use crate::{
+    core::{errors::SyntaxError, token::Kind},
+    parser::Parser,
+    scanner::KEYWORDS,
+};
+
+/// A candidate found by `Parser::did_you_mean` for a name that could
+/// not be resolved, symbols come with where they are declared unless
+/// they are not in the source (builtins, variables of the debugger)
+enum Suggestion {
+    Symbol(String, Option<String>),
+    Keyword(&'static str),
+}
+
+/// Maximum edit distance for a candidate to be considered a typo of
+/// the given name, long names tolerate more mistakes and names of one
+/// or two characters are close to almost anything, they get no hint
+fn max_distance(name: &str) -> usize {
+    match name.chars().count() {
+        0..=2 => 0,
+        length => (length / 3).max(1),
+    }
+}
+
+impl Parser {
+    /// Looks for the closest symbol in all the visible scopes or
+    /// keyword to the given name, innermost scopes win ties over
+    /// outer ones and symbols win ties over keywords
+    fn did_you_mean(&self, name: &str) -> Option<Suggestion> {
+        let name = name.to_lowercase();
+        let threshold = max_distance(&name);
+        let mut best: Option<(usize, Suggestion)> = None;
+
+        for table in self.context.iter().rev() {
+            for sym in table.iter() {
+                let distance = osa_distance(&name, &sym.name.to_lowercase());
+                if distance <= threshold && best.as_ref().is_none_or(|(d, _)| distance < *d) {
+                    best = Some((
+                        distance,
+                        Suggestion::Symbol(
+                            sym.name.clone(),
+                            (sym.position.line > 0).then(|| sym.position.to_string()),
+                        ),
+                    ));
+                }
+            }
+        }
+
+        for (keyword, _) in KEYWORDS.iter() {
+            let distance = osa_distance(&name, keyword);
+            if distance <= threshold && best.as_ref().is_none_or(|(d, _)| distance < *d) {
+                best = Some((distance, Suggestion::Keyword(keyword)));
+            }
+        }
+
+        best.map(|(_, suggestion)| suggestion)
+    }
+
+    /// Returns the hint to append to an error about the given name,
+    /// empty if nothing close enough is known
+    pub fn hint_for(&self, name: &str) -> String {
+        match self.did_you_mean(name) {
+            Some(Suggestion::Symbol(sym, Some(position))) => {
+                format!("; did you mean `{}` (declared at {})?", sym, position)
+            }
+            Some(Suggestion::Symbol(sym, None)) => format!("; did you mean `{}`?", sym),
+            Some(Suggestion::Keyword(keyword)) => {
+                format!("; did you mean the keyword `{}`?", keyword)
+            }
+            None => String::new(),
+        }
+    }
+
+    /// Hint for an identifier found where a keyword was expected,
+    /// only given if the identifier is a misspelling of that keyword
+    pub fn keyword_hint(&self, expected: Kind, found: &str) -> String {
+        let found = found.to_lowercase();
+        match KEYWORDS.iter().find(|(_, kind)| *kind == expected) {
+            Some((keyword, _)) if osa_distance(&found, keyword) <= max_distance(&found) => {
+                format!("; did you mean `{}`?", keyword)
+            }
+            _ => String::new(),
+        }
+    }
+
+    /// Error for the current token being an identifier that is not
+    /// declared in any visible scope, `what` describes what was
+    /// expected (symbol, function, ...)
+    pub fn unknown_symbol_err(&self, what: &str) -> SyntaxError {
+        let name = self.current.lexeme.clone();
+        self.error_at_current(
+            format!("unknown {} `{}`{}", what, name, self.hint_for(&name)).as_str(),
+        )
+    }
+}
@@ -1,14 +1,10 @@
 use log::trace;
 
-use crate::{
-    core::{
-        ast::{ASTNode, ArrayRefExpr, VarNameNode},
-        errors::SyntaxError,
-        symbol_table::SymbolType,
-        token::Kind,
-        types::Type,
-    },
-    current_with_expected,
+use crate::core::{
+    ast::{ASTNode, VarNameNode},
+    errors::SyntaxError,
+    symbol_table::SymbolType,
+    token::Kind,
 };
 
 use super::Parser;
@@ -18,24 +14,19 @@ impl Parser {
         trace!("parsing symbol");
         let id = self.current.clone();
         trace!("parsing symbol {:?}", self.get_symbol(id.lexeme.clone()));
+        self.capture(&id.lexeme, false);
         match self.get_symbol(id.lexeme.clone()) {
             Some(symbol) => match symbol.s_type {
-                SymbolType::Function => self.parse_function_call(),
+                SymbolType::Function | SymbolType::Builtin => self.parse_function_call(),
                 SymbolType::Procedure => self.parse_procedure_call(),
+                SymbolType::Record => Err(vec![self
+                    .error_at_current(&format!("`{}` is a record type, not a value", id.lexeme))]),
+                SymbolType::Const => match self.constant(&id) {
+                    Some(value) => Ok(ASTNode::Literal(value)),
+                    None => Err(vec![self.unknown_symbol_err("constant")]),
+                },
                 SymbolType::Arr => match self.advance().kind {
-                    Kind::LeftSquare => {
-                        let xpr = self.parse_expression()?;
-                        current_with_expected!(
-                            Kind::RightSquare,
-                            self,
-                            Ok(ASTNode::ArrayRef(ArrayRefExpr {
-                                position: id.position,
-                                array: id,
-                                index: Box::new(xpr),
-                                r_type: Type::Simple(symbol.r_type.internal()),
-                            }))
-                        )
-                    }
+                    Kind::LeftSquare => self.parse_indexes(id, symbol.r_type),
                     _ => {
                         self.go_back();
                         Ok(ASTNode::VarName(VarNameNode {
@@ -53,9 +44,7 @@ impl Parser {
                     s_type: symbol.s_type,
                 })),
             },
-            None => Err(vec![self.error_at_current(
-                format!("Unknown symbol: {}", self.current.lexeme).as_str(),
-            )]),
+            None => Err(vec![self.unknown_symbol_err("symbol")]),
         }
     }
 }
@@ -1,43 +1,92 @@
 use log::trace;
 
 use crate::advance_with_expected;
+use crate::core::ast::LiteralExprNode;
+use crate::core::objects::Object;
+use crate::core::symbol_table::SymbolType;
 use crate::core::types::{SimpleType, Type};
 use crate::core::{errors::SyntaxError, token::Kind};
 
 use super::Parser;
 
 impl Parser {
-    fn parse_array_type(&mut self) -> Result<Type, Vec<SyntaxError>> {
+    /// Parses `[<size>] of <type>`, the size is None if it does not
+    /// fit in a usize. The elements can be arrays, whose size must be
+    /// known
+    fn parse_array_type(&mut self) -> Result<(Type, Option<usize>), Vec<SyntaxError>> {
         trace!("parsing type array");
-        advance_with_expected!(
-            Kind::LeftSquare,
-            self,
-            advance_with_expected!(
-                Kind::LitInt,
-                self,
+        advance_with_expected!(Kind::LeftSquare, self, {
+            self.advance();
+            let size = self.parse_array_size()?;
+            {
                 advance_with_expected!(
                     Kind::RightSquare,
                     self,
                     advance_with_expected!(Kind::Of, self, {
-                        let internal = self.parse_type()?.internal();
-                        Ok(Type::Array(internal))
+                        let element = self.parse_type()?;
+                        if let Type::Array(_, None) = element {
+                            return Err(vec![
+                                self.error_at_current("Size of the nested array is too large")
+                            ]);
+                        }
+                        Ok((Type::Array(Box::new(element), size), size))
                     })
                 )
-            )
-        )
+            }
+        })
     }
 
-    pub fn parse_type(&mut self) -> Result<Type, Vec<SyntaxError>> {
+    /// Size of an array type, either an int literal or an int
+    /// constant. None if it does not fit in a usize
+    fn parse_array_size(&mut self) -> Result<Option<usize>, Vec<SyntaxError>> {
+        match self.current.kind {
+            Kind::LitInt => Ok(self.current.lexeme.parse::<usize>().ok()),
+            Kind::Identifier => match self.constant(&self.current) {
+                Some(LiteralExprNode {
+                    value: Object::Int(n),
+                    ..
+                }) if n >= 0 => Ok(usize::try_from(n).ok()),
+                Some(LiteralExprNode {
+                    value: Object::Int(_),
+                    ..
+                }) => Err(vec![self.error_at_current(&format!(
+                    "Size of the array is `{}`, which is negative",
+                    self.current.lexeme
+                ))]),
+                _ => Err(vec![self.error_at_current(&format!(
+                    "Size of the array must be an int literal or an int constant, found `{}`",
+                    self.current.lexeme
+                ))]),
+            },
+            other => Err(self.unexpected_token_err(Kind::LitInt, other)),
+        }
+    }
+
+    /// Parses a type, together with the declared size if it is an
+    /// array type
+    pub fn parse_sized_type(&mut self) -> Result<(Type, Option<usize>), Vec<SyntaxError>> {
         trace!("parsing type");
         match self.advance().kind {
-            Kind::TString => Ok(Type::Simple(SimpleType::String)),
-            Kind::TInt => Ok(Type::Simple(SimpleType::Int)),
-            Kind::TReal => Ok(Type::Simple(SimpleType::Real)),
+            Kind::TString => Ok((Type::Simple(SimpleType::String), None)),
+            Kind::TInt => Ok((Type::Simple(SimpleType::Int), None)),
+            Kind::TReal => Ok((Type::Simple(SimpleType::Real), None)),
             Kind::TArray => self.parse_array_type(),
-            Kind::TBool => Ok(Type::Simple(SimpleType::Bool)),
+            Kind::TBool => Ok((Type::Simple(SimpleType::Bool), None)),
+            // records are declared in the global scope
+            Kind::Identifier => match self.context[0].get(self.current.lexeme.clone()) {
+                Some(sym) if sym.s_type == SymbolType::Record => Ok((sym.r_type, None)),
+                _ => Err(vec![self.error_at_current(&format!(
+                    "Expected type declaration, found `{}` which is not a record type",
+                    self.current.lexeme
+                ))]),
+            },
             other => Err(vec![self.error_at_current(
                 format!("Expected type declaration, found {}", other).as_str(),
             )]),
         }
     }
+
+    pub fn parse_type(&mut self) -> Result<Type, Vec<SyntaxError>> {
+        Ok(self.parse_sized_type()?.0)
+    }
 }
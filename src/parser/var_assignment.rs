@@ -1,12 +1,11 @@
 use log::trace;
 
 use crate::{
-    advance_with_expected,
     core::{
-        ast::{ASTNode, ArrayRefExpr, VarNameNode, VarReassignmentExprNode},
+        ast::{ASTNode, VarNameNode, VarReassignmentExprNode},
         errors::SyntaxError,
         symbol_table::SymbolType,
-        token::Kind,
+        token::{Kind, Token},
         types::Type,
     },
     current_with_expected,
@@ -19,39 +18,42 @@ impl Parser {
     pub fn parse_var_assignment(&mut self) -> Result<ASTNode, Vec<SyntaxError>> {
         trace!("parsing var assignment");
         let id = self.current.clone(); // the current token is the identifier of the variable
+        self.capture(&id.lexeme, true);
         match self.get_symbol(id.lexeme.clone()) {
             Some(sym) if sym.s_type == SymbolType::Arr => {
-                let nested_type = match sym.r_type {
-                    Type::Simple(_) => panic!("Mismatching symbol type and effective type"),
-                    Type::Array(s) => s,
-                };
+                if let Type::Simple(_) = &sym.r_type {
+                    return Err(vec![self.error_at_current(&format!(
+                        "`{}` is declared as an array but has type {}",
+                        id.lexeme, sym.r_type
+                    ))]);
+                }
                 match self.advance().kind {
                     Kind::LeftSquare => {
-                        let pos = self.parse_unary()?;
-                        current_with_expected!(
-                            Kind::RightSquare,
-                            self,
-                            advance_with_expected!(Kind::ColonEqual, self, {
-                                let new_val = self.parse_expression()?;
-                                Ok(ASTNode::VarReassignment(VarReassignmentExprNode {
-                                    position: id.position,
-                                    variable_to_reassign: Box::new(ASTNode::ArrayRef(
-                                        ArrayRefExpr {
-                                            position: id.position,
-                                            array: id,
-                                            index: Box::new(pos),
-                                            r_type: sym.r_type,
-                                        },
-                                    )),
-                                    new_value: Box::new(new_val),
-                                }))
-                            })
-                        )
+                        let target = self.parse_indexes(id.clone(), sym.r_type.clone())?;
+                        self.advance();
+                        if self.matches(Kind::Dot) {
+                            let target = self.parse_fields(target)?;
+                            return self.parse_field_assignment(id, target);
+                        }
+                        if let Type::Array(..) = target.r_type() {
+                            return Err(vec![self.error_at_current(&format!(
+                                "Cannot assign a whole row of `{}`, assign its elements",
+                                id.lexeme
+                            ))]);
+                        }
+                        current_with_expected!(Kind::ColonEqual, self, {
+                            let new_val = self.parse_expression()?;
+                            Ok(ASTNode::VarReassignment(VarReassignmentExprNode {
+                                position: id.position,
+                                variable_to_reassign: Box::new(target),
+                                new_value: Box::new(new_val),
+                            }))
+                        })
                     }
                     Kind::ColonEqual => {
                         self.advance();
                         let new_arr = self.parse_array()?;
-                        if new_arr.r_type().internal() != nested_type {
+                        if new_arr.r_type() != sym.r_type {
                             Err(vec![
                                 self.error_at_current("mismatching types in array assignment")
                             ])
@@ -79,15 +81,36 @@ impl Parser {
                 }
             }
             Some(sym)
-                if sym.s_type == SymbolType::Function || sym.s_type == SymbolType::Procedure =>
+                if matches!(
+                    sym.s_type,
+                    SymbolType::Function | SymbolType::Procedure | SymbolType::Builtin
+                ) =>
             {
                 Err(vec![self.error_at_current(
                     "Symbol alread exists and is associated either to a funciton or to a procedure",
                 )])
             }
+            Some(sym) if sym.s_type == SymbolType::Const => Err(vec![self.error_at_current(
+                &format!("`{}` is a constant, it cannot be assigned", id.lexeme),
+            )]),
+            Some(sym) if sym.s_type == SymbolType::Record => {
+                Err(vec![self.error_at_current(&format!(
+                    "`{}` is a record type, not a variable",
+                    id.lexeme
+                ))])
+            }
             Some(sym) => {
                 trace!("symbol found: {}", sym);
-                advance_with_expected!(Kind::ColonEqual, self, {
+                if self.advance().kind == Kind::Dot {
+                    let target = self.parse_fields(ASTNode::VarName(VarNameNode {
+                        position: id.position,
+                        id: id.clone(),
+                        r_type: sym.r_type,
+                        s_type: sym.s_type,
+                    }))?;
+                    return self.parse_field_assignment(id, target);
+                }
+                current_with_expected!(Kind::ColonEqual, self, {
                     let new_val = self.parse_expression()?;
                     if new_val.r_type() == sym.r_type {
                         Ok(ASTNode::VarReassignment(VarReassignmentExprNode {
@@ -107,9 +130,32 @@ impl Parser {
                     }
                 })
             }
-            None => Err(vec![self.error_at_current(
-                format!("Use of undeclared variable {}", id.lexeme).as_str(),
-            )]),
+            None => Err(vec![self.unknown_symbol_err("variable")]),
         }
     }
+
+    /// Assignment to a field of a record, the current token is the
+    /// one after the field
+    fn parse_field_assignment(
+        &mut self,
+        id: Token,
+        target: ASTNode,
+    ) -> Result<ASTNode, Vec<SyntaxError>> {
+        current_with_expected!(Kind::ColonEqual, self, {
+            let new_val = self.parse_expression()?;
+            if new_val.r_type() == target.r_type() {
+                Ok(ASTNode::VarReassignment(VarReassignmentExprNode {
+                    position: id.position,
+                    variable_to_reassign: Box::new(target),
+                    new_value: Box::new(new_val),
+                }))
+            } else {
+                Err(vec![self.error_at_current(&format!(
+                    "Mismatching types in field assignment, expected {} found {}",
+                    target.r_type(),
+                    new_val.r_type()
+                ))])
+            }
+        })
+    }
 }
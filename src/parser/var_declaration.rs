@@ -3,7 +3,7 @@ use log::trace;
 use crate::{
     advance_with_expected,
     core::{ast::*, errors::SyntaxError, symbol_table::*, token::Kind, types::Type},
-    parser::Parser,
+    parser::{builtin::declared_at, Parser},
     scanner::position::Position,
 };
 
@@ -19,11 +19,20 @@ impl Parser {
     ) -> Result<(), Vec<SyntaxError>> {
         trace!("declaring variable {} of type {}", lexeme, r_type);
         match self.context.pop() {
+            Some(table) if !self.context.is_empty() && self.hidden_global(&lexeme).is_some() => {
+                self.context.push(table);
+                Err(vec![self.global_hidden_err(&lexeme)])
+            }
+            Some(table) if self.hidden_local(&lexeme, self.context.len()).is_some() => {
+                self.context.push(table);
+                Err(vec![self.local_hidden_err(&lexeme)])
+            }
             Some(mut table) => match table.get(lexeme.clone()) {
                 Some(v) => Err(vec![self.error_at_current(
                     format!(
-			"Declaration of an already declared variable: {}, previously declared at {}",
-			lexeme, v.position
+                        "Declaration of an already declared variable: {}, {}",
+                        lexeme,
+                        declared_at(&v)
                     )
                     .as_str(),
                 )]),
@@ -31,7 +40,7 @@ impl Parser {
                     table.push(Symbol {
                         name: lexeme,
                         s_type: match r_type {
-                            Type::Array(_) => SymbolType::Arr,
+                            Type::Array(..) => SymbolType::Arr,
                             Type::Simple(_) => SymbolType::Var,
                         },
                         r_type,
@@ -56,14 +65,14 @@ impl Parser {
         advance_with_expected!(Kind::Identifier, self, {
             let id = self.current.clone();
             advance_with_expected!(Kind::Colon, self, {
-                let var_type = self.parse_type()?;
+                let (var_type, size) = self.parse_sized_type()?;
                 advance_with_expected!(Kind::Semicolon, self, {
-                    self.declare_variable(id.clone().lexeme, var_type, id.position)?;
+                    self.declare_variable(id.clone().lexeme, var_type.clone(), id.position)?;
                     Ok(ASTNode::VariableDecl(VariableDeclNode {
                         position: id.position,
                         id,
                         var_type,
-                        value: None,
+                        size,
                     }))
                 })
             })
@@ -24,7 +24,10 @@ impl Parser {
                 self,
                 advance_with_expected!(Kind::Begin, self, {
                     self.context.push(SymbolTable::new());
-                    let block = self.parse_block()?;
+                    self.loops.push(Kind::While);
+                    let block = self.parse_block();
+                    self.loops.pop();
+                    let block = block?;
                     Ok(ASTNode::WhileStmt(WhileStmtNode {
                         position: while_token.position,
                         guard: Box::new(expr),
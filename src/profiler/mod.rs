@@ -0,0 +1,280 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::{BufRead, Write},
+    time::{Duration, Instant},
+};
+
+use serde_json::{json, Value};
+
+use crate::{
+    core::{ast::ASTNode, ast::ProgramNode, errors::EvaluationError},
+    interpreter::{Hook, Interpreter},
+};
+
+/// What happened on a line of the source
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LineProfile {
+    /// Statements of the line that ran
+    pub count: u64,
+    /// Time from its statements to the next statement that ran
+    pub time: Duration,
+}
+
+/// What happened in a function or procedure, the main block is one too
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CallProfile {
+    pub calls: u64,
+    /// Statements that ran in it, not in what it called
+    pub statements: u64,
+    /// Time in it and in what it called, recursive calls are counted
+    /// once
+    pub total: Duration,
+    /// Time in it, not in what it called
+    pub own: Duration,
+}
+
+/// A running call
+#[derive(Debug)]
+struct Running {
+    name: String,
+    start: Instant,
+    /// Time spent in the calls it made
+    children: Duration,
+    /// Line of the statement that made the call
+    caller: Option<i64>,
+}
+
+/// Profiler over the interpreter, it is its `Hook`. Counts the
+/// statements run on every line and in every subprogram, the calls,
+/// and the time spent on them. The statements run are counted for
+/// every chain of calls too, that is what the collapsed stacks (the
+/// input of flamegraph tools) report
+pub struct Profiler {
+    program: String,
+    source: Vec<String>,
+    lines: BTreeMap<i64, LineProfile>,
+    subprograms: HashMap<String, CallProfile>,
+    /// Statements run by chain of calls, the names joined by `;`
+    stacks: HashMap<String, u64>,
+    stack: String,
+    running: Vec<Running>,
+    /// Line of the last statement and when it started
+    last: Option<(i64, Instant)>,
+}
+
+/// Milliseconds, with the microseconds
+fn millis(time: Duration) -> String {
+    format!("{:.3}ms", time.as_secs_f64() * 1000.0)
+}
+
+impl Profiler {
+    pub fn new(program: &ProgramNode, source: &str) -> Profiler {
+        Profiler {
+            program: program.program_name.name.lexeme.clone(),
+            source: source.lines().map(|l| l.trim().to_string()).collect(),
+            lines: BTreeMap::new(),
+            subprograms: HashMap::new(),
+            stacks: HashMap::new(),
+            stack: String::new(),
+            running: vec![],
+            last: None,
+        }
+    }
+
+    /// Runs the program in the interpreter, profiling it. What ran
+    /// until an error is still in the profile
+    pub fn run(
+        &mut self,
+        program: &ProgramNode,
+        input: &mut dyn BufRead,
+        output: &mut dyn Write,
+    ) -> Result<(), EvaluationError> {
+        self.enter(program.program_name.name.lexeme.clone());
+        let mut interpreter = Interpreter::new(input, output);
+        interpreter.set_hook(self);
+        let result = interpreter.run(program);
+        drop(interpreter);
+        while !self.running.is_empty() {
+            self.leave();
+        }
+        result
+    }
+
+    /// Statements and time of every line that ran
+    pub fn lines(&self) -> &BTreeMap<i64, LineProfile> {
+        &self.lines
+    }
+
+    /// Calls, statements and time of every subprogram that ran, and of
+    /// the main block
+    pub fn subprograms(&self) -> &HashMap<String, CallProfile> {
+        &self.subprograms
+    }
+
+    /// Gives the time since the last statement to its line
+    fn tick(&mut self, now: Instant) {
+        if let Some((line, start)) = self.last {
+            self.lines.entry(line).or_default().time += now - start;
+        }
+    }
+
+    fn enter(&mut self, name: String) {
+        self.subprograms.entry(name.clone()).or_default().calls += 1;
+        if !self.stack.is_empty() {
+            self.stack.push(';');
+        }
+        self.stack.push_str(&name);
+        self.running.push(Running {
+            name,
+            start: Instant::now(),
+            children: Duration::ZERO,
+            caller: self.last.map(|(line, _)| line),
+        });
+    }
+
+    fn leave(&mut self) {
+        let now = Instant::now();
+        self.tick(now);
+        let call = match self.running.pop() {
+            Some(call) => call,
+            None => return,
+        };
+        // the time after the return is for the line of the call
+        self.last = call.caller.map(|line| (line, now));
+        let elapsed = now - call.start;
+        let recursive = self.running.iter().any(|r| r.name == call.name);
+        let profile = self.subprograms.entry(call.name.clone()).or_default();
+        profile.own += elapsed.saturating_sub(call.children);
+        if !recursive {
+            profile.total += elapsed;
+        }
+        if let Some(caller) = self.running.last_mut() {
+            caller.children += elapsed;
+        }
+        let len = self.stack.len() - call.name.len();
+        self.stack.truncate(len.saturating_sub(1));
+    }
+
+    /// Lines sorted by the statements run, then subprograms sorted by
+    /// the statements run in them
+    pub fn report(&self) -> String {
+        let mut lines: Vec<_> = self.lines.iter().collect();
+        lines.sort_by(|a, b| b.1.count.cmp(&a.1.count).then(a.0.cmp(b.0)));
+        let mut report = format!(
+            "{:>6} {:>12} {:>14}  source\n",
+            "line", "statements", "time"
+        );
+        for (line, profile) in lines {
+            let source = self.source.get((*line - 1) as usize);
+            report += &format!(
+                "{:>6} {:>12} {:>14}  {}\n",
+                line,
+                profile.count,
+                millis(profile.time),
+                source.map(String::as_str).unwrap_or_default()
+            );
+        }
+        report += &format!(
+            "\n{:<20} {:>10} {:>12} {:>14} {:>14}\n",
+            "subprogram", "calls", "statements", "total", "self"
+        );
+        for (name, profile) in self.sorted_subprograms() {
+            report += &format!(
+                "{:<20} {:>10} {:>12} {:>14} {:>14}\n",
+                name,
+                profile.calls,
+                profile.statements,
+                millis(profile.total),
+                millis(profile.own)
+            );
+        }
+        report
+    }
+
+    fn sorted_subprograms(&self) -> Vec<(&String, &CallProfile)> {
+        let mut subprograms: Vec<_> = self.subprograms.iter().collect();
+        subprograms.sort_by(|a, b| b.1.statements.cmp(&a.1.statements).then(a.0.cmp(b.0)));
+        subprograms
+    }
+
+    /// The profile as JSON, times in nanoseconds
+    pub fn to_json(&self) -> Value {
+        let lines: Vec<Value> = self
+            .lines
+            .iter()
+            .map(|(line, profile)| {
+                json!({
+                    "line": line,
+                    "statements": profile.count,
+                    "time_ns": profile.time.as_nanos() as u64,
+                    "source": self.source.get((*line - 1) as usize),
+                })
+            })
+            .collect();
+        let subprograms: Vec<Value> = self
+            .sorted_subprograms()
+            .into_iter()
+            .map(|(name, profile)| {
+                json!({
+                    "name": name,
+                    "calls": profile.calls,
+                    "statements": profile.statements,
+                    "total_ns": profile.total.as_nanos() as u64,
+                    "self_ns": profile.own.as_nanos() as u64,
+                })
+            })
+            .collect();
+        json!({
+            "program": self.program,
+            "lines": lines,
+            "subprograms": subprograms,
+        })
+    }
+
+    /// One line per chain of calls with the statements run in it, the
+    /// format of `flamegraph.pl` and `inferno`
+    pub fn collapsed(&self) -> String {
+        let mut stacks: Vec<_> = self.stacks.iter().collect();
+        stacks.sort();
+        stacks
+            .into_iter()
+            .map(|(stack, count)| format!("{} {}\n", stack, count))
+            .collect()
+    }
+}
+
+impl Hook for Profiler {
+    fn statement(
+        &mut self,
+        _interpreter: &mut Interpreter<'_>,
+        node: &ASTNode,
+    ) -> Result<(), EvaluationError> {
+        let now = Instant::now();
+        self.tick(now);
+        let line = node.position().line;
+        self.lines.entry(line).or_default().count += 1;
+        self.last = Some((line, now));
+        if let Some(call) = self.running.last() {
+            if let Some(profile) = self.subprograms.get_mut(&call.name) {
+                profile.statements += 1;
+            }
+        }
+        match self.stacks.get_mut(&self.stack) {
+            Some(count) => *count += 1,
+            None => {
+                self.stacks.insert(self.stack.clone(), 1);
+            }
+        }
+        Ok(())
+    }
+
+    fn call(&mut self, interpreter: &mut Interpreter<'_>) {
+        if let Some(activation) = interpreter.call_stack().last() {
+            self.enter(activation.name.clone());
+        }
+    }
+
+    fn ret(&mut self, _interpreter: &mut Interpreter<'_>) {
+        self.leave();
+    }
+}
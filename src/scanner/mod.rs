@@ -4,6 +4,51 @@ use crate::core::errors::SyntaxError;
 use crate::core::token::{Kind, Token};
 use crate::scanner::position::Position;
 
+/// Reserved words of the language together with the token kind they
+/// are scanned as, anything else made of word characters is an
+/// identifier
+pub const KEYWORDS: &[(&str, Kind)] = &[
+    ("var", Kind::Var),
+    ("in", Kind::In),
+    ("do", Kind::Do),
+    ("end", Kind::End),
+    ("begin", Kind::Begin),
+    ("read", Kind::Read),
+    ("writeln", Kind::Print),
+    ("write", Kind::Write),
+    ("assert", Kind::Assert),
+    ("int", Kind::TInt),
+    ("real", Kind::TReal),
+    ("string", Kind::TString),
+    ("bool", Kind::TBool),
+    ("if", Kind::If),
+    ("then", Kind::Then),
+    ("else", Kind::Else),
+    ("false", Kind::False),
+    ("true", Kind::True),
+    ("program", Kind::Program),
+    ("function", Kind::Function),
+    ("procedure", Kind::Procedure),
+    ("array", Kind::TArray),
+    ("of", Kind::Of),
+    ("return", Kind::Return),
+    ("while", Kind::While),
+    ("for", Kind::For),
+    ("to", Kind::To),
+    ("downto", Kind::Downto),
+    ("repeat", Kind::Repeat),
+    ("until", Kind::Until),
+    ("break", Kind::Break),
+    ("continue", Kind::Continue),
+    ("case", Kind::Case),
+    ("type", Kind::Type),
+    ("record", Kind::Record),
+    ("const", Kind::Const),
+    ("forward", Kind::Forward),
+    ("or", Kind::Or),
+    ("and", Kind::And),
+];
+
 #[derive(Debug)]
 pub struct Scanner {
     source: Vec<char>,
@@ -11,6 +56,7 @@ pub struct Scanner {
     line_num: usize,
     line_start: usize,
     init: bool,
+    pragmas: Vec<Token>,
 }
 
 impl Scanner {
@@ -22,6 +68,7 @@ impl Scanner {
             line_num: 1,
             line_start: 0,
             init: true,
+            pragmas: Vec::new(),
         }
     }
 
@@ -65,7 +112,7 @@ impl Scanner {
     /// Goes back by one character
     pub fn go_back(&mut self) {
         if !self.init {
-            self.current -= 1;
+            self.current = self.current.saturating_sub(1);
         }
     }
 
@@ -195,7 +242,7 @@ impl Scanner {
         let mut sign_found = false;
         while let Some(c) = self.advance() {
             match c {
-                c if c.is_digit(10) => digit.push(c),
+                c if c.is_ascii_digit() => digit.push(c),
                 '.' => {
                     digit.push('.');
                     is_real = true;
@@ -224,34 +271,9 @@ impl Scanner {
     /// required an identifier, therefore starts to recognise an
     /// identifier
     fn find_word(&mut self, word: String) -> Result<Token, SyntaxError> {
-        match word.as_str() {
-            "var" => Ok(self.gen_token(Kind::Var, word)),
-            "in" => Ok(self.gen_token(Kind::In, word)),
-            "do" => Ok(self.gen_token(Kind::Do, word)),
-            "end" => Ok(self.gen_token(Kind::End, word)),
-            "begin" => Ok(self.gen_token(Kind::Begin, word)),
-            "read" => Ok(self.gen_token(Kind::Read, word)),
-            "writeln" => Ok(self.gen_token(Kind::Print, word)),
-            "assert" => Ok(self.gen_token(Kind::Assert, word)),
-            "int" => Ok(self.gen_token(Kind::TInt, word)),
-            "real" => Ok(self.gen_token(Kind::TReal, word)),
-            "string" => Ok(self.gen_token(Kind::TString, word)),
-            "bool" => Ok(self.gen_token(Kind::TBool, word)),
-            "if" => Ok(self.gen_token(Kind::If, word)),
-            "then" => Ok(self.gen_token(Kind::Then, word)),
-            "else" => Ok(self.gen_token(Kind::Else, word)),
-            "false" => Ok(self.gen_token(Kind::False, word)),
-            "true" => Ok(self.gen_token(Kind::True, word)),
-            "program" => Ok(self.gen_token(Kind::Program, word)),
-            "function" => Ok(self.gen_token(Kind::Function, word)),
-            "procedure" => Ok(self.gen_token(Kind::Procedure, word)),
-            "array" => Ok(self.gen_token(Kind::TArray, word)),
-            "of" => Ok(self.gen_token(Kind::Of, word)),
-            "return" => Ok(self.gen_token(Kind::Return, word)),
-            "while" => Ok(self.gen_token(Kind::While, word)),
-            "or" => Ok(self.gen_token(Kind::Or, word)),
-            "and" => Ok(self.gen_token(Kind::And, word)),
-            _ => Ok(self.gen_token(Kind::Identifier, word)),
+        match KEYWORDS.iter().find(|(keyword, _)| *keyword == word) {
+            Some((_, kind)) => Ok(self.gen_token(*kind, word)),
+            None => Ok(self.gen_token(Kind::Identifier, word)),
         }
     }
 
@@ -362,7 +384,7 @@ impl Scanner {
 
                 '"' => self.string(),
 
-                c if { c.is_digit(10) } => self.digits(),
+                c if { c.is_ascii_digit() } => self.digits(),
 
                 c if { c.is_alphanumeric() } => self.words(),
 
@@ -376,6 +398,9 @@ impl Scanner {
                             _ => erroneous.push(nc),
                         }
                     }
+                    // Leave the whitespace to the next scan, so that
+                    // line ends are still counted
+                    self.go_back();
                     Err(SyntaxError::new(
                         self.position(),
                         self.curr_line(),
@@ -391,23 +416,47 @@ impl Scanner {
         }
     }
 
-    /// Returns next token based on the scan, wich could recognise
-    /// also whitespace tokens
+    /// Pragma comments (`{* pragma ... *}`) found so far, the lexeme
+    /// of each token is the text following the `pragma` keyword
+    pub fn pragmas(&self) -> &[Token] {
+        &self.pragmas
+    }
+
+    /// Returns the next token, trivia included: whitespaces, line
+    /// ends and comments
+    pub fn next_token_with_trivia(&mut self) -> Result<Token, SyntaxError> {
+        let token = self.scan_token()?;
+        match token.kind {
+            Kind::Eol => {
+                self.line_num += 1;
+                self.line_start = self.current + 1;
+            }
+            Kind::Comment => {
+                let text = token.lexeme.trim_start_matches(['/', '*']).trim_start();
+                if let Some(pragma) = text.strip_prefix("pragma ") {
+                    self.pragmas.push(Token {
+                        kind: Kind::Comment,
+                        lexeme: pragma.trim().to_string(),
+                        position: token.position,
+                    });
+                }
+            }
+            _ => {}
+        }
+        Ok(token)
+    }
+
+    /// Returns next token based on the scan, skipping whitespaces,
+    /// line ends and comments
     pub fn next_token(&mut self) -> Result<Token, SyntaxError> {
         loop {
             /* invariant: at some point scan_token returns a token or
              * a syntax error, either a real token or a Eof since it
              * is at the end of file */
-            match self.scan_token() {
-                Ok(token) => match token.kind {
-                    Kind::Eol => {
-                        self.line_num += 1;
-                        self.line_start = self.current + 1;
-                    }
-                    Kind::Whites | Kind::Comment => {}
-                    _ => return Ok(token),
-                },
-                Err(syn_error) => return Err(syn_error),
+            let token = self.next_token_with_trivia()?;
+            match token.kind {
+                Kind::Eol | Kind::Comment | Kind::Whites => {}
+                _ => return Ok(token),
             }
         }
     }
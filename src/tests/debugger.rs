@@ -0,0 +1,103 @@
+use crate::{core::ast::ASTNode, debugger::Debugger, parser::Parser};
+
+const PROGRAM: &str = "program dbg;
+function sum(n : int) : int;
+begin
+  var total : int;
+  var i : int;
+  total := 0;
+  i := 1;
+  while i <= n do
+  begin
+    total := total + i;
+    i := i + 1;
+  end
+  return total;
+end
+begin
+  var k : int;
+  read(k);
+  writeln(sum(k));
+  writeln(sum(2));
+end
+";
+
+/// Runs `PROGRAM` on the input under the debugger with the given
+/// commands, returns what the debugger and the program printed
+fn session(commands: &str, stdin: &str) -> (String, String) {
+    let program = match Parser::new(PROGRAM.to_string()).parse() {
+        Ok(ASTNode::Program(program)) => program,
+        other => panic!("Expected a program, got {:?}", other),
+    };
+    let mut commands = commands.as_bytes();
+    let mut out = Vec::new();
+    let mut input = stdin.as_bytes();
+    let mut output = Vec::new();
+    Debugger::new(&program, PROGRAM, &mut commands, &mut out)
+        .run(&program, &mut input, &mut output)
+        .unwrap();
+    (
+        String::from_utf8(out).unwrap(),
+        String::from_utf8(output).unwrap(),
+    )
+}
+
+#[test]
+fn without_commands_the_program_runs_to_the_end() {
+    let (out, output) = session("", "3");
+    assert!(out.starts_with("  16   var k : int;\n"));
+    assert_eq!(output, "6\n3\n");
+}
+
+#[test]
+fn quit_stops_the_program() {
+    let (_, output) = session("b 19\nc\nq\n", "3");
+    assert_eq!(output, "6\n");
+}
+
+#[test]
+fn breakpoints_stop_in_calls() {
+    let (out, _) = session("b 10\nc\nbt\nlocals\np n * 2\nc\np total\n", "3");
+    assert!(out.contains("Breakpoint at line 10\n  10     total := total + i;\n"));
+    assert!(out.contains("#0 sum at line 10\n#1 main block at line 18\n"));
+    assert!(out.contains("i = 1\ntotal = 0\nn = 3\n"));
+    assert!(out.contains("n * 2 = 6\n"));
+    assert!(out.contains("total = 1\n"));
+}
+
+#[test]
+fn next_goes_over_calls_and_step_into_them() {
+    let (out, _) = session("n\nn\nn\n", "3");
+    assert!(out.contains("  18   writeln(sum(k));\n(mpdb)   19   writeln(sum(2));"));
+    let (out, _) = session("n\nn\ns\nfinish\n", "3");
+    assert!(out.contains("   4   var total : int;\n"));
+    assert!(out.contains("  19   writeln(sum(2));"));
+}
+
+#[test]
+fn watches_stop_when_they_change() {
+    let (out, _) = session("b 10\nc\nw total\nc\n", "3");
+    assert!(out.contains("Watch 1: total\nOld value = 0\nNew value = 1\n  11     i := i + 1;"));
+}
+
+#[test]
+fn watches_are_only_checked_in_their_frame() {
+    let (out, output) = session("b 18\nc\nw k\nc\n", "3");
+    assert!(out.contains("Watch 1: k = 3\n"));
+    assert!(!out.contains("Old value"));
+    assert_eq!(output, "6\n3\n");
+}
+
+#[test]
+fn hints_leave_out_unknown_positions() {
+    let (out, _) = session("b 10\nc\np totl\n", "3");
+    assert!(out.contains("did you mean `total`?"));
+}
+
+#[test]
+fn bad_expressions_are_reported() {
+    let (out, output) = session("p nope\np k +\np k = \"a\"\nb 99\n", "3");
+    assert!(out.contains("unknown symbol"));
+    assert!(out.contains("No line `99` in the program"));
+    assert_eq!(output, "6\n3\n");
+}
@@ -0,0 +1,52 @@
+use crate::{
+    compiler::{Compiler, State},
+    core::ast::ASTNode,
+    difftest::{generator::Generator, interpret, virtual_machine},
+    vm,
+};
+
+#[test]
+fn generated_programs_compile() {
+    for seed in 0..200 {
+        let generated = Generator::program(seed);
+        let mut compiler = Compiler::new();
+        match compiler.analyse(generated.source.clone()) {
+            Ok(ast) => compiler.compile_ast(ast),
+            Err(errs) => panic!("seed {} rejected: {:?}\n{}", seed, errs, generated.source),
+        }
+        assert!(
+            matches!(compiler.state, State::Sane),
+            "seed {} rejected: {:?}\n{}",
+            seed,
+            compiler.c_errors,
+            generated.source
+        );
+    }
+}
+
+#[test]
+fn same_seed_same_program() {
+    assert_eq!(Generator::program(7).source, Generator::program(7).source);
+    assert_ne!(Generator::program(7).source, Generator::program(8).source);
+}
+
+#[test]
+fn virtual_machine_agrees_with_the_interpreter() {
+    for seed in 0..50 {
+        let generated = Generator::program(seed);
+        let ast = Compiler::new()
+            .analyse(generated.source.clone())
+            .unwrap_or_else(|errs| panic!("seed {} rejected: {:?}", seed, errs));
+        let module = match &ast {
+            ASTNode::Program(program) => vm::compile(program).unwrap(),
+            other => panic!("Expected a program, got {:?}", other),
+        };
+        assert_eq!(
+            interpret(ast, &generated.stdin),
+            virtual_machine(&module, &generated.stdin),
+            "seed {}\n{}",
+            seed,
+            generated.source
+        );
+    }
+}
@@ -0,0 +1,159 @@
+use proptest::prelude::*;
+
+use crate::{
+    compiler::Compiler,
+    difftest::generator::Generator,
+    parser::{Parser, MAX_NESTING},
+};
+
+/// Pieces of MiniPL the grammar shaped inputs are made of
+const VOCABULARY: &[&str] = &[
+    "program",
+    "p",
+    "x",
+    "y",
+    "f",
+    "function",
+    "procedure",
+    "var",
+    "begin",
+    "end",
+    "if",
+    "then",
+    "else",
+    "while",
+    "for",
+    "to",
+    "downto",
+    "repeat",
+    "until",
+    "break",
+    "continue",
+    "case",
+    "type",
+    "record",
+    "const",
+    "forward",
+    "do",
+    "return",
+    "read",
+    "writeln",
+    "write",
+    "assert",
+    "array",
+    "of",
+    "int",
+    "real",
+    "string",
+    "bool",
+    "true",
+    "false",
+    "and",
+    "or",
+    "!",
+    "(",
+    ")",
+    "[",
+    "]",
+    ":",
+    ";",
+    ",",
+    ".",
+    ":=",
+    "=",
+    "<",
+    "<=",
+    ">",
+    ">=",
+    "+",
+    "-",
+    "*",
+    "/",
+    "0",
+    "1",
+    "42",
+    "1.5",
+    "1e3",
+    "99999999999999999999999",
+    "\"s\"",
+    "\"",
+    "{*",
+    "*}",
+    "$",
+    "\n",
+];
+
+/// Feeds the source to the parser and to the whole compiler, any panic
+/// fails the test
+fn compile(source: &str) {
+    let _ = Parser::new(source.to_string()).parse();
+    let _ = Compiler::new().compile(source.to_string());
+}
+
+fn tokens() -> impl Strategy<Value = String> {
+    prop::collection::vec(prop::sample::select(VOCABULARY), 0..60).prop_map(|t| t.join(" "))
+}
+
+// the number of cases is taken from PROPTEST_CASES (256 by default)
+proptest! {
+    #[test]
+    fn arbitrary_bytes_do_not_crash(bytes in prop::collection::vec(any::<u8>(), 0..200)) {
+        compile(&String::from_utf8_lossy(&bytes));
+    }
+
+    #[test]
+    fn arbitrary_tokens_do_not_crash(source in tokens()) {
+        compile(&source);
+    }
+
+    #[test]
+    fn tokens_in_a_program_do_not_crash(body in tokens()) {
+        compile(&format!("program p; function f(x: int): int; begin {} end begin {} end", body, body));
+    }
+
+    #[test]
+    fn mutated_programs_do_not_crash(
+        seed in 0..1000u64,
+        at in any::<prop::sample::Index>(),
+        cut in 0..20usize,
+        insert in tokens(),
+    ) {
+        let source = Generator::program(seed).source;
+        let mut start = at.index(source.len() + 1);
+        while !source.is_char_boundary(start) {
+            start -= 1;
+        }
+        let mut end = (start + cut).min(source.len());
+        while !source.is_char_boundary(end) {
+            end -= 1;
+        }
+        compile(&format!("{}{}{}", &source[..start], insert, &source[end..]));
+    }
+
+    #[test]
+    fn deeply_nested_expressions_are_errors(
+        depth in MAX_NESTING..500,
+        open in prop::sample::select(&["(", "!(", "f("][..]),
+    ) {
+        let source = nested(open, depth);
+        compile(&source);
+        let errors = Parser::new(source).parse().expect_err("too deep");
+        prop_assert!(errors.iter().any(|e| e.description.contains("nested too deep")));
+    }
+}
+
+/// A program printing an operand inside `depth` nested expressions
+fn nested(open: &str, depth: usize) -> String {
+    format!(
+        "program p; function f(x: int): int; begin return x; end begin writeln({}1{}); end",
+        open.repeat(depth),
+        ")".repeat(depth)
+    )
+}
+
+#[test]
+fn expressions_can_be_nested_up_to_the_limit() {
+    // the operand inside the parentheses is a level too
+    assert!(Parser::new(nested("(", MAX_NESTING - 1)).parse().is_ok());
+    assert!(Parser::new(nested("(", MAX_NESTING)).parse().is_err());
+}
@@ -0,0 +1,262 @@
+use crate::{
+    core::{ast::ASTNode, errors::EvaluationError},
+    interpreter::{statement::format_real, Interpreter},
+    parser::Parser,
+};
+
+/// Parses and runs the program on the given input, returns what it
+/// printed
+fn run(src: &str, stdin: &str) -> Result<String, EvaluationError> {
+    let program = match Parser::new(src.to_string()).parse() {
+        Ok(ASTNode::Program(program)) => program,
+        other => panic!("Expected a program, got {:?}", other),
+    };
+    let mut input = stdin.as_bytes();
+    let mut output = Vec::new();
+    Interpreter::new(&mut input, &mut output).run(&program)?;
+    Ok(String::from_utf8(output).unwrap())
+}
+
+#[test]
+fn prints_like_the_c_translation() {
+    let out = run(
+        "program p; begin writeln(3); writeln(\"s\"); writeln(1 = 1); writeln(1.5); end",
+        "",
+    );
+    assert_eq!("3\ns\ntrue\n1.5\n", out.unwrap());
+}
+
+#[test]
+fn reals_are_formatted_with_the_fewest_digits() {
+    assert_eq!("0.0", format_real(0.0));
+    assert_eq!("100.0", format_real(100.0));
+    assert_eq!("0.30000000000000004", format_real(0.1 + 0.2));
+    assert_eq!("0.00001", format_real(1e-5));
+    assert_eq!("-1.2345e-07", format_real(-1.2345e-7));
+    assert_eq!("1e+100", format_real(1e100));
+    assert_eq!("123456789012345.0", format_real(123456789012345.0));
+    assert_eq!("1.2345678901234568e+15", format_real(1234567890123456.8));
+}
+
+#[test]
+fn write_pads_values_to_their_width() {
+    let out = run(
+        "program p; begin write(1, \"|\"); writeln(7:3, \"|\", \"ab\":0 - 4, \"|\", false:6); writeln(3.14159:8:2, 2.5:0:0, 1.0:4); writeln; end",
+        "",
+    );
+    assert_eq!("1|  7|ab  | false\n    3.142 1.0\n\n", out.unwrap());
+}
+
+#[test]
+fn integer_arithmetic_wraps_like_c_ints() {
+    let out = run("program p; begin writeln(2147483647 + 1); end", "");
+    assert_eq!("-2147483648\n", out.unwrap());
+}
+
+#[test]
+fn division_by_zero_is_an_error() {
+    assert!(run("program p; begin var z: int; writeln(1 / z); end", "").is_err());
+}
+
+#[test]
+fn reads_words_from_the_input() {
+    let out = run(
+        "program p; begin var x: int; var s: string; read(x); read(s); writeln(x * 2); writeln(s); end",
+        "21\n  word  other\n",
+    );
+    assert_eq!("42\nword\n", out.unwrap());
+}
+
+#[test]
+fn reading_past_the_end_of_the_input_is_an_error() {
+    assert!(run("program p; begin var x: int; read(x); end", "").is_err());
+}
+
+#[test]
+fn recursion_gets_its_own_variables() {
+    let src = "program p;
+function fact(n: int): int;
+begin
+  if n = 0 then return 1;
+  return n * fact(n + -1);
+end
+begin
+  writeln(fact(5));
+end";
+    // there is no unary minus, `n + -1` is not valid yet
+    if Parser::new(src.to_string()).parse().is_ok() {
+        panic!("unary minus is not part of the language");
+    }
+    let src = "program p;
+function count(n: int): int;
+begin
+  var local: int;
+  local := n;
+  if n < 3 then count(n + 1);
+  return local;
+end
+begin
+  writeln(count(0));
+end";
+    assert_eq!("0\n", run(src, "").unwrap());
+}
+
+#[test]
+fn var_parameters_are_copied_back() {
+    let src = "program p;
+procedure inc(var x: int);
+begin
+  x := x + 1;
+end
+begin
+  var y: int;
+  y := 1;
+  inc(y);
+  writeln(y);
+end";
+    assert_eq!("2\n", run(src, "").unwrap());
+}
+
+#[test]
+fn arrays_are_bound_checked() {
+    let src = "program p; begin var a: array [2] of int; a[1] := 4; writeln(a[1]); a[2] := 1; end";
+    let mut input = "".as_bytes();
+    let mut output = Vec::new();
+    let program = match Parser::new(src.to_string()).parse() {
+        Ok(ASTNode::Program(program)) => program,
+        other => panic!("Expected a program, got {:?}", other),
+    };
+    let result = Interpreter::new(&mut input, &mut output).run(&program);
+    assert!(result.is_err());
+    assert_eq!("4\n", String::from_utf8(output).unwrap());
+}
+
+#[test]
+fn failed_assert_is_an_error() {
+    assert!(run("program p; begin assert(1 = 2); end", "").is_err());
+}
+
+#[test]
+fn for_loop_bounds_are_evaluated_once() {
+    let src = "program p;
+begin
+  var i: int;
+  var n: int;
+  n := 2;
+  for i := 1 to n do
+  begin
+    n := n + 1;
+    writeln(i);
+  end
+  writeln(i);
+  for i := 2 to 1 do
+  begin
+    writeln(0);
+  end
+  writeln(i);
+end";
+    assert_eq!("1\n2\n2\n2\n", run(src, "").unwrap());
+}
+
+#[test]
+fn for_loop_stops_at_the_int_limits() {
+    let src = "program p;
+begin
+  var i: int;
+  for i := 2147483646 to 2147483647 do
+  begin
+    writeln(i);
+  end
+  for i := 0 - 2147483647 downto 0 - 2147483647 - 1 do
+  begin
+    writeln(i);
+  end
+end";
+    assert_eq!(
+        "2147483646\n2147483647\n-2147483647\n-2147483648\n",
+        run(src, "").unwrap()
+    );
+}
+
+#[test]
+fn break_and_continue_act_on_the_innermost_loop() {
+    let src = "program p;
+begin
+  var i: int;
+  var j: int;
+  i := 0;
+  while i < 3 do
+  begin
+    i := i + 1;
+    if i = 2 then continue;
+    for j := 1 to 5 do
+    begin
+      if j = 3 then break;
+      writeln(i * 10 + j);
+    end
+  end
+  repeat
+    i := i - 1;
+    if i = 1 then continue;
+    writeln(i);
+  until i = 0;
+end";
+    assert_eq!("11\n12\n31\n32\n2\n0\n", run(src, "").unwrap());
+}
+
+#[test]
+fn case_runs_the_branch_with_the_value() {
+    let src = "program p;
+procedure show(s: string);
+begin
+  case s of
+    \"a\", \"b\": writeln(1);
+    \"c\": writeln(2);
+  end
+end
+begin
+  var i: int;
+  for i := 0 to 3 do
+  begin
+    case i * 2 of
+      0: writeln(10);
+      2, 4: writeln(20);
+    else
+      writeln(30);
+    end
+  end
+  show(\"b\");
+  show(\"c\");
+  show(\"d\");
+end";
+    assert_eq!("10\n20\n20\n30\n1\n2\n", run(src, "").unwrap());
+}
+
+#[test]
+fn records_are_copied_by_value() {
+    let src = "program p;
+type Point = record x: int; y: int end;
+type Line = record a: Point; b: Point; name: string end;
+function moved(p: Point, d: int): Point;
+begin
+  p.x := p.x + d;
+  return p;
+end
+begin
+  var l: Line;
+  var ls: array [2] of Line;
+  var q: Point;
+  l.b.y := 3;
+  q := l.b;
+  q.y := 4;
+  ls[1] := l;
+  ls[1].a := moved(q, 10);
+  writeln(l.b.y);
+  writeln(ls[1].a.x + ls[1].a.y);
+  writeln(moved(q, 1).x);
+  writeln(q.x);
+  read(ls[0].name);
+  writeln(ls[0].name + l.name);
+end";
+    assert_eq!("3\n14\n1\n0\nab\n", run(src, "ab").unwrap());
+}
@@ -1,9 +1,24 @@
 #[cfg(test)]
 #[cfg(not(tarpaulin_include))]
+mod debugger;
+#[cfg(test)]
+#[cfg(not(tarpaulin_include))]
+mod difftest;
+#[cfg(test)]
+#[cfg(not(tarpaulin_include))]
+mod fuzz;
+#[cfg(test)]
+#[cfg(not(tarpaulin_include))]
 mod interpreter;
 #[cfg(test)]
 #[cfg(not(tarpaulin_include))]
 mod parser;
 #[cfg(test)]
 #[cfg(not(tarpaulin_include))]
+mod profiler;
+#[cfg(test)]
+#[cfg(not(tarpaulin_include))]
 mod scanner;
+#[cfg(test)]
+#[cfg(not(tarpaulin_include))]
+mod vm;
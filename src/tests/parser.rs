@@ -1,12 +1,51 @@
+use std::io;
+
 use crate::{
-    core::{ast::ASTNode, variable::Type},
+    core::{
+        ast::ASTNode,
+        objects::Object,
+        types::{SimpleType, Type},
+    },
     interpreter::Interpreter,
     parser::Parser,
 };
 
+fn eval(node: &ASTNode) -> Object {
+    let mut input = io::empty();
+    let mut output = Vec::new();
+    Interpreter::new(&mut input, &mut output)
+        .eval(node)
+        .unwrap()
+}
+
+/// Parses the program, checking that it fails with an error containing
+/// `error`
+fn assert_rejected(src: &str, error: &str) {
+    match Parser::new(src.to_string()).parse() {
+        Err(errors) => assert!(
+            errors.iter().any(|e| e.description.contains(error)),
+            "{:?} for {}",
+            errors,
+            src
+        ),
+        Ok(_) => panic!("{} accepted", src),
+    }
+}
+
+fn eval_bool(src: &str) -> bool {
+    let mut parser = Parser::new(src.to_string());
+    match parser.parse_expression() {
+        Ok(node) => match eval(&node) {
+            Object::Bool(b) => b,
+            other => panic!("Expected a bool, got {}", other),
+        },
+        Err(e) => panic!("Expression not parsed correctly, got errors {:?}", e),
+    }
+}
+
 #[test]
 pub fn parse_emits_program() {
-    let mut parser = Parser::new("var x : string := \"string value\";".to_string());
+    let mut parser = Parser::new("program p; begin var x : string; end".to_string());
     match parser.parse() {
         Ok(ASTNode::Program(_)) => {}
         Ok(_) => panic!("Something other than a program node came out"),
@@ -16,17 +55,25 @@ pub fn parse_emits_program() {
 
 #[test]
 pub fn var_declaration_emits_var_node() {
-    let mut parser = Parser::new("var x : string := \"string value\";".to_string());
+    let mut parser = Parser::new("var x : string;".to_string());
     if let ASTNode::VariableDecl(node) = parser.parse_statement().unwrap() {
         assert_eq!("x", node.id.lexeme);
-        assert_eq!(Type::String, node.var_type);
+        assert_eq!(Type::Simple(SimpleType::String), node.var_type);
+    } else {
+        panic!("parse_var_declaration doesn't emit a variableDecl node")
+    }
+}
+
+#[test]
+pub fn array_declaration_keeps_the_size() {
+    let mut parser = Parser::new("var a : array [5] of int;".to_string());
+    if let ASTNode::VariableDecl(node) = parser.parse_statement().unwrap() {
         assert_eq!(
-            "string value",
-            Interpreter::new(false)
-                .eval_expression_statement(node.value.unwrap())
-                .unwrap()
-                .string_value
-        )
+            Type::Array(Box::new(Type::Simple(SimpleType::Int)), Some(5)),
+            node.var_type
+        );
+        assert_eq!(Some(5), node.size);
+        assert_eq!(Some(5), node.var_type.size());
     } else {
         panic!("parse_var_declaration doesn't emit a variableDecl node")
     }
@@ -42,7 +89,7 @@ pub fn var_reassignment_error_if_not_init() {
 
 #[test]
 pub fn var_decl_of_already_declared_fails() {
-    let mut parser = Parser::new("var x : int; var x : string".to_string());
+    let mut parser = Parser::new("program p; begin var x : int; var x : string; end".to_string());
     if parser.parse().is_ok() {
         panic!("How does this work?!");
     }
@@ -52,168 +99,50 @@ pub fn var_decl_of_already_declared_fails() {
 pub fn valid_expression_gets_parsed_correctly() {
     let mut parser = Parser::new("3 + 1 * 8;".to_string());
     match parser.parse_expression() {
-        Ok(ASTNode::ExpressionStmt(node)) => assert_eq!(
-            11,
-            Interpreter::new(false)
-                .eval_expression_statement(node)
-                .unwrap()
-                .int_value
-        ),
-        _ => panic!("Expression fail"),
+        Ok(node) => match eval(&node) {
+            Object::Int(value) => assert_eq!(11, value),
+            other => panic!("Expected an int, got {}", other),
+        },
+        Err(e) => panic!("Expression fail: {:?}", e),
     };
 }
 
 #[test]
 pub fn boolean_equality_gets_parsed_correctly() {
-    let mut parser = Parser::new("1 = 1".to_string());
-    match parser.parse_expression() {
-        Ok(ASTNode::ExpressionStmt(node)) => assert!(
-            Interpreter::new(false)
-                .eval_expression_statement(node)
-                .unwrap()
-                .bool_value
-        ),
-        _ => panic!("Boolean expression not parsed correctly"),
-    }
+    assert!(eval_bool("1 = 1"));
 }
 
 #[test]
 pub fn boolean_expression_gets_parsed_correctly() {
-    let mut parser = Parser::new("true & true".to_string());
-    match parser.parse_expression() {
-        Ok(ASTNode::ExpressionStmt(node)) => assert!(
-            Interpreter::new(false)
-                .eval_expression_statement(node)
-                .unwrap()
-                .bool_value
-        ),
-        Ok(_) => panic!("Boolean expression not parsed correctly, got something that is not an expression statement"),
-	Err(e) => panic!("Boolean expression not parsed correctly, got a syntax error: {:?}", e),
-    }
+    assert!(eval_bool("true and true"));
 }
 
 #[test]
 pub fn assert_works_as_expected() {
-    let mut parser = Parser::new("assert(false);".to_string());
-    match parser.parse_statement() {
-	Ok(ASTNode::AssertStmt(node)) =>  assert!(!Interpreter::new(false).eval_assert(node).unwrap().bool_value),
-	Ok(_) => panic!("Boolean expression not parsed correctly, got something that is not an assert statement"),
-	Err(e) => panic!("Boolean expression not parsed correctly, got a syntax error: {:?}", e),
-    }
-}
-
-#[test]
-pub fn line_comments_are_skipped() {
-    let mut parser = Parser::new("//this is a comment\n".to_string());
+    let mut parser = Parser::new("assert(1 = 2);".to_string());
     match parser.parse_statement() {
-        Ok(ASTNode::EofStmt(_)) => {}
-        Ok(node) => panic!("Should have gotten EOF, got node {}", node),
-        Err(err) => panic!("Should have gotten EOF, got errors {:?}", err),
-    }
-}
-
-#[test]
-pub fn block_comments_are_skipped() {
-    let mut parser = Parser::new("/*all of this is skipped*/".to_string());
-    match parser.parse_statement() {
-        Ok(ASTNode::EofStmt(_)) => {}
-        Ok(node) => panic!("Should have gotten EOF, got node {}", node),
-        Err(err) => panic!("Should have gotten EOF, got errors {:?}", err),
-    }
-}
-
-#[test]
-fn for_loop_correct() {
-    let mut parser = Parser::new("var x : int; for x in 0..10 do print x; end for;".to_string());
-    let var_node = parser.parse_statement();
-    match var_node {
-        Ok(ASTNode::VariableDecl(_)) => {}
-        Ok(other) => panic!("Should have declared a variable, didnt, got: {}", other),
-        Err(e) => panic!("Should have initialized variable, actually didnt: {:?}", e),
-    }
-    let for_node = parser.parse_statement();
-
-    match for_node {
-        Ok(ASTNode::ForStmt(node)) => {
-            assert_eq!("x", node.increment.lexeme);
-            let start = Interpreter::new(false)
-                .eval_expression_statement(*(node.range_start))
-                .unwrap();
-            let end = Interpreter::new(false)
-                .eval_expression_statement(*(node.range_end))
-                .unwrap();
-
-            assert_eq!(0, start.int_value);
-            assert_eq!(10, end.int_value);
-            match node.statements.iter().next() {
-                Some(ASTNode::PrintStmt(_)) => {}
-                Some(node) => panic!("Expected print stmt as child, got {}", node),
-                None => panic!("Expected print stmt as child, got nothing"),
-            }
+        Ok(node @ ASTNode::AssertStmt(_)) => {
+            let mut input = io::empty();
+            let mut output = Vec::new();
+            assert!(Interpreter::new(&mut input, &mut output)
+                .exec(&node)
+                .is_err())
         }
-        Ok(node) => panic!("Expected for node, got {}", node),
-        Err(e) => panic!("Expected for node, got error {:?}", e),
-    }
-}
-
-#[test]
-fn for_loop_missing_variable_error() {
-    let mut parser = Parser::new("for x in 0..10 do print x; end for;".to_string());
-    let for_node = parser.parse_statement();
-    if let Ok(e) = for_node {
-        panic!("Expected error, got node {}", e)
-    }
-}
-
-#[test]
-fn for_missing_semicolon_error() {
-    let mut parser = Parser::new("var x : int; for x in 0..10 do print x; end for".to_string());
-    let _var_decl = parser.parse_statement();
-    let for_node = parser.parse_statement();
-    if let Ok(e) = for_node {
-        panic!("Expected error, got node {}", e)
-    }
-}
-
-#[test]
-fn for_internal_statements_error() {
-    let mut parser = Parser::new("var x : int; for x in 0..10 do read y; end for;".to_string());
-    let _var_decl = parser.parse_statement();
-    let for_node = parser.parse_statement();
-    if let Ok(e) = for_node {
-        panic!("Expected error, got node {}", e)
-    }
-}
-
-#[test]
-fn for_start_expression_error() {
-    let mut parser = Parser::new("var x : int; for x in var..10 do read y; end for;".to_string());
-    let _var_decl = parser.parse_statement();
-    let for_node = parser.parse_statement();
-    if let Ok(e) = for_node {
-        panic!("Expected error, got node {}", e)
-    }
-}
-
-#[test]
-fn for_end_expression_error() {
-    let mut parser = Parser::new("var x : int; for x in 0..; do read y; end for;".to_string());
-    let _var_decl = parser.parse_statement();
-    let for_node = parser.parse_statement();
-    if let Ok(e) = for_node {
-        panic!("Expected error, got node {}", e)
+        Ok(_) => panic!("Boolean expression not parsed correctly, got something that is not an assert statement"),
+        Err(e) => panic!("Boolean expression not parsed correctly, got a syntax error: {:?}", e),
     }
 }
 
 #[test]
 fn read_returns_valid_ast() {
-    let mut parser = Parser::new("var x : int; read x;".to_string());
+    let mut parser = Parser::new("var x : int; read(x);".to_string());
     let _var_node = parser.parse_statement();
     let read_node = parser.parse_statement();
     match read_node {
-        Ok(ASTNode::ReadStmt(node)) => {
-            assert_eq!("x", node.variable_to_read_in.lexeme);
-        }
+        Ok(ASTNode::ReadStmt(node)) => match node.variable_to_read_in.as_ref() {
+            ASTNode::VarName(v) => assert_eq!("x", v.id.lexeme),
+            other => panic!("Expected a variable to read in, got {}", other),
+        },
         Ok(other) => panic!("Expected ASTNode::ReadStmt, got {}", other),
         Err(e) => panic!("Expected ASTNode::ReadStmt, got error {:?}", e),
     }
@@ -221,7 +150,7 @@ fn read_returns_valid_ast() {
 
 #[test]
 fn read_returns_error_if_var_not_initialized() {
-    let mut parser = Parser::new("read x;".to_string());
+    let mut parser = Parser::new("read(x);".to_string());
     let read_node = parser.parse_statement();
     if let Ok(other) = read_node {
         panic!("Expected ASTNode::ReadStmt, got {}", other)
@@ -235,14 +164,14 @@ fn var_reasssignment_valid_generates_valid_ast_node() {
     let assign = parser.parse_statement();
     match assign {
         Ok(ASTNode::VarReassignment(node)) => {
-            assert_eq!("x", node.variable_to_reassign.lexeme);
-            assert_eq!(
-                5,
-                Interpreter::new(false)
-                    .eval_expression_statement(node.new_value)
-                    .unwrap()
-                    .int_value
-            );
+            match node.variable_to_reassign.as_ref() {
+                ASTNode::VarName(v) => assert_eq!("x", v.id.lexeme),
+                other => panic!("Expected a variable to assign, got {}", other),
+            }
+            match eval(&node.new_value) {
+                Object::Int(value) => assert_eq!(5, value),
+                other => panic!("Expected an int, got {}", other),
+            }
         }
         Ok(other) => panic!("Expected ASTNode::VarReassignment, got {}", other),
         Err(e) => panic!("Expected ASTNode::VarReassignment, got {:?}", e),
@@ -269,7 +198,7 @@ fn invalid_statement_returns_error() {
 
 #[test]
 fn print_that_does_not_make_sense_returns_error() {
-    let mut parser = Parser::new("print var x;".to_string());
+    let mut parser = Parser::new("writeln(var x);".to_string());
     let err = parser.parse_statement();
     if let Ok(what) = err {
         panic!("Expected error, got {}", what)
@@ -278,136 +207,550 @@ fn print_that_does_not_make_sense_returns_error() {
 
 #[test]
 fn bool_bang_expression_ok() {
-    let mut parser = Parser::new("var x : bool := !false;".to_string());
-    let x = parser.parse_statement();
-    match x {
-        Ok(ASTNode::VariableDecl(node)) => {
-            assert_eq!("x", node.id.lexeme);
-            match Interpreter::new(false).eval_expression_statement(node.value.unwrap()) {
-                Ok(value) => assert!(value.bool_value),
-                Err(e) => panic!("Expected successful evaluation, got errors {:?}", e),
-            }
-        }
-        Ok(other) => panic!("expected valid node, found {}", other),
-        Err(vecc) => panic!("expected valid node, found {:?}", vecc),
-    }
+    assert!(eval_bool("!(1 = 2)"));
 }
 
 #[test]
 fn bool_greater_expression_ok() {
-    let mut parser = Parser::new("var x : bool := 1 > 0;".to_string());
-    let x = parser.parse_statement();
-    match x {
-        Ok(ASTNode::VariableDecl(node)) => {
-            assert_eq!("x", node.id.lexeme);
-            match Interpreter::new(false).eval_expression_statement(node.value.unwrap()) {
-                Ok(value) => assert!(value.bool_value),
-                Err(e) => panic!("Expected successful evaluation, got errors {:?}", e),
-            }
-        }
-        Ok(other) => panic!("expected valid node, found {}", other),
-        Err(vecc) => panic!("expected valid node, found {:?}", vecc),
-    }
+    assert!(eval_bool("1 > 0"));
 }
 
 #[test]
 fn bool_greater_eq_graeater_expression_ok() {
-    let mut parser = Parser::new("var x : bool := 1 >= 0;".to_string());
-    let x = parser.parse_statement();
-    match x {
-        Ok(ASTNode::VariableDecl(node)) => {
-            assert_eq!("x", node.id.lexeme);
-            match Interpreter::new(false).eval_expression_statement(node.value.unwrap()) {
-                Ok(value) => assert!(value.bool_value),
-                Err(e) => panic!("Expected successful evaluation, got errors {:?}", e),
-            }
-        }
-        Ok(other) => panic!("expected valid node, found {}", other),
-        Err(vecc) => panic!("expected valid node, found {:?}", vecc),
-    }
+    assert!(eval_bool("1 >= 0"));
 }
 
 #[test]
 fn bool_greater_eq_eq_expression_ok() {
-    let mut parser = Parser::new("var x : bool := 1 >= 1;".to_string());
-    let x = parser.parse_statement();
-    match x {
-        Ok(ASTNode::VariableDecl(node)) => {
-            assert_eq!("x", node.id.lexeme);
-            match Interpreter::new(false).eval_expression_statement(node.value.unwrap()) {
-                Ok(value) => assert!(value.bool_value),
-                Err(e) => panic!("Expected successful evaluation, got errors {:?}", e),
-            }
+    assert!(eval_bool("1 >= 1"));
+}
+
+#[test]
+fn bool_greater_eq_lower_expression_ok() {
+    assert!(!eval_bool("0 >= 1"));
+}
+
+#[test]
+fn bool_lower_eq_graeater_expression_ok() {
+    assert!(!eval_bool("1 <= 0"));
+}
+
+#[test]
+fn bool_lower_eq_eq_expression_ok() {
+    assert!(eval_bool("1 <= 1"));
+}
+
+#[test]
+fn bool_lower_eq_lower_expression_ok() {
+    assert!(eval_bool("0 <= 1"));
+}
+
+#[test]
+fn false_is_false() {
+    assert!(!eval_bool("false"));
+}
+
+#[test]
+fn operators_chain_from_the_left() {
+    let mut parser = Parser::new("10 - 2 - 3 * 2 * 1;".to_string());
+    match eval(&parser.parse_expression().unwrap()) {
+        Object::Int(value) => assert_eq!(2, value),
+        other => panic!("Expected an int, got {}", other),
+    }
+    assert!(eval_bool("1 < 2 and 2 < 3 and 3 < 4"));
+}
+
+#[test]
+fn comparison_of_different_types_fails() {
+    let mut parser = Parser::new("1 < \"a\"".to_string());
+    if let Ok(node) = parser.parse_expression() {
+        panic!("Expected error, got {}", node)
+    }
+}
+
+#[test]
+fn literals_that_do_not_fit_are_errors() {
+    for src in ["99999999999999999999999", "2147483648", "1.5e", "1..2"] {
+        let mut parser = Parser::new(format!("program p; begin writeln({}); end", src));
+        assert!(parser.parse().is_err(), "{} accepted", src);
+    }
+}
+
+#[test]
+fn declaration_in_an_unclosed_block_ends_it() {
+    let mut parser = Parser::new("program p; begin function".to_string());
+    assert!(parser.parse().is_err());
+    let mut parser = Parser::new("begin \" \" procedure".to_string());
+    assert!(parser.parse().is_err());
+}
+
+#[test]
+fn subprograms_can_follow_the_main_block() {
+    let mut parser = Parser::new(
+        "program p; begin end function f(x: int): int; begin return x; end".to_string(),
+    );
+    assert!(parser.parse().is_ok());
+}
+
+#[test]
+fn for_loop_keeps_its_bounds_and_direction() {
+    let mut parser = Parser::new(
+        "program p; begin var i : int; for i := 3 downto 1 do begin writeln(i); end end"
+            .to_string(),
+    );
+    let program = match parser.parse() {
+        Ok(ASTNode::Program(program)) => program,
+        other => panic!("Expected a program, got {:?}", other),
+    };
+    match &program.main_block.statements[1] {
+        ASTNode::ForStmt(node) => {
+            assert!(node.downto);
+            assert!(matches!(node.start.as_ref(), ASTNode::Literal(_)));
+            assert!(matches!(node.variable.as_ref(), ASTNode::VarName(v) if v.id.lexeme == "i"));
         }
-        Ok(other) => panic!("expected valid node, found {}", other),
-        Err(vecc) => panic!("expected valid node, found {:?}", vecc),
+        other => panic!("Expected a for loop, got {}", other),
     }
 }
 
 #[test]
-fn bool_greater_eq_lower_expression_ok() {
-    let mut parser = Parser::new("var x : bool := 0 >= 1;".to_string());
-    let x = parser.parse_statement();
-    match x {
-        Ok(ASTNode::VariableDecl(node)) => {
-            assert_eq!("x", node.id.lexeme);
-            match Interpreter::new(false).eval_expression_statement(node.value.unwrap()) {
-                Ok(value) => assert!(!value.bool_value),
-                Err(e) => panic!("Expected successful evaluation, got errors {:?}", e),
-            }
+fn for_loop_needs_an_int_variable_and_int_bounds() {
+    for src in [
+        "var r : real; for r := 1 to 2 do begin end",
+        "var i : int; for i := 1.0 to 2 do begin end",
+        "var i : int; for i := 1 to true do begin end",
+        "var i : int; for i := 1 until 2 do begin end",
+    ] {
+        let mut parser = Parser::new(format!("program p; begin {} end", src));
+        assert!(parser.parse().is_err(), "{} accepted", src);
+    }
+}
+
+#[test]
+fn repeat_loop_keeps_its_body_and_guard() {
+    let mut parser = Parser::new(
+        "program p; begin var i : int; repeat i := i + 1; writeln(i); until i >= 3; end"
+            .to_string(),
+    );
+    let program = match parser.parse() {
+        Ok(ASTNode::Program(program)) => program,
+        other => panic!("Expected a program, got {:?}", other),
+    };
+    match &program.main_block.statements[1] {
+        ASTNode::RepeatStmt(node) => {
+            assert!(matches!(node.block.as_ref(), ASTNode::Block(b) if b.statements.len() == 2));
+            assert_eq!(Type::Simple(SimpleType::Bool), node.guard.r_type());
         }
-        Ok(other) => panic!("expected valid node, found {}", other),
-        Err(vecc) => panic!("expected valid node, found {:?}", vecc),
+        other => panic!("Expected a repeat loop, got {}", other),
     }
 }
 
 #[test]
-fn bool_lower_eq_graeater_expression_ok() {
-    let mut parser = Parser::new("var x : bool := 1 <= 0;".to_string());
-    let x = parser.parse_statement();
-    match x {
-        Ok(ASTNode::VariableDecl(node)) => {
-            assert_eq!("x", node.id.lexeme);
-            match Interpreter::new(false).eval_expression_statement(node.value.unwrap()) {
-                Ok(value) => assert!(!value.bool_value),
-                Err(e) => panic!("Expected successful evaluation, got errors {:?}", e),
-            }
+fn break_and_continue_need_a_loop() {
+    for src in [
+        "break;",
+        "continue;",
+        "var i : int; while i < 1 do begin i := 1; end break;",
+        "repeat writeln(1); until 1;",
+    ] {
+        let mut parser = Parser::new(format!("program p; begin {} end", src));
+        assert!(parser.parse().is_err(), "{} accepted", src);
+    }
+    let mut parser = Parser::new(
+        "program p; begin var i : int; while i < 1 do begin if i = 0 then break; continue; end end"
+            .to_string(),
+    );
+    assert!(parser.parse().is_ok());
+}
+
+#[test]
+fn case_statement_keeps_its_branches() {
+    let mut parser = Parser::new(
+        "program p; begin var i : int; case i of 1, 2: writeln(1); 3: i := 0; else writeln(2); end end"
+            .to_string(),
+    );
+    let program = match parser.parse() {
+        Ok(ASTNode::Program(program)) => program,
+        other => panic!("Expected a program, got {:?}", other),
+    };
+    match &program.main_block.statements[1] {
+        ASTNode::CaseStmt(node) => {
+            assert_eq!(2, node.branches.len());
+            assert_eq!(2, node.branches[0].labels.len());
+            assert!(matches!(
+                node.branches[1].body.as_ref(),
+                ASTNode::VarReassignment(_)
+            ));
+            assert!(node.else_stmt.is_some());
         }
-        Ok(other) => panic!("expected valid node, found {}", other),
-        Err(vecc) => panic!("expected valid node, found {:?}", vecc),
+        other => panic!("Expected a case statement, got {}", other),
     }
 }
 
 #[test]
-fn bool_lower_eq_eq_expression_ok() {
-    let mut parser = Parser::new("var x : bool := 1 <= 1;".to_string());
-    let x = parser.parse_statement();
-    match x {
-        Ok(ASTNode::VariableDecl(node)) => {
-            assert_eq!("x", node.id.lexeme);
-            match Interpreter::new(false).eval_expression_statement(node.value.unwrap()) {
-                Ok(value) => assert!(value.bool_value),
-                Err(e) => panic!("Expected successful evaluation, got errors {:?}", e),
+fn case_labels_are_checked() {
+    for (src, error) in [
+        (
+            "case i of 1: writeln(1); 2, 1: writeln(2); end",
+            "Duplicate case label `1`",
+        ),
+        (
+            "case s of \"a\", \"a\": writeln(1); end",
+            "Duplicate case label `\"a\"`",
+        ),
+        ("case i of \"a\": writeln(1); end", "Case label of type"),
+        ("case s of 1: writeln(1); end", "Case label of type"),
+        (
+            "case r of 1: writeln(1); end",
+            "Expected int or string value",
+        ),
+        (
+            "case i of i: writeln(1); end",
+            "Expected an int or string literal",
+        ),
+    ] {
+        assert_rejected(
+            &format!(
+                "program p; begin var i : int; var s : string; var r : real; {} end",
+                src
+            ),
+            error,
+        );
+    }
+}
+
+#[test]
+fn records_are_declared_in_the_global_scope() {
+    let mut parser = Parser::new(
+        "program p; type Point = record x: int; y: real end; begin var p : Point; p.y := 1.5; end"
+            .to_string(),
+    );
+    let program = match parser.parse() {
+        Ok(ASTNode::Program(program)) => program,
+        other => panic!("Expected a program, got {:?}", other),
+    };
+    assert_eq!(1, program.records.len());
+    assert_eq!("Point", program.records[0].r_type.name);
+    assert_eq!(2, program.records[0].r_type.fields.len());
+    match &program.main_block.statements[1] {
+        ASTNode::VarReassignment(node) => match node.variable_to_reassign.as_ref() {
+            ASTNode::FieldRef(field) => {
+                assert_eq!(1, field.index);
+                assert_eq!(Type::Simple(SimpleType::Real), field.r_type);
             }
+            other => panic!("Expected a field reference, got {}", other),
+        },
+        other => panic!("Expected an assignment, got {}", other),
+    }
+}
+
+#[test]
+fn record_fields_are_checked() {
+    for (src, error) in [
+        ("p.z := 1;", "Record Point has no field `z`"),
+        ("p.x := \"a\";", "Mismatching types in field assignment"),
+        ("p.x.y := 1;", "which is not a record"),
+        ("writeln(p);", "Unable to print a whole record"),
+        ("read(p);", "Unable to read a whole record"),
+        ("assert(p = p);", "mismatched types in equality expression"),
+        (
+            "var q : Point; q := 1;",
+            "Mismatching types in var assignment",
+        ),
+    ] {
+        assert_rejected(
+            &format!(
+                "program p; type Point = record x: int; y: int end; begin var p : Point; {} end",
+                src
+            ),
+            error,
+        );
+    }
+    for (src, error) in [
+        ("record x: int; x: int end;", "Duplicate field `x`"),
+        ("record a: array [2] of int end;", "is an array"),
+        ("record end;", "has no fields"),
+        ("record s: Unknown end;", "not a record type"),
+    ] {
+        assert_rejected(&format!("program p; type R = {} begin end", src), error);
+    }
+}
+
+#[test]
+fn var_arguments_must_be_variables() {
+    for src in ["inc(p.x);", "inc(a[0]);", "inc(1);", "writeln(next(p.y));"] {
+        assert_rejected(
+            &format!(
+                "program p;
+type Point = record x: int; y: int end;
+procedure inc(var x: int); begin x := x + 1; end
+function next(var x: int): int; begin x := x + 1; return x; end
+begin var p : Point; var a : array [2] of int; {} end",
+                src
+            ),
+            "Expected a variable for the var parameter `x`",
+        );
+    }
+}
+
+#[test]
+fn nested_arrays_keep_the_size_of_their_rows() {
+    let mut parser = Parser::new("var m : array [2] of array [3] of int;".to_string());
+    if let ASTNode::VariableDecl(node) = parser.parse_statement().unwrap() {
+        let row = Type::Array(Box::new(Type::Simple(SimpleType::Int)), Some(3));
+        assert_eq!(Type::Array(Box::new(row.clone()), Some(2)), node.var_type);
+        assert_eq!(Some(&row), node.var_type.element());
+        assert_eq!(6, node.var_type.cells());
+        assert_eq!(SimpleType::Int, node.var_type.internal());
+    } else {
+        panic!("parse_var_declaration doesn't emit a variableDecl node")
+    }
+    let other_rows = Type::Array(
+        Box::new(Type::Array(
+            Box::new(Type::Simple(SimpleType::Int)),
+            Some(4),
+        )),
+        Some(2),
+    );
+    let mut parser = Parser::new("var m : array [5] of array [3] of int;".to_string());
+    if let ASTNode::VariableDecl(node) = parser.parse_statement().unwrap() {
+        assert_ne!(other_rows, node.var_type);
+    }
+}
+
+#[test]
+fn nested_array_indexes_are_checked() {
+    for (src, error) in [
+        (
+            "writeln(m[0][1][2]);",
+            "Too many indexes for `m`, it has 2 dimensions",
+        ),
+        ("m[0] := a;", "Cannot assign a whole row of `m`"),
+        ("writeln(sum(m));", "Mismatching types in function call"),
+        ("m := [1, 2];", "mismatching types in array assignment"),
+    ] {
+        assert_rejected(
+            &format!(
+                "program p;
+function sum(r: array [2] of array [4] of int): int;
+begin
+  return r[0][0];
+end
+begin var m : array [2] of array [3] of int; var a : array [3] of int; {} end",
+                src
+            ),
+            error,
+        );
+    }
+}
+
+#[test]
+fn constants_are_replaced_by_their_values() {
+    let mut parser = Parser::new(
+        "program p;
+const n = 2 + 3;
+const l = max(length(\"abc\"), 2);
+var a: array [n] of int;
+begin writeln(n * 2); end"
+            .to_string(),
+    );
+    match parser.parse() {
+        Ok(ASTNode::Program(program)) => {
+            assert!(matches!(program.constants[0].value.value, Object::Int(5)));
+            assert!(matches!(program.constants[1].value.value, Object::Int(3)));
+            assert_eq!(Some(5), program.globals[0].size);
         }
-        Ok(other) => panic!("expected valid node, found {}", other),
-        Err(vecc) => panic!("expected valid node, found {:?}", vecc),
+        other => panic!("Expected a program, got {:?}", other),
     }
 }
 
 #[test]
-fn bool_lower_eq_lower_expression_ok() {
-    let mut parser = Parser::new("var x : bool := 0 <= 1;".to_string());
-    let x = parser.parse_statement();
-    match x {
-        Ok(ASTNode::VariableDecl(node)) => {
-            assert_eq!("x", node.id.lexeme);
-            match Interpreter::new(false).eval_expression_statement(node.value.unwrap()) {
-                Ok(value) => assert!(value.bool_value),
-                Err(e) => panic!("Expected successful evaluation, got errors {:?}", e),
+fn globals_and_constants_are_checked() {
+    for (src, error) in [
+        ("const m = 1;", "already declared symbol: m"),
+        ("const k = g + 1;", "must be known at compile time"),
+        (
+            "const k = 1 + max(random(10), 2);",
+            "`random` gives a different value at every call",
+        ),
+        (
+            "const k = 0 - 1; var b: array [k] of int;",
+            "which is negative",
+        ),
+        (
+            "var b: array [g] of int;",
+            "must be an int literal or an int constant",
+        ),
+        (
+            "procedure q(); begin end var h: int;",
+            "Global declarations must come before",
+        ),
+        (
+            "procedure q(g: int); begin end",
+            "`g` is already declared as a global variable",
+        ),
+        (
+            "procedure q(); begin var m: int; end",
+            "`m` is already declared as a global constant",
+        ),
+        (
+            "begin m := 2; end",
+            "`m` is a constant, it cannot be assigned",
+        ),
+        ("begin read(m); end", "`m` is a constant, it cannot be read"),
+    ] {
+        assert_rejected(
+            &format!(
+                "program p;
+const m = 3;
+var g: int;
+{}",
+                src
+            ),
+            error,
+        );
+    }
+}
+
+#[test]
+fn short_names_get_no_hints() {
+    for (name, hint) in [
+        ("i", None),
+        ("n", None),
+        ("totl", Some("did you mean `total`")),
+    ] {
+        let src = format!(
+            "program p;
+begin
+  var total: int;
+  var g: int;
+  var f: int;
+  writeln({});
+end",
+            name
+        );
+        match Parser::new(src).parse() {
+            Err(errors) => {
+                let description = &errors[0].description;
+                match hint {
+                    Some(hint) => assert!(description.contains(hint), "{}", description),
+                    None => assert!(!description.contains("did you mean"), "{}", description),
+                }
             }
+            Ok(_) => panic!("`{}` accepted", name),
         }
-        Ok(other) => panic!("expected valid node, found {}", other),
-        Err(vecc) => panic!("expected valid node, found {:?}", vecc),
     }
 }
+
+#[test]
+fn forward_declarations_are_checked() {
+    for (src, error) in [
+        (
+            "function f(a: int): bool; forward;",
+            "Function `f` is declared forward but never defined",
+        ),
+        (
+            "function f(a: int): bool; forward;
+function f(b: int): bool; begin return true; end",
+            "Declaration of `f` does not match its forward declaration",
+        ),
+        (
+            "procedure f(var a: int); forward;
+procedure f(a: int); begin end",
+            "Declaration of `f` does not match its forward declaration",
+        ),
+        (
+            "function f(a: int): bool; forward;
+function f(a: int): int; begin return a; end",
+            "Declaration of `f` does not match its forward declaration",
+        ),
+        (
+            "procedure f(); begin end
+procedure f(); forward;",
+            "Declaration of an already declared symbol: f",
+        ),
+    ] {
+        assert_rejected(&format!("program p;\n{}\nbegin end", src), error);
+    }
+}
+
+#[test]
+fn nested_subprograms_are_checked() {
+    for (src, error) in [
+        (
+            "begin procedure f(); begin end end",
+            "Functions and procedures can only be declared globally or in other functions and procedures",
+        ),
+        (
+            "procedure f(a: int); begin procedure g(a: int); begin end end",
+            "nested subprograms can't hide it",
+        ),
+        (
+            "procedure f(); begin var a: int; procedure g(); begin var a: int; end end",
+            "`a` is already declared at 3:",
+        ),
+        (
+            "procedure f(); begin procedure g(); begin end g(); end begin g(); end",
+            "unknown symbol `g`",
+        ),
+        (
+            "procedure f(); begin procedure g(); forward; var a: int; procedure g(); begin a := 1; end end",
+            "`f_g` needs `a`, which is declared after it",
+        ),
+        (
+            "procedure f_g(); begin end procedure f(); begin procedure g(); begin end end",
+            "`g` clashes with the name given to a nested subprogram",
+        ),
+    ] {
+        assert_rejected(&format!("program p;\n\n{}\nbegin end", src), error);
+    }
+}
+
+#[test]
+fn builtins_are_checked() {
+    for (src, error) in [
+        (
+            "begin writeln(max(1, \"a\")); end",
+            "Wrong arguments for `max`, expected max(a: number, b: number): number",
+        ),
+        (
+            "begin writeln(length()); end",
+            "Wrong arguments for `length`",
+        ),
+        (
+            "begin var s: string; s := sqrt(2.0); end",
+            "Mismatching types in var assignment",
+        ),
+        (
+            "function Length(s: string): int; begin return 0; end begin end",
+            "which is a builtin function",
+        ),
+        ("const random = 4; begin end", "which is a builtin function"),
+    ] {
+        assert_rejected(&format!("program p;\n\n{}", src), error);
+    }
+}
+
+#[test]
+fn write_formats_are_checked() {
+    for (src, error) in [
+        (
+            "begin writeln(1:2:3); end",
+            "Expected a real to print with a precision, found int",
+        ),
+        (
+            "begin writeln(\"a\":\"b\"); end",
+            "Expected an int as the width of a printed value, found string",
+        ),
+        (
+            "begin writeln(1.5:2:0.5); end",
+            "Expected an int as the precision of a printed value, found double",
+        ),
+        ("begin writeln(1, ); end", ""),
+        ("begin write(1 2); end", "Expected token: )"),
+    ] {
+        assert_rejected(&format!("program p;\n\n{}", src), error);
+    }
+    let mut parser =
+        Parser::new("program p; begin write; writeln(); writeln(1.5:2:1, 3:4); end".to_string());
+    assert!(parser.parse().is_ok());
+}
@@ -0,0 +1,107 @@
+use crate::{core::ast::ASTNode, parser::Parser, profiler::Profiler};
+
+const SRC: &str = "program loops;
+function square(x: int): int;
+begin
+  return x * x;
+end
+procedure show(x: int);
+begin
+  writeln(square(x));
+end
+begin
+  var i: int;
+  while i < 3 do
+  begin
+    show(i);
+    i := i + 1;
+  end
+  assert(i = 4);
+end";
+
+/// Profiles the program, with its output
+fn profile(src: &str) -> (Profiler, String) {
+    let program = match Parser::new(src.to_string()).parse() {
+        Ok(ASTNode::Program(program)) => program,
+        other => panic!("Expected a program, got {:?}", other),
+    };
+    let mut input = "".as_bytes();
+    let mut output = Vec::new();
+    let mut profiler = Profiler::new(&program, src);
+    let _ = profiler.run(&program, &mut input, &mut output);
+    (profiler, String::from_utf8(output).unwrap())
+}
+
+#[test]
+fn counts_the_statements_of_every_line() {
+    let (profiler, output) = profile(SRC);
+    assert_eq!("0\n1\n4\n", output);
+    let count = |line: i64| profiler.lines().get(&line).map(|l| l.count);
+    assert_eq!(Some(3), count(4));
+    assert_eq!(Some(3), count(8));
+    assert_eq!(Some(1), count(12));
+    assert_eq!(Some(3), count(14));
+    assert_eq!(Some(3), count(15));
+    // the failed assert still ran
+    assert_eq!(Some(1), count(17));
+}
+
+#[test]
+fn counts_the_calls_of_every_subprogram() {
+    let (profiler, _) = profile(SRC);
+    let subprograms = profiler.subprograms();
+    assert_eq!(3, subprograms["square"].calls);
+    assert_eq!(3, subprograms["square"].statements);
+    assert_eq!(3, subprograms["show"].calls);
+    assert_eq!(1, subprograms["loops"].calls);
+    // the declarations, the loop, the two statements in it, the assert
+    assert_eq!(9, subprograms["loops"].statements);
+    assert!(subprograms["loops"].total >= subprograms["show"].total);
+    assert!(subprograms["show"].total >= subprograms["square"].total);
+}
+
+#[test]
+fn recursive_calls_are_timed_once() {
+    let src = "program p;
+function down(n: int): int;
+begin
+  if n = 0 then return 0;
+  return down(n - 1);
+end
+begin
+  writeln(down(50));
+end";
+    let (profiler, _) = profile(src);
+    let down = profiler.subprograms()["down"];
+    assert_eq!(51, down.calls);
+    assert!(down.total <= profiler.subprograms()["p"].total);
+}
+
+#[test]
+fn collapsed_stacks_count_the_statements() {
+    let (profiler, _) = profile(SRC);
+    assert_eq!(
+        "loops 9\nloops;show 3\nloops;show;square 3\n",
+        profiler.collapsed()
+    );
+}
+
+#[test]
+fn report_and_json_have_every_line() {
+    let (profiler, _) = profile(SRC);
+    let report = profiler.report();
+    let first = report.lines().nth(1).unwrap();
+    // ties go by line
+    assert!(first.contains("return x * x;"), "{}", report);
+    assert!(report.contains("square"));
+
+    let json = profiler.to_json();
+    assert_eq!("loops", json["program"]);
+    assert_eq!(
+        profiler.lines().len(),
+        json["lines"].as_array().unwrap().len()
+    );
+    assert_eq!(11, json["lines"][2]["line"]);
+    assert_eq!("var i: int;", json["lines"][2]["source"]);
+    assert_eq!(3, json["subprograms"].as_array().unwrap().len());
+}
@@ -1,8 +1,9 @@
+use crate::core::token::Kind;
 use crate::scanner::Scanner;
 
 #[test]
 fn scanner_error_if_eof_before_closing_comment() {
-    let mut scanner = Scanner::new("/* This is an unclosed comment".to_string());
+    let mut scanner = Scanner::new("{* This is an unclosed comment".to_string());
     if let Ok(token) = scanner.next_token() {
         panic!("Expected error, got token {}", token)
     }
@@ -10,25 +11,55 @@ fn scanner_error_if_eof_before_closing_comment() {
 
 #[test]
 fn scanner_error_if_eof_before_closing_comment_branch2() {
-    let mut scanner = Scanner::new("/* This is an unclosed comment*".to_string());
+    let mut scanner = Scanner::new("{* This is an unclosed comment*".to_string());
     if let Ok(token) = scanner.next_token() {
         panic!("Expected error, got token {}", token)
     }
 }
 
 #[test]
-fn scanner_error_if_single_dot() {
-    let mut scanner = Scanner::new(".;".to_string());
+fn scanner_error_if_unknown_symbol() {
+    let mut scanner = Scanner::new("$;".to_string());
     if let Ok(token) = scanner.next_token() {
         panic!("Expected error, got token {}", token)
     }
 }
 
 #[test]
-fn scanner_error_if_single_dot_branch2() {
-    let mut scanner = Scanner::new("; .".to_string());
+fn scanner_error_if_unknown_symbol_branch2() {
+    let mut scanner = Scanner::new("; $".to_string());
     let _unused = scanner.next_token();
     if let Ok(token) = scanner.next_token() {
         panic!("Expected error, got token {}", token)
     }
 }
+
+#[test]
+fn comments_are_skipped() {
+    let mut scanner = Scanner::new("{* all of this is skipped *}\n".to_string());
+    match scanner.next_token() {
+        Ok(token) => assert_eq!(Kind::Eof, token.kind),
+        Err(err) => panic!("Should have gotten EOF, got error {:?}", err),
+    }
+}
+
+#[test]
+fn trivia_is_returned_on_request() {
+    let mut scanner = Scanner::new("{* c *} x".to_string());
+    let kinds: Vec<Kind> = (0..4)
+        .map(|_| scanner.next_token_with_trivia().unwrap().kind)
+        .collect();
+    assert_eq!(
+        vec![Kind::Comment, Kind::Whites, Kind::Identifier, Kind::Eof],
+        kinds
+    );
+}
+
+#[test]
+fn scanning_goes_on_after_an_unknown_token() {
+    let mut scanner = Scanner::new("$$\nx".to_string());
+    assert!(scanner.next_token().is_err());
+    let token = scanner.next_token().unwrap();
+    assert_eq!(Kind::Identifier, token.kind);
+    assert_eq!(2, token.position.line);
+}
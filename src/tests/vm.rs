@@ -0,0 +1,551 @@
+use crate::{
+    core::{ast::ASTNode, ast::ProgramNode},
+    difftest::generator::Generator,
+    interpreter::{Interpreter, STACK_SIZE},
+    parser::Parser,
+    scanner::position::Position,
+    vm::{self, Module, Op, Vm},
+};
+
+type Run = Result<String, (Position, String)>;
+
+fn parse(src: &str) -> ProgramNode {
+    match Parser::new(src.to_string()).parse() {
+        Ok(ASTNode::Program(program)) => program,
+        other => panic!("Expected a program, got {:?}", other),
+    }
+}
+
+fn execute(module: &Module, stdin: &str) -> Run {
+    let mut input = stdin.as_bytes();
+    let mut output = Vec::new();
+    let result = Vm::new(module, &mut input, &mut output).run();
+    let output = String::from_utf8(output).unwrap();
+    result
+        .map(|_| output)
+        .map_err(|e| (e.position, e.description))
+}
+
+fn interpret(program: ProgramNode, stdin: &str) -> Run {
+    let stdin = stdin.to_string();
+    std::thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(move || {
+            let mut input = stdin.as_bytes();
+            let mut output = Vec::new();
+            let result = Interpreter::new(&mut input, &mut output).run(&program);
+            let output = String::from_utf8(output).unwrap();
+            result
+                .map(|_| output)
+                .map_err(|e| (e.position, e.description))
+        })
+        .unwrap()
+        .join()
+        .unwrap()
+}
+
+/// Runs the program in the virtual machine and checks that the
+/// interpreter does the same
+fn run(src: &str, stdin: &str) -> Run {
+    let program = parse(src);
+    let module = vm::compile(&program).unwrap();
+    let result = execute(&module, stdin);
+    assert_eq!(interpret(program, stdin), result, "{}", module);
+    result
+}
+
+#[test]
+fn generated_programs_behave_like_in_the_interpreter() {
+    for seed in 0..200 {
+        let generated = Generator::program(seed);
+        let _ = run(&generated.source, &generated.stdin);
+    }
+}
+
+#[test]
+fn subprograms_get_their_own_slots() {
+    let src = "program p;
+function count(n: int): int;
+begin
+  var local: int;
+  local := n;
+  if n < 3 then count(n + 1);
+  return local;
+end
+begin
+  var local: int;
+  local := 7;
+  writeln(count(0));
+  writeln(local);
+end";
+    assert_eq!("0\n7\n", run(src, "").unwrap());
+}
+
+#[test]
+fn var_parameters_and_arrays_are_copied_back() {
+    let src = "program p;
+procedure fill(var a: array[2] of int, var n: int);
+begin
+  a[0] := 4;
+  n := n + 1;
+end
+begin
+  var a: array[2] of int;
+  var n: int;
+  n := 1;
+  fill(a, n);
+  writeln(a[0]);
+  writeln(n);
+end";
+    assert_eq!("4\n2\n", run(src, "").unwrap());
+}
+
+#[test]
+fn loop_variables_are_declared_again_every_iteration() {
+    let src = "program p;
+begin
+  var i: int;
+  while i < 2 do
+  begin
+    var x: int;
+    writeln(x);
+    x := 5;
+    i := i + 1;
+  end
+end";
+    assert_eq!("0\n0\n", run(src, "").unwrap());
+}
+
+#[test]
+fn for_loops_count_in_both_directions() {
+    let src = "program p;
+function first(n: int): int;
+begin
+  var i: int;
+  for i := n downto 0 do
+  begin
+    if i * i < n then return i;
+  end
+  return 0 - 1;
+end
+begin
+  var i: int;
+  var j: int;
+  for i := 1 to 3 do
+  begin
+    for j := i downto 1 do
+    begin
+      writeln(i * 10 + j);
+    end
+  end
+  writeln(first(50));
+  for i := 2147483647 to 2147483647 do
+  begin
+    writeln(i);
+  end
+end";
+    assert_eq!(
+        "11\n22\n21\n33\n32\n31\n7\n2147483647\n",
+        run(src, "").unwrap()
+    );
+}
+
+#[test]
+fn loop_exits_jump_to_the_right_place() {
+    let src = "program p;
+function first_even(n: int): int;
+begin
+  var i: int;
+  i := n;
+  repeat
+    i := i + 1;
+    if i - (i / 2) * 2 = 1 then continue;
+    break;
+  until false;
+  return i;
+end
+begin
+  var i: int;
+  var j: int;
+  for i := 3 downto 0 do
+  begin
+    j := 0;
+    while true do
+    begin
+      j := j + 1;
+      if j < i then continue;
+      break;
+    end
+    if i = 1 then continue;
+    writeln(j);
+  end
+  writeln(first_even(7));
+end";
+    assert_eq!("3\n2\n1\n8\n", run(src, "").unwrap());
+}
+
+#[test]
+fn reads_words_from_the_input() {
+    let src = "program p; begin var x: int; var a: array[2] of string; read(x); read(a[1]); writeln(x * 2); writeln(a[1]); end";
+    assert_eq!("42\nword\n", run(src, "21\n  word  other\n").unwrap());
+}
+
+#[test]
+fn runtime_errors_are_the_interpreter_ones() {
+    run(
+        "program p; begin var z: int; writeln(1); writeln(1 / z); end",
+        "",
+    )
+    .unwrap_err();
+    run(
+        "program p; begin var a: array[2] of int; a[2] := 1; end",
+        "",
+    )
+    .unwrap_err();
+    run(
+        "program p; begin var a: array[2] of int; writeln(a[0 - 1]); end",
+        "",
+    )
+    .unwrap_err();
+    run("program p; begin assert(1 = 2); end", "").unwrap_err();
+    run("program p; begin var x: int; read(x); end", "word").unwrap_err();
+    run("program p; begin var x: int; read(x); end", "").unwrap_err();
+}
+
+#[test]
+fn deep_recursion_ends_in_a_stack_overflow() {
+    let src = "program p;
+function down(n: int): int;
+begin
+  if n = 0 then return 0;
+  return down(n - 1) + 1;
+end
+begin
+  writeln(down(5000));
+  writeln(down(20000));
+end";
+    let module = vm::compile(&parse(src)).unwrap();
+    let err = execute(&module, "").unwrap_err();
+    assert_eq!("Stack overflow, too many nested calls", err.1);
+}
+
+#[test]
+fn bytecode_survives_a_round_trip() {
+    let src = "program p;
+function half(x: real): real;
+begin
+  return x / 2.0;
+end
+begin
+  var s: array[2] of string;
+  s[1] := \"a\" + \"b\";
+  writeln(s[1]);
+  writeln(half(3.0));
+  writeln(!(1 < 2));
+end";
+    let module = vm::compile(&parse(src)).unwrap();
+    let mut bytes = vec![];
+    module.write(&mut bytes).unwrap();
+    assert_eq!(vm::MAGIC, &bytes[..4]);
+    let read = Module::read(&mut bytes.as_slice()).unwrap();
+    assert_eq!(module.to_string(), read.to_string());
+    assert_eq!(execute(&module, ""), execute(&read, ""));
+}
+
+#[test]
+fn malformed_bytecode_is_refused() {
+    let module = vm::compile(&parse("program p; begin writeln(1); end")).unwrap();
+    let mut bytes = vec![];
+    module.write(&mut bytes).unwrap();
+
+    assert!(Module::read(&mut &b"ELF\x7f"[..]).is_err());
+    for len in 0..bytes.len() {
+        assert!(Module::read(&mut &bytes[..len]).is_err(), "{} bytes", len);
+    }
+    let mut version = bytes.clone();
+    version[4] += 1;
+    assert!(Module::read(&mut version.as_slice()).is_err());
+
+    let mut jump = module.clone();
+    jump.code.insert(0, Op::Jump(100));
+    jump.positions.insert(0, Position::new(0, 0, 0));
+    let mut bytes = vec![];
+    jump.write(&mut bytes).unwrap();
+    assert!(Module::read(&mut bytes.as_slice()).is_err());
+}
+
+#[test]
+fn code_can_not_leave_its_subprogram() {
+    let module = vm::compile(&parse(
+        "program p;
+procedure q(a: int);
+begin
+  var b: int;
+  b := a;
+  writeln(b);
+end
+begin
+  q(1);
+end",
+    ))
+    .unwrap();
+    let q = module.functions[1].entry;
+    // a jump into q and the main block running into it both use the
+    // slots of q in the frame of the main block, which has none
+    for (at, op) in [(0, Op::Jump(q + 1)), (q as usize - 1, Op::Newline)] {
+        let mut crafted = module.clone();
+        crafted.code[at] = op;
+        let mut bytes = vec![];
+        crafted.write(&mut bytes).unwrap();
+        assert!(Module::read(&mut bytes.as_slice()).is_err(), "{}", crafted);
+    }
+}
+
+#[test]
+fn case_jumps_to_the_matching_branch() {
+    let src = "program p;
+function kind(n: int): string;
+begin
+  case n of
+    1, 3, 5: return \"odd\";
+    2, 4: return \"even\";
+  else
+    return \"big\";
+  end
+end
+begin
+  var i: int;
+  for i := 1 to 6 do
+  begin
+    case i of
+      3: continue;
+      5: break;
+    end
+    writeln(kind(i));
+  end
+end";
+    assert_eq!("odd\neven\neven\n", run(src, "").unwrap());
+}
+
+#[test]
+fn fields_are_stored_in_their_records() {
+    let src = "program p;
+type Point = record x: int; y: int end;
+type Box = record min: Point; max: Point end;
+function area(b: Box): int;
+begin
+  return (b.max.x - b.min.x) * (b.max.y - b.min.y);
+end
+begin
+  var boxes: array [3] of Box;
+  var i: int;
+  for i := 0 to 2 do
+  begin
+    boxes[i].max.x := i + 1;
+    boxes[i].max.y := 2;
+    read(boxes[i].min.y);
+  end
+  for i := 0 to 2 do
+  begin
+    writeln(area(boxes[i]));
+  end
+end";
+    assert_eq!("2\n2\n0\n", run(src, "0 1 2").unwrap());
+    let module = vm::compile(&parse(src)).unwrap();
+    let mut bytes = vec![];
+    module.write(&mut bytes).unwrap();
+    let read = Module::read(&mut bytes.as_slice()).unwrap();
+    assert_eq!(module.to_string(), read.to_string());
+}
+
+#[test]
+fn nested_arrays_are_stored_row_by_row() {
+    let src = "program p;
+function last(row: array [3] of int): int;
+begin
+  return row[2];
+end
+procedure fill(var m: array [2] of array [3] of int);
+begin
+  var i: int;
+  var j: int;
+  for i := 0 to 1 do
+  begin
+    for j := 0 to 2 do
+    begin
+      m[i][j] := i * 10 + j;
+    end
+  end
+end
+begin
+  var m: array [2] of array [3] of int;
+  fill(m);
+  writeln(last(m[1]));
+  writeln(m[0][2]);
+end";
+    assert_eq!("12\n2\n", run(src, "").unwrap());
+    let (_, error) = run(&src.replace("m[0][2]", "m[0][3]"), "").unwrap_err();
+    assert_eq!("Index 3 out of bounds for array `m` of length 3", error);
+    let module = vm::compile(&parse(src)).unwrap();
+    let mut bytes = vec![];
+    module.write(&mut bytes).unwrap();
+    let read = Module::read(&mut bytes.as_slice()).unwrap();
+    assert_eq!(module.to_string(), read.to_string());
+}
+
+#[test]
+fn globals_are_slots_of_the_main_block() {
+    let src = "program p;
+const step = 2;
+var total: int;
+var seen: array [3] of int;
+procedure add(var n: int, depth: int);
+begin
+  total := total + step;
+  seen[depth] := total;
+  n := n + 1;
+  if depth < 2 then add(n, depth + 1);
+end
+begin
+  var calls: int;
+  add(calls, 0);
+  writeln(calls);
+  writeln(total);
+  writeln(seen[2]);
+end";
+    assert_eq!("3\n6\n6\n", run(src, "").unwrap());
+    let mut module = vm::compile(&parse(src)).unwrap();
+    let mut bytes = vec![];
+    module.write(&mut bytes).unwrap();
+    let read = Module::read(&mut bytes.as_slice()).unwrap();
+    assert_eq!(module.to_string(), read.to_string());
+    let main_slots = module.functions[0].locals;
+    module.code.insert(0, Op::Load(vm::GLOBAL | main_slots));
+    let mut bytes = vec![];
+    module.write(&mut bytes).unwrap();
+    assert!(Module::read(&mut bytes.as_slice()).is_err());
+}
+
+#[test]
+fn forward_declarations_allow_mutual_recursion() {
+    let src = "program p;
+function odd(n: int): bool; forward;
+function even(n: int): bool;
+begin
+  if n = 0 then return true;
+  return odd(n - 1);
+end
+function odd(n: int): bool;
+begin
+  if n = 0 then return false;
+  return even(n - 1);
+end
+begin
+  assert(even(10));
+  assert(odd(7));
+  writeln(\"done\");
+end";
+    assert_eq!("done\n", run(src, "").unwrap());
+}
+
+#[test]
+fn nested_subprograms_share_the_variables_of_their_activation() {
+    let src = "program p;
+function depth(n: int): int;
+begin
+  var calls: int;
+  procedure bump();
+  begin
+    procedure twice();
+    begin
+      calls := calls + 1;
+      n := n - 1;
+    end
+    twice();
+    twice();
+  end
+  calls := 0;
+  if n <= 0 then return 0;
+  bump();
+  return calls + depth(n);
+end
+procedure walk(n: int);
+begin
+  var steps: int;
+  procedure left(k: int); forward;
+  procedure right(k: int);
+  begin
+    steps := steps + 1;
+    if k > 0 then left(k - 1);
+  end
+  procedure left(k: int);
+  begin
+    steps := steps + 1;
+    if k > 0 then right(k - 1);
+  end
+  steps := 0;
+  left(n);
+  writeln(steps);
+end
+begin
+  writeln(depth(5));
+  walk(4);
+end";
+    assert_eq!("6\n5\n", run(src, "").unwrap());
+}
+
+#[test]
+fn builtins_are_the_interpreter_ones() {
+    let src = "program p;
+begin
+  var i: int;
+  writeln(substr(\"miniplc\", 4, 10) + substr(\"ab\", 5, 1) + chr(ord(\"a\") + 1));
+  writeln(max(abs(0 - 3), 2.5));
+  writeln(round(sqrt(10.0)) + trunc(0.0 - 2.7) + strtoint(inttostr(40)));
+  for i := 1 to 5 do
+  begin
+    writeln(random(100));
+  end
+end";
+    let module = vm::compile(&parse(src)).unwrap();
+    let mut bytes = vec![];
+    module.write(&mut bytes).unwrap();
+    let read = Module::read(&mut bytes.as_slice()).unwrap();
+    assert_eq!(module.to_string(), read.to_string());
+    run(src, "").unwrap();
+
+    for call in ["chr(0)", "chr(200)", "strtoint(\"1 \")", "random(0)"] {
+        let src = format!("program p; begin writeln({}); end", call);
+        let err = run(&src, "").unwrap_err();
+        assert_eq!(1, err.0.line, "{}", call);
+    }
+}
+
+#[test]
+fn writes_are_formatted_like_in_the_interpreter() {
+    let src = "program p;
+begin
+  var x: real;
+  var i: int;
+  x := 2.0 / 3.0;
+  write(x, \" \", x:6:2, \"|\");
+  writeln(\"a\":i - 3, true:6, 7:i + 4, x:0 - 8);
+  writeln;
+end";
+    let module = vm::compile(&parse(src)).unwrap();
+    let mut bytes = vec![];
+    module.write(&mut bytes).unwrap();
+    let read = Module::read(&mut bytes.as_slice()).unwrap();
+    assert_eq!(module.to_string(), read.to_string());
+    assert_eq!(
+        "0.6666666666666666   0.67|a    true   70.6666666666666666\n\n",
+        run(src, "").unwrap()
+    );
+
+    let mut write = module.clone();
+    write.code.insert(0, Op::Write(3));
+    write.positions.insert(0, Position::new(0, 0, 0));
+    let mut bytes = vec![];
+    write.write(&mut bytes).unwrap();
+    assert!(Module::read(&mut bytes.as_slice()).is_err());
+}
@@ -0,0 +1,608 @@
+use std::{
+    fmt,
+    io::{self, Read, Write},
+    sync::Arc,
+};
+
+use crate::{
+    core::{
+        ast::BinaryExprType,
+        builtins::Builtin,
+        objects::Object,
+        types::{RecordType, SimpleType, Type},
+    },
+    scanner::position::Position,
+};
+
+/// First bytes of every `.mplc` file
+pub const MAGIC: &[u8; 4] = b"MPLC";
+
+/// Version of the format, files of other versions are refused
+pub const VERSION: u16 = 5;
+
+/// Flag of the slots of the global variables, they are slots of the
+/// main block whatever subprogram is running
+pub const GLOBAL: u32 = 1 << 31;
+
+/// How deep records and arrays can be nested in a file
+const MAX_NESTING: usize = 64;
+
+/// Instructions of the virtual machine. They work on a stack of
+/// values, the slots are the variables of the running subprogram
+#[derive(Debug, Clone, PartialEq)]
+pub enum Op {
+    /// Pushes a constant
+    Const(u32),
+    /// Pushes the value of a slot
+    Load(u32),
+    /// Pops a value into a slot
+    Store(u32),
+    /// Checks that the int on top can index an array
+    Index,
+    /// Pops an index and pushes that element of the array in the slot,
+    /// the constant is the name of the array for the errors
+    LoadElement(u32, u32),
+    /// Pops an index and a value and stores the value in that element
+    /// of the array in the slot
+    StoreElement(u32, u32),
+    /// Pops an index and an array and pushes that element, for the
+    /// dimensions after the first one
+    GetElement(u32),
+    /// Pops an index, an array and a value and pushes the array with
+    /// the value in that element
+    SetElement(u32),
+    /// Pops a record and pushes its field
+    GetField(u32),
+    /// Pops a record and a value and pushes the record with the value
+    /// in the field
+    SetField(u32),
+    Add,
+    Sub,
+    Mul,
+    Div,
+    And,
+    Eq,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+    Not,
+    Jump(u32),
+    /// Pops a value and jumps unless it is true
+    JumpUnless(u32),
+    /// Calls a subprogram, its arguments are on the stack
+    Call(u32),
+    /// Pops the arguments of the builtin and pushes its result
+    Builtin(Builtin),
+    /// Stores a parameter of the subprogram that just returned in a
+    /// slot, for `var` parameters and arrays
+    CopyBack(u32, u32),
+    Return,
+    /// Returns the value on top of the stack
+    ReturnValue,
+    /// End of a function without a `return`
+    NoReturn,
+    Pop,
+    /// Pops a value and writes it like `write`, with its width and
+    /// precision on top of it when it has 1 or 2 of them
+    Write(u8),
+    /// Ends the line of `writeln`
+    Newline,
+    /// Reads a word of the input as a value of the type and pushes it
+    Read(Type),
+    /// Pops a value, an error unless it is true
+    Assert,
+    Halt,
+}
+
+impl Op {
+    /// The operator of the binary expressions the instruction computes
+    pub fn binary_type(&self) -> Option<BinaryExprType> {
+        Some(match self {
+            Op::Add => BinaryExprType::Addition,
+            Op::Sub => BinaryExprType::Subtraction,
+            Op::Mul => BinaryExprType::Multiplication,
+            Op::Div => BinaryExprType::Division,
+            Op::And => BinaryExprType::LogicAND,
+            Op::Eq => BinaryExprType::LogicEQ,
+            Op::Gt => BinaryExprType::LogicGreaterThan,
+            Op::Ge => BinaryExprType::LogicGreaterThanEQ,
+            Op::Lt => BinaryExprType::LogicLessThan,
+            Op::Le => BinaryExprType::LogicLessThanEQ,
+            _ => return None,
+        })
+    }
+
+    /// The instruction computing the binary operator
+    pub fn binary(op_type: &BinaryExprType) -> Op {
+        match op_type {
+            BinaryExprType::Addition => Op::Add,
+            BinaryExprType::Subtraction => Op::Sub,
+            BinaryExprType::Multiplication => Op::Mul,
+            BinaryExprType::Division => Op::Div,
+            BinaryExprType::LogicAND => Op::And,
+            BinaryExprType::LogicEQ => Op::Eq,
+            BinaryExprType::LogicGreaterThan => Op::Gt,
+            BinaryExprType::LogicGreaterThanEQ => Op::Ge,
+            BinaryExprType::LogicLessThan => Op::Lt,
+            BinaryExprType::LogicLessThanEQ => Op::Le,
+        }
+    }
+
+    fn code(&self) -> u8 {
+        match self {
+            Op::Const(_) => 0,
+            Op::Load(_) => 1,
+            Op::Store(_) => 2,
+            Op::Index => 3,
+            Op::LoadElement(..) => 4,
+            Op::StoreElement(..) => 5,
+            Op::Add => 6,
+            Op::Sub => 7,
+            Op::Mul => 8,
+            Op::Div => 9,
+            Op::And => 10,
+            Op::Eq => 11,
+            Op::Gt => 12,
+            Op::Ge => 13,
+            Op::Lt => 14,
+            Op::Le => 15,
+            Op::Not => 16,
+            Op::Jump(_) => 17,
+            Op::JumpUnless(_) => 18,
+            Op::Call(_) => 19,
+            Op::CopyBack(..) => 20,
+            Op::Return => 21,
+            Op::ReturnValue => 22,
+            Op::NoReturn => 23,
+            Op::Pop => 24,
+            Op::Write(_) => 25,
+            Op::Read(_) => 26,
+            Op::Assert => 27,
+            Op::Halt => 28,
+            Op::GetField(_) => 29,
+            Op::SetField(_) => 30,
+            Op::GetElement(_) => 31,
+            Op::SetElement(_) => 32,
+            Op::Builtin(_) => 33,
+            Op::Newline => 34,
+        }
+    }
+}
+
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Op::Read(t) => write!(f, "Read({})", t),
+            _ => write!(f, "{:?}", self),
+        }
+    }
+}
+
+/// A subprogram, the main block is the first one
+#[derive(Debug, Clone, PartialEq)]
+pub struct Function {
+    pub name: String,
+    /// Number of parameters, they are the first slots
+    pub params: u32,
+    /// Number of slots, parameters included
+    pub locals: u32,
+    /// Its first instruction
+    pub entry: u32,
+}
+
+/// A whole program for the virtual machine
+#[derive(Debug, Clone, Default)]
+pub struct Module {
+    pub constants: Vec<Object>,
+    pub functions: Vec<Function>,
+    pub code: Vec<Op>,
+    /// Position in the source of every instruction
+    pub positions: Vec<Position>,
+}
+
+fn invalid(what: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, what.to_string())
+}
+
+fn write_u32(out: &mut dyn Write, n: u32) -> io::Result<()> {
+    out.write_all(&n.to_le_bytes())
+}
+
+fn write_str(out: &mut dyn Write, s: &str) -> io::Result<()> {
+    write_u32(out, s.len() as u32)?;
+    out.write_all(s.as_bytes())
+}
+
+fn simple_code(s: &SimpleType) -> u8 {
+    match s {
+        SimpleType::Int => 0,
+        SimpleType::Real => 1,
+        SimpleType::String => 2,
+        SimpleType::Bool => 3,
+        SimpleType::Void => 4,
+        SimpleType::Record(_) => 5,
+    }
+}
+
+/// The code of the type, records follow it with their declaration.
+/// Arrays are the code 8 followed by their size (`u32::MAX` when it
+/// is not known) and the type of the elements
+fn write_type(out: &mut dyn Write, t: &Type) -> io::Result<()> {
+    match t {
+        Type::Simple(s) => {
+            out.write_all(&[simple_code(s)])?;
+            match s {
+                SimpleType::Record(r) => write_record_type(out, r),
+                _ => Ok(()),
+            }
+        }
+        Type::Array(e, size) => {
+            out.write_all(&[8])?;
+            write_u32(out, size.map(|s| s as u32).unwrap_or(u32::MAX))?;
+            write_type(out, e)
+        }
+    }
+}
+
+/// The name of the record and its fields, each one with its type
+fn write_record_type(out: &mut dyn Write, record: &RecordType) -> io::Result<()> {
+    write_str(out, &record.name)?;
+    write_u32(out, record.fields.len() as u32)?;
+    for (name, f_type) in record.fields.iter() {
+        write_str(out, name)?;
+        write_type(out, f_type)?;
+    }
+    Ok(())
+}
+
+fn write_object(out: &mut dyn Write, object: &Object) -> io::Result<()> {
+    match object {
+        Object::Int(i) => {
+            out.write_all(&[0])?;
+            out.write_all(&i.to_le_bytes())
+        }
+        Object::Real(r) => {
+            out.write_all(&[1])?;
+            out.write_all(&r.to_bits().to_le_bytes())
+        }
+        Object::String(s) => {
+            out.write_all(&[2])?;
+            write_str(out, s)
+        }
+        Object::Bool(b) => out.write_all(&[3, *b as u8]),
+        Object::Array(elements) => {
+            out.write_all(&[4])?;
+            write_u32(out, elements.len() as u32)?;
+            elements.iter().try_for_each(|e| write_object(out, e))
+        }
+        Object::Record(r, fields) => {
+            out.write_all(&[5])?;
+            write_record_type(out, r)?;
+            fields.iter().try_for_each(|f| write_object(out, f))
+        }
+    }
+}
+
+/// Reads the binary format back, every read checks it does not go
+/// past the end
+struct Reader<'a> {
+    input: &'a mut dyn Read,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes<const N: usize>(&mut self) -> io::Result<[u8; N]> {
+        let mut buf = [0; N];
+        self.input.read_exact(&mut buf)?;
+        Ok(buf)
+    }
+
+    fn u8(&mut self) -> io::Result<u8> {
+        Ok(self.bytes::<1>()?[0])
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        Ok(u32::from_le_bytes(self.bytes()?))
+    }
+
+    fn i64(&mut self) -> io::Result<i64> {
+        Ok(i64::from_le_bytes(self.bytes()?))
+    }
+
+    fn string(&mut self) -> io::Result<String> {
+        let len = self.u32()? as usize;
+        let mut buf = vec![];
+        self.input.take(len as u64).read_to_end(&mut buf)?;
+        if buf.len() != len {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        String::from_utf8(buf).map_err(|_| invalid("string constant is not UTF-8"))
+    }
+
+    fn simple_type(code: u8) -> io::Result<SimpleType> {
+        Ok(match code {
+            0 => SimpleType::Int,
+            1 => SimpleType::Real,
+            2 => SimpleType::String,
+            3 => SimpleType::Bool,
+            4 => SimpleType::Void,
+            _ => return Err(invalid("unknown type")),
+        })
+    }
+
+    fn r_type(&mut self, depth: usize) -> io::Result<Type> {
+        Ok(match self.u8()? {
+            5 => Type::Simple(SimpleType::Record(self.record_type(depth)?)),
+            8 if depth < MAX_NESTING => {
+                let size = match self.u32()? {
+                    u32::MAX => None,
+                    size => Some(size as usize),
+                };
+                Type::Array(Box::new(self.r_type(depth + 1)?), size)
+            }
+            8 => return Err(invalid("arrays nested too deep")),
+            code => Type::Simple(Reader::simple_type(code)?),
+        })
+    }
+
+    /// The fields of a record can be records, up to `MAX_NESTING`
+    fn record_type(&mut self, depth: usize) -> io::Result<Arc<RecordType>> {
+        if depth >= MAX_NESTING {
+            return Err(invalid("records nested too deep"));
+        }
+        let name = self.string()?;
+        let mut fields = vec![];
+        for _ in 0..self.u32()? {
+            fields.push((self.string()?, self.r_type(depth + 1)?));
+        }
+        Ok(Arc::new(RecordType { name, fields }))
+    }
+
+    fn object(&mut self, depth: usize) -> io::Result<Object> {
+        Ok(match self.u8()? {
+            0 => Object::Int(self.i64()?),
+            1 => Object::Real(f64::from_bits(u64::from_le_bytes(self.bytes()?))),
+            2 => Object::String(self.string()?),
+            3 => Object::Bool(self.u8()? != 0),
+            4 if depth < MAX_NESTING => {
+                let len = self.u32()?;
+                let mut elements = vec![];
+                for _ in 0..len {
+                    elements.push(self.object(depth + 1)?);
+                }
+                Object::Array(elements.into_boxed_slice())
+            }
+            5 if depth < MAX_NESTING => {
+                let record = self.record_type(0)?;
+                let mut fields = vec![];
+                for _ in 0..record.fields.len() {
+                    fields.push(self.object(depth + 1)?);
+                }
+                Object::Record(record, fields.into_boxed_slice())
+            }
+            _ => return Err(invalid("unknown constant")),
+        })
+    }
+
+    fn op(&mut self) -> io::Result<Op> {
+        Ok(match self.u8()? {
+            0 => Op::Const(self.u32()?),
+            1 => Op::Load(self.u32()?),
+            2 => Op::Store(self.u32()?),
+            3 => Op::Index,
+            4 => Op::LoadElement(self.u32()?, self.u32()?),
+            5 => Op::StoreElement(self.u32()?, self.u32()?),
+            6 => Op::Add,
+            7 => Op::Sub,
+            8 => Op::Mul,
+            9 => Op::Div,
+            10 => Op::And,
+            11 => Op::Eq,
+            12 => Op::Gt,
+            13 => Op::Ge,
+            14 => Op::Lt,
+            15 => Op::Le,
+            16 => Op::Not,
+            17 => Op::Jump(self.u32()?),
+            18 => Op::JumpUnless(self.u32()?),
+            19 => Op::Call(self.u32()?),
+            20 => Op::CopyBack(self.u32()?, self.u32()?),
+            21 => Op::Return,
+            22 => Op::ReturnValue,
+            23 => Op::NoReturn,
+            24 => Op::Pop,
+            25 => Op::Write(self.u8()?),
+            26 => Op::Read(self.r_type(0)?),
+            27 => Op::Assert,
+            28 => Op::Halt,
+            29 => Op::GetField(self.u32()?),
+            30 => Op::SetField(self.u32()?),
+            31 => Op::GetElement(self.u32()?),
+            32 => Op::SetElement(self.u32()?),
+            33 => Op::Builtin(Builtin::from_code(self.u8()?).ok_or(invalid("unknown builtin"))?),
+            34 => Op::Newline,
+            _ => return Err(invalid("unknown instruction")),
+        })
+    }
+}
+
+impl Module {
+    /// Writes the module in the `.mplc` format: the magic and the
+    /// version, then the constants, the subprograms and the
+    /// instructions with their positions, all little endian
+    pub fn write(&self, out: &mut dyn Write) -> io::Result<()> {
+        out.write_all(MAGIC)?;
+        out.write_all(&VERSION.to_le_bytes())?;
+        write_u32(out, self.constants.len() as u32)?;
+        for constant in self.constants.iter() {
+            write_object(out, constant)?;
+        }
+        write_u32(out, self.functions.len() as u32)?;
+        for function in self.functions.iter() {
+            write_str(out, &function.name)?;
+            write_u32(out, function.params)?;
+            write_u32(out, function.locals)?;
+            write_u32(out, function.entry)?;
+        }
+        write_u32(out, self.code.len() as u32)?;
+        for (op, position) in self.code.iter().zip(self.positions.iter()) {
+            out.write_all(&[op.code()])?;
+            match op {
+                Op::Const(a)
+                | Op::Load(a)
+                | Op::Store(a)
+                | Op::Jump(a)
+                | Op::JumpUnless(a)
+                | Op::Call(a)
+                | Op::GetField(a)
+                | Op::SetField(a)
+                | Op::GetElement(a)
+                | Op::SetElement(a) => write_u32(out, *a)?,
+                Op::LoadElement(a, b) | Op::StoreElement(a, b) | Op::CopyBack(a, b) => {
+                    write_u32(out, *a)?;
+                    write_u32(out, *b)?;
+                }
+                Op::Read(t) => write_type(out, t)?,
+                Op::Builtin(b) => out.write_all(&[b.code()])?,
+                Op::Write(n) => out.write_all(&[*n])?,
+                _ => {}
+            }
+            write_u32(out, position.line as u32)?;
+            write_u32(out, position.col as u32)?;
+        }
+        Ok(())
+    }
+
+    /// Reads a module written by `write`, checking that every
+    /// reference in it points to something
+    pub fn read(input: &mut dyn Read) -> io::Result<Module> {
+        let mut reader = Reader { input };
+        if &reader.bytes::<4>()? != MAGIC {
+            return Err(invalid("not a MiniPascal bytecode file"));
+        }
+        let version = u16::from_le_bytes(reader.bytes()?);
+        if version != VERSION {
+            return Err(invalid(&format!(
+                "bytecode version {} is not supported, expected {}",
+                version, VERSION
+            )));
+        }
+        let mut module = Module::default();
+        for _ in 0..reader.u32()? {
+            module.constants.push(reader.object(0)?);
+        }
+        for _ in 0..reader.u32()? {
+            module.functions.push(Function {
+                name: reader.string()?,
+                params: reader.u32()?,
+                locals: reader.u32()?,
+                entry: reader.u32()?,
+            });
+        }
+        for _ in 0..reader.u32()? {
+            module.code.push(reader.op()?);
+            let (line, col) = (reader.u32()?, reader.u32()?);
+            module
+                .positions
+                .push(Position::new(0, line as i64, col as i64));
+        }
+        module.validate()?;
+        Ok(module)
+    }
+
+    /// Checks that the jumps, calls, constants and slots are in range,
+    /// so that the virtual machine can trust them. The code of a
+    /// subprogram goes from its entry to the next one, its jumps stay
+    /// in it and it can't fall into the next one, so it only ever runs
+    /// with its own slots
+    fn validate(&self) -> io::Result<()> {
+        let main = self.functions.first().ok_or(invalid("no main block"))?;
+        let code = self.code.len() as u32;
+        if main.params != 0 {
+            return Err(invalid("the main block has parameters"));
+        }
+        if self
+            .functions
+            .iter()
+            // every variable is declared by a `Store`
+            .any(|f| f.entry >= code || f.params > f.locals || f.locals - f.params > code)
+        {
+            return Err(invalid("subprogram out of range"));
+        }
+        let mut entries: Vec<&Function> = self.functions.iter().collect();
+        entries.sort_by_key(|f| f.entry);
+        if entries.windows(2).any(|w| w[0].entry == w[1].entry) {
+            return Err(invalid("subprograms with the same entry"));
+        }
+        // 0 before the first entry, where no slot is valid
+        let owner = |i: u32| entries.partition_point(|f| f.entry <= i);
+        let name = |n: u32| matches!(self.constants.get(n as usize), Some(Object::String(_)));
+        let slot = |s: u32, locals: u32| match s & GLOBAL {
+            0 => s < locals,
+            _ => s & !GLOBAL < main.locals,
+        };
+        for (i, op) in self.code.iter().enumerate() {
+            let i = i as u32;
+            let locals = match owner(i) {
+                0 => 0,
+                n => entries[n - 1].locals,
+            };
+            let valid = match *op {
+                Op::Const(c) => (c as usize) < self.constants.len(),
+                Op::Load(s) | Op::Store(s) => slot(s, locals),
+                Op::LoadElement(s, n) | Op::StoreElement(s, n) => slot(s, locals) && name(n),
+                Op::GetElement(n) | Op::SetElement(n) => name(n),
+                Op::Jump(t) | Op::JumpUnless(t) => t < code && owner(t) == owner(i),
+                Op::Call(f) => (f as usize) < self.functions.len() && f != 0,
+                Op::CopyBack(_, s) => slot(s, locals),
+                Op::Write(n) => n <= 2,
+                _ => true,
+            };
+            if !valid {
+                return Err(invalid(&format!("instruction {} out of range", i)));
+            }
+            let last = i + 1 == code || owner(i + 1) != owner(i);
+            let ends = matches!(
+                op,
+                Op::Halt | Op::Return | Op::ReturnValue | Op::NoReturn | Op::Jump(_)
+            );
+            if last && !ends {
+                return Err(invalid(&format!(
+                    "instruction {} runs into the next subprogram",
+                    i
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Module {
+    /// Listing of the instructions, with the constants they use
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, op) in self.code.iter().enumerate() {
+            for function in self.functions.iter().filter(|f| f.entry as usize == i) {
+                writeln!(
+                    f,
+                    "{}: ({} params, {} slots)",
+                    function.name, function.params, function.locals
+                )?;
+            }
+            let position = self.positions[i].to_string();
+            write!(f, "{:>6} {:<8} {}", i, position, op)?;
+            match op {
+                Op::Const(c) => write!(f, "\t; {:?}", self.constants[*c as usize])?,
+                Op::LoadElement(_, n) | Op::StoreElement(_, n) => {
+                    write!(f, "\t; {:?}", self.constants[*n as usize])?
+                }
+                Op::Call(c) => write!(f, "\t; {}", self.functions[*c as usize].name)?,
+                Op::Load(s) | Op::Store(s) if s & GLOBAL != 0 => {
+                    write!(f, "\t; global {}", s & !GLOBAL)?
+                }
+                _ => {}
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
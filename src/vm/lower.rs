@@ -0,0 +1,623 @@
+use std::collections::HashMap;
+
+use crate::{
+    core::{
+        ast::{ASTNode, BlockNode, ProgramNode},
+        builtins::Builtin,
+        errors::CompilationError,
+        objects::Object,
+        symbol_table::{SymbolTable, SymbolType},
+        types::Type,
+    },
+    interpreter::default_value,
+    scanner::position::Position,
+};
+
+use super::bytecode::{Function, Module, Op, GLOBAL};
+
+/// What a `return` means in the code being lowered
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    Main,
+    Function,
+    Procedure,
+}
+
+/// Where a store in a field of a record or in an element of a nested
+/// array goes, the indexes of the array elements in it are computed
+/// once, in slots of their own
+enum Place {
+    Var(u32),
+    /// The slot of the array, the constant with its name and the slots
+    /// with the indexes, one for each dimension
+    Element(u32, u32, Vec<u32>),
+    Field(Box<Place>, u32),
+}
+
+/// Turns the AST in bytecode. Variables get a slot of the subprogram
+/// when they are declared, so that the virtual machine never looks up
+/// a name
+struct Lowering {
+    module: Module,
+    /// Index of every function and procedure, by lowercase name
+    functions: HashMap<String, u32>,
+    procedures: HashMap<String, u32>,
+    /// Parameters of every subprogram, with whether they are copied
+    /// back
+    params: Vec<Vec<(String, bool)>>,
+    /// Slots of the visible variables, one map per nested block
+    scopes: Vec<HashMap<String, u32>>,
+    /// Slots of the global variables in the main block
+    globals: HashMap<String, u32>,
+    locals: u32,
+    /// Jumps of the `break` and of the `continue` statements of the
+    /// loops being lowered, patched at the end of each loop
+    loops: Vec<(Vec<usize>, Vec<usize>)>,
+    kind: Kind,
+    position: Position,
+    errors: Vec<CompilationError>,
+}
+
+/// Lowers a checked program to bytecode, the errors are only for
+/// programs the checks would have refused
+pub fn compile(program: &ProgramNode) -> Result<Module, Vec<CompilationError>> {
+    let mut lowering = Lowering {
+        module: Module::default(),
+        functions: HashMap::new(),
+        procedures: HashMap::new(),
+        params: vec![],
+        scopes: vec![],
+        globals: HashMap::new(),
+        locals: 0,
+        loops: vec![],
+        kind: Kind::Main,
+        position: Position::new(0, 0, 0),
+        errors: vec![],
+    };
+    lowering.declare(&program.program_name.name.lexeme, &SymbolTable::new());
+    for f in program.functions.iter() {
+        let index = lowering.declare(&f.name, &f.args);
+        lowering.functions.insert(f.name.to_lowercase(), index);
+    }
+    for p in program.procedures.iter() {
+        let index = lowering.declare(&p.name, &p.args);
+        lowering.procedures.insert(p.name.to_lowercase(), index);
+    }
+
+    // the globals are the first slots of the main block, lowered
+    // before the subprograms that use them
+    lowering.subprogram(0, Kind::Main, &SymbolTable::new(), |l| {
+        for g in program.globals.iter() {
+            let slot = l.locals;
+            l.statement(&ASTNode::VariableDecl(g.clone()));
+            l.globals.insert(g.id.lexeme.to_lowercase(), slot);
+        }
+        l.block(&program.main_block);
+        l.emit(Op::Halt);
+    });
+    let subprograms = program
+        .functions
+        .iter()
+        .map(|f| (Kind::Function, &f.args, &f.block))
+        .chain(
+            program
+                .procedures
+                .iter()
+                .map(|p| (Kind::Procedure, &p.args, &p.block)),
+        );
+    for (index, (kind, params, block)) in subprograms.enumerate() {
+        lowering.subprogram(index as u32 + 1, kind, params, |l| {
+            l.statement(block);
+            l.emit(match kind {
+                Kind::Function => Op::NoReturn,
+                _ => Op::Return,
+            });
+        });
+    }
+
+    match lowering.errors.is_empty() {
+        true => Ok(lowering.module),
+        false => Err(lowering.errors),
+    }
+}
+
+impl Lowering {
+    fn declare(&mut self, name: &str, params: &SymbolTable) -> u32 {
+        self.module.functions.push(Function {
+            name: name.to_string(),
+            params: params.len() as u32,
+            locals: params.len() as u32,
+            entry: 0,
+        });
+        self.params.push(
+            params
+                .iter()
+                .map(|p| {
+                    let by_reference =
+                        p.s_type == SymbolType::VarParam || matches!(p.r_type, Type::Array(..));
+                    (p.name.clone(), by_reference)
+                })
+                .collect(),
+        );
+        self.module.functions.len() as u32 - 1
+    }
+
+    /// Lowers the body of a subprogram, its parameters are the first
+    /// slots
+    fn subprogram(
+        &mut self,
+        index: u32,
+        kind: Kind,
+        params: &SymbolTable,
+        body: impl FnOnce(&mut Lowering),
+    ) {
+        self.kind = kind;
+        self.scopes = vec![params
+            .iter()
+            .enumerate()
+            .map(|(slot, p)| (p.name.to_lowercase(), slot as u32))
+            .collect()];
+        self.locals = params.len() as u32;
+        self.module.functions[index as usize].entry = self.module.code.len() as u32;
+        body(self);
+        self.module.functions[index as usize].locals = self.locals;
+    }
+
+    fn emit(&mut self, op: Op) -> usize {
+        self.module.code.push(op);
+        self.module.positions.push(self.position);
+        self.module.code.len() - 1
+    }
+
+    /// Points the jump at `at` to the next instruction
+    fn patch(&mut self, at: usize) {
+        let target = self.module.code.len() as u32;
+        match &mut self.module.code[at] {
+            Op::Jump(t) | Op::JumpUnless(t) => *t = target,
+            op => unreachable!("{} is not a jump", op),
+        }
+    }
+
+    /// Lowers the body of a loop, the `continue` statements in it jump
+    /// to the code right after it. Returns the jumps of its `break`
+    /// statements
+    fn loop_body(&mut self, body: &ASTNode) -> Vec<usize> {
+        self.loops.push((vec![], vec![]));
+        self.statement(body);
+        let (breaks, continues) = self.loops.pop().unwrap_or_default();
+        continues.into_iter().for_each(|at| self.patch(at));
+        breaks
+    }
+
+    fn constant(&mut self, value: Object) -> u32 {
+        self.module.constants.push(value);
+        self.module.constants.len() as u32 - 1
+    }
+
+    fn error(&mut self, position: Position, description: String) {
+        self.errors.push(CompilationError {
+            position,
+            description,
+        });
+    }
+
+    fn slot(&mut self, name: &str, position: Position) -> u32 {
+        let name = name.to_lowercase();
+        match self.scopes.iter().rev().find_map(|s| s.get(&name)) {
+            Some(slot) => *slot,
+            None if self.globals.contains_key(&name) => GLOBAL | self.globals[&name],
+            None => {
+                self.error(position, format!("Unknown variable `{}`", name));
+                0
+            }
+        }
+    }
+
+    fn block(&mut self, block: &BlockNode) {
+        self.scopes.push(HashMap::new());
+        for stmt in block.statements.iter() {
+            self.statement(stmt);
+        }
+        self.scopes.pop();
+    }
+
+    fn statement(&mut self, node: &ASTNode) {
+        let outer = self.position;
+        self.position = node.position();
+        match node {
+            ASTNode::Block(b) => self.block(b),
+            ASTNode::VariableDecl(v) => {
+                let value = self.constant(default_value(&v.var_type, v.size));
+                self.emit(Op::Const(value));
+                let slot = self.locals;
+                self.locals += 1;
+                if let Some(scope) = self.scopes.last_mut() {
+                    scope.insert(v.id.lexeme.to_lowercase(), slot);
+                }
+                self.emit(Op::Store(slot));
+            }
+            ASTNode::VarReassignment(v) => {
+                self.expression(&v.new_value);
+                self.store(&v.variable_to_reassign);
+            }
+            ASTNode::IfStmt(i) => {
+                self.expression(&i.guard);
+                let to_else = self.emit(Op::JumpUnless(0));
+                self.statement(&i.then);
+                match &i.else_stmt {
+                    Some(else_stmt) => {
+                        let to_end = self.emit(Op::Jump(0));
+                        self.patch(to_else);
+                        self.statement(else_stmt);
+                        self.patch(to_end);
+                    }
+                    None => self.patch(to_else),
+                }
+            }
+            ASTNode::CaseStmt(c) => {
+                // the value gets a slot of its own, evaluated once
+                let value = self.locals;
+                self.locals += 1;
+                self.expression(&c.value);
+                self.emit(Op::Store(value));
+                let mut to_branches = Vec::new();
+                for b in c.branches.iter() {
+                    let mut jumps = Vec::new();
+                    for l in b.labels.iter() {
+                        let label = self.constant(l.value.clone());
+                        self.emit(Op::Load(value));
+                        self.emit(Op::Const(label));
+                        self.emit(Op::Eq);
+                        self.emit(Op::Not);
+                        jumps.push(self.emit(Op::JumpUnless(0)));
+                    }
+                    to_branches.push(jumps);
+                }
+                if let Some(else_stmt) = &c.else_stmt {
+                    self.statement(else_stmt);
+                }
+                let mut to_end = vec![self.emit(Op::Jump(0))];
+                for (b, jumps) in c.branches.iter().zip(to_branches) {
+                    jumps.into_iter().for_each(|at| self.patch(at));
+                    self.statement(&b.body);
+                    to_end.push(self.emit(Op::Jump(0)));
+                }
+                to_end.into_iter().for_each(|at| self.patch(at));
+            }
+            ASTNode::ElseStmt(e) => self.statement(&e.block),
+            ASTNode::WhileStmt(w) => {
+                let start = self.module.code.len() as u32;
+                self.expression(&w.guard);
+                let to_end = self.emit(Op::JumpUnless(0));
+                let breaks = self.loop_body(&w.block);
+                self.position = w.position;
+                self.emit(Op::Jump(start));
+                self.patch(to_end);
+                breaks.into_iter().for_each(|at| self.patch(at));
+            }
+            ASTNode::RepeatStmt(r) => {
+                let start = self.module.code.len() as u32;
+                let breaks = self.loop_body(&r.block);
+                self.expression(&r.guard);
+                self.emit(Op::JumpUnless(start));
+                breaks.into_iter().for_each(|at| self.patch(at));
+            }
+            ASTNode::BreakStmt(_) => {
+                let at = self.emit(Op::Jump(0));
+                if let Some((breaks, _)) = self.loops.last_mut() {
+                    breaks.push(at);
+                }
+            }
+            ASTNode::ContinueStmt(_) => {
+                let at = self.emit(Op::Jump(0));
+                if let Some((_, continues)) = self.loops.last_mut() {
+                    continues.push(at);
+                }
+            }
+            ASTNode::ForStmt(l) => {
+                // the end gets a slot of its own, evaluated once
+                let end = self.locals;
+                self.locals += 1;
+                self.expression(&l.start);
+                self.expression(&l.end);
+                self.emit(Op::Store(end));
+                let variable = match l.variable.as_ref() {
+                    ASTNode::VarName(v) => self.slot(&v.id.lexeme, v.position),
+                    other => {
+                        self.error(other.position(), "Expected a variable".to_string());
+                        0
+                    }
+                };
+                self.emit(Op::Store(variable));
+                let (compare, step) = match l.downto {
+                    false => (Op::Le, Op::Add),
+                    true => (Op::Ge, Op::Sub),
+                };
+                self.emit(Op::Load(variable));
+                self.emit(Op::Load(end));
+                self.emit(compare);
+                let to_end = self.emit(Op::JumpUnless(0));
+                let start = self.module.code.len() as u32;
+                let breaks = self.loop_body(&l.block);
+                self.position = l.position;
+                self.emit(Op::Load(variable));
+                self.emit(Op::Load(end));
+                self.emit(Op::Eq);
+                self.emit(Op::Not);
+                let to_exit = self.emit(Op::JumpUnless(0));
+                let one = self.constant(Object::Int(1));
+                self.emit(Op::Load(variable));
+                self.emit(Op::Const(one));
+                self.emit(step);
+                self.emit(Op::Store(variable));
+                self.emit(Op::Jump(start));
+                self.patch(to_end);
+                self.patch(to_exit);
+                breaks.into_iter().for_each(|at| self.patch(at));
+            }
+            ASTNode::PrintStmt(p) => {
+                for arg in p.args.iter() {
+                    // the value, then its width and precision
+                    arg.nodes().for_each(|n| self.expression(n));
+                    self.emit(Op::Write(arg.nodes().count() as u8 - 1));
+                }
+                if p.newline {
+                    self.emit(Op::Newline);
+                }
+            }
+            ASTNode::ReadStmt(r) => {
+                self.emit(Op::Read(r.variable_to_read_in.r_type()));
+                self.store(&r.variable_to_read_in);
+            }
+            ASTNode::AssertStmt(a) => {
+                self.expression(&a.expr);
+                self.emit(Op::Assert);
+            }
+            ASTNode::ProcedureCallStmt(p) => {
+                let target = self.procedures.get(&p.target.to_lowercase()).copied();
+                match target {
+                    Some(target) => self.call(target, &p.args),
+                    None => self.error(p.position, format!("Unknown procedure `{}`", p.target)),
+                }
+            }
+            ASTNode::FunctionCallStmt(_) => {
+                self.expression(node);
+                self.emit(Op::Pop);
+            }
+            ASTNode::ReturnStmt(r) => {
+                if let Some(value) = &r.value {
+                    self.expression(value);
+                }
+                match (self.kind, &r.value) {
+                    (Kind::Function, Some(_)) => self.emit(Op::ReturnValue),
+                    (Kind::Function, None) => self.emit(Op::NoReturn),
+                    (kind, value) => {
+                        if value.is_some() {
+                            self.emit(Op::Pop);
+                        }
+                        self.emit(match kind {
+                            Kind::Main => Op::Halt,
+                            _ => Op::Return,
+                        })
+                    }
+                };
+            }
+            ASTNode::EofStmt(_) => {}
+            other => self.error(other.position(), format!("Unable to execute {}", other)),
+        }
+        self.position = outer;
+    }
+
+    /// Stores the value on top of the stack in a variable, in an
+    /// element of an array or in a field of a record
+    fn store(&mut self, target: &ASTNode) {
+        match target {
+            ASTNode::VarName(v) => {
+                let slot = self.slot(&v.id.lexeme, v.position);
+                self.emit(Op::Store(slot));
+            }
+            ASTNode::ArrayRef(a) if a.indexes.len() == 1 => {
+                let slot = self.slot(&a.array.lexeme, a.position);
+                let name = self.constant(Object::String(a.array.lexeme.clone()));
+                self.index(&a.indexes[0]);
+                self.at(a.position, Op::StoreElement(slot, name));
+            }
+            ASTNode::ArrayRef(_) | ASTNode::FieldRef(_) => {
+                if let Some(place) = self.place(target) {
+                    self.store_place(&place);
+                }
+            }
+            other => self.error(
+                other.position(),
+                "Assignment to something that is not a variable".to_string(),
+            ),
+        }
+    }
+
+    fn place(&mut self, target: &ASTNode) -> Option<Place> {
+        match target {
+            ASTNode::VarName(v) => Some(Place::Var(self.slot(&v.id.lexeme, v.position))),
+            ASTNode::ArrayRef(a) => {
+                let slot = self.slot(&a.array.lexeme, a.position);
+                let name = self.constant(Object::String(a.array.lexeme.clone()));
+                let mut indexes = vec![];
+                for index in a.indexes.iter() {
+                    self.index(index);
+                    indexes.push(self.locals);
+                    self.locals += 1;
+                    self.emit(Op::Store(self.locals - 1));
+                }
+                Some(Place::Element(slot, name, indexes))
+            }
+            ASTNode::FieldRef(f) => Some(Place::Field(
+                Box::new(self.place(&f.record)?),
+                f.index as u32,
+            )),
+            other => {
+                self.error(
+                    other.position(),
+                    "Assignment to something that is not a variable".to_string(),
+                );
+                None
+            }
+        }
+    }
+
+    fn load_place(&mut self, place: &Place) {
+        match place {
+            Place::Var(slot) => {
+                self.emit(Op::Load(*slot));
+            }
+            Place::Element(slot, name, indexes) => self.load_element(*slot, *name, indexes),
+            Place::Field(record, field) => {
+                self.load_place(record);
+                self.emit(Op::GetField(*field));
+            }
+        }
+    }
+
+    /// Stores the value on top of the stack, a field is stored by
+    /// storing back its whole record with the field changed
+    fn store_place(&mut self, place: &Place) {
+        match place {
+            Place::Var(slot) => {
+                self.emit(Op::Store(*slot));
+            }
+            Place::Element(slot, name, indexes) => self.store_element(*slot, *name, indexes),
+            Place::Field(record, field) => {
+                self.load_place(record);
+                self.emit(Op::SetField(*field));
+                self.store_place(record);
+            }
+        }
+    }
+
+    /// Pushes the element of the array, or its row with less indexes
+    /// than dimensions
+    fn load_element(&mut self, slot: u32, name: u32, indexes: &[u32]) {
+        if let Some((first, rest)) = indexes.split_first() {
+            self.emit(Op::Load(*first));
+            self.emit(Op::LoadElement(slot, name));
+            for index in rest {
+                self.emit(Op::Load(*index));
+                self.emit(Op::GetElement(name));
+            }
+        }
+    }
+
+    /// Stores the value on top of the stack in an element of the
+    /// array, the rows it is in are stored back with it changed
+    fn store_element(&mut self, slot: u32, name: u32, indexes: &[u32]) {
+        match indexes.split_last() {
+            Some((last, [])) => {
+                self.emit(Op::Load(*last));
+                self.emit(Op::StoreElement(slot, name));
+            }
+            Some((last, rows)) => {
+                self.load_element(slot, name, rows);
+                self.emit(Op::Load(*last));
+                self.emit(Op::SetElement(name));
+                self.store_element(slot, name, rows);
+            }
+            None => {
+                self.emit(Op::Store(slot));
+            }
+        }
+    }
+
+    /// Emits the instruction with the position of a node other than
+    /// the one being lowered
+    fn at(&mut self, position: Position, op: Op) {
+        let outer = self.position;
+        self.position = position;
+        self.emit(op);
+        self.position = outer;
+    }
+
+    fn index(&mut self, index: &ASTNode) {
+        self.expression(index);
+        self.at(index.position(), Op::Index);
+    }
+
+    /// Pushes the arguments and calls the subprogram, the arguments
+    /// that are variables get back the `var` parameters and arrays.
+    /// The parameters without an argument are the variables captured
+    /// by a nested subprogram, passed by name
+    fn call(&mut self, target: u32, args: &[(String, ASTNode)]) {
+        let params = self.params[target as usize].clone();
+        for (_, arg) in args.iter() {
+            self.expression(arg);
+        }
+        for (name, _) in params.iter().skip(args.len()) {
+            let slot = self.slot(name, self.position);
+            self.emit(Op::Load(slot));
+        }
+        self.emit(Op::Call(target));
+        for (param, (name, by_reference)) in params.iter().enumerate() {
+            let slot = match args.get(param) {
+                _ if !by_reference => continue,
+                Some((_, ASTNode::VarName(v))) => self.slot(&v.id.lexeme, v.position),
+                Some(_) => continue,
+                None => self.slot(name, self.position),
+            };
+            self.emit(Op::CopyBack(param as u32, slot));
+        }
+    }
+
+    fn expression(&mut self, node: &ASTNode) {
+        let outer = self.position;
+        self.position = node.position();
+        match node {
+            ASTNode::Literal(l) => {
+                let value = self.constant(l.value.clone());
+                self.emit(Op::Const(value));
+            }
+            ASTNode::VarName(v) => {
+                let slot = self.slot(&v.id.lexeme, v.position);
+                self.emit(Op::Load(slot));
+            }
+            ASTNode::ArrayRef(a) if a.indexes.len() == 1 => {
+                self.index(&a.indexes[0]);
+                let slot = self.slot(&a.array.lexeme, a.position);
+                let name = self.constant(Object::String(a.array.lexeme.clone()));
+                self.emit(Op::LoadElement(slot, name));
+            }
+            // the indexes are all computed before the array is read,
+            // like the interpreter does
+            ASTNode::ArrayRef(_) => {
+                if let Some(place) = self.place(node) {
+                    self.load_place(&place);
+                }
+            }
+            ASTNode::FieldRef(f) => {
+                self.expression(&f.record);
+                self.emit(Op::GetField(f.index as u32));
+            }
+            ASTNode::UnaryExpression(u) => {
+                self.expression(&u.expression);
+                self.emit(Op::Not);
+            }
+            ASTNode::BinaryExpression(b) => {
+                self.expression(&b.left);
+                self.expression(&b.right);
+                self.emit(Op::binary(&b.op_type));
+            }
+            ASTNode::FunctionCallStmt(f) if Builtin::from_name(&f.target).is_some() => {
+                for (_, arg) in f.args.iter() {
+                    self.expression(arg);
+                }
+                let builtin = Builtin::from_name(&f.target).expect("a builtin");
+                self.emit(Op::Builtin(builtin));
+            }
+            ASTNode::FunctionCallStmt(f) => {
+                let target = self.functions.get(&f.target.to_lowercase()).copied();
+                match target {
+                    Some(target) => self.call(target, &f.args),
+                    None => self.error(f.position, format!("Unknown function `{}`", f.target)),
+                }
+            }
+            other => self.error(other.position(), format!("{} is not an expression", other)),
+        }
+        self.position = outer;
+    }
+}
@@ -0,0 +1,403 @@
+//! Bytecode backend: the program is lowered once to a flat list of
+//! instructions (`compile`) and run by a stack based virtual machine,
+//! with the same observable behaviour as the interpreter. The bytecode
+//! can be saved in a `.mplc` file and run later
+
+mod bytecode;
+mod lower;
+
+pub use bytecode::{Function, Module, Op, GLOBAL, MAGIC, VERSION};
+pub use lower::compile;
+
+use std::{
+    collections::VecDeque,
+    io::{BufRead, Write},
+};
+
+use crate::{
+    core::{errors::EvaluationError, objects::Object},
+    interpreter::{
+        builtins,
+        expression::{binary, type_error},
+        statement::{format_value, io_error, next_word, parse_word},
+        MAX_DEPTH,
+    },
+    scanner::position::Position,
+};
+
+/// A running call of a subprogram
+#[derive(Debug)]
+struct Frame {
+    /// Where its slots start
+    base: usize,
+    return_pc: usize,
+}
+
+/// Runs a module, the main block first
+pub struct Vm<'a> {
+    module: &'a Module,
+    input: &'a mut dyn BufRead,
+    output: &'a mut dyn Write,
+    words: VecDeque<String>,
+    stack: Vec<Object>,
+    /// Slots of every running call, one after the other
+    slots: Vec<Object>,
+    frames: Vec<Frame>,
+    /// Parameters of the subprogram that just returned, for `CopyBack`
+    returned: Vec<Object>,
+    /// State of the generator behind `random`
+    random: u32,
+}
+
+fn malformed(position: Position, what: &str) -> EvaluationError {
+    EvaluationError {
+        position,
+        description: format!("Malformed bytecode: {}", what),
+    }
+}
+
+impl<'a> Vm<'a> {
+    pub fn new(
+        module: &'a Module,
+        input: &'a mut dyn BufRead,
+        output: &'a mut dyn Write,
+    ) -> Vm<'a> {
+        Vm {
+            module,
+            input,
+            output,
+            words: VecDeque::new(),
+            stack: vec![],
+            slots: vec![],
+            frames: vec![],
+            returned: vec![],
+            random: builtins::RANDOM_SEED,
+        }
+    }
+
+    /// Runs the program until the end of the main block
+    pub fn run(&mut self) -> Result<(), EvaluationError> {
+        let result = self.execute();
+        let flushed = self.output.flush();
+        result?;
+        flushed.map_err(|e| io_error(Position::new(0, 0, 0), e))
+    }
+
+    fn pop(&mut self, position: Position) -> Result<Object, EvaluationError> {
+        self.stack
+            .pop()
+            .ok_or_else(|| malformed(position, "the stack is empty"))
+    }
+
+    /// The slot of the running subprogram, or of the main block for
+    /// the globals
+    fn slot(&mut self, slot: u32) -> &mut Object {
+        if slot & GLOBAL != 0 {
+            return &mut self.slots[(slot & !GLOBAL) as usize];
+        }
+        let base = self.frames.last().map(|f| f.base).unwrap_or(0);
+        &mut self.slots[base + slot as usize]
+    }
+
+    /// Pops an index checked by `Op::Index`
+    fn pop_index(&mut self, position: Position) -> Result<usize, EvaluationError> {
+        match self.pop(position)? {
+            Object::Int(i) => Ok(i as usize),
+            _ => Err(type_error(position, "array index is not an int")),
+        }
+    }
+
+    fn name(&self, constant: u32) -> &str {
+        match &self.module.constants[constant as usize] {
+            Object::String(name) => name,
+            _ => "",
+        }
+    }
+
+    fn out_of_bounds(
+        &self,
+        position: Position,
+        index: usize,
+        name: u32,
+        len: usize,
+    ) -> EvaluationError {
+        EvaluationError {
+            position,
+            description: format!(
+                "Index {} out of bounds for array `{}` of length {}",
+                index,
+                self.name(name),
+                len
+            ),
+        }
+    }
+
+    fn execute(&mut self) -> Result<(), EvaluationError> {
+        let module = self.module;
+        let main = module
+            .functions
+            .first()
+            .ok_or_else(|| malformed(Position::new(0, 0, 0), "no main block"))?;
+        self.slots.resize(main.locals as usize, Object::Int(0));
+        let mut pc = main.entry as usize;
+        loop {
+            let op = module
+                .code
+                .get(pc)
+                .cloned()
+                .ok_or_else(|| malformed(Position::new(0, 0, 0), "jump out of the code"))?;
+            let position = module.positions[pc];
+            pc += 1;
+            match op {
+                Op::Const(c) => self.stack.push(module.constants[c as usize].clone()),
+                Op::Load(s) => {
+                    let value = self.slot(s).clone();
+                    self.stack.push(value);
+                }
+                Op::Store(s) => *self.slot(s) = self.pop(position)?,
+                Op::Index => match self.pop(position)? {
+                    Object::Int(i) if i >= 0 => self.stack.push(Object::Int(i)),
+                    Object::Int(i) => {
+                        return Err(EvaluationError {
+                            position,
+                            description: format!("Negative array index {}", i),
+                        })
+                    }
+                    _ => return Err(type_error(position, "array index is not an int")),
+                },
+                Op::LoadElement(s, name) => {
+                    let index = self.pop_index(position)?;
+                    let element = match self.slot(s) {
+                        Object::Array(elements) => {
+                            elements.get(index).cloned().ok_or(elements.len())
+                        }
+                        _ => {
+                            return Err(type_error(
+                                position,
+                                "indexing something that is not an array",
+                            ))
+                        }
+                    };
+                    match element {
+                        Ok(element) => self.stack.push(element),
+                        Err(len) => return Err(self.out_of_bounds(position, index, name, len)),
+                    }
+                }
+                Op::StoreElement(s, name) => {
+                    let index = self.pop_index(position)?;
+                    let value = self.pop(position)?;
+                    let stored = match self.slot(s) {
+                        Object::Array(elements) if index < elements.len() => {
+                            elements[index] = value;
+                            Ok(())
+                        }
+                        Object::Array(elements) => Err(elements.len()),
+                        _ => {
+                            return Err(EvaluationError {
+                                position,
+                                description: format!("`{}` is not an array", self.name(name)),
+                            })
+                        }
+                    };
+                    if let Err(len) = stored {
+                        return Err(self.out_of_bounds(position, index, name, len));
+                    }
+                }
+                Op::GetElement(name) => {
+                    let index = self.pop_index(position)?;
+                    match self.pop(position)? {
+                        Object::Array(elements) if index < elements.len() => {
+                            let mut elements = elements.into_vec();
+                            self.stack.push(elements.swap_remove(index));
+                        }
+                        Object::Array(elements) => {
+                            return Err(self.out_of_bounds(position, index, name, elements.len()))
+                        }
+                        _ => {
+                            return Err(type_error(
+                                position,
+                                "indexing something that is not an array",
+                            ))
+                        }
+                    }
+                }
+                Op::SetElement(name) => {
+                    let index = self.pop_index(position)?;
+                    let array = self.pop(position)?;
+                    let value = self.pop(position)?;
+                    match array {
+                        Object::Array(mut elements) if index < elements.len() => {
+                            elements[index] = value;
+                            self.stack.push(Object::Array(elements));
+                        }
+                        Object::Array(elements) => {
+                            return Err(self.out_of_bounds(position, index, name, elements.len()))
+                        }
+                        _ => {
+                            return Err(type_error(
+                                position,
+                                "indexing something that is not an array",
+                            ))
+                        }
+                    }
+                }
+                Op::GetField(field) => match self.pop(position)? {
+                    Object::Record(_, mut fields) if (field as usize) < fields.len() => {
+                        let value = std::mem::replace(&mut fields[field as usize], Object::Int(0));
+                        self.stack.push(value);
+                    }
+                    _ => {
+                        return Err(malformed(
+                            position,
+                            "field of something that is not a record",
+                        ))
+                    }
+                },
+                Op::SetField(field) => {
+                    let record = self.pop(position)?;
+                    let value = self.pop(position)?;
+                    match record {
+                        Object::Record(r, mut fields) if (field as usize) < fields.len() => {
+                            fields[field as usize] = value;
+                            self.stack.push(Object::Record(r, fields));
+                        }
+                        _ => {
+                            return Err(malformed(
+                                position,
+                                "field of something that is not a record",
+                            ))
+                        }
+                    }
+                }
+                Op::Add
+                | Op::Sub
+                | Op::Mul
+                | Op::Div
+                | Op::And
+                | Op::Eq
+                | Op::Gt
+                | Op::Ge
+                | Op::Lt
+                | Op::Le => {
+                    let right = self.pop(position)?;
+                    let left = self.pop(position)?;
+                    let op_type = op.binary_type().expect("a binary operator");
+                    self.stack.push(binary(&op_type, left, right, position)?);
+                }
+                Op::Not => match self.pop(position)? {
+                    Object::Bool(b) => self.stack.push(Object::Bool(!b)),
+                    _ => return Err(type_error(position, "`!` applied to a non bool value")),
+                },
+                Op::Jump(target) => pc = target as usize,
+                Op::JumpUnless(target) => {
+                    if !matches!(self.pop(position)?, Object::Bool(true)) {
+                        pc = target as usize;
+                    }
+                }
+                Op::Call(f) => {
+                    // the main block counts as a frame for the interpreter
+                    if self.frames.len() + 1 >= MAX_DEPTH {
+                        return Err(EvaluationError {
+                            position,
+                            description: "Stack overflow, too many nested calls".to_string(),
+                        });
+                    }
+                    let function = &module.functions[f as usize];
+                    let params = function.params as usize;
+                    if self.stack.len() < params {
+                        return Err(malformed(position, "missing arguments"));
+                    }
+                    let base = self.slots.len();
+                    self.slots
+                        .extend(self.stack.drain(self.stack.len() - params..));
+                    self.slots
+                        .resize(base + function.locals as usize, Object::Int(0));
+                    self.frames.push(Frame {
+                        base,
+                        return_pc: pc,
+                    });
+                    pc = function.entry as usize;
+                }
+                Op::Builtin(builtin) => {
+                    let count = builtin.param_names().len();
+                    if self.stack.len() < count {
+                        return Err(malformed(position, "missing arguments"));
+                    }
+                    let args: Vec<Object> = self.stack.drain(self.stack.len() - count..).collect();
+                    let result = builtins::call(builtin, &args, &mut self.random, position)?;
+                    self.stack.push(result);
+                }
+                Op::CopyBack(param, s) => {
+                    if let Some(value) = self.returned.get_mut(param as usize) {
+                        let value = std::mem::replace(value, Object::Int(0));
+                        *self.slot(s) = value;
+                    }
+                }
+                Op::Return | Op::ReturnValue => {
+                    let frame = match self.frames.pop() {
+                        Some(frame) => frame,
+                        None => break,
+                    };
+                    if let Some(Op::CopyBack(..)) = module.code.get(frame.return_pc) {
+                        self.returned.clear();
+                        self.returned.extend(self.slots.drain(frame.base..));
+                    }
+                    self.slots.truncate(frame.base);
+                    pc = frame.return_pc;
+                }
+                Op::NoReturn => {
+                    let frame = self.frames.last().map(|f| f.return_pc).unwrap_or(pc);
+                    let call = frame.saturating_sub(1);
+                    let name = match module.code.get(call) {
+                        Some(Op::Call(f)) => module.functions[*f as usize].name.as_str(),
+                        _ => "",
+                    };
+                    return Err(EvaluationError {
+                        position: module.positions.get(call).copied().unwrap_or(position),
+                        description: format!("Function `{}` ended without a return", name),
+                    });
+                }
+                Op::Pop => {
+                    self.pop(position)?;
+                }
+                Op::Write(formats) => {
+                    let mut format = [None, None];
+                    for i in (0..formats as usize).rev() {
+                        format[i] = match self.pop(position)? {
+                            Object::Int(n) => Some(n),
+                            _ => {
+                                return Err(type_error(
+                                    position,
+                                    "the width of a value is not an int",
+                                ))
+                            }
+                        };
+                    }
+                    let text = format_value(&self.pop(position)?, format[0], format[1]);
+                    self.output
+                        .write_all(text.as_bytes())
+                        .map_err(|e| io_error(position, e))?;
+                }
+                Op::Newline => {
+                    self.output
+                        .write_all(b"\n")
+                        .map_err(|e| io_error(position, e))?;
+                }
+                Op::Read(r_type) => {
+                    let word = next_word(self.input, &mut self.words, position)?;
+                    self.stack.push(parse_word(word, r_type, position)?);
+                }
+                Op::Assert => {
+                    if !matches!(self.pop(position)?, Object::Bool(true)) {
+                        return Err(EvaluationError {
+                            position,
+                            description: "Assertion failed".to_string(),
+                        });
+                    }
+                }
+                Op::Halt => break,
+            }
+        }
+        Ok(())
+    }
+}
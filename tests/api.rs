@@ -0,0 +1,300 @@
+//! The library API, used the way other tools would use it
+
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+use miniplc::{AssertMode, CompileOptions, Dialect, Lint, LintLevel, OptLevel, RuntimeMode};
+
+const HELLO: &str = "program hello;\nbegin\n  writeln(\"hello\");\nend\n";
+const UNUSED: &str = "program lints;\nbegin\n  var unused : int;\n  writeln(1);\nend\n";
+
+#[test]
+fn compile_str_gives_c() {
+    let output = miniplc::compile_str(HELLO, &CompileOptions::default()).unwrap();
+    assert!(output.c_source.contains("int main()"));
+    assert!(output.warnings.is_empty());
+}
+
+#[test]
+fn syntax_errors_are_diagnostics() {
+    let diagnostics =
+        miniplc::compile_str("program;\nbegin\nend\n", &CompileOptions::default()).unwrap_err();
+    assert!(!diagnostics.syntax.is_empty());
+    assert!(!diagnostics.to_string().is_empty());
+}
+
+#[test]
+fn missing_returns_are_diagnostics() {
+    let src = "program p;\nfunction f(x : int) : int;\nbegin\n  if x > 0 then return x;\nend\nbegin\n  writeln(f(1));\nend\n";
+    let diagnostics = miniplc::check_str(src, &CompileOptions::default()).unwrap_err();
+    assert!(diagnostics.syntax.is_empty());
+    assert!(!diagnostics.compilation.is_empty());
+}
+
+#[test]
+fn endless_loops_need_no_return_after_them() {
+    let src = "program p;\nfunction f(x : int) : int;\nbegin\n  while true do\n  begin\n    if x > 0 then return x;\n    x := x + 1;\n  end\nend\nbegin\n  writeln(f(0));\nend\n";
+    let checked = miniplc::check_str(src, &CompileOptions::default()).unwrap();
+    assert!(checked.warnings.is_empty(), "{:?}", checked.warnings);
+
+    let exits = src.replace("return x;", "break;");
+    let diagnostics = miniplc::check_str(&exits, &CompileOptions::default()).unwrap_err();
+    assert_eq!(diagnostics.compilation.len(), 1);
+}
+
+#[test]
+fn for_loop_variables_can_not_change_in_the_body() {
+    let src = "program p;\nprocedure bump(var x : int);\nbegin\n  x := x + 1;\nend\nbegin\n  var i : int;\n  for i := 1 to 3 do\n  begin\n    bump(i);\n    i := 4;\n  end\n  i := 5;\nend\n";
+    let diagnostics = miniplc::check_str(src, &CompileOptions::default()).unwrap_err();
+    let lines: Vec<i64> = diagnostics
+        .compilation
+        .iter()
+        .map(|e| e.position.line)
+        .collect();
+    assert_eq!(vec![10, 11], lines);
+}
+
+#[test]
+fn repeat_bodies_run_at_least_once() {
+    let src = "program p;\nbegin\n  var x : int;\n  repeat\n    x := 1;\n  until x > 0;\n  writeln(x);\nend\n";
+    let checked = miniplc::check_str(src, &CompileOptions::default()).unwrap();
+    assert!(checked.warnings.is_empty());
+}
+
+#[test]
+fn calls_assign_their_var_arguments() {
+    let src = "program p;\nprocedure init(var r : int);\nbegin\n  r := 1;\nend\nprocedure outer();\nbegin\n  var z : int;\n  procedure set();\n  begin\n    z := 3;\n  end\n  set();\n  writeln(z);\nend\nbegin\n  var x : int;\n  init(x);\n  writeln(x);\n  outer();\nend\n";
+    let checked = miniplc::check_str(src, &CompileOptions::default()).unwrap();
+    let reads: Vec<_> = checked
+        .warnings
+        .iter()
+        .filter(|w| w.lint == Lint::UninitializedRead)
+        .collect();
+    assert!(reads.is_empty(), "{:?}", reads);
+}
+
+#[test]
+fn var_arguments_change_loop_guards() {
+    let inc = "procedure inc(var x : int);\nbegin\n  x := x + 1;\nend\nfunction next(var x : int) : int;\nbegin\n  x := x + 1;\n  return x;\nend\n";
+    for body in ["inc(k);", "writeln(next(k));"] {
+        let src = format!(
+            "program p;\n{}begin\n  var k : int;\n  k := 0;\n  while k < 3 do\n  begin\n    {}\n  end\nend\n",
+            inc, body
+        );
+        let checked = miniplc::check_str(&src, &CompileOptions::default()).unwrap();
+        assert!(checked.warnings.is_empty(), "{:?}", checked.warnings);
+    }
+}
+
+#[test]
+fn written_var_parameters_are_used() {
+    for body in ["r := 1;", "read(r);"] {
+        let src = format!(
+            "program p;\nprocedure get(var r : int);\nbegin\n  {}\nend\nbegin\n  var x : int;\n  get(x);\n  writeln(x);\nend\n",
+            body
+        );
+        let checked = miniplc::check_str(&src, &CompileOptions::default()).unwrap();
+        assert!(checked.warnings.is_empty(), "{:?}", checked.warnings);
+    }
+}
+
+#[test]
+fn captured_variables_are_not_parameters() {
+    let src = "program p;\nprocedure outer();\nbegin\n  var i : int;\n  procedure a();\n  begin\n    procedure c();\n    begin\n      i := i + 1;\n    end\n    writeln(1);\n  end\n  i := 0;\n  a();\n  writeln(i);\nend\nbegin\n  outer();\nend\n";
+    let checked = miniplc::check_str(src, &CompileOptions::default()).unwrap();
+    assert!(checked.warnings.is_empty(), "{:?}", checked.warnings);
+}
+
+#[test]
+fn lint_levels_come_from_the_options() {
+    let mut options = CompileOptions::default();
+    let checked = miniplc::check_str(UNUSED, &options).unwrap();
+    assert_eq!(checked.warnings.len(), 1);
+
+    options.lints.set(Lint::UnusedVariable, LintLevel::Allow);
+    assert!(miniplc::check_str(UNUSED, &options)
+        .unwrap()
+        .warnings
+        .is_empty());
+
+    options.lints.set(Lint::UnusedVariable, LintLevel::Deny);
+    let diagnostics = miniplc::compile_str(UNUSED, &options).unwrap_err();
+    assert_eq!(diagnostics.warnings.len(), 1);
+}
+
+#[test]
+fn checked_programs_run_in_the_interpreter() {
+    let checked = miniplc::check_str(HELLO, &CompileOptions::default()).unwrap();
+    let mut output = Vec::new();
+    miniplc::Interpreter::new(&mut std::io::empty(), &mut output)
+        .run(&checked.program)
+        .unwrap();
+    assert_eq!(String::from_utf8(output).unwrap(), "hello\n");
+}
+
+const ARRAYS: &str = "program arrays;\nbegin\n  var a : array [3] of int;\n  var i : int;\n  read(i);\n  a[i] := 1 + 2 * 3;\n  writeln(a[i]);\n  assert(i < 2);\nend\n";
+
+#[test]
+fn removed_lints_are_still_accepted() {
+    let mut options = CompileOptions::default();
+    assert!(options
+        .lints
+        .set_by_name("missing-return", LintLevel::Allow)
+        .is_ok());
+    let src = "{* pragma allow(missing_return) *}\nprogram p;\nbegin\n  writeln(1);\nend\n";
+    assert!(miniplc::check_str(src, &options).is_ok());
+}
+
+#[test]
+fn c99_has_no_label_addresses() {
+    let options = CompileOptions {
+        dialect: Dialect::C99,
+        ..CompileOptions::default()
+    };
+    let c_source = miniplc::compile_str(ARRAYS, &options).unwrap().c_source;
+    assert!(!c_source.contains("= &&"));
+    assert!(!c_source.contains("goto *"));
+}
+
+#[test]
+fn constant_expressions_are_folded() {
+    let options = CompileOptions {
+        opt_level: OptLevel::O1,
+        ..CompileOptions::default()
+    };
+    let c_source = miniplc::compile_str(ARRAYS, &options).unwrap().c_source;
+    assert!(c_source.contains("last_int = 7;"));
+    assert!(!c_source.contains("last_int = 3;"));
+}
+
+#[test]
+fn runtime_goes_in_the_header() {
+    let options = CompileOptions {
+        runtime: RuntimeMode::Header,
+        asserts: AssertMode::Off,
+        string_buffer: 64,
+        ..CompileOptions::default()
+    };
+    let output = miniplc::compile_str(ARRAYS, &options).unwrap();
+    assert!(output.c_source.contains(miniplc::RUNTIME_HEADER));
+    assert!(!output.c_source.contains("assert"));
+    assert!(output.runtime_header.unwrap().contains("mpl_check_index"));
+}
+
+/// Builds the program with `cc` and runs it, None without a C compiler
+fn run_native(src: &str, options: &CompileOptions, name: &str, stdin: &str) -> Option<Output> {
+    let c_source = miniplc::compile_str(src, options).unwrap().c_source;
+    let work = std::path::Path::new(env!("CARGO_TARGET_TMPDIR"));
+    let c_file = work.join(format!("{}.c", name));
+    let binary = work.join(name);
+    std::fs::write(&c_file, c_source).unwrap();
+    let built = Command::new("cc")
+        .args(["-w", "-o"])
+        .arg(&binary)
+        .arg(&c_file)
+        .arg("-lm")
+        .status()
+        .ok()?;
+    assert!(built.success());
+    let mut child = Command::new(&binary)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(stdin.as_bytes())
+        .unwrap();
+    Some(child.wait_with_output().unwrap())
+}
+
+#[test]
+fn line_directives_point_at_the_source() {
+    let options = CompileOptions {
+        line_directives: true,
+        source_name: "arrays.mpl".to_string(),
+        ..CompileOptions::default()
+    };
+    let output = miniplc::compile_str(ARRAYS, &options).unwrap();
+    let lines: Vec<&str> = output.c_source.lines().collect();
+    assert!(lines.contains(&"#line 3 \"arrays.mpl\""));
+    // a line of the source is a single line of C
+    let read = lines.iter().position(|l| l.contains("scanf")).unwrap();
+    assert_eq!(lines[read + 1].matches("main_a[tmp_0] =").count(), 1);
+
+    let map = &output.source_map;
+    assert!(map.windows(2).all(|w| w[0].c_line < w[1].c_line));
+    for mapping in map {
+        assert!(!lines[mapping.c_line - 1].starts_with("#line"));
+    }
+    let assert = map.iter().find(|m| m.position.line == 8).unwrap();
+    assert!(lines[assert.c_line - 1].contains("assert(last_bool)"));
+}
+
+#[test]
+fn source_map_without_directives() {
+    let output = miniplc::compile_str(ARRAYS, &CompileOptions::default()).unwrap();
+    let lines: Vec<&str> = output.c_source.lines().collect();
+    let printf = output
+        .source_map
+        .iter()
+        .find(|m| lines[m.c_line - 1].contains("printf"))
+        .unwrap();
+    assert_eq!(printf.position.line, 7);
+    let json = miniplc::source_map_json("arrays.mpl", &output.source_map);
+    assert_eq!(json["source"], "arrays.mpl");
+    assert_eq!(
+        json["lines"].as_array().unwrap().len(),
+        output.source_map.len()
+    );
+}
+
+#[test]
+fn c_diagnostics_land_on_the_source() {
+    let options = CompileOptions {
+        line_directives: true,
+        source_name: "arrays.mpl".to_string(),
+        ..CompileOptions::default()
+    };
+    let Some(output) = run_native(ARRAYS, &options, "directives", "2") else {
+        return;
+    };
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("arrays.mpl"));
+}
+
+#[test]
+fn out_of_bounds_indexes_are_reported() {
+    let options = CompileOptions {
+        bounds_checks: true,
+        ..CompileOptions::default()
+    };
+    let Some(output) = run_native(ARRAYS, &options, "bounds", "5") else {
+        return;
+    };
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "Evaluation error in 6:2 > Index 5 out of bounds for array `a` of length 3\n"
+    );
+}
+
+#[test]
+fn failed_asserts_are_reported() {
+    let options = CompileOptions {
+        asserts: AssertMode::Report,
+        ..CompileOptions::default()
+    };
+    let Some(output) = run_native(ARRAYS, &options, "asserts", "2") else {
+        return;
+    };
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "7\n");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "Evaluation error in 8:15 > Assertion failed\n"
+    );
+}
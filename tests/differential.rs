@@ -0,0 +1,53 @@
+//! Random programs must behave the same way in the interpreter and in
+//! the C translation, skipped when there is no C compiler
+
+use std::process::{Command, Stdio};
+
+/// Runs `miniplc difftest` on 100 programs with the given options
+fn difftest(options: &[&str], keep: &str) {
+    let cc = Command::new("cc")
+        .arg("--version")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map(|s| s.success())
+        .unwrap_or(false);
+    if !cc {
+        eprintln!("no C compiler found, skipping");
+        return;
+    }
+    let keep = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join(keep);
+    let output = Command::new(env!("CARGO_BIN_EXE_miniplc"))
+        .args(options)
+        .args(["difftest", "--seed", "0", "--count", "100", "--keep"])
+        .arg(&keep)
+        .output()
+        .expect("miniplc runs");
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stdout)
+    );
+}
+
+#[test]
+fn interpreter_and_c_agree_on_generated_programs() {
+    difftest(&[], "difftest");
+}
+
+#[test]
+fn options_do_not_change_the_behaviour() {
+    difftest(
+        &[
+            "--dialect",
+            "c99",
+            "-O",
+            "1",
+            "--asserts",
+            "report",
+            "--bounds-checks",
+            "--line-directives",
+        ],
+        "difftest-options",
+    );
+}
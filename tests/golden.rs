@@ -0,0 +1,277 @@
+//! End to end tests over the programs in `tests/programs`.
+//!
+//! Every `name.mpl` comes with expectation files next to it, a missing
+//! one means the output is expected to be empty:
+//!
+//! - `name.diag`: what the compiler prints on stderr (warnings, errors)
+//! - `name.stdin`: input given to the program
+//! - `name.stdout`: what the program prints
+//! - `name.stderr`: what the interpreter prints on stderr (runtime errors)
+//!
+//! Programs that compile are run through the interpreter, through the
+//! bytecode in the virtual machine, and through the C translation when
+//! a C compiler (`cc`) is around.
+//!
+//! `cargo test --test golden -- --bless` rewrites the expectations from
+//! the actual outputs, any other argument filters the programs by name.
+
+use std::{
+    env, fs,
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Command, Output, Stdio},
+};
+
+const MINIPLC: &str = env!("CARGO_BIN_EXE_miniplc");
+
+struct Case {
+    source: PathBuf,
+    name: String,
+}
+
+impl Case {
+    fn expectation(&self, extension: &str) -> PathBuf {
+        self.source.with_extension(extension)
+    }
+
+    fn expected(&self, extension: &str) -> String {
+        fs::read_to_string(self.expectation(extension)).unwrap_or_default()
+    }
+}
+
+/// Runs a command with the given stdin, colors turned off
+fn run(command: &mut Command, stdin: &str) -> Output {
+    let mut child = command
+        .env("NO_COLOR", "1")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap_or_else(|e| panic!("unable to run {:?}: {}", command, e));
+    // a program that does not read its input closes the pipe early,
+    // the write error does not matter then
+    let _ = child
+        .stdin
+        .take()
+        .expect("stdin is piped")
+        .write_all(stdin.as_bytes());
+    child.wait_with_output().expect("the child was started")
+}
+
+fn has_c_compiler() -> bool {
+    Command::new("cc")
+        .arg("--version")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map(|s| s.success())
+        .unwrap_or(false)
+}
+
+fn discover(dir: &Path, filters: &[String]) -> Vec<Case> {
+    let mut cases: Vec<Case> = fs::read_dir(dir)
+        .expect("tests/programs exists")
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().is_some_and(|e| e == "mpl"))
+        .map(|source| Case {
+            name: source
+                .file_stem()
+                .map(|s| s.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            source,
+        })
+        .filter(|case| filters.is_empty() || filters.iter().any(|f| case.name.contains(f)))
+        .collect();
+    cases.sort_by(|a, b| a.name.cmp(&b.name));
+    cases
+}
+
+/// Checks one program, returns the list of mismatches
+fn check(case: &Case, work: &Path, cc: bool, bless: bool) -> Vec<String> {
+    let mut failures = Vec::new();
+    let mut compare = |what: &str, extension: &str, actual: &str| {
+        if bless {
+            let path = case.expectation(extension);
+            if actual.is_empty() {
+                let _ = fs::remove_file(path);
+            } else {
+                fs::write(path, actual).expect("expectation files are writable");
+            }
+        } else {
+            let expected = case.expected(extension);
+            if expected != actual {
+                failures.push(format!(
+                    "{}: {} differs\n--- expected\n{}--- actual\n{}",
+                    case.name, what, expected, actual
+                ));
+            }
+        }
+    };
+
+    let c_source = work.join(format!("{}.c", case.name));
+    let _ = fs::remove_file(&c_source);
+    let compiled = run(
+        Command::new(MINIPLC)
+            .arg("-f")
+            .arg(&case.source)
+            .arg("-o")
+            .arg(&c_source),
+        "",
+    );
+    // errors in the program are output, not a failure of the compiler
+    if !compiled.status.success() {
+        return vec![format!(
+            "{}: the compiler failed\n{}",
+            case.name,
+            String::from_utf8_lossy(&compiled.stderr)
+        )];
+    }
+    compare(
+        "compiler output",
+        "diag",
+        &String::from_utf8_lossy(&compiled.stderr),
+    );
+    if !c_source.exists() {
+        return failures;
+    }
+
+    let stdin = case.expected("stdin");
+    let interpreted = run(
+        Command::new(MINIPLC)
+            .args(["run", "-A", "warnings"])
+            .arg(&case.source),
+        &stdin,
+    );
+    let stdout = String::from_utf8_lossy(&interpreted.stdout).to_string();
+    compare("interpreter stdout", "stdout", &stdout);
+    compare(
+        "interpreter stderr",
+        "stderr",
+        &String::from_utf8_lossy(&interpreted.stderr),
+    );
+
+    let bytecode = work.join(format!("{}.mplc", case.name));
+    let built = run(
+        Command::new(MINIPLC)
+            .args(["bytecode", "-A", "warnings", "-o"])
+            .arg(&bytecode)
+            .arg(&case.source),
+        "",
+    );
+    if built.status.success() {
+        let executed = run(Command::new(MINIPLC).arg("exec").arg(&bytecode), &stdin);
+        compare(
+            "virtual machine stdout",
+            "stdout",
+            &String::from_utf8_lossy(&executed.stdout),
+        );
+        compare(
+            "virtual machine stderr",
+            "stderr",
+            &String::from_utf8_lossy(&executed.stderr),
+        );
+    } else {
+        failures.push(format!(
+            "{}: the bytecode does not build\n{}",
+            case.name,
+            String::from_utf8_lossy(&built.stderr)
+        ));
+    }
+
+    if !cc {
+        return failures;
+    }
+    let binary = work.join(&case.name);
+    let built = run(
+        Command::new("cc")
+            .arg("-w")
+            .arg("-o")
+            .arg(&binary)
+            .arg(&c_source)
+            .arg("-lm"),
+        "",
+    );
+    if !built.status.success() {
+        failures.push(format!(
+            "{}: the C translation does not build\n{}",
+            case.name,
+            String::from_utf8_lossy(&built.stderr)
+        ));
+        return failures;
+    }
+    let native = run(&mut Command::new(&binary), &stdin);
+    let native_stdout = String::from_utf8_lossy(&native.stdout);
+    if native_stdout != stdout {
+        failures.push(format!(
+            "{}: the C translation and the interpreter disagree\n--- interpreter\n{}--- C\n{}",
+            case.name, stdout, native_stdout
+        ));
+    }
+    if native.status.success() != interpreted.status.success() {
+        failures.push(format!(
+            "{}: the C translation {} but the interpreter {}",
+            case.name,
+            if native.status.success() {
+                "succeeds"
+            } else {
+                "fails"
+            },
+            if interpreted.status.success() {
+                "succeeds"
+            } else {
+                "fails"
+            },
+        ));
+    }
+    failures
+}
+
+fn main() {
+    let mut bless = env::var_os("MINIPLC_BLESS").is_some();
+    let mut filters = Vec::new();
+    for arg in env::args().skip(1) {
+        match arg.as_str() {
+            "--bless" => bless = true,
+            // flags the test runner passes to every test binary
+            a if a.starts_with('-') => {}
+            a => filters.push(a.to_string()),
+        }
+    }
+
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let work = Path::new(env!("CARGO_TARGET_TMPDIR")).join("golden");
+    fs::create_dir_all(&work).expect("the target directory is writable");
+    let cc = has_c_compiler();
+    if !cc {
+        println!("no C compiler found, only checking the interpreter");
+    }
+
+    let cases = discover(&root.join("tests").join("programs"), &filters);
+    let mut failed = 0;
+    for case in cases.iter() {
+        let failures = check(case, &work, cc, bless);
+        println!(
+            "golden {} ... {}",
+            case.name,
+            if bless {
+                "blessed"
+            } else if failures.is_empty() {
+                "ok"
+            } else {
+                "FAILED"
+            }
+        );
+        for failure in failures.iter() {
+            println!("{}", failure);
+        }
+        failed += !failures.is_empty() as usize;
+    }
+    println!(
+        "\ngolden result: {} passed; {} failed",
+        cases.len() - failed,
+        failed
+    );
+    if failed > 0 {
+        std::process::exit(1);
+    }
+}